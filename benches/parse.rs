@@ -0,0 +1,187 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use soqa_sdk::exchanges::decode::Decoder;
+use soqa_sdk::exchanges::{binance, bybit, kraken, kucoin, okx};
+
+const BINANCE: &str = include_str!("../tests/data/binance_ticker.json");
+const BYBIT: &str = include_str!("../tests/data/bybit_orderbook.json");
+const OKX: &str = include_str!("../tests/data/okx_books.json");
+const KRAKEN: &str = include_str!("../tests/data/kraken_ticker.json");
+const KUCOIN: &str = include_str!("../tests/data/kucoin_ticker.json");
+
+// The original `Value`-based parsers, kept here as the baseline the typed ones are measured against.
+mod value {
+    use serde_json::Value;
+    use soqa_sdk::models::{OrderBookL1, QuoteSource};
+    use std::time::SystemTime;
+
+    pub fn binance(text: &str) -> Option<OrderBookL1> {
+        let data = serde_json::from_str::<Value>(text).ok()?;
+        Some(OrderBookL1 {
+            exchange: "binance".to_string(),
+            symbol: data["s"].as_str().unwrap_or("").to_string(),
+            bid: data["b"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            bid_volume: data["B"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            ask: data["a"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            ask_volume: data["A"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            timestamp: SystemTime::now(),
+            source: QuoteSource::Ticker,
+        })
+    }
+
+    pub fn bybit(text: &str, symbol: &str) -> Option<OrderBookL1> {
+        let data = serde_json::from_str::<Value>(text).ok()?;
+        let topic = data.get("topic").and_then(|t| t.as_str())?;
+        if !topic.starts_with("orderbook") {
+            return None;
+        }
+        let data = data.get("data")?;
+        let bids = data.get("b").and_then(|b| b.as_array())?;
+        let asks = data.get("a").and_then(|a| a.as_array())?;
+        if bids.is_empty() || asks.is_empty() {
+            return None;
+        }
+        Some(OrderBookL1 {
+            exchange: "bybit".to_string(),
+            symbol: symbol.to_string(),
+            bid: bids[0][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            bid_volume: bids[0][1].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            ask: asks[0][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            ask_volume: asks[0][1].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            timestamp: SystemTime::now(),
+            source: QuoteSource::Bbo,
+        })
+    }
+
+    pub fn okx(text: &str, symbol: &str) -> Option<OrderBookL1> {
+        let data = serde_json::from_str::<Value>(text).ok()?;
+        let book = data.get("data").and_then(|d| d.as_array())?.first()?;
+        let bids = book.get("bids").and_then(|b| b.as_array())?;
+        let asks = book.get("asks").and_then(|a| a.as_array())?;
+        if bids.is_empty() || asks.is_empty() {
+            return None;
+        }
+        Some(OrderBookL1 {
+            exchange: "okx".to_string(),
+            symbol: symbol.to_string(),
+            bid: bids[0][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            bid_volume: bids[0][1].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            ask: asks[0][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            ask_volume: asks[0][1].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            timestamp: SystemTime::now(),
+            source: QuoteSource::Book,
+        })
+    }
+
+    pub fn kraken(text: &str, symbol: &str) -> Option<OrderBookL1> {
+        let data = serde_json::from_str::<Value>(text).ok()?;
+        data.as_array()?;
+        let ticker = &data[1];
+        Some(OrderBookL1 {
+            exchange: "kraken".to_string(),
+            symbol: symbol.to_string(),
+            bid: ticker["b"][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            bid_volume: ticker["b"][2].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            ask: ticker["a"][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            ask_volume: ticker["a"][2].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            timestamp: SystemTime::now(),
+            source: QuoteSource::Ticker,
+        })
+    }
+
+    pub fn kucoin(text: &str, symbol: &str) -> Option<OrderBookL1> {
+        let data = serde_json::from_str::<Value>(text).ok()?;
+        let topic = data.get("topic").and_then(|t| t.as_str())?;
+        if !topic.starts_with("/market/ticker") {
+            return None;
+        }
+        let data = data.get("data")?;
+        Some(OrderBookL1 {
+            exchange: "kucoin".to_string(),
+            symbol: symbol.to_string(),
+            bid: data["bestBid"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            bid_volume: data["bestBidSize"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            ask: data["bestAsk"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            ask_volume: data["bestAskSize"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
+            timestamp: SystemTime::now(),
+            source: QuoteSource::Ticker,
+        })
+    }
+}
+
+fn bench_binance(c: &mut Criterion) {
+    let mut group = c.benchmark_group("binance");
+    group.bench_function("value", |b| b.iter(|| value::binance(black_box(BINANCE))));
+    group.bench_function("typed", |b| b.iter(|| binance::parse_l1(black_box(BINANCE))));
+    let mut decoder = Decoder::new();
+    group.bench_function("decoder", |b| {
+        b.iter(|| {
+            decoder
+                .decode::<binance::TickerMsg>(black_box(BINANCE))
+                .and_then(|m| m.to_l1())
+        })
+    });
+    group.finish();
+}
+
+fn bench_bybit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bybit");
+    group.bench_function("value", |b| b.iter(|| value::bybit(black_box(BYBIT), "ETHUSDT")));
+    group.bench_function("typed", |b| b.iter(|| bybit::parse_l1(black_box(BYBIT), "ETHUSDT")));
+    let mut decoder = Decoder::new();
+    group.bench_function("decoder", |b| {
+        b.iter(|| {
+            decoder
+                .decode::<bybit::OrderbookMsg>(black_box(BYBIT))
+                .and_then(|m| m.to_l1("ETHUSDT"))
+        })
+    });
+    group.finish();
+}
+
+fn bench_okx(c: &mut Criterion) {
+    let mut group = c.benchmark_group("okx");
+    group.bench_function("value", |b| b.iter(|| value::okx(black_box(OKX), "BTCUSDT")));
+    group.bench_function("typed", |b| b.iter(|| okx::parse_l1(black_box(OKX), "BTCUSDT")));
+    let mut decoder = Decoder::new();
+    group.bench_function("decoder", |b| {
+        b.iter(|| {
+            decoder
+                .decode::<okx::BooksMsg>(black_box(OKX))
+                .and_then(|m| m.to_l1("BTCUSDT"))
+        })
+    });
+    group.finish();
+}
+
+fn bench_kraken(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kraken");
+    group.bench_function("value", |b| b.iter(|| value::kraken(black_box(KRAKEN), "BTCUSD")));
+    group.bench_function("typed", |b| b.iter(|| kraken::parse_l1(black_box(KRAKEN), "BTCUSD")));
+    let mut decoder = Decoder::new();
+    group.bench_function("decoder", |b| {
+        b.iter(|| {
+            decoder
+                .decode::<kraken::TickerMsg>(black_box(KRAKEN))
+                .and_then(|m| m.to_l1("BTCUSD"))
+        })
+    });
+    group.finish();
+}
+
+fn bench_kucoin(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kucoin");
+    group.bench_function("value", |b| b.iter(|| value::kucoin(black_box(KUCOIN), "ETHUSDT")));
+    group.bench_function("typed", |b| b.iter(|| kucoin::parse_l1(black_box(KUCOIN), "ETHUSDT")));
+    let mut decoder = Decoder::new();
+    group.bench_function("decoder", |b| {
+        b.iter(|| {
+            decoder
+                .decode::<kucoin::TickerMsg>(black_box(KUCOIN))
+                .and_then(|m| m.to_l1("ETHUSDT"))
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_binance, bench_bybit, bench_okx, bench_kraken, bench_kucoin);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use soqa_sdk::models::{MarketEvent, OrderBookL1, QuoteSource};
+use soqa_sdk::shm::{ShmPublisher, ShmReader};
+use std::time::SystemTime;
+
+fn ring(c: &mut Criterion) {
+    let dir = if std::path::Path::new("/dev/shm").is_dir() { "/dev/shm".into() } else { std::env::temp_dir() };
+    let path = dir.join(format!("soqa-bench-{}", std::process::id()));
+    let mut publisher = ShmPublisher::create(&path, 4096).unwrap();
+    let mut reader = ShmReader::open(&path).unwrap();
+    let event = MarketEvent::L1(OrderBookL1 {
+        exchange: "binance".to_string(),
+        symbol: "BTCUSDT".to_string(),
+        bid: 67012.34,
+        bid_volume: 1.5,
+        ask: 67012.35,
+        ask_volume: 0.25,
+        timestamp: SystemTime::now(),
+        source: QuoteSource::Ticker,
+    });
+
+    c.bench_function("shm publish + read", |b| {
+        b.iter(|| {
+            publisher.publish(&event).unwrap();
+            reader.try_read().unwrap().unwrap()
+        })
+    });
+    std::fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, ring);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // gRPC service stubs for proto/soqa.proto. The messages are declared by hand in
+    // src/api/grpc.rs, so no protoc is needed.
+    #[cfg(feature = "grpc")]
+    {
+        use tonic_build::manual::{Builder, Method, Service};
+
+        let method = |name: &str, route: &str, input: &str, output: &str| {
+            Method::builder()
+                .name(name)
+                .route_name(route)
+                .input_type(format!("crate::api::grpc::proto::{}", input))
+                .output_type(format!("crate::api::grpc::proto::{}", output))
+                .codec_path("tonic_prost::ProstCodec")
+        };
+        let service = Service::builder()
+            .name("MarketData")
+            .package("soqa.v1")
+            .method(method("subscribe", "Subscribe", "SubscribeRequest", "Event").server_streaming().build())
+            .method(method("get_snapshot", "GetSnapshot", "SnapshotRequest", "Snapshot").build())
+            .method(method("list_instruments", "ListInstruments", "ListInstrumentsRequest", "ListInstrumentsResponse").build())
+            .method(method("health", "Health", "HealthRequest", "HealthResponse").build())
+            .build();
+        Builder::new().compile(&[service]);
+    }
+}
@@ -0,0 +1,292 @@
+use crate::models::{MarketEvent, OrderBookL1, OrderBookL2, Trade};
+use clap::ValueEnum;
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalKind {
+    Vwap,
+    Twap,
+    Microprice,
+    Imbalance,
+    SpreadBps,
+    TradeFlowImbalance,
+    RealizedVol,
+}
+
+impl SignalKind {
+    // Signals computed from trades, which need a trade feed next to the quotes.
+    pub fn needs_trades(&self) -> bool {
+        matches!(self, SignalKind::Vwap | SignalKind::TradeFlowImbalance)
+    }
+
+    // Signals computed from L1 quotes, which need a quote feed whatever the chosen level.
+    pub fn needs_quotes(&self) -> bool {
+        matches!(self, SignalKind::Twap | SignalKind::Microprice | SignalKind::SpreadBps | SignalKind::RealizedVol)
+    }
+
+    // Signals computed from either book: the top of an L1 quote or the levels of an L2 book.
+    pub fn needs_book(&self) -> bool {
+        matches!(self, SignalKind::Imbalance)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Signal {
+    pub exchange: String,
+    pub symbol: String,
+    pub kind: SignalKind,
+    pub value: f64,
+    pub timestamp: SystemTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnalyticsConfig {
+    pub window: Duration,
+    pub levels: usize,
+    pub signals: Vec<SignalKind>,
+}
+
+impl Default for AnalyticsConfig {
+    fn default() -> Self {
+        AnalyticsConfig {
+            window: Duration::from_secs(60),
+            levels: 5,
+            signals: SignalKind::value_variants().to_vec(),
+        }
+    }
+}
+
+pub fn mid(book: &OrderBookL1) -> f64 {
+    (book.bid + book.ask) / 2.0
+}
+
+pub fn microprice(book: &OrderBookL1) -> Option<f64> {
+    let total = book.bid_volume + book.ask_volume;
+    (total > 0.0).then(|| (book.bid * book.ask_volume + book.ask * book.bid_volume) / total)
+}
+
+pub fn spread_bps(book: &OrderBookL1) -> Option<f64> {
+    let mid = mid(book);
+    (mid > 0.0).then(|| (book.ask - book.bid) / mid * 10_000.0)
+}
+
+// (bid volume - ask volume) / total over the top `levels` of each side, in [-1, 1].
+pub fn book_imbalance(book: &OrderBookL2, levels: usize) -> Option<f64> {
+    let bids: f64 = book.bids.iter().take(levels).map(|(_, size)| size).sum();
+    let asks: f64 = book.asks.iter().take(levels).map(|(_, size)| size).sum();
+    imbalance(bids, asks)
+}
+
+pub fn l1_imbalance(book: &OrderBookL1) -> Option<f64> {
+    imbalance(book.bid_volume, book.ask_volume)
+}
+
+fn imbalance(bids: f64, asks: f64) -> Option<f64> {
+    let total = bids + asks;
+    (total > 0.0).then(|| (bids - asks) / total)
+}
+
+// Samples inside a trailing time window, keyed by event timestamp.
+#[derive(Debug, Clone)]
+struct Rolling<T> {
+    window: Duration,
+    samples: VecDeque<(SystemTime, T)>,
+}
+
+impl<T> Rolling<T> {
+    fn new(window: Duration) -> Self {
+        Rolling { window, samples: VecDeque::new() }
+    }
+
+    fn push(&mut self, at: SystemTime, value: T) {
+        self.samples.push_back((at, value));
+        let cutoff = at.checked_sub(self.window).unwrap_or(SystemTime::UNIX_EPOCH);
+        while self.samples.front().map(|(t, _)| *t < cutoff).unwrap_or(false) {
+            self.samples.pop_front();
+        }
+    }
+
+    fn values(&self) -> impl Iterator<Item = &T> {
+        self.samples.iter().map(|(_, value)| value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Vwap(Rolling<(f64, f64)>);
+
+impl Vwap {
+    pub fn new(window: Duration) -> Self {
+        Vwap(Rolling::new(window))
+    }
+
+    pub fn update(&mut self, trade: &Trade) -> Option<f64> {
+        self.0.push(trade.timestamp, (trade.price, trade.volume));
+        let (notional, volume) = self
+            .0
+            .values()
+            .fold((0.0, 0.0), |(n, v), (price, volume)| (n + price * volume, v + volume));
+        (volume > 0.0).then(|| notional / volume)
+    }
+}
+
+// Time-weighted average: each price is weighted by how long it stood before the next one.
+#[derive(Debug, Clone)]
+pub struct Twap(Rolling<f64>);
+
+impl Twap {
+    pub fn new(window: Duration) -> Self {
+        Twap(Rolling::new(window))
+    }
+
+    pub fn update(&mut self, at: SystemTime, price: f64) -> Option<f64> {
+        self.0.push(at, price);
+        let samples = &self.0.samples;
+        if samples.len() < 2 {
+            return Some(price);
+        }
+        let mut weighted = 0.0;
+        let mut total = 0.0;
+        for pair in samples.iter().zip(samples.iter().skip(1)) {
+            let ((t0, p0), (t1, _)) = pair;
+            let dt = t1.duration_since(*t0).unwrap_or_default().as_secs_f64();
+            weighted += p0 * dt;
+            total += dt;
+        }
+        Some(if total > 0.0 { weighted / total } else { price })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TradeFlowImbalance(Rolling<f64>);
+
+impl TradeFlowImbalance {
+    pub fn new(window: Duration) -> Self {
+        TradeFlowImbalance(Rolling::new(window))
+    }
+
+    // Buys count as positive volume, sells as negative.
+    pub fn update(&mut self, trade: &Trade) -> Option<f64> {
+        let signed = if trade.side.eq_ignore_ascii_case("buy") { trade.volume } else { -trade.volume };
+        self.0.push(trade.timestamp, signed);
+        let (buys, sells) = self
+            .0
+            .values()
+            .fold((0.0, 0.0), |(b, s), v| if *v >= 0.0 { (b + v, s) } else { (b, s - v) });
+        imbalance(buys, sells)
+    }
+}
+
+// Square root of the sum of squared log returns inside the window (not annualized).
+#[derive(Debug, Clone)]
+pub struct RealizedVolatility {
+    returns: Rolling<f64>,
+    last: Option<f64>,
+}
+
+impl RealizedVolatility {
+    pub fn new(window: Duration) -> Self {
+        RealizedVolatility { returns: Rolling::new(window), last: None }
+    }
+
+    pub fn update(&mut self, at: SystemTime, price: f64) -> Option<f64> {
+        if price <= 0.0 {
+            return None;
+        }
+        let last = self.last.replace(price)?;
+        self.returns.push(at, (price / last).ln());
+        Some(self.returns.values().map(|r| r * r).sum::<f64>().sqrt())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FeedState {
+    vwap: Vwap,
+    twap: Twap,
+    flow: TradeFlowImbalance,
+    volatility: RealizedVolatility,
+    // Set once an L2 book arrives; imbalance then comes from its levels rather than the quotes.
+    depth: bool,
+}
+
+// Rolling signals per (exchange, symbol) computed from a normalized event stream.
+#[derive(Debug, Clone)]
+pub struct Analytics {
+    config: AnalyticsConfig,
+    feeds: HashMap<(String, String), FeedState>,
+}
+
+impl Analytics {
+    pub fn new(config: AnalyticsConfig) -> Self {
+        Analytics { config, feeds: HashMap::new() }
+    }
+
+    fn wants(&self, kind: SignalKind) -> bool {
+        self.config.signals.contains(&kind)
+    }
+
+    pub fn on_event(&mut self, event: &MarketEvent) -> Vec<Signal> {
+        let window = self.config.window;
+        let key = (event.exchange().to_string(), event.symbol().to_string());
+        let mut values = Vec::new();
+        let state = self.feeds.entry(key).or_insert_with(|| FeedState {
+            vwap: Vwap::new(window),
+            twap: Twap::new(window),
+            flow: TradeFlowImbalance::new(window),
+            volatility: RealizedVolatility::new(window),
+            depth: false,
+        });
+        match event {
+            MarketEvent::L1(book) => {
+                let mid = mid(book);
+                values.push((SignalKind::Microprice, microprice(book)));
+                values.push((SignalKind::SpreadBps, spread_bps(book)));
+                if !state.depth {
+                    values.push((SignalKind::Imbalance, l1_imbalance(book)));
+                }
+                values.push((SignalKind::Twap, state.twap.update(book.timestamp, mid)));
+                values.push((SignalKind::RealizedVol, state.volatility.update(book.timestamp, mid)));
+            }
+            MarketEvent::L2(book) => {
+                state.depth = true;
+                values.push((SignalKind::Imbalance, book_imbalance(book, self.config.levels)));
+            }
+            MarketEvent::Trade(trade) => {
+                values.push((SignalKind::Vwap, state.vwap.update(trade)));
+                values.push((SignalKind::TradeFlowImbalance, state.flow.update(trade)));
+            }
+        }
+        values
+            .into_iter()
+            .filter(|(kind, _)| self.wants(*kind))
+            .filter_map(|(kind, value)| {
+                Some(Signal {
+                    exchange: event.exchange().to_string(),
+                    symbol: event.symbol().to_string(),
+                    kind,
+                    value: value?,
+                    timestamp: event.timestamp(),
+                })
+            })
+            .collect()
+    }
+}
+
+pub trait AnalyticsExt: Stream<Item = MarketEvent> + Sized {
+    fn analytics(self, config: AnalyticsConfig) -> impl Stream<Item = Signal> {
+        self.scan(Analytics::new(config), |analytics, event| {
+            future::ready(Some(analytics.on_event(&event)))
+        })
+        .flat_map(stream::iter)
+    }
+
+    fn signal(self, kind: SignalKind, window: Duration) -> impl Stream<Item = Signal> {
+        self.analytics(AnalyticsConfig { window, signals: vec![kind], ..AnalyticsConfig::default() })
+    }
+}
+
+impl<S: Stream<Item = MarketEvent>> AnalyticsExt for S {}
@@ -0,0 +1,65 @@
+// API-key check shared by the gateways. Clients send the key in an `x-api-key` header, as
+// `Authorization: Bearer <key>`, or as `?api_key=<key>` where they cannot set headers, e.g.
+// browser WebSockets. With no keys configured the gateway is open.
+
+use crate::api::rest::ApiResponse;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys(Arc<HashSet<String>>);
+
+impl ApiKeys {
+    pub fn new(keys: impl IntoIterator<Item = String>) -> Self {
+        ApiKeys(Arc::new(keys.into_iter().filter(|key| !key.is_empty()).collect()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn allows(&self, key: Option<&str>) -> bool {
+        self.0.is_empty() || key.is_some_and(|key| self.0.contains(key))
+    }
+
+    // The key presented in an `x-api-key` or `Authorization: Bearer` header.
+    pub fn presented<'a>(api_key: Option<&'a str>, authorization: Option<&'a str>) -> Option<&'a str> {
+        api_key.or_else(|| authorization.and_then(|value| value.strip_prefix("Bearer ")))
+    }
+}
+
+#[derive(Debug)]
+pub struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+pub fn require(keys: ApiKeys) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-api-key")
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(move |api_key: Option<String>, authorization: Option<String>, query: HashMap<String, String>| {
+            let keys = keys.clone();
+            async move {
+                let presented = ApiKeys::presented(api_key.as_deref(), authorization.as_deref())
+                    .or(query.get("api_key").map(String::as_str));
+                if keys.allows(presented) {
+                    Ok(())
+                } else {
+                    Err(warp::reject::custom(Unauthorized))
+                }
+            }
+        })
+        .untuple_one()
+}
+
+// Turns `Unauthorized` into a 401 JSON response; other rejections get warp's default handling.
+pub async fn recover(rejection: Rejection) -> Result<Box<dyn Reply>, Rejection> {
+    if rejection.find::<Unauthorized>().is_some() {
+        let response = ApiResponse { status: "unauthorized".to_string(), data: serde_json::Value::Null };
+        return Ok(Box::new(warp::reply::with_status(warp::reply::json(&response), StatusCode::UNAUTHORIZED)));
+    }
+    Err(rejection)
+}
+
@@ -0,0 +1,311 @@
+// `soqa serve`: runs the configured feeds, sinks and the REST/WebSocket (and gRPC) gateways in
+// one process until shutdown.
+
+use crate::api::auth::{self, ApiKeys};
+use crate::api::rest::rest_routes;
+use crate::api::websocket::websocket_upgrade;
+use crate::codec::Encoding;
+use crate::config::Config;
+use crate::delivery::Delivery;
+use crate::error::SoqaError;
+use crate::exchanges;
+use crate::exchanges::adapter::{AdapterSpec, GenericClient};
+use crate::health::HealthMonitor;
+use crate::models::MarketEvent;
+use crate::shutdown::Shutdown;
+use crate::subscriptions::{Channel, SubscriptionManager};
+use serde::Deserialize;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{error, info, warn};
+use warp::filters::BoxedFilter;
+use warp::{Filter, Reply};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GatewayConfig {
+    #[serde(default = "default_bind")]
+    pub bind: SocketAddr,
+    // Needs the `grpc` feature.
+    pub grpc_bind: Option<SocketAddr>,
+    // Needs the `tls` feature.
+    pub tls: Option<TlsConfig>,
+    // Origins allowed to call the gateway from a browser; `*` allows any.
+    #[serde(default)]
+    pub cors_origins: Vec<String>,
+    // Required on `/ws` and gRPC when set; `/health` and `/metrics` stay open for probes.
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    #[serde(default)]
+    pub feeds: Vec<FeedConfig>,
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeedConfig {
+    pub exchange: String,
+    pub symbol: String,
+    #[serde(default = "default_channels")]
+    pub channels: Vec<Channel>,
+    // Declarative adapter spec to use instead of a built-in client (L1 only).
+    pub adapter: Option<String>,
+    // L1 from the venue's best bid/offer channel.
+    #[serde(default)]
+    pub bbo: bool,
+    // `queue_capacity` and `overflow`, per feed.
+    #[serde(flatten)]
+    pub delivery: Delivery,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SinkConfig {
+    pub url: String,
+    #[serde(default)]
+    pub encoding: Encoding,
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    pub ttl_secs: Option<u64>,
+    // For redis sinks: newest trades kept per market.
+    #[serde(default)]
+    pub max_trades: usize,
+    // For .db and .parquet sinks: how often each market's L2 book is written whole.
+    #[serde(default = "default_snapshot_secs")]
+    pub snapshot_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+fn default_bind() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 8081))
+}
+
+fn default_channels() -> Vec<Channel> {
+    vec![Channel::L1]
+}
+
+fn default_prefix() -> String {
+    "soqa".to_string()
+}
+
+fn default_snapshot_secs() -> u64 {
+    60
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        GatewayConfig {
+            bind: default_bind(),
+            grpc_bind: None,
+            tls: None,
+            cors_origins: Vec::new(),
+            api_keys: Vec::new(),
+            feeds: Vec::new(),
+            sinks: Vec::new(),
+        }
+    }
+}
+
+impl GatewayConfig {
+    pub fn from_toml_str(s: &str) -> Result<Self, SoqaError> {
+        toml::from_str(s).map_err(|e| SoqaError::ConfigError(e.to_string()))
+    }
+
+    pub fn from_json_str(s: &str) -> Result<Self, SoqaError> {
+        serde_json::from_str(s).map_err(|e| SoqaError::ConfigError(e.to_string()))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SoqaError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| SoqaError::ConfigError(format!("{}: {}", path.display(), e)))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json_str(&contents),
+            _ => Self::from_toml_str(&contents),
+        }
+    }
+
+    // Catches what would otherwise fail after some feeds are already running.
+    pub fn validate(&self) -> Result<(), SoqaError> {
+        for feed in &self.feeds {
+            if feed.adapter.is_some() && feed.channels.iter().any(|channel| *channel != Channel::L1) {
+                return Err(SoqaError::ConfigError(format!(
+                    "{} {}: adapters only provide L1",
+                    feed.exchange, feed.symbol
+                )));
+            }
+            if feed.adapter.is_some() && feed.bbo {
+                return Err(SoqaError::ConfigError(format!(
+                    "{} {}: adapters have no best bid/offer channel",
+                    feed.exchange, feed.symbol
+                )));
+            }
+            if feed.adapter.is_none() && !exchanges::SUPPORTED.contains(&feed.exchange.as_str()) {
+                return Err(SoqaError::ExchangeNotSupported(feed.exchange.clone()));
+            }
+        }
+        for sink in &self.sinks {
+            let redis = crate::export::sink_kind(&sink.url) == Some("redis");
+            if !redis && (sink.ttl_secs.is_some() || sink.max_trades > 0) {
+                return Err(SoqaError::ConfigError(format!("{}: ttl_secs and max_trades only apply to redis sinks", sink.url)));
+            }
+        }
+        for origin in &self.cors_origins {
+            let valid = origin == "*"
+                || origin
+                    .strip_prefix("https://")
+                    .or_else(|| origin.strip_prefix("http://"))
+                    .is_some_and(|host| !host.is_empty() && !host.contains('/'));
+            if !valid {
+                return Err(SoqaError::ConfigError(format!("invalid CORS origin {}, expected e.g. https://example.com", origin)));
+            }
+        }
+        if cfg!(not(feature = "grpc")) && self.grpc_bind.is_some() {
+            return Err(SoqaError::ConfigError("grpc_bind needs a build with the `grpc` feature".to_string()));
+        }
+        if cfg!(not(feature = "tls")) && self.tls.is_some() {
+            return Err(SoqaError::ConfigError("tls needs a build with the `tls` feature".to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn boxed(reply: impl Reply + 'static) -> Box<dyn Reply> {
+    Box::new(reply)
+}
+
+// `/ws` behind the API-key check, plus `/health` (and `/metrics` with the `metrics` feature).
+pub fn routes(
+    manager: &SubscriptionManager,
+    monitor: HealthMonitor,
+    keys: ApiKeys,
+    cors_origins: &[String],
+) -> BoxedFilter<(Box<dyn Reply>,)> {
+    let ws = warp::path("ws").and(auth::require(keys)).and(websocket_upgrade(manager.sender())).map(boxed);
+    let routes = ws.or(rest_routes(monitor).map(boxed)).unify();
+    #[cfg(feature = "metrics")]
+    let routes = routes.or(crate::api::rest::metrics_route().map(boxed)).unify();
+    let routes = routes.recover(auth::recover).unify();
+    if cors_origins.is_empty() {
+        return routes.boxed();
+    }
+    let cors = warp::cors()
+        .allow_methods(["GET"])
+        .allow_headers(["x-api-key", "authorization", "content-type"]);
+    let cors = if cors_origins.iter().any(|origin| origin == "*") {
+        cors.allow_any_origin()
+    } else {
+        cors.allow_origins(cors_origins.iter().map(String::as_str))
+    };
+    routes.with(cors).map(boxed).boxed()
+}
+
+async fn start_feed(feed: &FeedConfig, channel: Channel, config: Config, manager: &SubscriptionManager) -> Result<(), SoqaError> {
+    let manager = manager.clone();
+    match &feed.adapter {
+        Some(path) => {
+            let spec = AdapterSpec::from_file(path)?;
+            GenericClient::new(spec, config)
+                .subscribe_l1(move |order_book| manager.publish(MarketEvent::L1(order_book)))
+                .await
+        }
+        None => exchanges::subscribe(config, channel, move |event| manager.publish(event)).await,
+    }
+}
+
+// Runs `server` until it finishes, or until 5 seconds after shutdown if clients keep it busy.
+async fn drain(server: impl Future<Output = ()>, shutdown: &Shutdown) {
+    let deadline = async {
+        shutdown.wait().await;
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    };
+    tokio::select! {
+        _ = server => {}
+        _ = deadline => warn!(target: "soqa::api", "clients still connected, stopping anyway"),
+    }
+}
+
+#[cfg(feature = "tls")]
+fn read_pem(path: &Path) -> Result<Vec<u8>, SoqaError> {
+    std::fs::read(path).map_err(|e| SoqaError::ConfigError(format!("{}: {}", path.display(), e)))
+}
+
+// Serves until `shutdown` fires, then waits for the feeds to close and the sinks to flush.
+pub async fn serve(config: GatewayConfig, monitor: HealthMonitor, shutdown: Shutdown) -> Result<(), SoqaError> {
+    config.validate()?;
+    let manager = SubscriptionManager::default();
+    let keys = ApiKeys::new(config.api_keys.iter().cloned());
+
+    let mut sinks = Vec::new();
+    for sink in &config.sinks {
+        let ttl = sink.ttl_secs.map(Duration::from_secs);
+        let snapshot_every = Duration::from_secs(sink.snapshot_secs);
+        let events = manager.subscribe();
+        sinks.push(crate::export::spawn_sink(&sink.url, sink.encoding, &sink.prefix, ttl, sink.max_trades, snapshot_every, events).await?);
+    }
+
+    for feed in &config.feeds {
+        for &channel in &feed.channels {
+            let feed_config = Config::new(&feed.exchange, &feed.symbol)
+                .with_health(monitor.clone())
+                .with_shutdown(shutdown.clone())
+                .with_delivery(feed.delivery)
+                .with_bbo(feed.bbo);
+            // A venue that is down at startup should not take the others with it.
+            if let Err(e) = start_feed(feed, channel, feed_config, &manager).await {
+                error!(target: "soqa::api", exchange = %feed.exchange, symbol = %feed.symbol, channel = channel.as_str(), error = %e, "feed failed to start");
+            }
+        }
+    }
+
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = config.grpc_bind {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| SoqaError::ConnectionError(format!("{}: {}", addr, e)))?;
+        let grpc = crate::api::grpc::serve_with(listener, manager.clone(), monitor.clone(), keys.clone(), shutdown.clone());
+        tokio::spawn(async move {
+            if let Err(e) = grpc.await {
+                error!(target: "soqa::api", error = %e, "gRPC gateway failed");
+            }
+        });
+    }
+
+    let routes = routes(&manager, monitor, keys, &config.cors_origins);
+    let stop = {
+        let shutdown = shutdown.clone();
+        async move { shutdown.wait().await }
+    };
+    match &config.tls {
+        #[cfg(feature = "tls")]
+        Some(tls) => {
+            let (cert, key) = (read_pem(&tls.cert)?, read_pem(&tls.key)?);
+            let (addr, server) = warp::serve(routes).tls().cert(cert).key(key).bind_with_graceful_shutdown(config.bind, stop);
+            info!(target: "soqa::api", %addr, "gateway listening with TLS");
+            drain(server, &shutdown).await;
+        }
+        #[cfg(not(feature = "tls"))]
+        Some(_) => unreachable!("rejected by validate"),
+        None => {
+            let (addr, server) = warp::serve(routes)
+                .try_bind_with_graceful_shutdown(config.bind, stop)
+                .map_err(|e| SoqaError::ConnectionError(format!("{}: {}", config.bind, e)))?;
+            info!(target: "soqa::api", %addr, "gateway listening");
+            drain(server, &shutdown).await;
+        }
+    }
+
+    // Feeds hold the remaining senders; the sinks end once the feeds have closed.
+    drop(manager);
+    for sink in sinks {
+        if tokio::time::timeout(Duration::from_secs(5), sink).await.is_err() {
+            warn!(target: "soqa::api", "sink did not flush within 5s");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,407 @@
+// gRPC gateway (proto/soqa.proto) over the same subscription manager as the WebSocket gateway.
+
+use crate::api::auth::ApiKeys;
+use crate::arbitrage::canonical_symbol;
+use crate::error::SoqaError;
+use crate::exchanges::instruments::Instrument;
+use crate::health::HealthMonitor;
+use crate::shutdown::Shutdown;
+use crate::models::{MarketEvent, OrderBookL1, OrderBookL2, Trade};
+use crate::subscriptions::{Channel, EventFilter, SubscriptionManager};
+use futures_util::Stream;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+use tonic::{Request, Response, Status};
+use tracing::warn;
+
+// Hand-written equivalents of the messages in proto/soqa.proto.
+pub mod proto {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Channel {
+        Unspecified = 0,
+        L1 = 1,
+        L2 = 2,
+        Trades = 3,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeRequest {
+        #[prost(string, repeated, tag = "1")]
+        pub exchanges: Vec<String>,
+        #[prost(string, repeated, tag = "2")]
+        pub symbols: Vec<String>,
+        #[prost(enumeration = "Channel", repeated, tag = "3")]
+        pub channels: Vec<i32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Level {
+        #[prost(double, tag = "1")]
+        pub price: f64,
+        #[prost(double, tag = "2")]
+        pub size: f64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct L1 {
+        #[prost(double, tag = "1")]
+        pub bid: f64,
+        #[prost(double, tag = "2")]
+        pub bid_volume: f64,
+        #[prost(double, tag = "3")]
+        pub ask: f64,
+        #[prost(double, tag = "4")]
+        pub ask_volume: f64,
+        #[prost(string, tag = "5")]
+        pub source: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct L2 {
+        #[prost(message, repeated, tag = "1")]
+        pub bids: Vec<Level>,
+        #[prost(message, repeated, tag = "2")]
+        pub asks: Vec<Level>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Trade {
+        #[prost(double, tag = "1")]
+        pub price: f64,
+        #[prost(double, tag = "2")]
+        pub volume: f64,
+        #[prost(string, tag = "3")]
+        pub side: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Event {
+        #[prost(string, tag = "1")]
+        pub exchange: String,
+        #[prost(string, tag = "2")]
+        pub symbol: String,
+        #[prost(uint64, tag = "3")]
+        pub timestamp_ns: u64,
+        #[prost(oneof = "Payload", tags = "4, 5, 6")]
+        pub payload: Option<Payload>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Payload {
+        #[prost(message, tag = "4")]
+        L1(L1),
+        #[prost(message, tag = "5")]
+        L2(L2),
+        #[prost(message, tag = "6")]
+        Trade(Trade),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SnapshotRequest {
+        #[prost(string, tag = "1")]
+        pub exchange: String,
+        #[prost(string, tag = "2")]
+        pub symbol: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Snapshot {
+        #[prost(string, tag = "1")]
+        pub exchange: String,
+        #[prost(string, tag = "2")]
+        pub symbol: String,
+        #[prost(message, optional, tag = "3")]
+        pub l1: Option<Event>,
+        #[prost(message, optional, tag = "4")]
+        pub l2: Option<Event>,
+        #[prost(message, optional, tag = "5")]
+        pub last_trade: Option<Event>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ListInstrumentsRequest {
+        #[prost(string, tag = "1")]
+        pub exchange: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Instrument {
+        #[prost(string, tag = "1")]
+        pub exchange: String,
+        #[prost(string, tag = "2")]
+        pub symbol: String,
+        #[prost(string, tag = "3")]
+        pub base: String,
+        #[prost(string, tag = "4")]
+        pub quote: String,
+        #[prost(bool, tag = "5")]
+        pub streaming: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ListInstrumentsResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub instruments: Vec<Instrument>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct HealthRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FeedHealth {
+        #[prost(string, tag = "1")]
+        pub exchange: String,
+        #[prost(string, tag = "2")]
+        pub symbol: String,
+        #[prost(string, tag = "3")]
+        pub status: String,
+        #[prost(uint64, optional, tag = "4")]
+        pub last_message_age_ms: Option<u64>,
+        #[prost(double, tag = "5")]
+        pub messages_per_sec: f64,
+        #[prost(uint64, tag = "6")]
+        pub messages: u64,
+        #[prost(uint64, tag = "7")]
+        pub reconnects: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct HealthResponse {
+        #[prost(bool, tag = "1")]
+        pub healthy: bool,
+        #[prost(message, repeated, tag = "2")]
+        pub feeds: Vec<FeedHealth>,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/soqa.v1.MarketData.rs"));
+}
+
+use proto::market_data_server::{MarketData, MarketDataServer};
+
+fn nanos(timestamp: SystemTime) -> u64 {
+    timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+}
+
+fn levels(levels: &[(f64, f64)]) -> Vec<proto::Level> {
+    levels.iter().map(|&(price, size)| proto::Level { price, size }).collect()
+}
+
+fn l1(book: &OrderBookL1) -> proto::Event {
+    proto::Event {
+        exchange: book.exchange.clone(),
+        symbol: book.symbol.clone(),
+        timestamp_ns: nanos(book.timestamp),
+        payload: Some(proto::Payload::L1(proto::L1 {
+            bid: book.bid,
+            bid_volume: book.bid_volume,
+            ask: book.ask,
+            ask_volume: book.ask_volume,
+            source: book.source.as_str().to_string(),
+        })),
+    }
+}
+
+fn l2(book: &OrderBookL2) -> proto::Event {
+    proto::Event {
+        exchange: book.exchange.clone(),
+        symbol: book.symbol.clone(),
+        timestamp_ns: nanos(book.timestamp),
+        payload: Some(proto::Payload::L2(proto::L2 { bids: levels(&book.bids), asks: levels(&book.asks) })),
+    }
+}
+
+fn trade(trade: &Trade) -> proto::Event {
+    proto::Event {
+        exchange: trade.exchange.clone(),
+        symbol: trade.symbol.clone(),
+        timestamp_ns: nanos(trade.timestamp),
+        payload: Some(proto::Payload::Trade(proto::Trade {
+            price: trade.price,
+            volume: trade.volume,
+            side: trade.side.clone(),
+        })),
+    }
+}
+
+impl From<&MarketEvent> for proto::Event {
+    fn from(event: &MarketEvent) -> Self {
+        match event {
+            MarketEvent::L1(book) => l1(book),
+            MarketEvent::L2(book) => l2(book),
+            MarketEvent::Trade(t) => trade(t),
+        }
+    }
+}
+
+impl From<proto::SubscribeRequest> for EventFilter {
+    fn from(request: proto::SubscribeRequest) -> Self {
+        let channels = request
+            .channels()
+            .filter_map(|channel| match channel {
+                proto::Channel::L1 => Some(Channel::L1),
+                proto::Channel::L2 => Some(Channel::L2),
+                proto::Channel::Trades => Some(Channel::Trades),
+                proto::Channel::Unspecified => None,
+            })
+            .collect();
+        EventFilter { exchanges: request.exchanges, symbols: request.symbols, channels }
+    }
+}
+
+pub struct MarketDataService {
+    manager: SubscriptionManager,
+    monitor: HealthMonitor,
+}
+
+impl MarketDataService {
+    pub fn new(manager: SubscriptionManager, monitor: HealthMonitor) -> Self {
+        MarketDataService { manager, monitor }
+    }
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<proto::Event, Status>> + Send>>;
+
+// A venue's instrument list, marking the ones being streamed. Venues spell symbols their own way
+// (`BTC-USDT`, `XBT/USD`) while markets are keyed by the configured symbol, so both are compared
+// in canonical form.
+pub fn venue_instruments(
+    exchange: &str,
+    instruments: Vec<Instrument>,
+    markets: &[(String, String)],
+) -> Vec<proto::Instrument> {
+    instruments
+        .into_iter()
+        .map(|i| {
+            let symbol = canonical_symbol(&i.symbol);
+            proto::Instrument {
+                streaming: markets.iter().any(|(e, s)| e == exchange && canonical_symbol(s) == symbol),
+                exchange: exchange.to_string(),
+                symbol: i.symbol,
+                base: i.base,
+                quote: i.quote,
+            }
+        })
+        .collect()
+}
+
+#[tonic::async_trait]
+impl MarketData for MarketDataService {
+    type SubscribeStream = EventStream;
+
+    async fn subscribe(&self, request: Request<proto::SubscribeRequest>) -> Result<Response<EventStream>, Status> {
+        let filter = EventFilter::from(request.into_inner());
+        let events = self.manager.subscribe();
+        let stream = futures_util::stream::unfold((events, filter), |(mut events, filter)| async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) if filter.matches(&event) => return Some((Ok(proto::Event::from(&event)), (events, filter))),
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(target: "soqa::api", skipped, "gRPC subscriber lagging, events dropped");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_snapshot(&self, request: Request<proto::SnapshotRequest>) -> Result<Response<proto::Snapshot>, Status> {
+        let request = request.into_inner();
+        let snapshot = self
+            .manager
+            .snapshot(&request.exchange, &request.symbol)
+            .ok_or_else(|| Status::not_found(format!("no data for {} {}", request.exchange, request.symbol)))?;
+        Ok(Response::new(proto::Snapshot {
+            l1: snapshot.l1.as_ref().map(l1),
+            l2: snapshot.l2.as_ref().map(l2),
+            last_trade: snapshot.last_trade.as_ref().map(trade),
+            exchange: snapshot.exchange,
+            symbol: snapshot.symbol,
+        }))
+    }
+
+    async fn list_instruments(
+        &self,
+        request: Request<proto::ListInstrumentsRequest>,
+    ) -> Result<Response<proto::ListInstrumentsResponse>, Status> {
+        let exchange = request.into_inner().exchange;
+        let markets = self.manager.markets();
+        let instruments = if exchange.is_empty() {
+            markets
+                .into_iter()
+                .map(|(exchange, symbol)| proto::Instrument { exchange, symbol, streaming: true, ..Default::default() })
+                .collect()
+        } else {
+            let instruments = crate::exchanges::instruments::fetch_instruments(&exchange)
+                .await
+                .map_err(|e| Status::unavailable(e.to_string()))?;
+            venue_instruments(&exchange, instruments, &markets)
+        };
+        Ok(Response::new(proto::ListInstrumentsResponse { instruments }))
+    }
+
+    async fn health(&self, _request: Request<proto::HealthRequest>) -> Result<Response<proto::HealthResponse>, Status> {
+        let feeds = self
+            .monitor
+            .snapshot()
+            .into_iter()
+            .map(|feed| proto::FeedHealth {
+                status: serde_json::to_value(feed.status).ok().and_then(|s| s.as_str().map(str::to_string)).unwrap_or_default(),
+                exchange: feed.exchange,
+                symbol: feed.symbol,
+                last_message_age_ms: feed.last_message_age_ms,
+                messages_per_sec: feed.messages_per_sec,
+                messages: feed.messages,
+                reconnects: feed.reconnects,
+            })
+            .collect();
+        Ok(Response::new(proto::HealthResponse { healthy: self.monitor.is_healthy(), feeds }))
+    }
+}
+
+pub fn grpc_service(manager: SubscriptionManager, monitor: HealthMonitor) -> MarketDataServer<MarketDataService> {
+    MarketDataServer::new(MarketDataService::new(manager, monitor))
+}
+
+// Serves the gRPC gateway on `listener` until the process exits.
+pub async fn serve(
+    listener: tokio::net::TcpListener,
+    manager: SubscriptionManager,
+    monitor: HealthMonitor,
+) -> Result<(), SoqaError> {
+    serve_with(listener, manager, monitor, ApiKeys::default(), Shutdown::default()).await
+}
+
+// Like `serve`, but requires one of `keys` in the `x-api-key` or `authorization: Bearer`
+// metadata and stops when `shutdown` fires.
+pub async fn serve_with(
+    listener: tokio::net::TcpListener,
+    manager: SubscriptionManager,
+    monitor: HealthMonitor,
+    keys: ApiKeys,
+    shutdown: Shutdown,
+) -> Result<(), SoqaError> {
+    let addr: Option<SocketAddr> = listener.local_addr().ok();
+    tracing::info!(target: "soqa::api", ?addr, "gRPC gateway listening");
+    let check = move |request: Request<()>| {
+        let metadata = request.metadata();
+        let api_key = metadata.get("x-api-key").and_then(|v| v.to_str().ok());
+        let authorization = metadata.get("authorization").and_then(|v| v.to_str().ok());
+        if keys.allows(ApiKeys::presented(api_key, authorization)) {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("missing or unknown API key"))
+        }
+    };
+    tonic::transport::Server::builder()
+        .add_service(MarketDataServer::with_interceptor(MarketDataService::new(manager, monitor), check))
+        .serve_with_incoming_shutdown(tonic::transport::server::TcpIncoming::from(listener), async move {
+            shutdown.wait().await
+        })
+        .await
+        .map_err(|e| SoqaError::ConnectionError(e.to_string()))
+}
@@ -1,3 +1,4 @@
+use crate::health::HealthMonitor;
 use warp::Filter;
 use serde::{Deserialize, Serialize};
 
@@ -7,16 +8,27 @@ pub struct ApiResponse {
     pub data: serde_json::Value,
 }
 
-pub fn rest_routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    let health_check = warp::path("health")
+pub fn rest_routes(monitor: HealthMonitor) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("health")
         .and(warp::get())
-        .map(|| {
+        .map(move || {
             let response = ApiResponse {
-                status: "ok".to_string(),
-                data: serde_json::json!({}),
+                status: if monitor.is_healthy() { "ok" } else { "degraded" }.to_string(),
+                data: serde_json::json!({ "feeds": monitor.snapshot() }),
             };
             warp::reply::json(&response)
-        });
+        })
+}
 
-    health_check
-}   
\ No newline at end of file
+#[cfg(feature = "metrics")]
+pub fn metrics_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("metrics")
+        .and(warp::get())
+        .map(|| {
+            warp::reply::with_header(
+                crate::metrics::gather(),
+                "content-type",
+                "text/plain; version=0.0.4",
+            )
+        })
+}
@@ -1,5 +1,11 @@
+use crate::codec::Encoding;
+use crate::models::MarketEvent;
 use warp::Filter;
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::warn;
+use crate::conflate::{ConflateExt, Conflation};
+use futures_util::stream::{self, Stream};
 use futures_util::{StreamExt, SinkExt};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -10,36 +16,92 @@ pub struct WebSocketMessage {
     pub data: serde_json::Value,
 }
 
-pub fn websocket_route() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    warp::path("ws")
-        .and(warp::ws())
-        .map(|ws: warp::ws::Ws| {
-            ws.on_upgrade(|websocket| async move {
-                let (mut ws_sender, mut ws_receiver) = websocket.split();
-                
-                while let Some(result) = ws_receiver.next().await {
-                    match result {
-                        Ok(msg) => {
-                            if let Ok(text) = msg.to_str() {
-                                // Здесь можно добавить обработку входящих сообщений
-                                let response = WebSocketMessage {
-                                    exchange: "kraken".to_string(),
-                                    symbol: "BTCUSD".to_string(),
-                                    message_type: "response".to_string(),
-                                    data: serde_json::json!({}),
-                                };
-                                
-                                if let Ok(response_text) = serde_json::to_string(&response) {
-                                    let _ = ws_sender.send(warp::ws::Message::text(response_text)).await;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("WebSocket error: {}", e);
-                            break;
-                        }
-                    }
-                }
+#[derive(Debug, Default, Deserialize)]
+pub struct StreamOptions {
+    #[serde(default)]
+    pub encoding: Encoding,
+    // Per-client L1 conflation, see `conflate::Conflation`.
+    pub conflate_ms: Option<u64>,
+    pub conflate_bps: Option<f64>,
+}
+
+// `/ws` streams every event published on `events`; `/ws?encoding=binary` switches to binary frames
+// and `conflate_ms`/`conflate_bps` thin out L1 quotes for this client.
+pub fn websocket_route(
+    events: broadcast::Sender<MarketEvent>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("ws").and(websocket_upgrade(events))
+}
+
+// The upgrade without the path, for mounting behind other filters such as the API-key check.
+pub fn websocket_upgrade(
+    events: broadcast::Sender<MarketEvent>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::ws()
+        .and(warp::query::<StreamOptions>())
+        .map(move |ws: warp::ws::Ws, options: StreamOptions| {
+            let events = events.subscribe();
+            ws.on_upgrade(move |websocket| async move {
+                crate::metrics::api_client_connected();
+                let conflation = Conflation::from_options(options.conflate_ms, options.conflate_bps);
+                stream_events(websocket, events, options.encoding, conflation).await;
+                crate::metrics::api_client_disconnected();
             })
         })
-}   
\ No newline at end of file
+}
+
+// The broadcast as a stream, skipping what a lagging client missed.
+fn broadcast_stream(events: broadcast::Receiver<MarketEvent>) -> impl Stream<Item = MarketEvent> {
+    stream::unfold(events, |mut events| async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => return Some((event, events)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(target: "soqa::api", skipped, "client lagging, events dropped");
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+async fn stream_events(
+    websocket: warp::ws::WebSocket,
+    events: broadcast::Receiver<MarketEvent>,
+    encoding: Encoding,
+    conflation: Option<Conflation>,
+) {
+    let (mut ws_sender, mut ws_receiver) = websocket.split();
+    let mut events = match conflation {
+        Some(conflation) => broadcast_stream(events).conflate(conflation).boxed(),
+        None => broadcast_stream(events).boxed(),
+    };
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                let message = match event {
+                    Some(event) => match encoding {
+                        Encoding::Json => match serde_json::to_string(&event) {
+                            Ok(text) => warp::ws::Message::text(text),
+                            Err(_) => continue,
+                        },
+                        Encoding::Binary => warp::ws::Message::binary(encoding.encode(&event)),
+                    },
+                    None => break,
+                };
+                if ws_sender.send(message).await.is_err() {
+                    break;
+                }
+            }
+            incoming = ws_receiver.next() => match incoming {
+                Some(Ok(msg)) if msg.is_close() => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    warn!(target: "soqa::api", error = %e, "client WebSocket error");
+                    break;
+                }
+                None => break,
+            },
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::models::{MarketEvent, OrderBookL1, OrderBookL2};
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+// "BTC-USDT", "XBT/USDT" and "btcusdt" all map to "BTCUSDT".
+pub fn canonical_symbol(symbol: &str) -> String {
+    let symbol: String = symbol
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_uppercase();
+    match symbol.strip_prefix("XBT") {
+        Some(rest) => format!("BTC{}", rest),
+        None => symbol,
+    }
+}
+
+pub fn default_taker_fee_bps(exchange: &str) -> f64 {
+    match exchange {
+        "binance" => 10.0,
+        "bybit" => 10.0,
+        "okx" => 10.0,
+        "kraken" => 40.0,
+        "kucoin" => 10.0,
+        _ => 10.0,
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FeeModel {
+    // Taker fee per venue in basis points; venues not listed use `default_taker_fee_bps`.
+    pub taker_fees_bps: HashMap<String, f64>,
+    // Fixed cost in quote currency of moving inventory out of the venue bought on.
+    pub withdrawal_costs: HashMap<String, f64>,
+    // Minimum net edge after fees for an opportunity to be reported.
+    pub threshold_bps: f64,
+    // Venue books older than this, next to the newest event, are dropped along with the
+    // opportunities that used them. `None` keeps them until they are replaced.
+    pub max_quote_age: Option<Duration>,
+}
+
+impl FeeModel {
+    pub fn taker_fee(&self, exchange: &str) -> f64 {
+        self.taker_fees_bps
+            .get(exchange)
+            .copied()
+            .unwrap_or_else(|| default_taker_fee_bps(exchange))
+            / 10_000.0
+    }
+
+    pub fn withdrawal_cost(&self, exchange: &str) -> f64 {
+        self.withdrawal_costs.get(exchange).copied().unwrap_or(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpportunityStatus {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArbOpportunity {
+    pub instrument: String,
+    pub buy_exchange: String,
+    pub sell_exchange: String,
+    pub buy_price: f64,
+    pub sell_price: f64,
+    pub size: f64,
+    pub edge_bps: f64,
+    pub expected_profit: f64,
+    pub status: OpportunityStatus,
+    pub opened_at: SystemTime,
+    pub duration: Duration,
+}
+
+// Executable fill of buying on one venue and selling on another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fill {
+    pub size: f64,
+    pub buy_price: f64,
+    pub sell_price: f64,
+}
+
+// Walks asks of the buy venue against bids of the sell venue while each marginal
+// unit is still profitable after taker fees; prices are volume-weighted.
+pub fn executable_fill(asks: &[(f64, f64)], bids: &[(f64, f64)], buy_fee: f64, sell_fee: f64) -> Option<Fill> {
+    let (mut i, mut j) = (0, 0);
+    let mut ask_left = asks.first()?.1;
+    let mut bid_left = bids.first()?.1;
+    let (mut size, mut cost, mut proceeds) = (0.0, 0.0, 0.0);
+    while i < asks.len() && j < bids.len() {
+        let (ask, _) = asks[i];
+        let (bid, _) = bids[j];
+        if bid * (1.0 - sell_fee) <= ask * (1.0 + buy_fee) {
+            break;
+        }
+        let qty = ask_left.min(bid_left);
+        size += qty;
+        cost += qty * ask;
+        proceeds += qty * bid;
+        ask_left -= qty;
+        bid_left -= qty;
+        if ask_left <= 0.0 {
+            i += 1;
+            ask_left = asks.get(i).map(|level| level.1).unwrap_or(0.0);
+        }
+        if bid_left <= 0.0 {
+            j += 1;
+            bid_left = bids.get(j).map(|level| level.1).unwrap_or(0.0);
+        }
+    }
+    (size > 0.0).then(|| Fill { size, buy_price: cost / size, sell_price: proceeds / size })
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct VenueBook {
+    pub(crate) bids: Vec<(f64, f64)>,
+    pub(crate) asks: Vec<(f64, f64)>,
+    pub(crate) timestamp: SystemTime,
+}
+
+impl From<&OrderBookL1> for VenueBook {
+    fn from(book: &OrderBookL1) -> Self {
+        VenueBook {
+            bids: vec![(book.bid, book.bid_volume)],
+            asks: vec![(book.ask, book.ask_volume)],
+            timestamp: book.timestamp,
+        }
+    }
+}
+
+impl From<&OrderBookL2> for VenueBook {
+    fn from(book: &OrderBookL2) -> Self {
+        VenueBook { bids: book.bids.clone(), asks: book.asks.clone(), timestamp: book.timestamp }
+    }
+}
+
+// Watches the latest book of each venue per canonical instrument and reports
+// cross-venue opportunities while they last, plus a final `Closed` event.
+#[derive(Debug, Clone)]
+pub struct ArbitrageDetector {
+    fees: FeeModel,
+    books: HashMap<String, HashMap<String, VenueBook>>,
+    open: HashMap<(String, String, String), SystemTime>,
+    latest: SystemTime,
+}
+
+impl ArbitrageDetector {
+    pub fn new(fees: FeeModel) -> Self {
+        ArbitrageDetector { fees, books: HashMap::new(), open: HashMap::new(), latest: SystemTime::UNIX_EPOCH }
+    }
+
+    // Drops venue books that have gone quiet for longer than `max_quote_age`, e.g. after a
+    // disconnect, and closes the opportunities that relied on them.
+    fn expire(&mut self) -> Vec<ArbOpportunity> {
+        let mut closed = Vec::new();
+        let Some(cutoff) = self.fees.max_quote_age.and_then(|age| self.latest.checked_sub(age)) else {
+            return closed;
+        };
+        for (instrument, venues) in &mut self.books {
+            let stale: Vec<String> =
+                venues.iter().filter(|(_, book)| book.timestamp < cutoff).map(|(exchange, _)| exchange.clone()).collect();
+            for exchange in stale {
+                let keys: Vec<_> = self
+                    .open
+                    .keys()
+                    .filter(|(i, buy, sell)| i == instrument && (*buy == exchange || *sell == exchange))
+                    .cloned()
+                    .collect();
+                for key in keys {
+                    let opened_at = self.open.remove(&key).unwrap();
+                    let (_, buy, sell) = &key;
+                    closed.push(closed_opportunity(instrument, buy, sell, &venues[buy], &venues[sell], opened_at, self.latest));
+                }
+                venues.remove(&exchange);
+            }
+        }
+        closed
+    }
+
+    pub fn on_event(&mut self, event: &MarketEvent) -> Vec<ArbOpportunity> {
+        let book = match event {
+            MarketEvent::L1(book) => VenueBook::from(book),
+            MarketEvent::L2(book) => VenueBook::from(book),
+            MarketEvent::Trade(_) => return Vec::new(),
+        };
+        let instrument = canonical_symbol(event.symbol());
+        let exchange = event.exchange().to_string();
+        let now = book.timestamp;
+        self.latest = self.latest.max(now);
+        let mut opportunities = self.expire();
+        let venues = self.books.entry(instrument.clone()).or_default();
+        venues.insert(exchange.clone(), book);
+
+        for other in venues.keys().filter(|other| **other != exchange) {
+            for (buy, sell) in [(&exchange, other), (other, &exchange)] {
+                let key = (instrument.clone(), buy.clone(), sell.clone());
+                match evaluate(&self.fees, &instrument, buy, sell, &venues[buy], &venues[sell]) {
+                    Some(mut opportunity) => {
+                        let opened_at = *self.open.entry(key).or_insert(now);
+                        opportunity.opened_at = opened_at;
+                        opportunity.duration = now.duration_since(opened_at).unwrap_or_default();
+                        opportunities.push(opportunity);
+                    }
+                    None => {
+                        if let Some(opened_at) = self.open.remove(&key) {
+                            opportunities.push(closed_opportunity(&instrument, buy, sell, &venues[buy], &venues[sell], opened_at, now));
+                        }
+                    }
+                }
+            }
+        }
+        opportunities
+    }
+}
+
+fn closed_opportunity(
+    instrument: &str,
+    buy: &str,
+    sell: &str,
+    buy_book: &VenueBook,
+    sell_book: &VenueBook,
+    opened_at: SystemTime,
+    now: SystemTime,
+) -> ArbOpportunity {
+    ArbOpportunity {
+        instrument: instrument.to_string(),
+        buy_exchange: buy.to_string(),
+        sell_exchange: sell.to_string(),
+        buy_price: buy_book.asks.first().map(|l| l.0).unwrap_or(0.0),
+        sell_price: sell_book.bids.first().map(|l| l.0).unwrap_or(0.0),
+        size: 0.0,
+        edge_bps: 0.0,
+        expected_profit: 0.0,
+        status: OpportunityStatus::Closed,
+        opened_at,
+        duration: now.duration_since(opened_at).unwrap_or_default(),
+    }
+}
+
+fn evaluate(fees: &FeeModel, instrument: &str, buy: &str, sell: &str, buy_book: &VenueBook, sell_book: &VenueBook) -> Option<ArbOpportunity> {
+    let buy_fee = fees.taker_fee(buy);
+    let sell_fee = fees.taker_fee(sell);
+    let fill = executable_fill(&buy_book.asks, &sell_book.bids, buy_fee, sell_fee)?;
+    let net_per_unit = fill.sell_price * (1.0 - sell_fee) - fill.buy_price * (1.0 + buy_fee);
+    let expected_profit = net_per_unit * fill.size - fees.withdrawal_cost(buy);
+    let edge_bps = net_per_unit / fill.buy_price * 10_000.0;
+    if expected_profit <= 0.0 || edge_bps < fees.threshold_bps {
+        return None;
+    }
+    Some(ArbOpportunity {
+        instrument: instrument.to_string(),
+        buy_exchange: buy.to_string(),
+        sell_exchange: sell.to_string(),
+        buy_price: fill.buy_price,
+        sell_price: fill.sell_price,
+        size: fill.size,
+        edge_bps,
+        expected_profit,
+        status: OpportunityStatus::Open,
+        opened_at: buy_book.timestamp.max(sell_book.timestamp),
+        duration: Duration::ZERO,
+    })
+}
+
+pub trait ArbitrageExt: Stream<Item = MarketEvent> + Sized {
+    fn arbitrage(self, fees: FeeModel) -> impl Stream<Item = ArbOpportunity> {
+        self.scan(ArbitrageDetector::new(fees), |detector, event| {
+            future::ready(Some(detector.on_event(&event)))
+        })
+        .flat_map(stream::iter)
+    }
+}
+
+impl<S: Stream<Item = MarketEvent>> ArbitrageExt for S {}
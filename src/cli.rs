@@ -1,7 +1,21 @@
+use crate::analytics::SignalKind;
+use crate::codec::Encoding;
+use crate::delivery::OverflowPolicy;
+use crate::logging::LogFormat;
+use crate::merge::LatePolicy;
+use crate::output::OutputFormat;
+use crate::query::Report;
+use crate::subscriptions::Channel;
 use clap::{Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 pub struct Cli {
+    #[arg(long, global = true, default_value = "info")]
+    pub log_level: String,
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    pub log_format: LogFormat,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -9,12 +23,198 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     Start {
+        #[arg(long, value_delimiter = ',', required = true)]
+        exchange: Vec<String>,
+        // Symbols, globs such as '*USDT', or regexes such as '/^(BTC|ETH)/', matched against each
+        // venue's instrument list.
+        #[arg(long, value_delimiter = ',', required = true)]
+        symbol: Vec<String>,
+        // Refuse to start when the symbols expand to more markets than this, across all venues.
+        #[arg(long, default_value_t = 100)]
+        max_symbols: usize,
+        // With several feeds, how long to hold events so they come out in exchange-time order.
+        #[arg(long, default_value_t = 100)]
+        lateness_ms: u64,
+        // What to do with events that arrive after the watermark has passed them.
+        #[arg(long, value_enum, default_value = "emit")]
+        late: LatePolicy,
+        // Events queued between the connections and the output, across all feeds.
+        #[arg(long, default_value_t = 1024)]
+        queue_capacity: usize,
+        // What to do when that queue is full.
+        #[arg(long, value_enum, default_value = "block")]
+        overflow: OverflowPolicy,
+        #[arg(long, value_enum, ignore_case = true, default_value = "L1")]
+        level: Channel,
+        // Take L1 from each venue's best bid/offer channel, e.g. Binance @bookTicker.
+        #[arg(long, conflicts_with = "adapter")]
+        bbo: bool,
+        // Declarative adapter spec to use instead of a built-in client (L1 only).
+        #[arg(long)]
+        adapter: Option<String>,
+        #[arg(long, value_enum, default_value = "table")]
+        output_format: OutputFormat,
+        // Emit at most the latest L1 quote per market every this many milliseconds.
+        #[arg(long)]
+        conflate_ms: Option<u64>,
+        // With conflation, emit a quote at once when its mid moves at least this far.
+        #[arg(long)]
+        conflate_bps: Option<f64>,
+        #[arg(long, value_enum, value_delimiter = ',')]
+        analytics: Vec<SignalKind>,
+        #[arg(long, default_value_t = 60)]
+        window_secs: u64,
+        // Book levels per side for the `imbalance` signal (default 5). L1 quotes only have one, so this
+        // needs --level L2.
+        #[arg(long)]
+        levels: Option<usize>,
+        // Also publish events to a shared-memory ring at this path (needs the `shm` feature).
+        #[arg(long)]
+        shm: Option<String>,
+        // Also publish events to a message bus or cache: nats://host:4222, kafka://host:9092[,host:9092]
+        // or redis://host:6379 (needs the matching feature), or record quotes, trades and L2
+        // history to a .db or .parquet file.
+        // The --sink-* options below are refused for sinks they do not apply to.
+        #[arg(long)]
+        sink: Option<String>,
+        // NATS and Kafka message encoding; defaults to json.
+        #[arg(long, value_enum)]
+        sink_encoding: Option<Encoding>,
+        // Topic or key prefix for NATS, Kafka and Redis; defaults to soqa.
+        #[arg(long)]
+        sink_prefix: Option<String>,
+        // Expire cached Redis quotes this long after their last update.
+        #[arg(long)]
+        sink_ttl_secs: Option<u64>,
+        // Keep the newest trades per market in Redis, this many of them.
+        #[arg(long)]
+        sink_max_trades: Option<usize>,
+        // How often a .db or .parquet sink writes each L2 book whole, deltas in between; defaults to 60.
+        #[arg(long)]
+        sink_snapshot_secs: Option<u64>,
+    },
+    Arb {
+        #[arg(long, value_delimiter = ',', required = true)]
+        exchange: Vec<String>,
+        #[arg(long)]
+        symbol: String,
+        // Per-venue symbol when it differs from --symbol, e.g. okx=BTC-USDT.
+        #[arg(long = "venue-symbol", value_parser = parse_key_value::<String>)]
+        venue_symbols: Vec<(String, String)>,
+        #[arg(long, default_value_t = 0.0)]
+        threshold_bps: f64,
+        #[arg(long = "fee", value_parser = parse_key_value::<f64>)]
+        fees: Vec<(String, f64)>,
+        #[arg(long = "withdrawal-cost", value_parser = parse_key_value::<f64>)]
+        withdrawal_costs: Vec<(String, f64)>,
+        #[arg(long)]
+        bbo: bool,
+        // Hold quotes this long so venues are compared in exchange-time order.
+        #[arg(long, default_value_t = 100)]
+        lateness_ms: u64,
+        // Ignore a venue's quote once it is this much older than the newest one, e.g. after a disconnect.
+        #[arg(long, default_value_t = 5000)]
+        max_quote_age_ms: u64,
+        #[arg(long, value_enum, default_value = "table")]
+        output_format: OutputFormat,
+    },
+    Tri {
+        #[arg(long)]
+        exchange: String,
+        // Restrict the currency graph to these assets, e.g. BTC,ETH,USDT.
+        #[arg(long, value_delimiter = ',')]
+        assets: Vec<String>,
+        #[arg(long, default_value_t = 0.0)]
+        threshold_bps: f64,
+        #[arg(long)]
+        fee_bps: Option<f64>,
+        #[arg(long, value_enum, default_value = "table")]
+        output_format: OutputFormat,
+    },
+    Download {
         #[arg(long)]
         exchange: String,
         #[arg(long)]
         symbol: String,
-        #[arg(long, default_value = "L1")]
-        level: String,
+        // Unix milliseconds; defaults to one hour before --end.
+        #[arg(long)]
+        start: Option<u64>,
+        // Unix milliseconds; defaults to now.
+        #[arg(long)]
+        end: Option<u64>,
+        #[arg(long)]
+        output: String,
+        // Binance: use the keyed historicalTrades endpoint instead of aggTrades.
+        #[arg(long)]
+        api_key: Option<String>,
+        // Takes REST snapshots of the order book instead of downloading trades; --start and --end
+        // do not apply.
+        #[arg(long)]
+        book: bool,
+        // Book levels per side.
+        #[arg(long, default_value_t = 100)]
+        depth: usize,
+        #[arg(long, default_value_t = 1)]
+        snapshots: u64,
+        // Milliseconds between book snapshots.
+        #[arg(long, default_value_t = 1000)]
+        interval_ms: u64,
+    },
+    // Runs the REST/WebSocket gateway with the feeds and sinks listed in --config. The flags
+    // override the file.
+    Serve {
+        #[arg(long)]
+        config: Option<String>,
+        #[arg(long)]
+        bind: Option<SocketAddr>,
+        // Needs the `grpc` feature.
+        #[arg(long)]
+        grpc_bind: Option<SocketAddr>,
+        // PEM certificate chain and private key; needs the `tls` feature.
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        #[arg(long = "cors-origin")]
+        cors_origins: Vec<String>,
+        #[arg(long = "api-key")]
+        api_keys: Vec<String>,
+    },
+    // Reports on quotes and trades recorded by `start --sink` (or trades saved by `download` to
+    // SQLite), read from the .db or .parquet file.
+    Query {
+        #[arg(value_enum)]
+        report: Report,
+        #[arg(long)]
+        file: String,
+        #[arg(long)]
+        exchange: Option<String>,
+        #[arg(long)]
+        symbol: Option<String>,
+        // Unix milliseconds; the start is inclusive, the end exclusive.
+        #[arg(long)]
+        start: Option<u64>,
+        #[arg(long)]
+        end: Option<u64>,
+        // Candle length for `ohlcv`.
+        #[arg(long, default_value_t = 60)]
+        interval_secs: u64,
+        #[arg(long, value_enum, default_value = "table")]
+        output_format: OutputFormat,
+    },
+    // Rebuilds a recorded L2 book (see `start --sink`) as of a point in time.
+    Book {
+        #[arg(long)]
+        file: String,
+        #[arg(long)]
+        exchange: String,
+        #[arg(long)]
+        symbol: String,
+        // Unix milliseconds; defaults to now, i.e. the last recorded state.
+        #[arg(long)]
+        at: Option<u64>,
+        #[arg(long, value_enum, default_value = "table")]
+        output_format: OutputFormat,
     },
     Export {
         #[arg(long)]
@@ -24,4 +224,14 @@ pub enum Commands {
         #[arg(long)]
         output: String,
     },
-}
\ No newline at end of file
+}
+
+// Parses `venue=value` arguments.
+fn parse_key_value<T: std::str::FromStr>(arg: &str) -> Result<(String, T), String>
+where
+    T::Err: std::fmt::Display,
+{
+    let (key, value) = arg.split_once('=').ok_or_else(|| format!("expected venue=value, got {}", arg))?;
+    let value = value.parse().map_err(|e| format!("invalid value for {}: {}", key, e))?;
+    Ok((key.to_string(), value))
+}
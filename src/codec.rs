@@ -0,0 +1,273 @@
+// Compact binary encoding of `MarketEvent`s; the layout is described in docs/binary-encoding.md.
+// Every record is self-delimiting and carries its own version, so records can be concatenated
+// in files or sent one per WebSocket frame.
+
+use crate::error::SoqaError;
+use crate::models::{MarketEvent, OrderBookL1, OrderBookL2, QuoteSource, Trade};
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime};
+
+pub const VERSION: u8 = 1;
+
+const KIND_L1: u8 = 1;
+const KIND_L2: u8 = 2;
+const KIND_TRADE: u8 = 3;
+
+const SOURCE_TICKER: u8 = 0;
+const SOURCE_BOOK: u8 = 1;
+const SOURCE_BBO: u8 = 2;
+
+const SIDE_BUY: u8 = 0;
+const SIDE_SELL: u8 = 1;
+const SIDE_UNKNOWN: u8 = 2;
+
+// Payload format for the gateway and message bus sinks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    // `MarketEvent` as JSON.
+    #[default]
+    Json,
+    // One record in the format below.
+    Binary,
+}
+
+impl Encoding {
+    pub fn encode(&self, event: &MarketEvent) -> Vec<u8> {
+        match self {
+            Encoding::Json => serde_json::to_vec(event).unwrap_or_default(),
+            Encoding::Binary => encode_to_vec(event),
+        }
+    }
+}
+
+// Length prefix (u32) + version + kind + timestamp.
+const HEADER_LEN: usize = 4 + 1 + 1 + 8;
+
+// Twice the largest record `encode` produces (an L2 book with two full sides of u16::MAX levels),
+// leaving room for appended fields. Readers refuse longer length prefixes rather than allocate them.
+pub const MAX_RECORD_LEN: usize = 4 << 20;
+
+// Strings longer than 255 bytes are cut at the last character boundary that fits.
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    let mut len = s.len().min(u8::MAX as usize);
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    buf.push(len as u8);
+    buf.extend_from_slice(&s.as_bytes()[..len]);
+}
+
+fn put_levels(buf: &mut Vec<u8>, levels: &[(f64, f64)]) {
+    let levels = &levels[..levels.len().min(u16::MAX as usize)];
+    buf.extend_from_slice(&(levels.len() as u16).to_le_bytes());
+    for (price, size) in levels {
+        buf.extend_from_slice(&price.to_le_bytes());
+        buf.extend_from_slice(&size.to_le_bytes());
+    }
+}
+
+fn nanos(timestamp: SystemTime) -> u64 {
+    timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+}
+
+// Appends one record to `buf`.
+pub fn encode(event: &MarketEvent, buf: &mut Vec<u8>) {
+    let start = buf.len();
+    buf.extend_from_slice(&[0; 4]);
+    buf.push(VERSION);
+    buf.push(match event {
+        MarketEvent::L1(_) => KIND_L1,
+        MarketEvent::L2(_) => KIND_L2,
+        MarketEvent::Trade(_) => KIND_TRADE,
+    });
+    buf.extend_from_slice(&nanos(event.timestamp()).to_le_bytes());
+    put_str(buf, event.exchange());
+    put_str(buf, event.symbol());
+    match event {
+        MarketEvent::L1(book) => {
+            for value in [book.bid, book.bid_volume, book.ask, book.ask_volume] {
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+            buf.push(match book.source {
+                QuoteSource::Ticker => SOURCE_TICKER,
+                QuoteSource::Book => SOURCE_BOOK,
+                QuoteSource::Bbo => SOURCE_BBO,
+            });
+        }
+        MarketEvent::L2(book) => {
+            put_levels(buf, &book.bids);
+            put_levels(buf, &book.asks);
+        }
+        MarketEvent::Trade(trade) => {
+            buf.extend_from_slice(&trade.price.to_le_bytes());
+            buf.extend_from_slice(&trade.volume.to_le_bytes());
+            buf.push(match trade.side.to_ascii_lowercase().as_str() {
+                "buy" => SIDE_BUY,
+                "sell" => SIDE_SELL,
+                _ => SIDE_UNKNOWN,
+            });
+        }
+    }
+    let len = (buf.len() - start - 4) as u32;
+    buf[start..start + 4].copy_from_slice(&len.to_le_bytes());
+}
+
+pub fn encode_to_vec(event: &MarketEvent) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(64);
+    encode(event, &mut buf);
+    buf
+}
+
+struct Cursor<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], SoqaError> {
+        if self.buf.len() < n {
+            return Err(SoqaError::CodecError("truncated record".to_string()));
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, SoqaError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SoqaError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, SoqaError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, SoqaError> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Result<String, SoqaError> {
+        let len = self.u8()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|e| SoqaError::CodecError(e.to_string()))
+    }
+
+    fn levels(&mut self) -> Result<Vec<(f64, f64)>, SoqaError> {
+        (0..self.u16()?).map(|_| Ok((self.f64()?, self.f64()?))).collect()
+    }
+}
+
+// Decodes the record at the start of `buf` and returns it with the number of bytes consumed.
+pub fn decode(buf: &[u8]) -> Result<(MarketEvent, usize), SoqaError> {
+    if buf.len() < HEADER_LEN {
+        return Err(SoqaError::CodecError("truncated record".to_string()));
+    }
+    let len = u32::from_le_bytes(buf[..4].try_into().unwrap()) as usize;
+    if len > MAX_RECORD_LEN {
+        return Err(SoqaError::CodecError(format!("record length {} exceeds {}", len, MAX_RECORD_LEN)));
+    }
+    let mut cursor = Cursor { buf: buf.get(4..4 + len).ok_or_else(|| SoqaError::CodecError("truncated record".to_string()))? };
+    let version = cursor.u8()?;
+    if version != VERSION {
+        return Err(SoqaError::CodecError(format!("unsupported version {}", version)));
+    }
+    let kind = cursor.u8()?;
+    let timestamp = SystemTime::UNIX_EPOCH + Duration::from_nanos(cursor.u64()?);
+    let exchange = cursor.str()?;
+    let symbol = cursor.str()?;
+    let event = match kind {
+        KIND_L1 => MarketEvent::L1(OrderBookL1 {
+            exchange,
+            symbol,
+            bid: cursor.f64()?,
+            bid_volume: cursor.f64()?,
+            ask: cursor.f64()?,
+            ask_volume: cursor.f64()?,
+            timestamp,
+            // Absent in records written before the source was added.
+            source: match cursor.buf.first() {
+                Some(&SOURCE_BOOK) => QuoteSource::Book,
+                Some(&SOURCE_BBO) => QuoteSource::Bbo,
+                _ => QuoteSource::Ticker,
+            },
+        }),
+        KIND_L2 => MarketEvent::L2(OrderBookL2 { exchange, symbol, bids: cursor.levels()?, asks: cursor.levels()?, timestamp }),
+        KIND_TRADE => MarketEvent::Trade(Trade {
+            exchange,
+            symbol,
+            price: cursor.f64()?,
+            volume: cursor.f64()?,
+            side: match cursor.u8()? {
+                SIDE_BUY => "buy",
+                SIDE_SELL => "sell",
+                _ => "unknown",
+            }
+            .to_string(),
+            timestamp,
+        }),
+        other => return Err(SoqaError::CodecError(format!("unknown record kind {}", other))),
+    };
+    Ok((event, 4 + len))
+}
+
+pub struct EventWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> EventWriter<W> {
+    pub fn new(inner: W) -> Self {
+        EventWriter { inner, buf: Vec::with_capacity(256) }
+    }
+
+    pub fn write(&mut self, event: &MarketEvent) -> std::io::Result<()> {
+        self.buf.clear();
+        encode(event, &mut self.buf);
+        self.inner.write_all(&self.buf)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Reads records back until end of input.
+pub struct EventReader<R: Read> {
+    inner: R,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> EventReader<R> {
+    pub fn new(inner: R) -> Self {
+        EventReader { inner, buf: Vec::new() }
+    }
+
+    fn next_record(&mut self) -> Result<Option<MarketEvent>, SoqaError> {
+        let mut len = [0; 4];
+        match self.inner.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(SoqaError::CodecError(e.to_string())),
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_RECORD_LEN {
+            return Err(SoqaError::CodecError(format!("record length {} exceeds {}", len, MAX_RECORD_LEN)));
+        }
+        self.buf.clear();
+        self.buf.extend_from_slice(&(len as u32).to_le_bytes());
+        self.buf.resize(4 + len, 0);
+        self.inner.read_exact(&mut self.buf[4..]).map_err(|e| SoqaError::CodecError(e.to_string()))?;
+        decode(&self.buf).map(|(event, _)| Some(event))
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = Result<MarketEvent, SoqaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
@@ -1,9 +1,24 @@
+use crate::delivery::{Delivery, EventQueue};
+use crate::health::{FeedTracker, HealthMonitor};
+use crate::shutdown::Shutdown;
+
 #[derive(Debug)]
 pub struct Config {
     pub exchange: String,
+    // Names the feed in health reports and logs.
     pub symbol: String,
+    // Every symbol subscribed on the feed's one connection.
+    pub symbols: Vec<String>,
     pub api_key: Option<String>,
     pub api_secret: Option<String>,
+    pub health: Option<HealthMonitor>,
+    pub shutdown: Option<Shutdown>,
+    // How events are queued between the connection and the callback.
+    pub delivery: Delivery,
+    // A queue, possibly shared with other feeds, that takes the events in place of the callback.
+    pub queue: Option<EventQueue>,
+    // Take L1 from the venue's best bid/offer channel instead of its ticker or depth channel.
+    pub bbo: bool,
 }
 
 impl Config {
@@ -11,8 +26,54 @@ impl Config {
         Config {
             exchange: exchange.to_string(),
             symbol: symbol.to_string(),
+            symbols: vec![symbol.to_string()],
             api_key: None,
             api_secret: None,
+            health: None,
+            shutdown: None,
+            delivery: Delivery::default(),
+            queue: None,
+            bbo: false,
         }
     }
-}
\ No newline at end of file
+
+    // Carry all of `symbols` on one connection; the feed is then named after them joined by commas.
+    // Health tracks the connection under that name and each symbol's events under the symbol.
+    pub fn with_symbols(mut self, symbols: Vec<String>) -> Self {
+        self.symbol = symbols.join(",");
+        self.symbols = symbols;
+        self
+    }
+
+    pub fn with_health(mut self, monitor: HealthMonitor) -> Self {
+        self.health = Some(monitor);
+        self
+    }
+
+    // Feeds started with this config close their connection when `shutdown` fires.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    pub fn with_delivery(mut self, delivery: Delivery) -> Self {
+        self.delivery = delivery;
+        self
+    }
+
+    // Push events into `queue` instead of calling the callback, so whoever holds the queue pops
+    // them at its own pace and the queue's overflow policy applies when it falls behind.
+    pub fn with_queue(mut self, queue: EventQueue) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+
+    pub fn with_bbo(mut self, bbo: bool) -> Self {
+        self.bbo = bbo;
+        self
+    }
+
+    pub fn tracker(&self) -> FeedTracker {
+        FeedTracker::new(&self.exchange, &self.symbol, self.health.clone())
+    }
+}
@@ -0,0 +1,155 @@
+// Coalesces L1 quotes per (exchange, symbol) for consumers that only need the latest quote every
+// so often. The first quote of a market goes out at once; later ones are held and the newest is
+// emitted on the next tick, or at once when the mid has moved at least `min_change_bps` since the
+// last quote emitted. Other events pass through untouched.
+
+use crate::models::{MarketEvent, OrderBookL1};
+use futures_util::stream::{self, Stream, StreamExt};
+use std::collections::{BTreeMap, VecDeque};
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::time::{Interval, MissedTickBehavior};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Conflation {
+    // Without an interval only significant changes are emitted; the rest are dropped.
+    pub interval: Option<Duration>,
+    pub min_change_bps: Option<f64>,
+}
+
+impl Conflation {
+    pub fn every(interval: Duration) -> Self {
+        Conflation { interval: Some(interval), min_change_bps: None }
+    }
+
+    pub fn on_change(min_change_bps: f64) -> Self {
+        Conflation { interval: None, min_change_bps: Some(min_change_bps) }
+    }
+
+    pub fn with_min_change_bps(mut self, min_change_bps: f64) -> Self {
+        self.min_change_bps = Some(min_change_bps);
+        self
+    }
+
+    // From the `start` flags and `/ws` query parameters; zero or unset means off.
+    pub fn from_options(interval_ms: Option<u64>, min_change_bps: Option<f64>) -> Option<Self> {
+        let interval = interval_ms.filter(|ms| *ms > 0).map(Duration::from_millis);
+        let min_change_bps = min_change_bps.filter(|bps| *bps > 0.0);
+        (interval.is_some() || min_change_bps.is_some()).then_some(Conflation { interval, min_change_bps })
+    }
+}
+
+fn mid(book: &OrderBookL1) -> f64 {
+    (book.bid + book.ask) / 2.0
+}
+
+#[derive(Debug, Default)]
+struct Market {
+    // Mid of the last quote emitted.
+    emitted_mid: Option<f64>,
+    pending: Option<OrderBookL1>,
+}
+
+// The conflation state behind `ConflateExt`, for callers that drive the clock themselves.
+#[derive(Debug, Default)]
+pub struct Conflator {
+    conflation: Conflation,
+    markets: BTreeMap<(String, String), Market>,
+}
+
+impl Conflator {
+    pub fn new(conflation: Conflation) -> Self {
+        Conflator { conflation, markets: BTreeMap::new() }
+    }
+
+    // Returns the event if it should go out now; otherwise it is held (or dropped) until `tick`.
+    pub fn push(&mut self, event: MarketEvent) -> Option<MarketEvent> {
+        let MarketEvent::L1(book) = event else { return Some(event) };
+        let market = self.markets.entry((book.exchange.clone(), book.symbol.clone())).or_default();
+        let significant = match (market.emitted_mid, self.conflation.min_change_bps) {
+            (None, _) => true,
+            (Some(last), Some(bps)) => last <= 0.0 || ((mid(&book) - last) / last).abs() * 10_000.0 >= bps,
+            (Some(_), None) => false,
+        };
+        if significant {
+            market.emitted_mid = Some(mid(&book));
+            market.pending = None;
+            Some(MarketEvent::L1(book))
+        } else {
+            if self.conflation.interval.is_some() {
+                market.pending = Some(book);
+            }
+            None
+        }
+    }
+
+    // The newest held quote of every market that changed since it was last emitted.
+    pub fn tick(&mut self) -> Vec<MarketEvent> {
+        self.markets
+            .values_mut()
+            .filter_map(|market| {
+                let book = market.pending.take()?;
+                market.emitted_mid = Some(mid(&book));
+                Some(MarketEvent::L1(book))
+            })
+            .collect()
+    }
+}
+
+struct State<S> {
+    events: Pin<Box<S>>,
+    conflator: Conflator,
+    interval: Option<Interval>,
+    ready: VecDeque<MarketEvent>,
+    ended: bool,
+}
+
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+pub trait ConflateExt: Stream<Item = MarketEvent> + Sized {
+    // Held quotes are flushed when the input ends.
+    fn conflate(self, conflation: Conflation) -> impl Stream<Item = MarketEvent> {
+        let interval = conflation.interval.map(|every| {
+            let mut interval = tokio::time::interval(every);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval.reset();
+            interval
+        });
+        let state = State {
+            events: Box::pin(self),
+            conflator: Conflator::new(conflation),
+            interval,
+            ready: VecDeque::new(),
+            ended: false,
+        };
+        stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(event) = state.ready.pop_front() {
+                    return Some((event, state));
+                }
+                if state.ended {
+                    return None;
+                }
+                tokio::select! {
+                    event = state.events.next() => match event {
+                        Some(event) => state.ready.extend(state.conflator.push(event)),
+                        None => {
+                            state.ended = true;
+                            state.ready.extend(state.conflator.tick());
+                        }
+                    },
+                    _ = tick(&mut state.interval) => state.ready.extend(state.conflator.tick()),
+                }
+            }
+        })
+    }
+}
+
+impl<S: Stream<Item = MarketEvent>> ConflateExt for S {}
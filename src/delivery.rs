@@ -0,0 +1,226 @@
+// Bounded hand-off between a feed's socket loop and its callback, so a slow callback does not
+// stall the connection. What happens when the queue is full is up to the overflow policy.
+
+use crate::models::MarketEvent;
+use crate::subscriptions::Channel;
+use futures_util::{stream, Stream};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverflowPolicy {
+    // Wait for room. Nothing is lost, but the socket is not read meanwhile, and the venue may
+    // disconnect a consumer that stays behind.
+    #[default]
+    Block,
+    DropOldest,
+    DropNewest,
+    // When full, replace the queued L1 or L2 event of the same symbol and channel with the newer
+    // one, or else drop the oldest queued book event. Trades are never dropped; they wait for room.
+    Conflate,
+}
+
+impl OverflowPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverflowPolicy::Block => "block",
+            OverflowPolicy::DropOldest => "drop-oldest",
+            OverflowPolicy::DropNewest => "drop-newest",
+            OverflowPolicy::Conflate => "conflate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Delivery {
+    #[serde(default = "default_capacity")]
+    pub queue_capacity: usize,
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+}
+
+fn default_capacity() -> usize {
+    1024
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        Delivery { queue_capacity: default_capacity(), overflow: OverflowPolicy::default() }
+    }
+}
+
+impl Delivery {
+    pub fn new(queue_capacity: usize, overflow: OverflowPolicy) -> Self {
+        Delivery { queue_capacity: queue_capacity.max(1), overflow }
+    }
+}
+
+struct Inner {
+    events: VecDeque<MarketEvent>,
+    closed: bool,
+    producers: usize,
+}
+
+// Any number of producers, one consumer; clones share it.
+#[derive(Clone)]
+pub struct EventQueue {
+    inner: Arc<Mutex<Inner>>,
+    delivery: Delivery,
+    dropped: Arc<AtomicU64>,
+    items: Arc<Notify>,
+    space: Arc<Notify>,
+}
+
+impl std::fmt::Debug for EventQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventQueue").field("delivery", &self.delivery).field("len", &self.len()).finish()
+    }
+}
+
+fn same_stream(a: &MarketEvent, b: &MarketEvent) -> bool {
+    Channel::of(a) == Channel::of(b) && a.symbol() == b.symbol() && a.exchange() == b.exchange()
+}
+
+fn is_book(event: &MarketEvent) -> bool {
+    Channel::of(event) != Channel::Trades
+}
+
+impl EventQueue {
+    pub fn new(delivery: Delivery) -> Self {
+        EventQueue {
+            inner: Arc::new(Mutex::new(Inner { events: VecDeque::new(), closed: false, producers: 0 })),
+            delivery: Delivery::new(delivery.queue_capacity, delivery.overflow),
+            dropped: Arc::new(AtomicU64::new(0)),
+            items: Arc::new(Notify::new()),
+            space: Arc::new(Notify::new()),
+        }
+    }
+
+    // Returns whether an event (this one or a queued one) was dropped to make room. Events
+    // pushed after `close` are dropped silently.
+    pub async fn push(&self, event: MarketEvent) -> bool {
+        let mut event = Some(event);
+        loop {
+            let space = self.space.notified();
+            if let Some(dropped) = self.try_push(&mut event) {
+                return dropped;
+            }
+            space.await;
+        }
+    }
+
+    // `None` while blocked on a full queue; `event` is left in place for the retry.
+    fn try_push(&self, event: &mut Option<MarketEvent>) -> Option<bool> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return Some(false);
+        }
+        let mut dropped = false;
+        if inner.events.len() >= self.delivery.queue_capacity {
+            match self.delivery.overflow {
+                OverflowPolicy::Block => return None,
+                OverflowPolicy::DropNewest => return Some(self.record_drop()),
+                OverflowPolicy::DropOldest => {
+                    inner.events.pop_front();
+                    dropped = self.record_drop();
+                }
+                OverflowPolicy::Conflate => {
+                    let new = event.as_ref().unwrap();
+                    if !is_book(new) {
+                        return None;
+                    }
+                    // The newest match, so the stream's events stay in order.
+                    if let Some(queued) = inner.events.iter_mut().rev().find(|queued| same_stream(queued, new)) {
+                        *queued = event.take().unwrap();
+                        return Some(self.record_drop());
+                    }
+                    let oldest = inner.events.iter().position(is_book)?;
+                    inner.events.remove(oldest);
+                    dropped = self.record_drop();
+                }
+            }
+        }
+        inner.events.push_back(event.take().unwrap());
+        self.items.notify_one();
+        Some(dropped)
+    }
+
+    fn record_drop(&self) -> bool {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    // The next event, or `None` once the queue is closed and empty.
+    pub async fn pop(&self) -> Option<MarketEvent> {
+        loop {
+            let items = self.items.notified();
+            {
+                let mut inner = self.inner.lock().unwrap();
+                if let Some(event) = inner.events.pop_front() {
+                    self.space.notify_one();
+                    return Some(event);
+                }
+                if inner.closed {
+                    return None;
+                }
+            }
+            items.await;
+        }
+    }
+
+    // Feeds attach while they run; the queue closes when the last of them detaches.
+    pub(crate) fn attach(&self) {
+        self.inner.lock().unwrap().producers += 1;
+    }
+
+    pub(crate) fn detach(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.producers = inner.producers.saturating_sub(1);
+        if inner.producers == 0 {
+            drop(inner);
+            self.close();
+        }
+    }
+
+    // Queued events are still delivered; producers blocked in `push` give up.
+    pub fn close(&self) {
+        self.inner.lock().unwrap().closed = true;
+        self.items.notify_one();
+        self.space.notify_waiters();
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn delivery(&self) -> Delivery {
+        self.delivery
+    }
+
+    // The events as a stream, which ends once the queue is closed and drained.
+    pub fn into_stream(self) -> impl Stream<Item = MarketEvent> {
+        stream::unfold(self, |queue| async move { queue.pop().await.map(|event| (event, queue)) })
+    }
+
+    // Runs `callback` on every event in a task of its own until the queue is closed and drained.
+    pub fn spawn_consumer(&self, callback: impl Fn(MarketEvent) + Send + 'static) -> tokio::task::JoinHandle<()> {
+        let queue = self.clone();
+        tokio::spawn(async move {
+            while let Some(event) = queue.pop().await {
+                callback(event);
+            }
+        })
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::SoqaError;
+use crate::exchanges::history::HistoryClient;
+use crate::export::book::BookRecorder;
+use crate::export::record::{self, ParquetSink, SqliteSink};
+use crate::export::{export_trades_to_csv, export_trades_to_sqlite, Sink};
+use crate::models::{HistoricalTrade, MarketEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tracing::info;
+
+#[derive(Debug, Clone)]
+pub struct DownloadRequest {
+    pub exchange: String,
+    pub symbol: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    // `.db`, `.sqlite` and `.sqlite3` go to SQLite, anything else to CSV.
+    pub output: String,
+}
+
+// Progress saved next to the output after every page so an interrupted download picks up where it stopped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DownloadState {
+    pub exchange: String,
+    pub symbol: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub cursor: Option<String>,
+    pub pages: u64,
+    pub trades: u64,
+    pub done: bool,
+}
+
+impl DownloadState {
+    fn new(request: &DownloadRequest) -> Self {
+        DownloadState {
+            exchange: request.exchange.clone(),
+            symbol: request.symbol.clone(),
+            start_ms: request.start_ms,
+            end_ms: request.end_ms,
+            cursor: None,
+            pages: 0,
+            trades: 0,
+            done: false,
+        }
+    }
+
+    fn matches(&self, request: &DownloadRequest) -> bool {
+        self.exchange == request.exchange
+            && self.symbol == request.symbol
+            && self.start_ms == request.start_ms
+            && self.end_ms == request.end_ms
+    }
+}
+
+pub fn state_path(output: &str) -> String {
+    format!("{}.cursor", output)
+}
+
+fn write(trades: &[HistoricalTrade], output: &str) -> Result<(), SoqaError> {
+    let sqlite = record::is_sqlite(Path::new(output));
+    let written = if sqlite { export_trades_to_sqlite(trades, output) } else { export_trades_to_csv(trades, output) };
+    written.map_err(|e| SoqaError::ExportError(e.to_string()))
+}
+
+fn load_state(request: &DownloadRequest) -> Result<DownloadState, SoqaError> {
+    let path = state_path(&request.output);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) => return Ok(DownloadState::new(request)),
+    };
+    let state: DownloadState = serde_json::from_str(&text)?;
+    if !state.matches(request) {
+        return Err(SoqaError::ExportError(format!(
+            "{} belongs to a different download; remove it to start over",
+            path
+        )));
+    }
+    Ok(state)
+}
+
+// Trade ids of the market already in a CSV output. A crash between appending a page and saving the
+// cursor replays that page on resume, and these keep it from being appended twice; SQLite skips
+// known ids itself.
+fn written_ids(request: &DownloadRequest) -> Result<HashSet<String>, SoqaError> {
+    if record::is_sqlite(Path::new(&request.output)) || !Path::new(&request.output).exists() {
+        return Ok(HashSet::new());
+    }
+    let mut reader = csv::Reader::from_path(&request.output).map_err(|e| SoqaError::ExportError(e.to_string()))?;
+    let mut ids = HashSet::new();
+    for trade in reader.deserialize::<HistoricalTrade>() {
+        let trade = trade.map_err(|e| SoqaError::ExportError(e.to_string()))?;
+        if trade.exchange == request.exchange && trade.symbol == request.symbol {
+            ids.insert(trade.trade_id);
+        }
+    }
+    Ok(ids)
+}
+
+pub async fn download(client: &mut HistoryClient, request: &DownloadRequest) -> Result<DownloadState, SoqaError> {
+    let mut state = load_state(request)?;
+    if state.cursor.is_some() {
+        info!(target: "soqa::download", pages = state.pages, trades = state.trades, "resuming");
+    }
+    let written = written_ids(request)?;
+    while !state.done {
+        let mut page = client
+            .fetch_page(&request.symbol, request.start_ms, request.end_ms, state.cursor.as_deref())
+            .await?;
+        if !written.is_empty() {
+            page.trades.retain(|t| !written.contains(&t.trade_id));
+        }
+        if !page.trades.is_empty() {
+            write(&page.trades, &request.output)?;
+        }
+        state.pages += 1;
+        state.trades += page.trades.len() as u64;
+        state.done = page.next.is_none();
+        state.cursor = page.next;
+        std::fs::write(state_path(&request.output), serde_json::to_string(&state)?)
+            .map_err(|e| SoqaError::ExportError(e.to_string()))?;
+        info!(target: "soqa::download", pages = state.pages, trades = state.trades, "page written");
+    }
+    Ok(state)
+}
+
+#[derive(Debug, Clone)]
+pub struct BookDownloadRequest {
+    pub exchange: String,
+    pub symbol: String,
+    // Levels per side; venues cap this (Bybit 200, OKX 400, Kraken 500, KuCoin 100).
+    pub depth: usize,
+    pub snapshots: u64,
+    pub interval: Duration,
+    // `.db`, `.sqlite` and `.sqlite3` go to the `l2_book` table, `.parquet` to `<name>.l2.parquet`,
+    // anything else to CSV with one row per level.
+    pub output: String,
+}
+
+// Appends each book as CSV level rows, writing the header only when the file is new.
+struct CsvBookSink {
+    path: String,
+    recorder: BookRecorder,
+}
+
+impl Sink for CsvBookSink {
+    async fn write(&mut self, event: &MarketEvent) -> Result<(), SoqaError> {
+        let MarketEvent::L2(book) = event else { return Ok(()) };
+        let error = |e: &dyn std::fmt::Display| SoqaError::ExportError(e.to_string());
+        let is_new = std::fs::metadata(&self.path).map(|m| m.len() == 0).unwrap_or(true);
+        let file = OpenOptions::new().create(true).append(true).open(&self.path).map_err(|e| error(&e))?;
+        let mut writer = csv::Writer::from_writer(file);
+        if is_new {
+            writer.write_record(["exchange", "symbol", "timestamp_ms", "sequence", "side", "price", "size"]).map_err(|e| error(&e))?;
+        }
+        for row in self.recorder.record(book) {
+            let timestamp_ms = row.timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis();
+            let values = [
+                row.exchange,
+                row.symbol,
+                timestamp_ms.to_string(),
+                row.sequence.to_string(),
+                row.side.as_str().to_string(),
+                row.price.to_string(),
+                row.size.to_string(),
+            ];
+            writer.write_record(&values).map_err(|e| error(&e))?;
+        }
+        writer.flush().map_err(|e| error(&e))
+    }
+
+    async fn flush(&mut self) -> Result<(), SoqaError> {
+        Ok(())
+    }
+}
+
+async fn poll_books(client: &mut HistoryClient, request: &BookDownloadRequest, sink: &mut impl Sink) -> Result<u64, SoqaError> {
+    for taken in 0..request.snapshots {
+        if taken > 0 {
+            tokio::time::sleep(request.interval).await;
+        }
+        let book = client.fetch_book(&request.symbol, request.depth).await?;
+        sink.write(&MarketEvent::L2(book)).await?;
+        sink.flush().await?;
+        info!(target: "soqa::download", snapshots = taken + 1, "book written");
+    }
+    Ok(request.snapshots)
+}
+
+// Takes `snapshots` REST snapshots of the book, `interval` apart. Venues keep no book history, so
+// the snapshots are of the book as it is now. Every one is stored whole.
+pub async fn download_book(client: &mut HistoryClient, request: &BookDownloadRequest) -> Result<u64, SoqaError> {
+    let path = Path::new(&request.output);
+    if record::is_sqlite(path) {
+        poll_books(client, request, &mut SqliteSink::open(path, Duration::ZERO)?).await
+    } else if record::is_parquet(path) {
+        let mut sink = ParquetSink::create(path, Duration::ZERO)?;
+        let taken = poll_books(client, request, &mut sink).await?;
+        sink.close().await?;
+        Ok(taken)
+    } else {
+        let mut sink = CsvBookSink { path: request.output.clone(), recorder: BookRecorder::new(Duration::ZERO) };
+        poll_books(client, request, &mut sink).await
+    }
+}
@@ -2,8 +2,10 @@ use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum SoqaError {
+    // Boxed because the tungstenite error would otherwise make every `Result` in the crate several
+    // times larger.
     #[error("WebSocket error: {0}")]
-    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
     #[error("Exchange not supported: {0}")]
@@ -14,4 +16,19 @@ pub enum SoqaError {
     ConnectionError(String),
     #[error("HTTP error: {0}")]
     Http(#[from] reqwest::Error),
-}
\ No newline at end of file
+    #[error("Adapter error: {0}")]
+    AdapterError(String),
+    #[error("Codec error: {0}")]
+    CodecError(String),
+    #[error("Shared memory error: {0}")]
+    ShmError(String),
+    #[error("Config error: {0}")]
+    ConfigError(String),
+    #[error("Rate limited by {exchange}, retry after {retry_after:?}")]
+    RateLimited { exchange: String, retry_after: std::time::Duration },
+}
+impl From<tokio_tungstenite::tungstenite::Error> for SoqaError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        SoqaError::WebSocket(Box::new(e))
+    }
+}
@@ -0,0 +1,372 @@
+use crate::models::{MarketEvent, OrderBookL1, QuoteSource};
+use crate::error::SoqaError;
+use crate::exchanges::feed::{Endpoint, Feed, Frame, Ping};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use reqwest::Client;
+
+// Declarative description of a venue's L1 feed, loaded from TOML or JSON.
+// Paths in `match`, `pong` and `fields` are JSON pointers (RFC 6901).
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdapterSpec {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub subscribe: Vec<String>,
+    #[serde(default)]
+    pub symbol: SymbolRule,
+    pub bootstrap: Option<Bootstrap>,
+    pub ping: Option<PingRule>,
+    #[serde(default)]
+    pub pong: Vec<PongRule>,
+    #[serde(default, rename = "match")]
+    pub matches: Vec<MatchRule>,
+    pub fields: FieldPaths,
+    // The kind of channel the quotes come from, recorded on every quote: `ticker` (the default),
+    // `book` or `bbo`.
+    #[serde(default)]
+    pub source: QuoteSource,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SymbolRule {
+    #[serde(default)]
+    pub case: SymbolCase,
+    #[serde(default)]
+    pub map: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolCase {
+    #[default]
+    Keep,
+    Lower,
+    Upper,
+}
+
+// REST call made before every connection attempt, e.g. KuCoin's `bullet-public` token.
+// Every entry in `vars` becomes a template variable for `url`, `subscribe` and the ping message.
+// `ping_interval_ms` points at a ping interval in milliseconds that overrides `ping.interval_secs`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Bootstrap {
+    #[serde(default = "default_bootstrap_method")]
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+    pub ping_interval_ms: Option<String>,
+}
+
+fn default_bootstrap_method() -> String {
+    "POST".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PingRule {
+    pub interval_secs: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PongRule {
+    pub text: Option<String>,
+    pub pointer: Option<String>,
+    pub equals: Option<String>,
+    pub reply: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MatchRule {
+    pub pointer: String,
+    pub equals: Option<String>,
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldPaths {
+    pub symbol: Option<String>,
+    pub bid: String,
+    pub bid_volume: String,
+    pub ask: String,
+    pub ask_volume: String,
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub timestamp_unit: TimestampUnit,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampUnit {
+    S,
+    #[default]
+    Ms,
+    Us,
+    Ns,
+}
+
+impl AdapterSpec {
+    pub fn from_toml_str(s: &str) -> Result<Self, SoqaError> {
+        toml::from_str(s).map_err(|e| SoqaError::AdapterError(e.to_string()))
+    }
+
+    pub fn from_json_str(s: &str) -> Result<Self, SoqaError> {
+        serde_json::from_str(s).map_err(|e| SoqaError::AdapterError(e.to_string()))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SoqaError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| SoqaError::AdapterError(format!("{}: {}", path.display(), e)))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json_str(&contents),
+            _ => Self::from_toml_str(&contents),
+        }
+    }
+
+    // Specs for the hand-written clients, kept in `adapters/` so they can be diffed against them.
+    pub fn builtin(name: &str) -> Result<Self, SoqaError> {
+        let source = match name {
+            "binance" => include_str!("../../adapters/binance.toml"),
+            "bybit" => include_str!("../../adapters/bybit.toml"),
+            "okx" => include_str!("../../adapters/okx.toml"),
+            "kraken" => include_str!("../../adapters/kraken.toml"),
+            "kucoin" => include_str!("../../adapters/kucoin.toml"),
+            _ => return Err(SoqaError::AdapterError(format!("no built-in adapter for {}", name))),
+        };
+        Self::from_toml_str(source)
+    }
+
+    pub fn exchange_symbol(&self, symbol: &str) -> String {
+        let mapped = self.symbol.map.get(symbol).map(String::as_str).unwrap_or(symbol);
+        match self.symbol.case {
+            SymbolCase::Keep => mapped.to_string(),
+            SymbolCase::Lower => mapped.to_lowercase(),
+            SymbolCase::Upper => mapped.to_uppercase(),
+        }
+    }
+
+    pub fn parse_l1(&self, text: &str, symbol: &str) -> Option<OrderBookL1> {
+        let data = serde_json::from_str::<Value>(text).ok()?;
+        self.extract_l1(&data, symbol)
+    }
+
+    fn is_data(&self, data: &Value) -> bool {
+        self.matches.iter().all(|rule| rule.is_match(data))
+    }
+
+    fn extract_l1(&self, data: &Value, symbol: &str) -> Option<OrderBookL1> {
+        if !self.is_data(data) {
+            return None;
+        }
+        let fields = &self.fields;
+        let symbol = match &fields.symbol {
+            Some(pointer) => data.pointer(pointer)?.as_str()?.to_string(),
+            None => symbol.to_string(),
+        };
+        let timestamp = fields
+            .timestamp
+            .as_ref()
+            .and_then(|pointer| data.pointer(pointer))
+            .and_then(number)
+            .map(|t| fields.timestamp_unit.to_system_time(t))
+            .unwrap_or_else(SystemTime::now);
+        Some(OrderBookL1 {
+            exchange: self.name.clone(),
+            symbol,
+            bid: number(data.pointer(&fields.bid)?)?,
+            bid_volume: number(data.pointer(&fields.bid_volume)?)?,
+            ask: number(data.pointer(&fields.ask)?)?,
+            ask_volume: number(data.pointer(&fields.ask_volume)?)?,
+            timestamp,
+            source: self.source,
+        })
+    }
+
+    // Runs the bootstrap request, if any, and renders the URL, subscribe messages and ping with a
+    // fresh `id`. Called for every connection attempt, since bootstrap tokens may be single-use.
+    async fn endpoint(&self, symbol: &str) -> Result<Endpoint, SoqaError> {
+        let mut vars = HashMap::new();
+        vars.insert("symbol".to_string(), symbol.to_string());
+        vars.insert("id".to_string(), now_millis());
+        let ping_interval = self.bootstrap(&mut vars).await?;
+
+        let ping = self.ping.clone().map(|ping| {
+            let mut vars = vars.clone();
+            Ping {
+                every: ping_interval.unwrap_or(Duration::from_secs(ping.interval_secs)),
+                message: Box::new(move || {
+                    vars.insert("id".to_string(), now_millis());
+                    render(&ping.message, &vars)
+                }),
+            }
+        });
+        Ok(Endpoint {
+            url: render(&self.url, &vars),
+            subscribe: self.subscribe.iter().map(|template| render(template, &vars)).collect(),
+            ping,
+        })
+    }
+
+    async fn bootstrap(&self, vars: &mut HashMap<String, String>) -> Result<Option<Duration>, SoqaError> {
+        let Some(bootstrap) = &self.bootstrap else {
+            return Ok(None);
+        };
+        let client = Client::new();
+        let request = match bootstrap.method.to_uppercase().as_str() {
+            "GET" => client.get(&bootstrap.url),
+            _ => client.post(&bootstrap.url),
+        };
+        let response = crate::ratelimit::global().send(&self.name, request).await?;
+        if !response.status().is_success() {
+            return Err(SoqaError::ConnectionError(format!(
+                "{} bootstrap request failed: HTTP {}",
+                self.name,
+                response.status()
+            )));
+        }
+        let data = response.json::<Value>().await?;
+        for (key, pointer) in &bootstrap.vars {
+            let value = data
+                .pointer(pointer)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| SoqaError::AdapterError(format!("{} bootstrap is missing {}", self.name, pointer)))?;
+            vars.insert(key.clone(), value.to_string());
+        }
+        let ping_interval = bootstrap
+            .ping_interval_ms
+            .as_ref()
+            .and_then(|pointer| data.pointer(pointer))
+            .and_then(number)
+            .map(|ms| Duration::from_millis(ms as u64));
+        Ok(ping_interval)
+    }
+
+    fn pong_reply(&self, text: &str, data: Option<&Value>) -> Option<&str> {
+        self.pong.iter().find_map(|rule| {
+            let matched = match (&rule.text, &rule.pointer, data) {
+                (Some(expected), _, _) => text == expected,
+                (None, Some(pointer), Some(data)) => {
+                    data.pointer(pointer).and_then(|v| v.as_str()) == rule.equals.as_deref()
+                }
+                _ => false,
+            };
+            matched.then_some(rule.reply.as_str())
+        })
+    }
+}
+
+impl MatchRule {
+    fn is_match(&self, data: &Value) -> bool {
+        let Some(value) = self.data_str(data) else {
+            return false;
+        };
+        if let Some(expected) = &self.equals {
+            if value != *expected {
+                return false;
+            }
+        }
+        if let Some(prefix) = &self.prefix {
+            if !value.starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn data_str(&self, data: &Value) -> Option<String> {
+        match data.pointer(&self.pointer)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Null => None,
+            other => Some(other.to_string()),
+        }
+    }
+}
+
+impl TimestampUnit {
+    fn to_system_time(self, value: f64) -> SystemTime {
+        let nanos = match self {
+            TimestampUnit::S => value * 1e9,
+            TimestampUnit::Ms => value * 1e6,
+            TimestampUnit::Us => value * 1e3,
+            TimestampUnit::Ns => value,
+        };
+        SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos as u64)
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        other => other.as_f64(),
+    }
+}
+
+fn render(template: &str, vars: &HashMap<String, String>) -> String {
+    let mut out = template.to_string();
+    for (key, value) in vars {
+        out = out.replace(&format!("{{{}}}", key), value);
+    }
+    out
+}
+
+fn now_millis() -> String {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+        .to_string()
+}
+
+pub struct GenericClient {
+    spec: AdapterSpec,
+    config: crate::config::Config,
+}
+
+impl GenericClient {
+    pub fn new(spec: AdapterSpec, config: crate::config::Config) -> Self {
+        GenericClient { spec, config }
+    }
+
+    pub async fn subscribe_l1(&self, callback: impl Fn(OrderBookL1) + Send + 'static) -> Result<(), SoqaError> {
+        let spec = self.spec.clone();
+        let symbol = self.spec.exchange_symbol(&self.config.symbol);
+        let mut feed = Feed::connect_with(&self.spec.name, move || {
+            let spec = spec.clone();
+            let symbol = symbol.clone();
+            async move { spec.endpoint(&symbol).await }
+        });
+        if self.spec.fields.timestamp.is_none() {
+            feed = feed.local_time();
+        }
+
+        let spec = self.spec.clone();
+        let symbol = self.config.symbol.clone();
+        let handler = move |text: &str, events: &mut Vec<MarketEvent>| {
+            let data = serde_json::from_str::<Value>(text).ok();
+            if let Some(reply) = spec.pong_reply(text, data.as_ref()) {
+                return Frame::Reply(reply.to_string());
+            }
+            // Messages that pass the `match` rules but lack the fields are parse failures
+            let Some(data) = data.filter(|data| spec.is_data(data)) else {
+                return Frame::Control;
+            };
+            match spec.extract_l1(&data, &symbol) {
+                Some(order_book) => {
+                    events.push(MarketEvent::L1(order_book));
+                    Frame::Data
+                }
+                None => Frame::Unknown,
+            }
+        };
+        feed.spawn(&self.config, handler, move |event| {
+            if let MarketEvent::L1(order_book) = event {
+                callback(order_book);
+            }
+        })
+        .await
+    }
+}
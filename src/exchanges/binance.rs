@@ -1,9 +1,138 @@
-use crate::models::OrderBookL1;
+use crate::models::{MarketEvent, OrderBookL1, OrderBookL2, QuoteSource, Trade};
 use crate::error::SoqaError;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use serde_json::Value;
+use crate::exchanges::decode::{levels, millis, price, Decoder, Level};
+use crate::exchanges::feed::{Feed, Frame};
+use crate::subscriptions::Channel;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::time::SystemTime;
-use futures_util::stream::StreamExt;
+
+// Envelope of the combined-stream endpoint: `{"stream":"btcusdt@ticker","data":{..}}`.
+#[derive(Deserialize)]
+pub struct StreamMsg<'a, T> {
+    pub stream: &'a str,
+    pub data: T,
+}
+
+impl<T> StreamMsg<'_, T> {
+    // The lowercase symbol the stream was subscribed with.
+    pub fn symbol(&self) -> &str {
+        self.stream.split('@').next().unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TickerMsg<'a> {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+    #[serde(rename = "b")]
+    pub bid: &'a str,
+    #[serde(rename = "B")]
+    pub bid_volume: &'a str,
+    #[serde(rename = "a")]
+    pub ask: &'a str,
+    #[serde(rename = "A")]
+    pub ask_volume: &'a str,
+}
+
+impl TickerMsg<'_> {
+    pub fn to_l1(&self) -> Option<OrderBookL1> {
+        Some(OrderBookL1 {
+            exchange: "binance".to_string(),
+            symbol: self.symbol.to_string(),
+            bid: price(self.bid)?,
+            bid_volume: price(self.bid_volume)?,
+            ask: price(self.ask)?,
+            ask_volume: price(self.ask_volume)?,
+            timestamp: millis(self.event_time),
+            source: QuoteSource::Ticker,
+        })
+    }
+}
+
+// `<symbol>@bookTicker`: pushed on every change of the best bid or ask. Carries no time.
+#[derive(Deserialize)]
+pub struct BookTickerMsg<'a> {
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+    #[serde(rename = "b")]
+    pub bid: &'a str,
+    #[serde(rename = "B")]
+    pub bid_volume: &'a str,
+    #[serde(rename = "a")]
+    pub ask: &'a str,
+    #[serde(rename = "A")]
+    pub ask_volume: &'a str,
+}
+
+impl BookTickerMsg<'_> {
+    pub fn to_l1(&self) -> Option<OrderBookL1> {
+        Some(OrderBookL1 {
+            exchange: "binance".to_string(),
+            symbol: self.symbol.to_string(),
+            bid: price(self.bid)?,
+            bid_volume: price(self.bid_volume)?,
+            ask: price(self.ask)?,
+            ask_volume: price(self.ask_volume)?,
+            timestamp: SystemTime::now(),
+            source: QuoteSource::Bbo,
+        })
+    }
+}
+
+// `<symbol>@depth20@100ms`: the top 20 levels, pushed whole every 100ms.
+#[derive(Deserialize)]
+pub struct DepthMsg<'a> {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+    #[serde(borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<Level<'a>>,
+}
+
+impl DepthMsg<'_> {
+    // Partial depth messages carry neither symbol nor time.
+    pub fn to_l2(&self, symbol: &str) -> Option<OrderBookL2> {
+        Some(OrderBookL2 {
+            exchange: "binance".to_string(),
+            symbol: symbol.to_string(),
+            bids: levels(&self.bids)?,
+            asks: levels(&self.asks)?,
+            timestamp: SystemTime::now(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TradeMsg<'a> {
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+    #[serde(rename = "p")]
+    pub price: &'a str,
+    #[serde(rename = "q")]
+    pub quantity: &'a str,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    // The buyer was the maker, so the aggressor sold.
+    #[serde(rename = "m")]
+    pub buyer_is_maker: bool,
+}
+
+impl TradeMsg<'_> {
+    pub fn to_trade(&self) -> Option<Trade> {
+        Some(Trade {
+            exchange: "binance".to_string(),
+            symbol: self.symbol.to_string(),
+            price: price(self.price)?,
+            volume: price(self.quantity)?,
+            side: if self.buyer_is_maker { "sell" } else { "buy" }.to_string(),
+            timestamp: millis(self.trade_time),
+        })
+    }
+}
 
 pub struct BinanceClient {
     config: crate::config::Config,
@@ -15,29 +144,67 @@ impl BinanceClient {
     }
 
     pub async fn subscribe_l1(&self, callback: impl Fn(OrderBookL1) + Send + 'static) -> Result<(), SoqaError> {
-        let url = format!("wss://stream.binance.com:9443/ws/{}@ticker", self.config.symbol.to_lowercase());
-        let (ws_stream, _) = connect_async(&url).await?;
-        let (mut _write, mut read) = ws_stream.split();
-
-        tokio::spawn(async move {
-            while let Some(msg) = read.next().await {
-                if let Ok(Message::Text(text)) = msg {
-                    if let Ok(data) = serde_json::from_str::<Value>(&text) {
-                        let order_book = OrderBookL1 {
-                            exchange: "binance".to_string(),
-                            symbol: data["s"].as_str().unwrap_or("").to_string(),
-                            bid: data["b"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                            bid_volume: data["B"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                            ask: data["a"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                            ask_volume: data["A"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                            timestamp: SystemTime::now(),
-                        };
-                        callback(order_book);
-                    }
-                }
+        self.subscribe(Channel::L1, move |event| {
+            if let MarketEvent::L1(order_book) = event {
+                callback(order_book);
             }
-        });
+        })
+        .await
+    }
 
-        Ok(())
+    pub async fn subscribe(&self, channel: Channel, callback: impl Fn(MarketEvent) + Send + 'static) -> Result<(), SoqaError> {
+        let bbo = channel == Channel::L1 && self.config.bbo;
+        let stream = match channel {
+            Channel::L1 if bbo => "bookTicker",
+            Channel::L1 => "ticker",
+            Channel::L2 => "depth20@100ms",
+            Channel::Trades => "trade",
+        };
+        // One combined stream carries every symbol; its envelope names the stream each message came from.
+        let streams: Vec<String> = self.config.symbols.iter().map(|symbol| format!("{}@{}", symbol.to_lowercase(), stream)).collect();
+        let url = format!("wss://stream.binance.com:9443/stream?streams={}", streams.join("/"));
+        let mut feed = Feed::new("binance", url);
+        if channel == Channel::L2 || bbo {
+            feed = feed.local_time();
+        }
+        let symbols: HashMap<String, String> =
+            self.config.symbols.iter().map(|symbol| (symbol.to_lowercase(), symbol.clone())).collect();
+        let mut decoder = Decoder::new();
+        let handler = move |text: &str, events: &mut Vec<MarketEvent>| {
+            let event = match channel {
+                Channel::L1 if bbo => decoder.decode::<StreamMsg<BookTickerMsg>>(text).and_then(|m| m.data.to_l1()).map(MarketEvent::L1),
+                Channel::L1 => decoder.decode::<StreamMsg<TickerMsg>>(text).and_then(|m| m.data.to_l1()).map(MarketEvent::L1),
+                Channel::L2 => decoder.decode::<StreamMsg<DepthMsg>>(text).and_then(|m| {
+                    let symbol = symbols.get(m.symbol()).map(String::as_str).unwrap_or(m.symbol());
+                    m.data.to_l2(symbol)
+                })
+                .map(MarketEvent::L2),
+                Channel::Trades => decoder.decode::<StreamMsg<TradeMsg>>(text).and_then(|m| m.data.to_trade()).map(MarketEvent::Trade),
+            };
+            match event {
+                Some(event) => {
+                    events.push(event);
+                    Frame::Data
+                }
+                None => Frame::Unknown,
+            }
+        };
+        feed.spawn(&self.config, handler, callback).await
     }
-}
\ No newline at end of file
+}
+
+pub fn parse_l1(text: &str) -> Option<OrderBookL1> {
+    serde_json::from_str::<TickerMsg>(text).ok()?.to_l1()
+}
+
+pub fn parse_bbo(text: &str) -> Option<OrderBookL1> {
+    serde_json::from_str::<BookTickerMsg>(text).ok()?.to_l1()
+}
+
+pub fn parse_l2(text: &str, symbol: &str) -> Option<OrderBookL2> {
+    serde_json::from_str::<DepthMsg>(text).ok()?.to_l2(symbol)
+}
+
+pub fn parse_trade(text: &str) -> Option<Trade> {
+    serde_json::from_str::<TradeMsg>(text).ok()?.to_trade()
+}
@@ -0,0 +1,237 @@
+use crate::models::{OrderBookL1, OrderBookL2, QuoteSource};
+use std::time::SystemTime;
+use thiserror::Error;
+
+// Price levels from a venue's depth channel; a size of zero removes the level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookUpdate {
+    // A snapshot replaces the whole book, otherwise the levels are applied on top of it.
+    pub snapshot: bool,
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+    // The same levels as the venue wrote them, for venues whose checksum is computed over its
+    // own formatting; empty otherwise.
+    pub raw_bids: Vec<(String, String)>,
+    pub raw_asks: Vec<(String, String)>,
+    // The venue's checksum of the book after this update, as an unsigned CRC32.
+    pub checksum: Option<u32>,
+    pub timestamp: SystemTime,
+}
+
+impl Default for BookUpdate {
+    fn default() -> Self {
+        BookUpdate {
+            snapshot: false,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            raw_bids: Vec::new(),
+            raw_asks: Vec::new(),
+            checksum: None,
+            timestamp: SystemTime::UNIX_EPOCH,
+        }
+    }
+}
+
+// Top-of-book checksum schemes. Both run CRC32 over the level strings exactly as the venue sent
+// them, so the local book keeps those strings next to the parsed levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    // Top 10 asks then top 10 bids; price and size each with the '.' and leading zeros removed,
+    // all concatenated without separators.
+    Kraken,
+    // Up to 25 levels interleaved as `bidPx:bidSz:askPx:askSz`, joined with ':'; a side that has
+    // run out of levels is skipped. The venue sends the CRC32 as a signed 32-bit integer.
+    Okx,
+}
+
+impl Checksum {
+    // `bids` and `asks` best first.
+    pub fn compute(&self, bids: &[(String, String)], asks: &[(String, String)]) -> u32 {
+        match self {
+            Checksum::Kraken => {
+                let digits = |s: &str| s.replace('.', "").trim_start_matches('0').to_string();
+                let text: String = asks
+                    .iter()
+                    .take(10)
+                    .chain(bids.iter().take(10))
+                    .flat_map(|(price, size)| [digits(price), digits(size)])
+                    .collect();
+                crc32fast::hash(text.as_bytes())
+            }
+            Checksum::Okx => {
+                let mut fields = Vec::new();
+                for i in 0..25 {
+                    for side in [bids, asks] {
+                        if let Some((price, size)) = side.get(i) {
+                            fields.push(price.as_str());
+                            fields.push(size.as_str());
+                        }
+                    }
+                }
+                crc32fast::hash(fields.join(":").as_bytes())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("book checksum mismatch: venue sent {expected}, local book has {actual}")]
+pub struct ChecksumMismatch {
+    pub expected: u32,
+    pub actual: u32,
+}
+
+// Book rebuilt from snapshot + delta channels (Bybit, Kraken, OKX), trimmed to `depth` levels a side.
+#[derive(Debug, Clone, Default)]
+pub struct LocalBook {
+    depth: usize,
+    // Best first: bids descending, asks ascending.
+    bids: Vec<(f64, f64)>,
+    asks: Vec<(f64, f64)>,
+    // Venue strings for the levels above, kept only when a checksum is verified.
+    raw_bids: Vec<(String, String)>,
+    raw_asks: Vec<(String, String)>,
+    checksum: Option<Checksum>,
+    // Set by a checksum mismatch; deltas are ignored until the next snapshot.
+    awaiting_snapshot: bool,
+}
+
+fn apply_level(
+    levels: &mut Vec<(f64, f64)>,
+    raw: &mut Vec<(String, String)>,
+    (price, size): (f64, f64),
+    text: Option<&(String, String)>,
+    descending: bool,
+) {
+    let text = text.filter(|_| raw.len() == levels.len());
+    let position = levels.binary_search_by(|(p, _)| {
+        if descending {
+            price.total_cmp(p)
+        } else {
+            p.total_cmp(&price)
+        }
+    });
+    match (position, size > 0.0) {
+        (Ok(i), true) => {
+            levels[i].1 = size;
+            if let Some(text) = text {
+                raw[i] = text.clone();
+            }
+        }
+        (Ok(i), false) => {
+            levels.remove(i);
+            if text.is_some() {
+                raw.remove(i);
+            }
+        }
+        (Err(i), true) => {
+            levels.insert(i, (price, size));
+            if let Some(text) = text {
+                raw.insert(i, text.clone());
+            }
+        }
+        (Err(_), false) => {}
+    }
+}
+
+impl LocalBook {
+    pub fn new(depth: usize) -> Self {
+        LocalBook { depth, ..LocalBook::default() }
+    }
+
+    // Verifies updates that carry a checksum against this scheme.
+    pub fn with_checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    // On a checksum mismatch the book is cleared and stays empty until the next snapshot; the
+    // caller is expected to ask the venue for one.
+    pub fn apply(&mut self, update: &BookUpdate) -> Result<(), ChecksumMismatch> {
+        if update.snapshot {
+            self.clear();
+            self.awaiting_snapshot = false;
+        } else if self.awaiting_snapshot {
+            return Ok(());
+        }
+        let track = self.checksum.is_some();
+        for (i, &level) in update.bids.iter().enumerate() {
+            let text = update.raw_bids.get(i).filter(|_| track);
+            apply_level(&mut self.bids, &mut self.raw_bids, level, text, true);
+        }
+        for (i, &level) in update.asks.iter().enumerate() {
+            let text = update.raw_asks.get(i).filter(|_| track);
+            apply_level(&mut self.asks, &mut self.raw_asks, level, text, false);
+        }
+        if self.depth > 0 {
+            self.bids.truncate(self.depth);
+            self.asks.truncate(self.depth);
+            self.raw_bids.truncate(self.depth);
+            self.raw_asks.truncate(self.depth);
+        }
+        if let (Some(checksum), Some(expected)) = (self.checksum, update.checksum) {
+            let actual = checksum.compute(&self.raw_bids, &self.raw_asks);
+            if actual != expected {
+                self.clear();
+                self.awaiting_snapshot = true;
+                return Err(ChecksumMismatch { expected, actual });
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.raw_bids.clear();
+        self.raw_asks.clear();
+    }
+
+    // Whether the book was dropped after a mismatch and nothing should be published from it.
+    pub fn awaiting_snapshot(&self) -> bool {
+        self.awaiting_snapshot
+    }
+
+    pub fn bids(&self) -> &[(f64, f64)] {
+        &self.bids
+    }
+
+    pub fn asks(&self) -> &[(f64, f64)] {
+        &self.asks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    pub fn to_l2(&self, exchange: &str, symbol: &str, timestamp: SystemTime) -> OrderBookL2 {
+        self.top(exchange, symbol, timestamp, usize::MAX)
+    }
+
+    // The best bid and ask as a quote, once both sides have a level.
+    pub fn quote(&self, exchange: &str, symbol: &str, timestamp: SystemTime) -> Option<OrderBookL1> {
+        let (bid, bid_volume) = *self.bids.first()?;
+        let (ask, ask_volume) = *self.asks.first()?;
+        Some(OrderBookL1 {
+            exchange: exchange.to_string(),
+            symbol: symbol.to_string(),
+            bid,
+            bid_volume,
+            ask,
+            ask_volume,
+            timestamp,
+            source: QuoteSource::Book,
+        })
+    }
+
+    // Only the best `levels` a side, for books kept deeper than they are published.
+    pub fn top(&self, exchange: &str, symbol: &str, timestamp: SystemTime, levels: usize) -> OrderBookL2 {
+        OrderBookL2 {
+            exchange: exchange.to_string(),
+            symbol: symbol.to_string(),
+            bids: self.bids.iter().take(levels).copied().collect(),
+            asks: self.asks.iter().take(levels).copied().collect(),
+            timestamp,
+        }
+    }
+}
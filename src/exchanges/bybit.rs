@@ -1,10 +1,150 @@
-use crate::models::{OrderBookL1};
+use crate::models::{MarketEvent, OrderBookL1, QuoteSource, Trade};
 use crate::error::SoqaError;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use crate::exchanges::book::{BookUpdate, LocalBook};
+use crate::exchanges::decode::{levels, millis, price, Decoder, First, Level};
+use crate::exchanges::feed::{Feed, Frame};
+use crate::subscriptions::Channel;
+use serde::Deserialize;
 use serde_json::Value;
-use std::time::SystemTime;
-use futures_util::stream::StreamExt;
-use futures_util::SinkExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{info, warn};
+
+#[derive(Deserialize)]
+pub struct OrderbookMsg<'a> {
+    pub topic: &'a str,
+    pub ts: u64,
+    #[serde(borrow)]
+    pub data: OrderbookData<'a>,
+}
+
+#[derive(Deserialize)]
+pub struct OrderbookData<'a> {
+    #[serde(rename = "b", borrow)]
+    pub bids: First<Level<'a>>,
+    #[serde(rename = "a", borrow)]
+    pub asks: First<Level<'a>>,
+}
+
+impl OrderbookMsg<'_> {
+    pub fn to_l1(&self, symbol: &str) -> Option<OrderBookL1> {
+        if !self.topic.starts_with("orderbook") {
+            return None;
+        }
+        let bid = self.data.bids.0?;
+        let ask = self.data.asks.0?;
+        Some(OrderBookL1 {
+            exchange: "bybit".to_string(),
+            symbol: symbol.to_string(),
+            bid: price(bid.price)?,
+            bid_volume: price(bid.size)?,
+            ask: price(ask.price)?,
+            ask_volume: price(ask.size)?,
+            timestamp: millis(self.ts),
+            source: QuoteSource::Bbo,
+        })
+    }
+}
+
+// `orderbook.50.<symbol>`: a snapshot followed by deltas.
+#[derive(Deserialize)]
+pub struct DepthMsg<'a> {
+    pub topic: &'a str,
+    #[serde(rename = "type")]
+    pub kind: &'a str,
+    pub ts: u64,
+    #[serde(borrow)]
+    pub data: DepthData<'a>,
+}
+
+#[derive(Deserialize)]
+pub struct DepthData<'a> {
+    #[serde(rename = "b", borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(rename = "a", borrow)]
+    pub asks: Vec<Level<'a>>,
+}
+
+impl DepthMsg<'_> {
+    pub fn to_update(&self) -> Option<BookUpdate> {
+        if !self.topic.starts_with("orderbook") {
+            return None;
+        }
+        Some(BookUpdate {
+            snapshot: self.kind == "snapshot",
+            bids: levels(&self.data.bids)?,
+            asks: levels(&self.data.asks)?,
+            timestamp: millis(self.ts),
+            ..BookUpdate::default()
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TradesMsg<'a> {
+    pub topic: &'a str,
+    #[serde(borrow)]
+    pub data: Vec<TradeData<'a>>,
+}
+
+#[derive(Deserialize)]
+pub struct TradeData<'a> {
+    #[serde(rename = "T")]
+    pub time: u64,
+    #[serde(rename = "S")]
+    pub side: &'a str,
+    #[serde(rename = "v")]
+    pub volume: &'a str,
+    #[serde(rename = "p")]
+    pub price: &'a str,
+}
+
+impl TradesMsg<'_> {
+    pub fn to_trades(&self, symbol: &str) -> Option<Vec<Trade>> {
+        if !self.topic.starts_with("publicTrade") {
+            return None;
+        }
+        self.data
+            .iter()
+            .map(|t| {
+                Some(Trade {
+                    exchange: "bybit".to_string(),
+                    symbol: symbol.to_string(),
+                    price: price(t.price)?,
+                    volume: price(t.volume)?,
+                    side: t.side.to_lowercase(),
+                    timestamp: millis(t.time),
+                })
+            })
+            .collect()
+    }
+}
+
+// The symbol a topic such as `orderbook.50.BTCUSDT` is for.
+fn topic_symbol(topic: &str) -> &str {
+    topic.rsplit('.').next().unwrap_or_default()
+}
+
+// Bybit takes at most ten topics per spot subscribe request.
+const TOPICS_PER_REQUEST: usize = 10;
+
+// Subscription acks, pongs and errors.
+fn control(text: &str) -> Frame {
+    let Ok(data) = serde_json::from_str::<Value>(text) else {
+        return Frame::Unknown;
+    };
+    if data.get("success").and_then(|s| s.as_bool()).unwrap_or(false) {
+        if data.get("op").and_then(|o| o.as_str()) == Some("subscribe") {
+            info!(target: "soqa::bybit", "subscribed");
+        }
+        return Frame::Control;
+    }
+    if let Some(error) = data.get("ret_msg").and_then(|e| e.as_str()) {
+        warn!(target: "soqa::bybit", %error, "exchange error");
+        return Frame::Control;
+    }
+    Frame::Unknown
+}
 
 pub struct BybitClient {
     config: crate::config::Config,
@@ -16,72 +156,65 @@ impl BybitClient {
     }
 
     pub async fn subscribe_l1(&self, callback: impl Fn(OrderBookL1) + Send + 'static) -> Result<(), SoqaError> {
-        let url = "wss://stream.bybit.com/v5/public/spot";
-        let (ws_stream, _) = connect_async(url).await?;
-        let (mut write, mut read) = ws_stream.split();
-
-        let symbol = self.config.symbol.clone();
-        let subscribe_msg = format!(
-            r#"{{"op":"subscribe","args":["orderbook.1.{}"]}}"#,
-            symbol
-        );
-        write.send(Message::Text(subscribe_msg)).await?;
-
-        tokio::spawn(async move {
-            while let Some(msg) = read.next().await {
-                match msg {
-                    Ok(Message::Text(text)) => {
-                        if let Ok(data) = serde_json::from_str::<Value>(&text) {
-                            // Handle subscription confirmation
-                            if data.get("success").and_then(|s| s.as_bool()).unwrap_or(false) {
-                                println!("Successfully subscribed to Bybit WebSocket");
-                                continue;
-                            }
-
-                            // Handle error messages
-                            if let Some(error) = data.get("ret_msg").and_then(|e| e.as_str()) {
-                                eprintln!("Bybit WebSocket error: {}", error);
-                                continue;
-                            }
-
-                            // Handle orderbook data
-                            if let Some(topic) = data.get("topic").and_then(|t| t.as_str()) {
-                                if topic.starts_with("orderbook") {
-                                    if let Some(data) = data.get("data") {
-                                        if let Some(bids) = data.get("b").and_then(|b| b.as_array()) {
-                                            if let Some(asks) = data.get("a").and_then(|a| a.as_array()) {
-                                                if !bids.is_empty() && !asks.is_empty() {
-                                                    let order_book = OrderBookL1 {
-                                                        exchange: "bybit".to_string(),
-                                                        symbol: symbol.clone(),
-                                                        bid: bids[0][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                                        bid_volume: bids[0][1].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                                        ask: asks[0][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                                        ask_volume: asks[0][1].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                                        timestamp: SystemTime::now(),
-                                                    };
-                                                    callback(order_book);
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+        self.subscribe(Channel::L1, move |event| {
+            if let MarketEvent::L1(order_book) = event {
+                callback(order_book);
+            }
+        })
+        .await
+    }
+
+    pub async fn subscribe(&self, channel: Channel, callback: impl Fn(MarketEvent) + Send + 'static) -> Result<(), SoqaError> {
+        let topic = match channel {
+            // `orderbook.1` is already the best bid/offer channel, so `Config::bbo` changes nothing.
+            Channel::L1 => "orderbook.1",
+            Channel::L2 => "orderbook.50",
+            Channel::Trades => "publicTrade",
+        };
+        let topics: Vec<String> = self.config.symbols.iter().map(|symbol| format!(r#""{}.{}""#, topic, symbol)).collect();
+        // Bybit drops connections that send nothing for 20 seconds.
+        let mut feed = Feed::new("bybit", "wss://stream.bybit.com/v5/public/spot")
+            .ping(Duration::from_secs(20), || r#"{"op":"ping"}"#.to_string());
+        for request in topics.chunks(TOPICS_PER_REQUEST) {
+            feed = feed.subscribe(format!(r#"{{"op":"subscribe","args":[{}]}}"#, request.join(",")));
+        }
+        let mut decoder = Decoder::new();
+        let mut books: HashMap<String, LocalBook> = HashMap::new();
+        let handler = move |text: &str, events: &mut Vec<MarketEvent>| {
+            match channel {
+                Channel::L1 => match decoder.decode::<OrderbookMsg>(text).and_then(|m| m.to_l1(topic_symbol(m.topic))) {
+                    Some(order_book) => events.push(MarketEvent::L1(order_book)),
+                    None => return control(text),
+                },
+                Channel::L2 => match decoder.decode::<DepthMsg>(text).and_then(|m| Some((topic_symbol(m.topic), m.to_update()?))) {
+                    // Bybit publishes no checksum, so `apply` cannot fail here.
+                    Some((symbol, update)) => {
+                        let book = books.entry(symbol.to_string()).or_insert_with(|| LocalBook::new(50));
+                        if book.apply(&update).is_ok() {
+                            events.push(MarketEvent::L2(book.to_l2("bybit", symbol, update.timestamp)));
                         }
                     }
-                    Ok(Message::Close(_)) => {
-                        println!("Bybit WebSocket connection closed");
-                        break;
-                    }
-                    Err(e) => {
-                        eprintln!("Bybit WebSocket error: {}", e);
-                        break;
-                    }
-                    _ => {}
-                }
+                    None => return control(text),
+                },
+                Channel::Trades => match decoder.decode::<TradesMsg>(text).and_then(|m| m.to_trades(topic_symbol(m.topic))) {
+                    Some(trades) => events.extend(trades.into_iter().map(MarketEvent::Trade)),
+                    None => return control(text),
+                },
             }
-        });
-
-        Ok(())
+            Frame::Data
+        };
+        feed.spawn(&self.config, handler, callback).await
     }
-}
\ No newline at end of file
+}
+
+pub fn parse_l1(text: &str, symbol: &str) -> Option<OrderBookL1> {
+    serde_json::from_str::<OrderbookMsg>(text).ok()?.to_l1(symbol)
+}
+
+pub fn parse_book_update(text: &str) -> Option<BookUpdate> {
+    serde_json::from_str::<DepthMsg>(text).ok()?.to_update()
+}
+
+pub fn parse_trades(text: &str, symbol: &str) -> Option<Vec<Trade>> {
+    serde_json::from_str::<TradesMsg>(text).ok()?.to_trades(symbol)
+}
@@ -0,0 +1,103 @@
+use serde::de::{Deserialize, Deserializer, IgnoredAny, SeqAccess, Visitor};
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime};
+
+// Deserializes exchange payloads into borrowed message structs. With the `simd`
+// feature the text is copied into a reusable scratch buffer and parsed by simd-json,
+// otherwise serde_json borrows straight from the frame.
+#[derive(Default)]
+pub struct Decoder {
+    #[cfg(feature = "simd")]
+    scratch: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(not(feature = "simd"))]
+    pub fn decode<'a, T: Deserialize<'a>>(&'a mut self, text: &'a str) -> Option<T> {
+        serde_json::from_str(text).ok()
+    }
+
+    #[cfg(feature = "simd")]
+    pub fn decode<'a, T: Deserialize<'a>>(&'a mut self, text: &'a str) -> Option<T> {
+        self.scratch.clear();
+        self.scratch.extend_from_slice(text.as_bytes());
+        simd_json::serde::from_slice(&mut self.scratch).ok()
+    }
+}
+
+// First element of a JSON array; the remaining elements are skipped without
+// allocating, which matters for deep book snapshots where only the top is used.
+pub struct First<T>(pub Option<T>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for First<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FirstVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for FirstVisitor<T> {
+            type Value = First<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let first = seq.next_element()?;
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(First(first))
+            }
+        }
+
+        deserializer.deserialize_seq(FirstVisitor(PhantomData))
+    }
+}
+
+// `[price, size, ...]` book level; trailing venue-specific fields are ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct Level<'a> {
+    pub price: &'a str,
+    pub size: &'a str,
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Level<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LevelVisitor;
+
+        impl<'de> Visitor<'de> for LevelVisitor {
+            type Value = Level<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a [price, size, ...] array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let price = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let size = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(Level { price, size })
+            }
+        }
+
+        deserializer.deserialize_seq(LevelVisitor)
+    }
+}
+
+pub fn price(s: &str) -> Option<f64> {
+    s.parse().ok()
+}
+
+pub fn levels(levels: &[Level]) -> Option<Vec<(f64, f64)>> {
+    levels.iter().map(|l| Some((price(l.price)?, price(l.size)?))).collect()
+}
+
+pub fn millis(ms: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(ms)
+}
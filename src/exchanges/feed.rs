@@ -0,0 +1,295 @@
+// Connection loop shared by the venue clients: connects, subscribes, keeps the connection alive
+// and hands every text frame to a venue-specific handler until the config's shutdown fires. When
+// the venue drops the connection it reconnects with capped exponential backoff and subscribes
+// again, asking the feed's connector for a fresh endpoint first. Events reach the callback
+// through the config's delivery queue, or go to the config's shared queue if it has one.
+
+use crate::config::Config;
+use crate::delivery::EventQueue;
+use crate::error::SoqaError;
+use crate::exchanges::book::ChecksumMismatch;
+use crate::health::FeedTracker;
+use crate::models::MarketEvent;
+use futures_util::future::BoxFuture;
+use futures_util::{FutureExt, SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::Interval;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tracing::{error, info, info_span, warn, Instrument};
+
+// What a handler made of one text frame. Events go into the buffer passed alongside.
+pub(crate) enum Frame {
+    // Market data; the buffer may be empty, e.g. an update that leaves the book unchanged.
+    Data,
+    // Acks, heartbeats and pongs.
+    Control,
+    // A message the venue must get back, e.g. a pong.
+    Reply(String),
+    // The handler's local book failed the venue's checksum. Counted, and `messages` (e.g. an
+    // unsubscribe and a fresh subscribe) are sent to get a new snapshot.
+    Resync { mismatch: ChecksumMismatch, messages: Vec<String> },
+    // Counted as a parse failure.
+    Unknown,
+}
+
+pub(crate) struct Ping {
+    pub every: Duration,
+    pub message: Box<dyn FnMut() -> String + Send>,
+}
+
+// Where one connection goes and what it sends once open.
+pub(crate) struct Endpoint {
+    pub url: String,
+    pub subscribe: Vec<String>,
+    // Replaces the feed's ping for this connection, e.g. with the interval the venue handed out.
+    pub ping: Option<Ping>,
+}
+
+// Called before every connection attempt, so venues that hand out one-time tokens (KuCoin's
+// `bullet-public`) get a fresh one on every reconnect.
+pub(crate) type Connector = Box<dyn FnMut() -> BoxFuture<'static, Result<Endpoint, SoqaError>> + Send>;
+
+pub(crate) struct Feed {
+    pub exchange: String,
+    pub url: String,
+    pub subscribe: Vec<String>,
+    pub connector: Option<Connector>,
+    pub ping: Option<Ping>,
+    // Whether event timestamps come from the venue, so they can be used for latency.
+    pub venue_time: bool,
+}
+
+// First wait before reconnecting; doubled after every failed attempt up to the maximum, and reset
+// once a connection has delivered messages again.
+const RECONNECT_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn connect(url: &str, subscribe: &[String]) -> Result<Socket, SoqaError> {
+    let (mut ws, _) = connect_async(url).await?;
+    for message in subscribe {
+        ws.send(Message::Text(message.clone())).await?;
+    }
+    Ok(ws)
+}
+
+async fn reconnect(connector: &mut Connector) -> Result<(Socket, Option<Ping>), SoqaError> {
+    let endpoint = connector().await?;
+    let ws = connect(&endpoint.url, &endpoint.subscribe).await?;
+    Ok((ws, endpoint.ping))
+}
+
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+async fn send_all<S: SinkExt<Message> + Unpin>(ws: &mut S, messages: Vec<String>) -> Result<(), S::Error> {
+    for message in messages {
+        ws.send(Message::Text(message)).await?;
+    }
+    Ok(())
+}
+
+impl Feed {
+    pub(crate) fn new(exchange: &str, url: impl Into<String>) -> Self {
+        Feed {
+            exchange: exchange.to_string(),
+            url: url.into(),
+            subscribe: Vec::new(),
+            connector: None,
+            ping: None,
+            venue_time: true,
+        }
+    }
+
+    // A feed whose URL and subscribe messages are worked out again for every connection.
+    pub(crate) fn connect_with<F>(exchange: &str, mut connector: impl FnMut() -> F + Send + 'static) -> Self
+    where
+        F: std::future::Future<Output = Result<Endpoint, SoqaError>> + Send + 'static,
+    {
+        Feed { connector: Some(Box::new(move || connector().boxed())), ..Feed::new(exchange, String::new()) }
+    }
+
+    pub(crate) fn subscribe(mut self, message: String) -> Self {
+        self.subscribe.push(message);
+        self
+    }
+
+    pub(crate) fn ping(mut self, every: Duration, message: impl FnMut() -> String + Send + 'static) -> Self {
+        self.ping = Some(Ping { every, message: Box::new(message) });
+        self
+    }
+
+    pub(crate) fn local_time(mut self) -> Self {
+        self.venue_time = false;
+        self
+    }
+
+    pub(crate) async fn spawn(
+        self,
+        config: &Config,
+        mut handler: impl FnMut(&str, &mut Vec<MarketEvent>) -> Frame + Send + 'static,
+        callback: impl Fn(MarketEvent) + Send + 'static,
+    ) -> Result<(), SoqaError> {
+        let Feed { exchange, url, subscribe, connector, ping, venue_time } = self;
+        let mut connector = connector.unwrap_or_else(|| {
+            Box::new(move || {
+                let endpoint = Endpoint { url: url.clone(), subscribe: subscribe.clone(), ping: None };
+                async move { Ok(endpoint) }.boxed()
+            })
+        });
+        let endpoint = connector().await?;
+        let mut ws = connect(&endpoint.url, &endpoint.subscribe).await?;
+        let mut endpoint_ping = endpoint.ping;
+        let tracker = config.tracker();
+        // A feed carrying several symbols checks each symbol's events against that symbol's tracker.
+        let per_symbol = config.symbols.len() > 1;
+        let mut symbol_trackers: HashMap<String, FeedTracker> = HashMap::new();
+        let shutdown = config.shutdown.clone().unwrap_or_default();
+        let queue = match &config.queue {
+            Some(queue) => queue.clone(),
+            None => {
+                let queue = EventQueue::new(config.delivery);
+                queue.spawn_consumer(callback);
+                queue
+            }
+        };
+        let policy = queue.delivery().overflow;
+        queue.attach();
+        let span = info_span!(target: "soqa::feed", "feed", exchange = %exchange, symbol = %config.symbol);
+
+        tokio::spawn(async move {
+            let mut ping = ping;
+            let mut events = Vec::new();
+            let mut backoff = RECONNECT_MIN;
+            'feed: loop {
+                tracker.connected();
+                if let Some(replacement) = endpoint_ping.take() {
+                    ping = Some(replacement);
+                }
+                let every = ping.as_ref().map(|ping| ping.every);
+                let mut interval = every.map(|every| {
+                    let mut interval = tokio::time::interval(every);
+                    interval.reset();
+                    interval
+                });
+                let mut received = false;
+                let stopping = loop {
+                    tokio::select! {
+                        msg = ws.next() => match msg {
+                            Some(Ok(Message::Text(text))) => {
+                                received = true;
+                                tracker.message(text.len());
+                                match handler(&text, &mut events) {
+                                    Frame::Data => {
+                                        for event in events.drain(..) {
+                                            let event_tracker = if per_symbol {
+                                                let symbol = event.symbol();
+                                                if !symbol_trackers.contains_key(symbol) {
+                                                    symbol_trackers.insert(symbol.to_string(), tracker.symbol(symbol));
+                                                }
+                                                let event_tracker = &symbol_trackers[symbol];
+                                                event_tracker.event();
+                                                event_tracker
+                                            } else {
+                                                &tracker
+                                            };
+                                            if venue_time {
+                                                event_tracker.event_latency(event.timestamp());
+                                            }
+                                            if let MarketEvent::L1(book) = &event {
+                                                event_tracker.quote(book);
+                                            }
+                                            let dropped = tokio::select! {
+                                                dropped = queue.push(event) => dropped,
+                                                _ = shutdown.wait() => break,
+                                            };
+                                            if dropped {
+                                                tracker.dropped(policy);
+                                            }
+                                        }
+                                    }
+                                    Frame::Control => {}
+                                    Frame::Reply(reply) => {
+                                        if let Err(e) = ws.send(Message::Text(reply)).await {
+                                            error!(target: "soqa::feed", error = %e, "WebSocket error");
+                                            break false;
+                                        }
+                                    }
+                                    Frame::Resync { mismatch, messages } => {
+                                        tracker.checksum_mismatch(mismatch);
+                                        if let Err(e) = send_all(&mut ws, messages).await {
+                                            error!(target: "soqa::feed", error = %e, "WebSocket error");
+                                            break false;
+                                        }
+                                    }
+                                    Frame::Unknown => tracker.parse_failure(),
+                                }
+                                events.clear();
+                            }
+                            Some(Ok(Message::Close(_))) | None => {
+                                info!(target: "soqa::feed", "connection closed");
+                                break false;
+                            }
+                            Some(Err(e)) => {
+                                error!(target: "soqa::feed", error = %e, "WebSocket error");
+                                break false;
+                            }
+                            Some(Ok(_)) => {}
+                        },
+                        _ = tick(&mut interval) => {
+                            let message = ping.as_mut().map(|ping| (ping.message)()).unwrap_or_default();
+                            if let Err(e) = ws.send(Message::Text(message)).await {
+                                warn!(target: "soqa::feed", error = %e, "failed to send ping");
+                                break false;
+                            }
+                        }
+                        _ = shutdown.wait() => {
+                            let _ = tokio::time::timeout(Duration::from_secs(2), ws.close(None)).await;
+                            info!(target: "soqa::feed", "connection closed on shutdown");
+                            break true;
+                        }
+                    }
+                };
+                tracker.disconnected();
+                for symbol_tracker in symbol_trackers.values() {
+                    symbol_tracker.detached();
+                }
+                if stopping {
+                    break;
+                }
+                if received {
+                    backoff = RECONNECT_MIN;
+                }
+                // Subscribing again makes the venue send a fresh snapshot, which resets the handler's books.
+                (ws, endpoint_ping) = loop {
+                    warn!(target: "soqa::feed", retry_in_ms = backoff.as_millis() as u64, "reconnecting");
+                    tokio::select! {
+                        _ = tokio::time::sleep(backoff) => {}
+                        _ = shutdown.wait() => break 'feed,
+                    }
+                    backoff = (backoff * 2).min(RECONNECT_MAX);
+                    let connected = tokio::select! {
+                        connected = reconnect(&mut connector) => connected,
+                        _ = shutdown.wait() => break 'feed,
+                    };
+                    match connected {
+                        Ok(connected) => break connected,
+                        Err(e) => warn!(target: "soqa::feed", error = %e, "reconnect failed"),
+                    }
+                };
+            }
+            queue.detach();
+        }.instrument(span));
+
+        Ok(())
+    }
+}
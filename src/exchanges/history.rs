@@ -0,0 +1,550 @@
+use crate::error::SoqaError;
+use crate::exchanges::decode::{levels, millis, price, Level};
+use crate::models::{HistoricalTrade, OrderBookL2};
+use crate::ratelimit::{self, RateLimiter};
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::SystemTime;
+use tracing::debug;
+
+// One page of trades plus the opaque cursor for the next request, if there is more.
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+    pub trades: Vec<HistoricalTrade>,
+    pub next: Option<String>,
+}
+
+fn base_url(exchange: &str) -> Result<&'static str, SoqaError> {
+    match exchange {
+        "binance" => Ok("https://api.binance.com"),
+        "bybit" => Ok("https://api.bybit.com"),
+        "okx" => Ok("https://www.okx.com"),
+        "kraken" => Ok("https://api.kraken.com"),
+        "kucoin" => Ok("https://api.kucoin.com"),
+        other => Err(SoqaError::ExchangeNotSupported(other.to_string())),
+    }
+}
+
+pub struct HistoryClient {
+    exchange: String,
+    base_url: String,
+    api_key: Option<String>,
+    http: reqwest::Client,
+    limiter: RateLimiter,
+}
+
+impl HistoryClient {
+    pub fn new(exchange: &str) -> Result<Self, SoqaError> {
+        Ok(HistoryClient {
+            exchange: exchange.to_string(),
+            base_url: base_url(exchange)?.to_string(),
+            api_key: None,
+            http: reqwest::Client::new(),
+            limiter: ratelimit::global(),
+        })
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    // Binance only: switches from aggTrades to the keyed historicalTrades endpoint.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    async fn get(&self, path: &str) -> Result<String, SoqaError> {
+        let url = format!("{}{}", self.base_url, path);
+        debug!(target: "soqa::history", %url, "request");
+        let mut request = self.http.get(&url);
+        if let Some(key) = &self.api_key {
+            request = request.header("X-MBX-APIKEY", key);
+        }
+        let (status, body) = self.limiter.send_text(&self.exchange, request).await?;
+        if !status.is_success() {
+            return Err(SoqaError::ConnectionError(format!(
+                "{} history request failed: HTTP {}: {}",
+                self.exchange, status, body
+            )));
+        }
+        Ok(body)
+    }
+
+    // Fetches the page after `cursor` (or the first page of the range) and keeps only trades in [start_ms, end_ms).
+    pub async fn fetch_page(&mut self, symbol: &str, start_ms: u64, end_ms: u64, cursor: Option<&str>) -> Result<Page, SoqaError> {
+        let page = match self.exchange.as_str() {
+            "binance" => self.binance_page(symbol, start_ms, end_ms, cursor).await?,
+            "bybit" => {
+                const LIMIT: usize = 60;
+                let body = self.get(&format!("/v5/market/recent-trade?category=spot&symbol={}&limit={}", symbol, LIMIT)).await?;
+                let trades = parse_bybit_trades(&body, symbol)?;
+                reaches_start("bybit", &trades, LIMIT, start_ms)?;
+                Page { trades, next: None }
+            }
+            "okx" => {
+                const LIMIT: usize = 100;
+                // OKX pages backwards: by timestamp from the end of the range, then by trade id.
+                let after = match cursor {
+                    Some(id) => format!("type=1&after={}", id),
+                    None => format!("type=2&after={}", end_ms),
+                };
+                let body = self.get(&format!("/api/v5/market/history-trades?instId={}&{}&limit={}", symbol, after, LIMIT)).await?;
+                let trades = parse_okx_trades(&body, symbol)?;
+                let next = match trades.last() {
+                    Some(oldest) if trades.len() == LIMIT && oldest.timestamp_ms >= start_ms => Some(oldest.trade_id.clone()),
+                    _ => None,
+                };
+                Page { trades, next }
+            }
+            "kraken" => {
+                let since = cursor.map(str::to_string).unwrap_or_else(|| (start_ms * 1_000_000).to_string());
+                let pair = symbol.replace('/', "");
+                let body = self.get(&format!("/0/public/Trades?pair={}&since={}&count=1000", pair, since)).await?;
+                let (trades, last) = parse_kraken_trades(&body, symbol)?;
+                let next = match trades.last() {
+                    Some(newest) if newest.timestamp_ms < end_ms && last != since => Some(last),
+                    _ => None,
+                };
+                Page { trades, next }
+            }
+            "kucoin" => {
+                const LIMIT: usize = 100;
+                let body = self.get(&format!("/api/v1/market/histories?symbol={}", symbol)).await?;
+                let trades = parse_kucoin_trades(&body, symbol)?;
+                reaches_start("kucoin", &trades, LIMIT, start_ms)?;
+                Page { trades, next: None }
+            }
+            other => return Err(SoqaError::ExchangeNotSupported(other.to_string())),
+        };
+        Ok(Page {
+            trades: page.trades.into_iter().filter(|t| t.timestamp_ms >= start_ms && t.timestamp_ms < end_ms).collect(),
+            next: page.next,
+        })
+    }
+
+    // The venue's current order book, `depth` levels a side at most (KuCoin serves 20 or 100).
+    pub async fn fetch_book(&mut self, symbol: &str, depth: usize) -> Result<OrderBookL2, SoqaError> {
+        let mut book = match self.exchange.as_str() {
+            "binance" => {
+                let body = self.get(&format!("/api/v3/depth?symbol={}&limit={}", symbol, depth.min(5000))).await?;
+                parse_binance_book(&body, symbol)?
+            }
+            "bybit" => {
+                let body = self.get(&format!("/v5/market/orderbook?category=spot&symbol={}&limit={}", symbol, depth.min(200))).await?;
+                parse_bybit_book(&body, symbol)?
+            }
+            "okx" => {
+                let body = self.get(&format!("/api/v5/market/books?instId={}&sz={}", symbol, depth.min(400))).await?;
+                parse_okx_book(&body, symbol)?
+            }
+            "kraken" => {
+                let pair = symbol.replace('/', "");
+                let body = self.get(&format!("/0/public/Depth?pair={}&count={}", pair, depth.min(500))).await?;
+                parse_kraken_book(&body, symbol)?
+            }
+            "kucoin" => {
+                let levels = if depth <= 20 { 20 } else { 100 };
+                let body = self.get(&format!("/api/v1/market/orderbook/level2_{}?symbol={}", levels, symbol)).await?;
+                parse_kucoin_book(&body, symbol)?
+            }
+            other => return Err(SoqaError::ExchangeNotSupported(other.to_string())),
+        };
+        book.bids.truncate(depth);
+        book.asks.truncate(depth);
+        Ok(book)
+    }
+
+    async fn binance_page(&mut self, symbol: &str, start_ms: u64, end_ms: u64, cursor: Option<&str>) -> Result<Page, SoqaError> {
+        const LIMIT: usize = 1000;
+        let trades = if self.api_key.is_some() {
+            let from_id = match cursor {
+                Some(id) => id.to_string(),
+                // historicalTrades has no time filter; seed the first id from the aggregate trade at the start.
+                None => {
+                    let body = self.get(&format!("/api/v3/aggTrades?symbol={}&startTime={}&limit=1", symbol, start_ms)).await?;
+                    match serde_json::from_str::<Vec<AggTrade>>(&body)?.first() {
+                        Some(first) => first.first_id.to_string(),
+                        None => return Ok(Page::default()),
+                    }
+                }
+            };
+            let body = self.get(&format!("/api/v3/historicalTrades?symbol={}&fromId={}&limit={}", symbol, from_id, LIMIT)).await?;
+            parse_binance_trades(&body, symbol)?
+        } else {
+            let from = match cursor {
+                Some(id) => format!("fromId={}", id),
+                None => format!("startTime={}", start_ms),
+            };
+            let body = self.get(&format!("/api/v3/aggTrades?symbol={}&{}&limit={}", symbol, from, LIMIT)).await?;
+            parse_binance_agg_trades(&body, symbol)?
+        };
+        let next = match trades.last() {
+            Some(last) if trades.len() == LIMIT && last.timestamp_ms < end_ms => {
+                last.trade_id.parse::<u64>().ok().map(|id| (id + 1).to_string())
+            }
+            _ => None,
+        };
+        Ok(Page { trades, next })
+    }
+}
+
+// Bybit and KuCoin serve only their latest trades and cannot page back. A full page that begins
+// after `start_ms` means part of the range is out of reach, so the download fails instead of
+// quietly writing a partial range.
+fn reaches_start(exchange: &str, trades: &[HistoricalTrade], limit: usize, start_ms: u64) -> Result<(), SoqaError> {
+    match trades.iter().map(|t| t.timestamp_ms).min() {
+        Some(oldest) if trades.len() >= limit && oldest > start_ms => Err(SoqaError::ConfigError(format!(
+            "{} only serves its latest {} trades, which begin at {}, after the requested start {}",
+            exchange, limit, oldest, start_ms
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn trade(exchange: &str, symbol: &str, trade_id: String, price: f64, volume: f64, side: &str, timestamp_ms: u64) -> HistoricalTrade {
+    HistoricalTrade {
+        exchange: exchange.to_string(),
+        symbol: symbol.to_string(),
+        trade_id,
+        price,
+        volume,
+        side: side.to_lowercase(),
+        timestamp_ms,
+    }
+}
+
+fn invalid(exchange: &str, what: &str) -> SoqaError {
+    SoqaError::ConnectionError(format!("{} history response: {}", exchange, what))
+}
+
+#[derive(Deserialize)]
+struct AggTrade<'a> {
+    #[serde(rename = "a")]
+    id: u64,
+    #[serde(rename = "f")]
+    first_id: u64,
+    #[serde(rename = "p")]
+    price: &'a str,
+    #[serde(rename = "q")]
+    qty: &'a str,
+    #[serde(rename = "T")]
+    time: u64,
+    #[serde(rename = "m")]
+    buyer_maker: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceTrade<'a> {
+    id: u64,
+    price: &'a str,
+    qty: &'a str,
+    time: u64,
+    is_buyer_maker: bool,
+}
+
+// A maker buyer means the taker sold.
+fn binance_side(buyer_maker: bool) -> &'static str {
+    if buyer_maker {
+        "sell"
+    } else {
+        "buy"
+    }
+}
+
+pub fn parse_binance_agg_trades(text: &str, symbol: &str) -> Result<Vec<HistoricalTrade>, SoqaError> {
+    serde_json::from_str::<Vec<AggTrade>>(text)?
+        .iter()
+        .map(|t| {
+            let (price, qty) = price(t.price).zip(price(t.qty)).ok_or_else(|| invalid("binance", "bad price"))?;
+            Ok(trade("binance", symbol, t.id.to_string(), price, qty, binance_side(t.buyer_maker), t.time))
+        })
+        .collect()
+}
+
+pub fn parse_binance_trades(text: &str, symbol: &str) -> Result<Vec<HistoricalTrade>, SoqaError> {
+    serde_json::from_str::<Vec<BinanceTrade>>(text)?
+        .iter()
+        .map(|t| {
+            let (price, qty) = price(t.price).zip(price(t.qty)).ok_or_else(|| invalid("binance", "bad price"))?;
+            Ok(trade("binance", symbol, t.id.to_string(), price, qty, binance_side(t.is_buyer_maker), t.time))
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitResponse<'a> {
+    ret_code: i64,
+    ret_msg: &'a str,
+    #[serde(borrow)]
+    result: Option<BybitResult<'a>>,
+}
+
+#[derive(Deserialize)]
+struct BybitResult<'a> {
+    #[serde(borrow)]
+    list: Vec<BybitTrade<'a>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitTrade<'a> {
+    exec_id: &'a str,
+    price: &'a str,
+    size: &'a str,
+    side: &'a str,
+    time: &'a str,
+}
+
+pub fn parse_bybit_trades(text: &str, symbol: &str) -> Result<Vec<HistoricalTrade>, SoqaError> {
+    let response: BybitResponse = serde_json::from_str(text)?;
+    if response.ret_code != 0 {
+        return Err(invalid("bybit", response.ret_msg));
+    }
+    response
+        .result
+        .map(|r| r.list)
+        .unwrap_or_default()
+        .iter()
+        .map(|t| {
+            let (price, size) = price(t.price).zip(price(t.size)).ok_or_else(|| invalid("bybit", "bad price"))?;
+            let time = t.time.parse().map_err(|_| invalid("bybit", "bad time"))?;
+            Ok(trade("bybit", symbol, t.exec_id.to_string(), price, size, t.side, time))
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct OkxResponse<'a> {
+    code: &'a str,
+    msg: &'a str,
+    #[serde(borrow, default)]
+    data: Vec<OkxTrade<'a>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxTrade<'a> {
+    trade_id: &'a str,
+    px: &'a str,
+    sz: &'a str,
+    side: &'a str,
+    ts: &'a str,
+}
+
+// Newest first, as OKX returns them.
+pub fn parse_okx_trades(text: &str, symbol: &str) -> Result<Vec<HistoricalTrade>, SoqaError> {
+    let response: OkxResponse = serde_json::from_str(text)?;
+    if response.code != "0" {
+        return Err(invalid("okx", response.msg));
+    }
+    response
+        .data
+        .iter()
+        .map(|t| {
+            let (price, size) = price(t.px).zip(price(t.sz)).ok_or_else(|| invalid("okx", "bad price"))?;
+            let ts = t.ts.parse().map_err(|_| invalid("okx", "bad time"))?;
+            Ok(trade("okx", symbol, t.trade_id.to_string(), price, size, t.side, ts))
+        })
+        .collect()
+}
+
+// Returns the trades and Kraken's `last` cursor (nanoseconds) for the next `since`.
+pub fn parse_kraken_trades(text: &str, symbol: &str) -> Result<(Vec<HistoricalTrade>, String), SoqaError> {
+    let data: Value = serde_json::from_str(text)?;
+    if let Some(error) = data["error"].as_array().and_then(|errors| errors.first()) {
+        return Err(invalid("kraken", error.as_str().unwrap_or("error")));
+    }
+    let result = data["result"].as_object().ok_or_else(|| invalid("kraken", "missing result"))?;
+    let last = match &result.get("last") {
+        Some(Value::String(last)) => last.clone(),
+        Some(Value::Number(last)) => last.to_string(),
+        _ => return Err(invalid("kraken", "missing last")),
+    };
+    let rows = result.iter().find(|(key, _)| *key != "last").and_then(|(_, rows)| rows.as_array());
+    let trades = rows
+        .into_iter()
+        .flatten()
+        .map(|row| {
+            let (price, volume) = row[0]
+                .as_str()
+                .and_then(price)
+                .zip(row[1].as_str().and_then(price))
+                .ok_or_else(|| invalid("kraken", "bad price"))?;
+            let seconds = row[2].as_f64().ok_or_else(|| invalid("kraken", "bad time"))?;
+            let side = if row[3] == "b" { "buy" } else { "sell" };
+            let trade_id = row.get(6).and_then(Value::as_u64).map(|id| id.to_string()).unwrap_or_else(|| format!("{:.9}", seconds));
+            Ok(trade("kraken", symbol, trade_id, price, volume, side, (seconds * 1000.0) as u64))
+        })
+        .collect::<Result<_, SoqaError>>()?;
+    Ok((trades, last))
+}
+
+#[derive(Deserialize)]
+struct KucoinResponse<'a> {
+    code: &'a str,
+    #[serde(borrow, default)]
+    data: Vec<KucoinTrade<'a>>,
+}
+
+#[derive(Deserialize)]
+struct KucoinTrade<'a> {
+    sequence: &'a str,
+    price: &'a str,
+    size: &'a str,
+    side: &'a str,
+    // Nanoseconds.
+    time: u64,
+}
+
+pub fn parse_kucoin_trades(text: &str, symbol: &str) -> Result<Vec<HistoricalTrade>, SoqaError> {
+    let response: KucoinResponse = serde_json::from_str(text)?;
+    if response.code != "200000" {
+        return Err(invalid("kucoin", response.code));
+    }
+    response
+        .data
+        .iter()
+        .map(|t| {
+            let (price, size) = price(t.price).zip(price(t.size)).ok_or_else(|| invalid("kucoin", "bad price"))?;
+            Ok(trade("kucoin", symbol, t.sequence.to_string(), price, size, t.side, t.time / 1_000_000))
+        })
+        .collect()
+}
+
+fn book(exchange: &str, symbol: &str, bids: &[Level], asks: &[Level], timestamp: SystemTime) -> Result<OrderBookL2, SoqaError> {
+    Ok(OrderBookL2 {
+        exchange: exchange.to_string(),
+        symbol: symbol.to_string(),
+        bids: levels(bids).ok_or_else(|| invalid(exchange, "bad price"))?,
+        asks: levels(asks).ok_or_else(|| invalid(exchange, "bad price"))?,
+        timestamp,
+    })
+}
+
+#[derive(Deserialize)]
+struct BookLevels<'a> {
+    #[serde(borrow)]
+    bids: Vec<Level<'a>>,
+    #[serde(borrow)]
+    asks: Vec<Level<'a>>,
+}
+
+// `/api/v3/depth` carries no time, so the book is stamped on arrival.
+pub fn parse_binance_book(text: &str, symbol: &str) -> Result<OrderBookL2, SoqaError> {
+    let data: BookLevels = serde_json::from_str(text)?;
+    book("binance", symbol, &data.bids, &data.asks, SystemTime::now())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitBookResponse<'a> {
+    ret_code: i64,
+    ret_msg: &'a str,
+    #[serde(borrow)]
+    result: Option<BybitBook<'a>>,
+}
+
+#[derive(Deserialize)]
+struct BybitBook<'a> {
+    #[serde(rename = "b", borrow)]
+    bids: Vec<Level<'a>>,
+    #[serde(rename = "a", borrow)]
+    asks: Vec<Level<'a>>,
+    ts: u64,
+}
+
+pub fn parse_bybit_book(text: &str, symbol: &str) -> Result<OrderBookL2, SoqaError> {
+    let response: BybitBookResponse = serde_json::from_str(text)?;
+    if response.ret_code != 0 {
+        return Err(invalid("bybit", response.ret_msg));
+    }
+    let data = response.result.ok_or_else(|| invalid("bybit", "missing result"))?;
+    book("bybit", symbol, &data.bids, &data.asks, millis(data.ts))
+}
+
+#[derive(Deserialize)]
+struct OkxBookResponse<'a> {
+    code: &'a str,
+    msg: &'a str,
+    #[serde(borrow, default)]
+    data: Vec<OkxBook<'a>>,
+}
+
+#[derive(Deserialize)]
+struct OkxBook<'a> {
+    #[serde(borrow)]
+    bids: Vec<Level<'a>>,
+    #[serde(borrow)]
+    asks: Vec<Level<'a>>,
+    ts: &'a str,
+}
+
+pub fn parse_okx_book(text: &str, symbol: &str) -> Result<OrderBookL2, SoqaError> {
+    let response: OkxBookResponse = serde_json::from_str(text)?;
+    if response.code != "0" {
+        return Err(invalid("okx", response.msg));
+    }
+    let data = response.data.first().ok_or_else(|| invalid("okx", "missing book"))?;
+    let ts = data.ts.parse().map_err(|_| invalid("okx", "bad time"))?;
+    book("okx", symbol, &data.bids, &data.asks, millis(ts))
+}
+
+// Levels are `[price, volume, timestamp]` with whole-second times; the book is stamped on arrival.
+pub fn parse_kraken_book(text: &str, symbol: &str) -> Result<OrderBookL2, SoqaError> {
+    let data: Value = serde_json::from_str(text)?;
+    if let Some(error) = data["error"].as_array().and_then(|errors| errors.first()) {
+        return Err(invalid("kraken", error.as_str().unwrap_or("error")));
+    }
+    let pair = data["result"].as_object().and_then(|result| result.values().next()).ok_or_else(|| invalid("kraken", "missing result"))?;
+    let side = |key: &str| -> Result<Vec<(f64, f64)>, SoqaError> {
+        pair[key]
+            .as_array()
+            .ok_or_else(|| invalid("kraken", "missing levels"))?
+            .iter()
+            .map(|level| {
+                level[0].as_str().and_then(price).zip(level[1].as_str().and_then(price)).ok_or_else(|| invalid("kraken", "bad price"))
+            })
+            .collect()
+    };
+    Ok(OrderBookL2 {
+        exchange: "kraken".to_string(),
+        symbol: symbol.to_string(),
+        bids: side("bids")?,
+        asks: side("asks")?,
+        timestamp: SystemTime::now(),
+    })
+}
+
+#[derive(Deserialize)]
+struct KucoinBookResponse<'a> {
+    code: &'a str,
+    #[serde(borrow)]
+    data: Option<KucoinBook<'a>>,
+}
+
+#[derive(Deserialize)]
+struct KucoinBook<'a> {
+    #[serde(borrow)]
+    bids: Vec<Level<'a>>,
+    #[serde(borrow)]
+    asks: Vec<Level<'a>>,
+    time: u64,
+}
+
+pub fn parse_kucoin_book(text: &str, symbol: &str) -> Result<OrderBookL2, SoqaError> {
+    let response: KucoinBookResponse = serde_json::from_str(text)?;
+    if response.code != "200000" {
+        return Err(invalid("kucoin", response.code));
+    }
+    let data = response.data.ok_or_else(|| invalid("kucoin", "missing book"))?;
+    book("kucoin", symbol, &data.bids, &data.asks, millis(data.time))
+}
@@ -0,0 +1,185 @@
+use crate::arbitrage::canonical_symbol;
+use crate::error::SoqaError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// A tradable spot pair; `symbol` is in the form the venue's WebSocket client expects.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instrument {
+    pub symbol: String,
+    pub base: String,
+    pub quote: String,
+}
+
+impl Instrument {
+    pub fn new(symbol: &str, base: &str, quote: &str) -> Self {
+        Instrument { symbol: symbol.to_string(), base: normalize(base), quote: normalize(quote) }
+    }
+}
+
+// Kraken still reports bitcoin as XBT.
+fn normalize(asset: &str) -> String {
+    match asset.to_uppercase().as_str() {
+        "XBT" => "BTC".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn metadata_url(exchange: &str) -> Result<&'static str, SoqaError> {
+    match exchange {
+        "binance" => Ok("https://api.binance.com/api/v3/exchangeInfo"),
+        "bybit" => Ok("https://api.bybit.com/v5/market/instruments-info?category=spot"),
+        "okx" => Ok("https://www.okx.com/api/v5/public/instruments?instType=SPOT"),
+        "kraken" => Ok("https://api.kraken.com/0/public/AssetPairs"),
+        "kucoin" => Ok("https://api.kucoin.com/api/v2/symbols"),
+        other => Err(SoqaError::ExchangeNotSupported(other.to_string())),
+    }
+}
+
+pub async fn fetch_instruments(exchange: &str) -> Result<Vec<Instrument>, SoqaError> {
+    let url = metadata_url(exchange)?;
+    let (status, body) = crate::ratelimit::global().send_text(exchange, reqwest::Client::new().get(url)).await?;
+    if !status.is_success() {
+        return Err(SoqaError::ConnectionError(format!("Failed to get {} instruments: HTTP {}", exchange, status)));
+    }
+    parse_instruments(exchange, &body)
+}
+
+// Parses the venue's instrument listing, keeping only pairs that are currently trading.
+pub fn parse_instruments(exchange: &str, body: &str) -> Result<Vec<Instrument>, SoqaError> {
+    let data: Value = serde_json::from_str(body)?;
+    let field = |item: &Value, key: &str| item[key].as_str().map(str::to_string);
+    let instruments = match exchange {
+        "binance" => array(&data["symbols"])
+            .filter(|s| s["status"] == "TRADING")
+            .filter_map(|s| Some(Instrument::new(&field(s, "symbol")?, &field(s, "baseAsset")?, &field(s, "quoteAsset")?)))
+            .collect(),
+        "bybit" => array(&data["result"]["list"])
+            .filter(|s| s["status"] == "Trading")
+            .filter_map(|s| Some(Instrument::new(&field(s, "symbol")?, &field(s, "baseCoin")?, &field(s, "quoteCoin")?)))
+            .collect(),
+        "okx" => array(&data["data"])
+            .filter(|s| s["state"] == "live")
+            .filter_map(|s| Some(Instrument::new(&field(s, "instId")?, &field(s, "baseCcy")?, &field(s, "quoteCcy")?)))
+            .collect(),
+        "kraken" => data["result"]
+            .as_object()
+            .into_iter()
+            .flat_map(|pairs| pairs.values())
+            .filter(|s| s["status"] == "online")
+            .filter_map(|s| {
+                let wsname = s["wsname"].as_str()?;
+                let (base, quote) = wsname.split_once('/')?;
+                Some(Instrument::new(wsname, base, quote))
+            })
+            .collect(),
+        "kucoin" => array(&data["data"])
+            .filter(|s| s["enableTrading"] == true)
+            .filter_map(|s| {
+                Some(Instrument::new(&field(s, "symbol")?, &field(s, "baseCurrency")?, &field(s, "quoteCurrency")?))
+            })
+            .collect(),
+        other => return Err(SoqaError::ExchangeNotSupported(other.to_string())),
+    };
+    Ok(instruments)
+}
+
+fn array(value: &Value) -> impl Iterator<Item = &Value> {
+    value.as_array().into_iter().flatten()
+}
+
+// A `--symbol` argument: a literal symbol, a glob such as `*USDT` or `BTC?USD`, or a regex
+// written as `/^(BTC|ETH)-/`. Patterns are matched, ignoring case, against both the venue's
+// symbol and `base + quote`, so `*USDT` finds `BTC-USDT` on OKX as well as `BTCUSDT` on Binance.
+#[derive(Debug, Clone)]
+pub enum SymbolPattern {
+    Exact(String),
+    Pattern(regex::Regex),
+}
+
+impl SymbolPattern {
+    pub fn parse(pattern: &str) -> Result<Self, SoqaError> {
+        let source = if let Some(regex) = pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            format!("(?i){}", regex)
+        } else if pattern.contains(['*', '?']) {
+            let glob: String = pattern
+                .chars()
+                .map(|c| match c {
+                    '*' => ".*".to_string(),
+                    '?' => ".".to_string(),
+                    c => regex::escape(&c.to_string()),
+                })
+                .collect();
+            format!("(?i)^{}$", glob)
+        } else {
+            return Ok(SymbolPattern::Exact(pattern.to_string()));
+        };
+        regex::Regex::new(&source)
+            .map(SymbolPattern::Pattern)
+            .map_err(|e| SoqaError::ConfigError(format!("invalid symbol pattern {}: {}", pattern, e)))
+    }
+
+    pub fn parse_all(patterns: &[String]) -> Result<Vec<Self>, SoqaError> {
+        patterns.iter().map(|pattern| Self::parse(pattern)).collect()
+    }
+
+    pub fn is_exact(&self) -> bool {
+        matches!(self, SymbolPattern::Exact(_))
+    }
+
+    pub fn matches(&self, instrument: &Instrument) -> bool {
+        match self {
+            SymbolPattern::Exact(symbol) => instrument.symbol.eq_ignore_ascii_case(symbol),
+            SymbolPattern::Pattern(regex) => {
+                regex.is_match(&instrument.symbol) || regex.is_match(&format!("{}{}", instrument.base, instrument.quote))
+            }
+        }
+    }
+}
+
+// The venue's spelling of a literal symbol: the listed instrument with the same canonical form, so
+// `BTC-USDT` becomes `BTCUSDT` on Binance and `XBT/USDT` on Kraken. Symbols the list does not
+// have are passed on in canonical form, which the clients map themselves.
+fn venue_symbol(symbol: &str, instruments: &[Instrument]) -> String {
+    let canonical = canonical_symbol(symbol);
+    instruments
+        .iter()
+        .find(|i| canonical_symbol(&i.symbol) == canonical || format!("{}{}", i.base, i.quote) == canonical)
+        .map(|i| i.symbol.clone())
+        .unwrap_or(canonical)
+}
+
+// Expands `patterns` into the venue's symbols, in the order given and without duplicates.
+pub fn resolve_symbols(patterns: &[SymbolPattern], instruments: &[Instrument]) -> Vec<String> {
+    let mut symbols: Vec<String> = Vec::new();
+    for pattern in patterns {
+        let matched: Vec<String> = match pattern {
+            SymbolPattern::Exact(symbol) => vec![venue_symbol(symbol, instruments)],
+            SymbolPattern::Pattern(_) => instruments.iter().filter(|i| pattern.matches(i)).map(|i| i.symbol.clone()).collect(),
+        };
+        for symbol in matched {
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+    }
+    symbols
+}
+
+// Every (exchange, symbol) pair named by `exchanges` × `patterns`. With only literal symbols a
+// venue whose instrument list cannot be fetched still gets them, in canonical form.
+pub async fn expand(exchanges: &[String], patterns: &[SymbolPattern]) -> Result<Vec<(String, String)>, SoqaError> {
+    let mut pairs = Vec::new();
+    for exchange in exchanges {
+        let instruments = match fetch_instruments(exchange).await {
+            Ok(instruments) => instruments,
+            Err(e) if patterns.iter().all(SymbolPattern::is_exact) => {
+                tracing::warn!("{}: {}; using canonical symbols", exchange, e);
+                Vec::new()
+            }
+            Err(e) => return Err(e),
+        };
+        pairs.extend(resolve_symbols(patterns, &instruments).into_iter().map(|symbol| (exchange.clone(), symbol)));
+    }
+    Ok(pairs)
+}
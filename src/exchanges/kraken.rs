@@ -1,10 +1,183 @@
-use crate::models::{OrderBookL1};
+use crate::models::{MarketEvent, OrderBookL1, QuoteSource, Trade};
 use crate::error::SoqaError;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use crate::exchanges::book::{BookUpdate, Checksum, LocalBook};
+use crate::exchanges::decode::{price, Decoder};
+use crate::exchanges::feed::{Feed, Frame};
+use crate::subscriptions::Channel;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
 use serde_json::Value;
-use std::time::SystemTime;
-use futures_util::stream::StreamExt;
-use futures_util::SinkExt;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+
+// `[channelID, {..}, "ticker", "XBT/USD"]`
+#[derive(Deserialize)]
+pub struct TickerMsg<'a>(pub IgnoredAny, #[serde(borrow)] pub Ticker<'a>, pub &'a str, pub &'a str);
+
+#[derive(Deserialize)]
+pub struct Ticker<'a> {
+    #[serde(rename = "b", borrow)]
+    pub bid: Quote<'a>,
+    #[serde(rename = "a", borrow)]
+    pub ask: Quote<'a>,
+}
+
+// `[price, wholeLotVolume, lotVolume]`
+#[derive(Deserialize)]
+pub struct Quote<'a>(pub &'a str, pub IgnoredAny, pub &'a str);
+
+impl TickerMsg<'_> {
+    pub fn to_l1(&self, symbol: &str) -> Option<OrderBookL1> {
+        if self.2 != "ticker" {
+            return None;
+        }
+        let ticker = &self.1;
+        Some(OrderBookL1 {
+            exchange: "kraken".to_string(),
+            symbol: symbol.to_string(),
+            bid: price(ticker.bid.0)?,
+            bid_volume: price(ticker.bid.2)?,
+            ask: price(ticker.ask.0)?,
+            ask_volume: price(ticker.ask.2)?,
+            timestamp: SystemTime::now(),
+            source: QuoteSource::Ticker,
+        })
+    }
+}
+
+// `[channelID, [bid, ask, timestamp, bidVolume, askVolume], "spread", "XBT/USD"]`
+#[derive(Deserialize)]
+pub struct SpreadMsg<'a>(pub IgnoredAny, #[serde(borrow)] pub Spread<'a>, pub &'a str, pub &'a str);
+
+#[derive(Deserialize)]
+pub struct Spread<'a>(pub &'a str, pub &'a str, pub &'a str, pub &'a str, pub &'a str);
+
+impl SpreadMsg<'_> {
+    pub fn to_l1(&self, symbol: &str) -> Option<OrderBookL1> {
+        if self.2 != "spread" {
+            return None;
+        }
+        let Spread(bid, ask, timestamp, bid_volume, ask_volume) = self.1;
+        Some(OrderBookL1 {
+            exchange: "kraken".to_string(),
+            symbol: symbol.to_string(),
+            bid: price(bid)?,
+            bid_volume: price(bid_volume)?,
+            ask: price(ask)?,
+            ask_volume: price(ask_volume)?,
+            timestamp: seconds(timestamp)?,
+            source: QuoteSource::Bbo,
+        })
+    }
+}
+
+// Kraken sends times as fractional seconds in a string; the fraction is read digit by digit
+// so microsecond timestamps survive without float rounding.
+fn seconds(s: &str) -> Option<SystemTime> {
+    let (secs, fraction) = s.split_once('.').unwrap_or((s, ""));
+    let digits = fraction.get(..fraction.len().min(9))?;
+    let nanos = if digits.is_empty() { 0 } else { digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32) };
+    Some(SystemTime::UNIX_EPOCH + Duration::new(secs.parse().ok()?, nanos))
+}
+
+// `[[price, volume, timestamp, ...], ...]`, appended to `levels` and, as sent, to `raw`; keeps
+// the latest timestamp.
+fn book_levels(
+    data: &Value,
+    levels: &mut Vec<(f64, f64)>,
+    raw: &mut Vec<(String, String)>,
+    latest: &mut Option<SystemTime>,
+) -> Option<()> {
+    for level in data.as_array()? {
+        let timestamp = seconds(level.get(2)?.as_str()?)?;
+        *latest = (*latest).max(Some(timestamp));
+        let (level_price, volume) = (level.get(0)?.as_str()?, level.get(1)?.as_str()?);
+        levels.push((price(level_price)?, price(volume)?));
+        raw.push((level_price.to_string(), volume.to_string()));
+    }
+    Some(())
+}
+
+// `[channelID, {"as": .., "bs": ..}, "book-25", pair]` for the snapshot, then
+// `[channelID, {"a": ..}, {"b": ..}, "book-25", pair]` with either side optional; the last
+// object of an update carries the checksum `"c"` of the book after it.
+fn book_update(data: &Value) -> Option<BookUpdate> {
+    let frame = data.as_array()?;
+    if frame.len() < 4 || !frame[frame.len() - 2].as_str()?.starts_with("book") {
+        return None;
+    }
+    let mut update = BookUpdate::default();
+    let mut latest = None;
+    for part in &frame[1..frame.len() - 2] {
+        let part = part.as_object()?;
+        if let (Some(asks), Some(bids)) = (part.get("as"), part.get("bs")) {
+            update.snapshot = true;
+            book_levels(asks, &mut update.asks, &mut update.raw_asks, &mut latest)?;
+            book_levels(bids, &mut update.bids, &mut update.raw_bids, &mut latest)?;
+        }
+        if let Some(asks) = part.get("a") {
+            book_levels(asks, &mut update.asks, &mut update.raw_asks, &mut latest)?;
+        }
+        if let Some(bids) = part.get("b") {
+            book_levels(bids, &mut update.bids, &mut update.raw_bids, &mut latest)?;
+        }
+        if let Some(checksum) = part.get("c") {
+            update.checksum = Some(checksum.as_str()?.parse().ok()?);
+        }
+    }
+    update.timestamp = latest.unwrap_or_else(SystemTime::now);
+    Some(update)
+}
+
+// `[channelID, [[price, volume, time, side, orderType, misc], ...], "trade", pair]`
+fn trades(data: &Value, symbol: &str) -> Option<Vec<Trade>> {
+    let frame = data.as_array()?;
+    if frame.len() != 4 || frame[2].as_str()? != "trade" {
+        return None;
+    }
+    frame[1]
+        .as_array()?
+        .iter()
+        .map(|trade| {
+            let side = match trade.get(3)?.as_str()? {
+                "b" => "buy",
+                _ => "sell",
+            };
+            Some(Trade {
+                exchange: "kraken".to_string(),
+                symbol: symbol.to_string(),
+                price: price(trade.get(0)?.as_str()?)?,
+                volume: price(trade.get(1)?.as_str()?)?,
+                side: side.to_string(),
+                timestamp: seconds(trade.get(2)?.as_str()?)?,
+            })
+        })
+        .collect()
+}
+
+// The pair a channel message is for, its last element.
+fn pair(data: &Value) -> Option<&str> {
+    data.as_array()?.last()?.as_str()
+}
+
+// Heartbeats, subscription status and errors.
+fn control(data: &Value) -> Frame {
+    match data.get("event").and_then(|e| e.as_str()) {
+        Some("heartbeat") => Frame::Control,
+        Some("subscriptionStatus") if data.get("status").and_then(|s| s.as_str()) == Some("subscribed") => {
+            info!(target: "soqa::kraken", "subscribed");
+            Frame::Control
+        }
+        Some(_) => {
+            if let Some(error_msg) = data.get("errorMessage").and_then(|e| e.as_str()) {
+                warn!(target: "soqa::kraken", error = %error_msg, "exchange error");
+            }
+            Frame::Control
+        }
+        None => Frame::Unknown,
+    }
+}
 
 pub struct KrakenClient {
     config: crate::config::Config,
@@ -26,80 +199,98 @@ impl KrakenClient {
     }
 
     pub async fn subscribe_l1(&self, callback: impl Fn(OrderBookL1) + Send + 'static) -> Result<(), SoqaError> {
-        let url = "wss://ws.kraken.com";
-        let (ws_stream, _) = connect_async(url).await?;
-        let (mut write, mut read) = ws_stream.split();
-
-        let symbol = self.convert_symbol(&self.config.symbol);
-        let original_symbol = self.config.symbol.clone();
-        println!("Subscribing to Kraken with symbol: {}", symbol);
-        
-        let subscribe_msg = format!(
-            r#"{{"event":"subscribe","pair":["{}"],"subscription":{{"name":"ticker"}}}}"#,
-            symbol
-        );
-        println!("Sending subscription message: {}", subscribe_msg);
-        write.send(Message::Text(subscribe_msg)).await?;
-
-        tokio::spawn(async move {
-            let mut last_heartbeat = SystemTime::now();
-            while let Some(msg) = read.next().await {
-                match msg {
-                    Ok(Message::Text(text)) => {
-                        if let Ok(data) = serde_json::from_str::<Value>(&text) {
-                            // Handle heartbeat silently
-                            if data.get("event").and_then(|e| e.as_str()) == Some("heartbeat") {
-                                let now = SystemTime::now();
-                                if now.duration_since(last_heartbeat).unwrap().as_secs() >= 30 {
-                                    println!("Kraken connection is alive");
-                                    last_heartbeat = now;
-                                }
-                                continue;
-                            }
-
-                            // Handle subscription confirmation
-                            if data.get("event").and_then(|e| e.as_str()) == Some("subscribe") {
-                                println!("Successfully subscribed to Kraken WebSocket");
-                                continue;
-                            }
-
-                            // Handle error messages
-                            if data.get("event").and_then(|e| e.as_str()) == Some("error") {
-                                if let Some(error_msg) = data.get("errorMessage").and_then(|e| e.as_str()) {
-                                    eprintln!("Kraken WebSocket error: {}", error_msg);
-                                }
-                                continue;
-                            }
-
-                            // Handle ticker data
-                            if data.as_array().is_some() {
-                                let ticker = &data[1];
-                                let order_book = OrderBookL1 {
-                                    exchange: "kraken".to_string(),
-                                    symbol: original_symbol.clone(),
-                                    bid: ticker["b"][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                    bid_volume: ticker["b"][1].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                    ask: ticker["a"][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                    ask_volume: ticker["a"][1].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                    timestamp: SystemTime::now(),
-                                };
-                                callback(order_book);
-                            }
+        self.subscribe(Channel::L1, move |event| {
+            if let MarketEvent::L1(order_book) = event {
+                callback(order_book);
+            }
+        })
+        .await
+    }
+
+    pub async fn subscribe(&self, channel: Channel, callback: impl Fn(MarketEvent) + Send + 'static) -> Result<(), SoqaError> {
+        let pairs: Vec<String> = self.config.symbols.iter().map(|symbol| self.convert_symbol(symbol)).collect();
+        // Events are named after the symbol each pair was requested as.
+        let symbols: HashMap<String, String> = pairs.iter().cloned().zip(self.config.symbols.iter().cloned()).collect();
+        info!(target: "soqa::kraken", pairs = %pairs.join(","), "subscribing");
+
+        let bbo = self.config.bbo;
+        let subscription = match channel {
+            Channel::L1 if bbo => r#"{"name":"spread"}"#,
+            Channel::L1 => r#"{"name":"ticker"}"#,
+            Channel::L2 => r#"{"name":"book","depth":25}"#,
+            Channel::Trades => r#"{"name":"trade"}"#,
+        };
+        let message = move |event: &str, pairs: &[String]| {
+            let pairs: Vec<String> = pairs.iter().map(|pair| format!(r#""{}""#, pair)).collect();
+            format!(r#"{{"event":"{}","pair":[{}],"subscription":{}}}"#, event, pairs.join(","), subscription)
+        };
+        let subscribe_msg = message("subscribe", &pairs);
+        debug!(target: "soqa::kraken", message = %subscribe_msg, "sending subscription");
+        let mut feed = Feed::new("kraken", "wss://ws.kraken.com").subscribe(subscribe_msg);
+        // The ticker carries no timestamp, so L1 events are stamped on arrival.
+        if channel == Channel::L1 && !bbo {
+            feed = feed.local_time();
+        }
+        let mut decoder = Decoder::new();
+        let mut books: HashMap<String, LocalBook> = HashMap::new();
+        let handler = move |text: &str, events: &mut Vec<MarketEvent>| {
+            let symbol = |pair: &str| symbols.get(pair).cloned().unwrap_or_else(|| pair.to_string());
+            if channel == Channel::L1 {
+                let order_book = if bbo {
+                    decoder.decode::<SpreadMsg>(text).and_then(|m| m.to_l1(&symbol(m.3)))
+                } else {
+                    decoder.decode::<TickerMsg>(text).and_then(|m| m.to_l1(&symbol(m.3)))
+                };
+                if let Some(order_book) = order_book {
+                    events.push(MarketEvent::L1(order_book));
+                    return Frame::Data;
+                }
+            }
+            let Ok(data) = serde_json::from_str::<Value>(text) else {
+                return Frame::Unknown;
+            };
+            match channel {
+                Channel::L2 => {
+                    if let (Some(update), Some(pair)) = (book_update(&data), pair(&data)) {
+                        let book = books.entry(pair.to_string()).or_insert_with(|| LocalBook::new(25).with_checksum(Checksum::Kraken));
+                        if let Err(mismatch) = book.apply(&update) {
+                            warn!(target: "soqa::kraken", %mismatch, pair, "resubscribing for a new book snapshot");
+                            // Resubscribing is the only way to get a fresh book snapshot.
+                            let pair = [pair.to_string()];
+                            return Frame::Resync { mismatch, messages: vec![message("unsubscribe", &pair), message("subscribe", &pair)] };
                         }
+                        if !book.awaiting_snapshot() {
+                            events.push(MarketEvent::L2(book.to_l2("kraken", &symbol(pair), update.timestamp)));
+                        }
+                        return Frame::Data;
                     }
-                    Ok(Message::Close(_)) => {
-                        println!("Kraken WebSocket connection closed");
-                        break;
-                    }
-                    Err(e) => {
-                        eprintln!("Kraken WebSocket error: {}", e);
-                        break;
+                }
+                Channel::Trades => {
+                    if let Some(trades) = pair(&data).and_then(|pair| trades(&data, &symbol(pair))) {
+                        events.extend(trades.into_iter().map(MarketEvent::Trade));
+                        return Frame::Data;
                     }
-                    _ => {}
                 }
+                Channel::L1 => {}
             }
-        });
-
-        Ok(())
+            control(&data)
+        };
+        feed.spawn(&self.config, handler, callback).await
     }
-}
\ No newline at end of file
+}
+
+pub fn parse_l1(text: &str, symbol: &str) -> Option<OrderBookL1> {
+    serde_json::from_str::<TickerMsg>(text).ok()?.to_l1(symbol)
+}
+
+pub fn parse_bbo(text: &str, symbol: &str) -> Option<OrderBookL1> {
+    serde_json::from_str::<SpreadMsg>(text).ok()?.to_l1(symbol)
+}
+
+pub fn parse_book_update(text: &str) -> Option<BookUpdate> {
+    book_update(&serde_json::from_str(text).ok()?)
+}
+
+pub fn parse_trades(text: &str, symbol: &str) -> Option<Vec<Trade>> {
+    trades(&serde_json::from_str(text).ok()?, symbol)
+}
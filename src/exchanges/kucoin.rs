@@ -1,12 +1,237 @@
-use crate::models::OrderBookL1;
+use crate::models::{MarketEvent, OrderBookL1, OrderBookL2, QuoteSource, Trade};
 use crate::error::SoqaError;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use crate::exchanges::decode::{levels, millis, price, Decoder, Level};
+use crate::exchanges::feed::{Endpoint, Feed, Frame, Ping};
+use crate::subscriptions::Channel;
+use serde::Deserialize;
 use serde_json::Value;
-use std::time::SystemTime;
-use futures_util::stream::StreamExt;
-use futures_util::SinkExt;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, trace};
 use reqwest::Client;
-use tokio::time::{sleep, Duration};
+
+// Used when `bullet-public` leaves out `pingInterval`; what it normally returns.
+const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(18);
+
+#[derive(Deserialize)]
+pub struct TickerMsg<'a> {
+    pub topic: &'a str,
+    #[serde(borrow)]
+    pub data: TickerData<'a>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TickerData<'a> {
+    pub best_bid: &'a str,
+    pub best_bid_size: &'a str,
+    pub best_ask: &'a str,
+    pub best_ask_size: &'a str,
+    pub time: u64,
+}
+
+impl TickerMsg<'_> {
+    pub fn to_l1(&self, symbol: &str) -> Option<OrderBookL1> {
+        if !self.topic.starts_with("/market/ticker") {
+            return None;
+        }
+        Some(OrderBookL1 {
+            exchange: "kucoin".to_string(),
+            symbol: symbol.to_string(),
+            bid: price(self.data.best_bid)?,
+            bid_volume: price(self.data.best_bid_size)?,
+            ask: price(self.data.best_ask)?,
+            ask_volume: price(self.data.best_ask_size)?,
+            timestamp: millis(self.data.time),
+            source: QuoteSource::Ticker,
+        })
+    }
+}
+
+// `/spotMarket/level1`: the best bid and ask, pushed on every change.
+#[derive(Deserialize)]
+pub struct Level1Msg<'a> {
+    pub topic: &'a str,
+    #[serde(borrow)]
+    pub data: Level1Data<'a>,
+}
+
+#[derive(Deserialize)]
+pub struct Level1Data<'a> {
+    #[serde(borrow)]
+    pub bids: Level<'a>,
+    #[serde(borrow)]
+    pub asks: Level<'a>,
+    pub timestamp: u64,
+}
+
+impl Level1Msg<'_> {
+    pub fn to_l1(&self, symbol: &str) -> Option<OrderBookL1> {
+        if !self.topic.starts_with("/spotMarket/level1") {
+            return None;
+        }
+        Some(OrderBookL1 {
+            exchange: "kucoin".to_string(),
+            symbol: symbol.to_string(),
+            bid: price(self.data.bids.price)?,
+            bid_volume: price(self.data.bids.size)?,
+            ask: price(self.data.asks.price)?,
+            ask_volume: price(self.data.asks.size)?,
+            timestamp: millis(self.data.timestamp),
+            source: QuoteSource::Bbo,
+        })
+    }
+}
+
+// `/spotMarket/level2Depth50`: the top fifty levels, pushed whole.
+#[derive(Deserialize)]
+pub struct DepthMsg<'a> {
+    pub topic: &'a str,
+    #[serde(borrow)]
+    pub data: DepthData<'a>,
+}
+
+#[derive(Deserialize)]
+pub struct DepthData<'a> {
+    #[serde(borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<Level<'a>>,
+    pub timestamp: u64,
+}
+
+impl DepthMsg<'_> {
+    pub fn to_l2(&self, symbol: &str) -> Option<OrderBookL2> {
+        if !self.topic.starts_with("/spotMarket/level2Depth") {
+            return None;
+        }
+        Some(OrderBookL2 {
+            exchange: "kucoin".to_string(),
+            symbol: symbol.to_string(),
+            bids: levels(&self.data.bids)?,
+            asks: levels(&self.data.asks)?,
+            timestamp: millis(self.data.timestamp),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MatchMsg<'a> {
+    pub topic: &'a str,
+    #[serde(borrow)]
+    pub data: MatchData<'a>,
+}
+
+#[derive(Deserialize)]
+pub struct MatchData<'a> {
+    pub price: &'a str,
+    pub size: &'a str,
+    pub side: &'a str,
+    // Nanoseconds, as a string.
+    pub time: &'a str,
+}
+
+impl MatchMsg<'_> {
+    pub fn to_trade(&self, symbol: &str) -> Option<Trade> {
+        if !self.topic.starts_with("/market/match") {
+            return None;
+        }
+        let nanos: u64 = self.data.time.parse().ok()?;
+        Some(Trade {
+            exchange: "kucoin".to_string(),
+            symbol: symbol.to_string(),
+            price: price(self.data.price)?,
+            volume: price(self.data.size)?,
+            side: self.data.side.to_string(),
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos),
+        })
+    }
+}
+
+// The symbol a topic such as `/market/ticker:BTC-USDT` is for.
+fn topic_symbol(topic: &str) -> &str {
+    topic.rsplit(':').next().unwrap_or_default()
+}
+
+// Welcome, subscription acks and pongs.
+fn control(text: &str) -> Frame {
+    let Ok(data) = serde_json::from_str::<Value>(text) else {
+        return Frame::Unknown;
+    };
+    match data.get("type").and_then(|t| t.as_str()) {
+        Some("welcome") => {
+            info!(target: "soqa::kucoin", "connected");
+            Frame::Control
+        }
+        Some("ack") => {
+            info!(target: "soqa::kucoin", "subscribed");
+            Frame::Control
+        }
+        Some("pong") => {
+            trace!(target: "soqa::kucoin", "pong");
+            Frame::Control
+        }
+        _ => {
+            if let Some(topic) = data.get("topic").and_then(|t| t.as_str()) {
+                debug!(target: "soqa::kucoin", %topic, "unhandled topic");
+            }
+            Frame::Unknown
+        }
+    }
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis()
+}
+
+// Gets a WebSocket server, a token and a ping interval from `bullet-public`, then builds the
+// connection URL and subscription around a new connectId.
+async fn endpoint(topic: String) -> Result<Endpoint, SoqaError> {
+    let client = Client::new();
+    let response = crate::ratelimit::global()
+        .send("kucoin", client.post("https://api.kucoin.com/api/v1/bullet-public"))
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(SoqaError::ConnectionError(format!(
+            "Failed to get KuCoin WebSocket URL: HTTP {}",
+            response.status()
+        )));
+    }
+
+    let data = response.json::<Value>()
+        .await
+        .map_err(SoqaError::Http)?;
+    let server = &data["data"]["instanceServers"][0];
+
+    let ws_url = server["endpoint"]
+        .as_str()
+        .ok_or_else(|| SoqaError::ConnectionError("Failed to get KuCoin WebSocket URL from response".into()))?;
+
+    let token = data["data"]["token"]
+        .as_str()
+        .ok_or_else(|| SoqaError::ConnectionError("Failed to get KuCoin token from response".into()))?;
+
+    // The server drops connections that stay quiet for longer than this.
+    let ping_interval = server["pingInterval"].as_u64().map(Duration::from_millis).unwrap_or(DEFAULT_PING_INTERVAL);
+
+    let connect_id = now_millis();
+    debug!(target: "soqa::kucoin", endpoint = %ws_url, "connecting");
+    let subscribe_msg = format!(
+        r#"{{"id":{},"type":"subscribe","topic":"{}","privateChannel":false,"response":true}}"#,
+        connect_id,
+        topic
+    );
+    debug!(target: "soqa::kucoin", message = %subscribe_msg, "sending subscription");
+    Ok(Endpoint {
+        url: format!("{}?token={}&connectId={}", ws_url, token, connect_id),
+        subscribe: vec![subscribe_msg],
+        ping: Some(Ping {
+            every: ping_interval,
+            message: Box::new(|| format!(r#"{{"id":{},"type":"ping"}}"#, now_millis())),
+        }),
+    })
+}
 
 pub struct KuCoinClient {
     config: crate::config::Config,
@@ -28,132 +253,73 @@ impl KuCoinClient {
     }
 
     pub async fn subscribe_l1(&self, callback: impl Fn(OrderBookL1) + Send + 'static) -> Result<(), SoqaError> {
-        // Получаем URL WebSocket-сервера от KuCoin API с использованием POST
-        let client = Client::new();
-        let response = client.post("https://api.kucoin.com/api/v1/bullet-public")
-            .send()
-            .await
-            .map_err(|e| SoqaError::Http(e))?;
-
-        if !response.status().is_success() {
-            return Err(SoqaError::ConnectionError(format!(
-                "Failed to get KuCoin WebSocket URL: HTTP {}",
-                response.status()
-            )));
-        }
-
-        let data = response.json::<Value>()
-            .await
-            .map_err(|e| SoqaError::Http(e))?;
-
-        let ws_url = data["data"]["instanceServers"][0]["endpoint"]
-            .as_str()
-            .ok_or_else(|| SoqaError::ConnectionError("Failed to get KuCoin WebSocket URL from response".into()))?;
-
-        let token = data["data"]["token"]
-            .as_str()
-            .ok_or_else(|| SoqaError::ConnectionError("Failed to get KuCoin token from response".into()))?;
-
-        let connect_id = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
-
-        let full_url = format!("{}?token={}&connectId={}", ws_url, token, connect_id);
-
-        println!("Connecting to KuCoin WebSocket at: {}", full_url);
-        let (ws_stream, _) = connect_async(&full_url).await?;
-        let (mut write, mut read) = ws_stream.split();
-
-        let symbol = self.convert_symbol(&self.config.symbol);
-        let original_symbol = self.config.symbol.clone();
-        println!("Subscribing to KuCoin with symbol: {}", symbol);
-
-        // Подписываемся на оба канала: level1 и ticker
-        let subscribe_msg = format!(
-            r#"{{"id":{},"type":"subscribe","topic":"/market/ticker:{}","privateChannel":false,"response":true}}"#,
-            connect_id,
-            symbol
-        );
-
-        println!("Sending subscription message: {}", subscribe_msg);
-        write.send(Message::Text(subscribe_msg)).await?;
-
-        // Запускаем пинг в отдельном таске
-        let mut write_ping = write;
-        tokio::spawn(async move {
-            loop {
-                sleep(Duration::from_secs(30)).await;
-                let ping_msg = format!(
-                    r#"{{"id":{},"type":"ping"}}"#,
-                    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis()
-                );
-                if let Err(e) = write_ping.send(Message::Text(ping_msg)).await {
-                    eprintln!("Failed to send ping: {}", e);
-                    break;
-                }
-            }
-        });
-
-        tokio::spawn(async move {
-            while let Some(msg) = read.next().await {
-                match msg {
-                    Ok(Message::Text(text)) => {
-                        println!("Received raw message: {}", text);
-                        if let Ok(data) = serde_json::from_str::<Value>(&text) {
-                            // Подтверждение подключения
-                            if data.get("type").and_then(|t| t.as_str()) == Some("welcome") {
-                                println!("Successfully connected to KuCoin WebSocket");
-                                continue;
-                            }
-
-                            // Подтверждение подписки
-                            if data.get("type").and_then(|t| t.as_str()) == Some("ack") {
-                                println!("Successfully subscribed to KuCoin WebSocket");
-                                continue;
-                            }
-
-                            // Обработка данных ticker
-                            if let Some(topic) = data.get("topic").and_then(|t| t.as_str()) {
-                                println!("Received topic: {}", topic);
-                                if topic.starts_with("/market/ticker") {
-                                    if let Some(data) = data.get("data") {
-                                        println!("Processing ticker data: {:?}", data);
-                                        let order_book = OrderBookL1 {
-                                            exchange: "kucoin".to_string(),
-                                            symbol: original_symbol.clone(),
-                                            bid: data["bestBid"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                            bid_volume: data["bestBidSize"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                            ask: data["bestAsk"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                            ask_volume: data["bestAskSize"].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                            timestamp: SystemTime::now(),
-                                        };
-                                        println!("Created OrderBookL1: {:?}", order_book);
-                                        callback(order_book);
-                                    }
-                                }
-                            }
-
-                            // Обработка pong сообщений
-                            if data.get("type").and_then(|t| t.as_str()) == Some("pong") {
-                                println!("Received pong");
-                                continue;
-                            }
-                        }
-                    }
-                    Ok(Message::Close(_)) => {
-                        println!("KuCoin WebSocket connection closed");
-                        break;
-                    }
-                    Err(e) => {
-                        eprintln!("KuCoin WebSocket error: {}", e);
-                        break;
-                    }
-                    _ => {}
-                }
+        self.subscribe(Channel::L1, move |event| {
+            if let MarketEvent::L1(order_book) = event {
+                callback(order_book);
             }
-        });
+        })
+        .await
+    }
+
+    pub async fn subscribe(&self, channel: Channel, callback: impl Fn(MarketEvent) + Send + 'static) -> Result<(), SoqaError> {
+        let venue_symbols: Vec<String> = self.config.symbols.iter().map(|symbol| self.convert_symbol(symbol)).collect();
+        // Events are named after the symbol each market was requested as.
+        let symbols: HashMap<String, String> = venue_symbols.iter().cloned().zip(self.config.symbols.iter().cloned()).collect();
+        let venue_symbols = venue_symbols.join(",");
+        info!(target: "soqa::kucoin", symbols = %venue_symbols, "subscribing");
 
-        Ok(())
+        let bbo = self.config.bbo;
+        // One topic takes a comma-separated list of symbols.
+        let topic = match channel {
+            Channel::L1 if bbo => format!("/spotMarket/level1:{}", venue_symbols),
+            Channel::L1 => format!("/market/ticker:{}", venue_symbols),
+            Channel::L2 => format!("/spotMarket/level2Depth50:{}", venue_symbols),
+            Channel::Trades => format!("/market/match:{}", venue_symbols),
+        };
+        // The token is good for one connection, so every reconnect asks for a new one.
+        let feed = Feed::connect_with("kucoin", move || endpoint(topic.clone()));
+        let mut decoder = Decoder::new();
+        let handler = move |text: &str, events: &mut Vec<MarketEvent>| {
+            let symbol = |topic: &str| {
+                let venue_symbol = topic_symbol(topic);
+                symbols.get(venue_symbol).cloned().unwrap_or_else(|| venue_symbol.to_string())
+            };
+            match channel {
+                Channel::L1 if bbo => match decoder.decode::<Level1Msg>(text).and_then(|m| m.to_l1(&symbol(m.topic))) {
+                    Some(order_book) => events.push(MarketEvent::L1(order_book)),
+                    None => return control(text),
+                },
+                Channel::L1 => match decoder.decode::<TickerMsg>(text).and_then(|m| m.to_l1(&symbol(m.topic))) {
+                    Some(order_book) => events.push(MarketEvent::L1(order_book)),
+                    None => return control(text),
+                },
+                Channel::L2 => match decoder.decode::<DepthMsg>(text).and_then(|m| m.to_l2(&symbol(m.topic))) {
+                    Some(order_book) => events.push(MarketEvent::L2(order_book)),
+                    None => return control(text),
+                },
+                Channel::Trades => match decoder.decode::<MatchMsg>(text).and_then(|m| m.to_trade(&symbol(m.topic))) {
+                    Some(trade) => events.push(MarketEvent::Trade(trade)),
+                    None => return control(text),
+                },
+            }
+            Frame::Data
+        };
+        feed.spawn(&self.config, handler, callback).await
     }
-}
\ No newline at end of file
+}
+
+pub fn parse_l1(text: &str, symbol: &str) -> Option<OrderBookL1> {
+    serde_json::from_str::<TickerMsg>(text).ok()?.to_l1(symbol)
+}
+
+pub fn parse_bbo(text: &str, symbol: &str) -> Option<OrderBookL1> {
+    serde_json::from_str::<Level1Msg>(text).ok()?.to_l1(symbol)
+}
+
+pub fn parse_l2(text: &str, symbol: &str) -> Option<OrderBookL2> {
+    serde_json::from_str::<DepthMsg>(text).ok()?.to_l2(symbol)
+}
+
+pub fn parse_trade(text: &str, symbol: &str) -> Option<Trade> {
+    serde_json::from_str::<MatchMsg>(text).ok()?.to_trade(symbol)
+}
@@ -2,4 +2,75 @@ pub mod binance;
 pub mod bybit;
 pub mod kraken;
 pub mod okx;
-pub mod kucoin;
\ No newline at end of file
+pub mod kucoin;
+pub mod adapter;
+pub mod decode;
+pub mod book;
+pub(crate) mod feed;
+pub mod instruments;
+pub mod history;
+
+use crate::config::Config;
+use crate::delivery::EventQueue;
+use crate::error::SoqaError;
+use crate::models::{MarketEvent, OrderBookL1};
+use crate::subscriptions::Channel;
+use futures_util::stream::{self, Stream};
+use tokio::sync::mpsc;
+
+pub const SUPPORTED: &[&str] = &["binance", "bybit", "kraken", "okx", "kucoin"];
+
+// Symbols carried on one connection by the built-in clients, well under every venue's limit
+// (KuCoin takes at most 100 symbols per topic).
+pub const SYMBOLS_PER_CONNECTION: usize = 50;
+
+// Groups (exchange, symbol) pairs by venue, in order, into batches of at most `size` symbols;
+// each batch is meant for one connection.
+pub fn batches(pairs: &[(String, String)], size: usize) -> Vec<(String, Vec<String>)> {
+    let mut venues: Vec<(String, Vec<String>)> = Vec::new();
+    for (exchange, symbol) in pairs {
+        match venues.iter_mut().find(|(venue, _)| venue == exchange) {
+            Some((_, symbols)) => symbols.push(symbol.clone()),
+            None => venues.push((exchange.clone(), vec![symbol.clone()])),
+        }
+    }
+    venues
+        .into_iter()
+        .flat_map(|(exchange, symbols)| {
+            symbols.chunks(size.max(1)).map(|chunk| (exchange.clone(), chunk.to_vec())).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+pub async fn subscribe_l1(config: Config, callback: impl Fn(OrderBookL1) + Send + 'static) -> Result<(), SoqaError> {
+    match config.exchange.as_str() {
+        "binance" => binance::BinanceClient::new(config).subscribe_l1(callback).await,
+        "bybit" => bybit::BybitClient::new(config).subscribe_l1(callback).await,
+        "kraken" => kraken::KrakenClient::new(config).subscribe_l1(callback).await,
+        "okx" => okx::OkxClient::new(config).subscribe_l1(callback).await,
+        "kucoin" => kucoin::KuCoinClient::new(config).subscribe_l1(callback).await,
+        other => Err(SoqaError::ExchangeNotSupported(other.to_string())),
+    }
+}
+
+pub async fn subscribe(config: Config, channel: Channel, callback: impl Fn(MarketEvent) + Send + 'static) -> Result<(), SoqaError> {
+    match config.exchange.as_str() {
+        "binance" => binance::BinanceClient::new(config).subscribe(channel, callback).await,
+        "bybit" => bybit::BybitClient::new(config).subscribe(channel, callback).await,
+        "kraken" => kraken::KrakenClient::new(config).subscribe(channel, callback).await,
+        "okx" => okx::OkxClient::new(config).subscribe(channel, callback).await,
+        "kucoin" => kucoin::KuCoinClient::new(config).subscribe(channel, callback).await,
+        other => Err(SoqaError::ExchangeNotSupported(other.to_string())),
+    }
+}
+
+// The feed's quotes, queued as the config's delivery settings say until the stream is polled.
+pub async fn l1_stream(config: Config) -> Result<impl Stream<Item = MarketEvent>, SoqaError> {
+    let queue = EventQueue::new(config.delivery);
+    subscribe_l1(config.with_queue(queue.clone()), |_| {}).await?;
+    Ok(queue.into_stream())
+}
+
+pub fn receiver_stream<T>(rx: mpsc::UnboundedReceiver<T>) -> impl Stream<Item = T> {
+    stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|item| (item, rx)) })
+}
@@ -1,10 +1,169 @@
-use crate::models::OrderBookL1;
+use crate::models::{MarketEvent, OrderBookL1, QuoteSource, Trade};
 use crate::error::SoqaError;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use crate::exchanges::book::{BookUpdate, Checksum, LocalBook};
+use crate::exchanges::decode::{levels, millis, price, Decoder, First, Level};
+use crate::exchanges::feed::{Feed, Frame};
+use crate::subscriptions::Channel;
+use serde::Deserialize;
 use serde_json::Value;
-use std::time::SystemTime;
-use futures_util::stream::StreamExt;
-use futures_util::SinkExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+// The channel and instrument a push is for.
+#[derive(Deserialize)]
+pub struct Arg<'a> {
+    #[serde(rename = "instId")]
+    pub inst_id: &'a str,
+}
+
+#[derive(Deserialize)]
+pub struct BooksMsg<'a> {
+    #[serde(borrow)]
+    pub arg: Arg<'a>,
+    #[serde(borrow)]
+    pub data: First<BookData<'a>>,
+}
+
+#[derive(Deserialize)]
+pub struct BookData<'a> {
+    #[serde(borrow)]
+    pub bids: First<Level<'a>>,
+    #[serde(borrow)]
+    pub asks: First<Level<'a>>,
+    pub ts: &'a str,
+}
+
+// The shape of `bbo-tbt`, which pushes the best bid and ask on every tick, and of a `books`
+// snapshot. `books` updates are deltas, so the feed takes L1 from its local book instead.
+impl BooksMsg<'_> {
+    pub fn to_l1(&self, symbol: &str) -> Option<OrderBookL1> {
+        let book = self.data.0.as_ref()?;
+        let bid = book.bids.0?;
+        let ask = book.asks.0?;
+        Some(OrderBookL1 {
+            exchange: "okx".to_string(),
+            symbol: symbol.to_string(),
+            bid: price(bid.price)?,
+            bid_volume: price(bid.size)?,
+            ask: price(ask.price)?,
+            ask_volume: price(ask.size)?,
+            timestamp: millis(book.ts.parse().ok()?),
+            source: QuoteSource::Book,
+        })
+    }
+
+    pub fn to_bbo(&self, symbol: &str) -> Option<OrderBookL1> {
+        Some(OrderBookL1 { source: QuoteSource::Bbo, ..self.to_l1(symbol)? })
+    }
+}
+
+// `books`: a 400-level snapshot, then incremental updates. Every message carries the checksum of
+// the top 25 levels of the book after it.
+#[derive(Deserialize)]
+pub struct BookUpdateMsg<'a> {
+    #[serde(borrow)]
+    pub arg: Arg<'a>,
+    pub action: &'a str,
+    #[serde(borrow)]
+    pub data: First<BookUpdateData<'a>>,
+}
+
+#[derive(Deserialize)]
+pub struct BookUpdateData<'a> {
+    #[serde(borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(borrow)]
+    pub asks: Vec<Level<'a>>,
+    pub ts: &'a str,
+    pub checksum: Option<i32>,
+}
+
+fn raw_levels(levels: &[Level]) -> Vec<(String, String)> {
+    levels.iter().map(|l| (l.price.to_string(), l.size.to_string())).collect()
+}
+
+impl BookUpdateMsg<'_> {
+    pub fn to_update(&self) -> Option<BookUpdate> {
+        let book = self.data.0.as_ref()?;
+        Some(BookUpdate {
+            snapshot: self.action == "snapshot",
+            bids: levels(&book.bids)?,
+            asks: levels(&book.asks)?,
+            raw_bids: raw_levels(&book.bids),
+            raw_asks: raw_levels(&book.asks),
+            checksum: book.checksum.map(|checksum| checksum as u32),
+            timestamp: millis(book.ts.parse().ok()?),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TradesMsg<'a> {
+    #[serde(borrow)]
+    pub arg: Arg<'a>,
+    #[serde(borrow)]
+    pub data: Vec<TradeData<'a>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TradeData<'a> {
+    pub px: &'a str,
+    pub sz: &'a str,
+    pub side: &'a str,
+    pub ts: &'a str,
+}
+
+impl TradesMsg<'_> {
+    pub fn to_trades(&self, symbol: &str) -> Option<Vec<Trade>> {
+        self.data
+            .iter()
+            .map(|t| {
+                Some(Trade {
+                    exchange: "okx".to_string(),
+                    symbol: symbol.to_string(),
+                    price: price(t.px)?,
+                    volume: price(t.sz)?,
+                    side: t.side.to_string(),
+                    timestamp: millis(t.ts.parse().ok()?),
+                })
+            })
+            .collect()
+    }
+}
+
+fn request(op: &str, channel: &str, inst_ids: &[String]) -> String {
+    let args: Vec<String> =
+        inst_ids.iter().map(|inst_id| format!(r#"{{"channel":"{}","instId":"{}"}}"#, channel, inst_id)).collect();
+    format!(r#"{{"op":"{}","args":[{}]}}"#, op, args.join(","))
+}
+
+// Subscription acks, errors and the plain-text pong.
+fn control(text: &str) -> Frame {
+    if text == "pong" {
+        return Frame::Control;
+    }
+    let Ok(data) = serde_json::from_str::<Value>(text) else {
+        return Frame::Unknown;
+    };
+    match data.get("event").and_then(|e| e.as_str()) {
+        Some("subscribe") => {
+            info!(target: "soqa::okx", "subscribed");
+            Frame::Control
+        }
+        Some("error") => {
+            if let Some(error_msg) = data.get("msg").and_then(|e| e.as_str()) {
+                warn!(target: "soqa::okx", error = %error_msg, "exchange error");
+            }
+            Frame::Control
+        }
+        Some(_) => Frame::Control,
+        // Incremental updates that leave one side of the top level empty
+        None if data.get("data").is_some() => Frame::Data,
+        None => Frame::Unknown,
+    }
+}
 
 pub struct OkxClient {
     config: crate::config::Config,
@@ -26,77 +185,93 @@ impl OkxClient {
     }
 
     pub async fn subscribe_l1(&self, callback: impl Fn(OrderBookL1) + Send + 'static) -> Result<(), SoqaError> {
-        let url = "wss://ws.okx.com:8443/ws/v5/public";
-        let (ws_stream, _) = connect_async(url).await?;
-        let (mut write, mut read) = ws_stream.split();
-
-        let symbol = self.convert_symbol(&self.config.symbol);
-        let original_symbol = self.config.symbol.clone();
-        println!("Subscribing to OKX with symbol: {}", symbol);
-
-        let subscribe_msg = format!(
-            r#"{{"op":"subscribe","args":[{{"channel":"books","instId":"{}"}}]}}"#,
-            symbol
-        );
-        println!("Sending subscription message: {}", subscribe_msg);
-        write.send(Message::Text(subscribe_msg)).await?;
-
-        tokio::spawn(async move {
-            while let Some(msg) = read.next().await {
-                match msg {
-                    Ok(Message::Text(text)) => {
-                        println!("Received message: {}", text);
-                        if let Ok(data) = serde_json::from_str::<Value>(&text) {
-                            // Handle subscription confirmation
-                            if data.get("event").and_then(|e| e.as_str()) == Some("subscribe") {
-                                println!("Successfully subscribed to OKX WebSocket");
-                                continue;
-                            }
+        self.subscribe(Channel::L1, move |event| {
+            if let MarketEvent::L1(order_book) = event {
+                callback(order_book);
+            }
+        })
+        .await
+    }
 
-                            // Handle error messages
-                            if data.get("event").and_then(|e| e.as_str()) == Some("error") {
-                                if let Some(error_msg) = data.get("msg").and_then(|e| e.as_str()) {
-                                    eprintln!("OKX WebSocket error: {}", error_msg);
-                                }
-                                continue;
-                            }
+    pub async fn subscribe(&self, channel: Channel, callback: impl Fn(MarketEvent) + Send + 'static) -> Result<(), SoqaError> {
+        let inst_ids: Vec<String> = self.config.symbols.iter().map(|symbol| self.convert_symbol(symbol)).collect();
+        // Events are named after the symbol each instrument was requested as.
+        let symbols: HashMap<String, String> = inst_ids.iter().cloned().zip(self.config.symbols.iter().cloned()).collect();
+        info!(target: "soqa::okx", symbols = %inst_ids.join(","), "subscribing");
 
-                            // Handle orderbook data
-                            if let Some(data) = data.get("data").and_then(|d| d.as_array()) {
-                                if let Some(book) = data.get(0) {
-                                    if let Some(bids) = book.get("bids").and_then(|b| b.as_array()) {
-                                        if let Some(asks) = book.get("asks").and_then(|a| a.as_array()) {
-                                            if !bids.is_empty() && !asks.is_empty() {
-                                                let order_book = OrderBookL1 {
-                                                    exchange: "okx".to_string(),
-                                                    symbol: original_symbol.clone(),
-                                                    bid: bids[0][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                                    bid_volume: bids[0][1].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                                    ask: asks[0][0].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                                    ask_volume: asks[0][1].as_str().unwrap_or("0").parse().unwrap_or(0.0),
-                                                    timestamp: SystemTime::now(),
-                                                };
-                                                callback(order_book);
-                                            }
-                                        }
-                                    }
-                                }
+        let bbo = self.config.bbo;
+        let name = match channel {
+            Channel::L1 if bbo => "bbo-tbt",
+            Channel::L1 => "books",
+            Channel::L2 => "books",
+            Channel::Trades => "trades",
+        };
+        let subscribe_msg = request("subscribe", name, &inst_ids);
+        debug!(target: "soqa::okx", message = %subscribe_msg, "sending subscription");
+        // OKX closes connections that are idle for 30 seconds.
+        let feed = Feed::new("okx", "wss://ws.okx.com:8443/ws/v5/public")
+            .subscribe(subscribe_msg)
+            .ping(Duration::from_secs(25), || "ping".to_string());
+        let mut decoder = Decoder::new();
+        let mut books: HashMap<String, LocalBook> = HashMap::new();
+        // The last quote published per instrument, so updates below the top publish nothing.
+        let mut tops: HashMap<String, [f64; 4]> = HashMap::new();
+        let handler = move |text: &str, events: &mut Vec<MarketEvent>| {
+            let symbol = |inst_id: &str| symbols.get(inst_id).cloned().unwrap_or_else(|| inst_id.to_string());
+            match channel {
+                Channel::L1 if bbo => match decoder.decode::<BooksMsg>(text).and_then(|m| m.to_bbo(&symbol(m.arg.inst_id))) {
+                    Some(order_book) => events.push(MarketEvent::L1(order_book)),
+                    None => return control(text),
+                },
+                Channel::L1 | Channel::L2 => match decoder.decode::<BookUpdateMsg>(text).and_then(|m| Some((m.arg.inst_id, m.to_update()?))) {
+                    Some((inst_id, update)) => {
+                        let book = books.entry(inst_id.to_string()).or_insert_with(|| LocalBook::new(400).with_checksum(Checksum::Okx));
+                        if let Err(mismatch) = book.apply(&update) {
+                            warn!(target: "soqa::okx", %mismatch, inst_id, "resubscribing for a new book snapshot");
+                            // A new subscription starts with a fresh snapshot.
+                            let instrument = [inst_id.to_string()];
+                            let messages = vec![request("unsubscribe", name, &instrument), request("subscribe", name, &instrument)];
+                            return Frame::Resync { mismatch, messages };
+                        }
+                        if book.awaiting_snapshot() {
+                            return Frame::Data;
+                        }
+                        if channel == Channel::L2 {
+                            // The checksum covers the top 25 levels; deeper ones are kept only so
+                            // that removals near the top can be backfilled.
+                            events.push(MarketEvent::L2(book.top("okx", &symbol(inst_id), update.timestamp, 25)));
+                        } else if let Some(quote) = book.quote("okx", &symbol(inst_id), update.timestamp) {
+                            let top = [quote.bid, quote.bid_volume, quote.ask, quote.ask_volume];
+                            if tops.insert(inst_id.to_string(), top) != Some(top) {
+                                events.push(MarketEvent::L1(quote));
                             }
                         }
                     }
-                    Ok(Message::Close(_)) => {
-                        println!("OKX WebSocket connection closed");
-                        break;
-                    }
-                    Err(e) => {
-                        eprintln!("OKX WebSocket error: {}", e);
-                        break;
-                    }
-                    _ => {}
-                }
+                    None => return control(text),
+                },
+                Channel::Trades => match decoder.decode::<TradesMsg>(text).and_then(|m| m.to_trades(&symbol(m.arg.inst_id))) {
+                    Some(trades) => events.extend(trades.into_iter().map(MarketEvent::Trade)),
+                    None => return control(text),
+                },
             }
-        });
-
-        Ok(())
+            Frame::Data
+        };
+        feed.spawn(&self.config, handler, callback).await
     }
-}
\ No newline at end of file
+}
+
+pub fn parse_l1(text: &str, symbol: &str) -> Option<OrderBookL1> {
+    serde_json::from_str::<BooksMsg>(text).ok()?.to_l1(symbol)
+}
+
+pub fn parse_bbo(text: &str, symbol: &str) -> Option<OrderBookL1> {
+    serde_json::from_str::<BooksMsg>(text).ok()?.to_bbo(symbol)
+}
+
+pub fn parse_book_update(text: &str) -> Option<BookUpdate> {
+    serde_json::from_str::<BookUpdateMsg>(text).ok()?.to_update()
+}
+
+pub fn parse_trades(text: &str, symbol: &str) -> Option<Vec<Trade>> {
+    serde_json::from_str::<TradesMsg>(text).ok()?.to_trades(symbol)
+}
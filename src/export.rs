@@ -1,12 +1,152 @@
-use crate::models::OrderBookL1;
-use csv::Writer;
+use crate::codec::{Encoding, EventWriter};
+use crate::error::SoqaError;
+use crate::models::{HistoricalTrade, MarketEvent, OrderBookL1};
+use csv::{Writer, WriterBuilder};
+use rusqlite::{params, Connection};
 use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::BufWriter;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+pub mod book;
+pub mod bus;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod record;
+#[cfg(feature = "redis")]
+pub mod redis;
+
+pub use bus::{forward, replay, replay_merged, BusSink, MemoryBroker, Message, Sink, Transport};
 
 pub fn export_to_csv(data: Vec<OrderBookL1>, file_path: &str) -> Result<(), Box<dyn Error>> {
+    let started = Instant::now();
+    let records = data.len();
     let mut wtr = Writer::from_path(file_path)?;
     for record in data {
         wtr.serialize(record)?;
     }
     wtr.flush()?;
+    crate::metrics::sink_write("csv", records, started.elapsed().as_secs_f64());
+    Ok(())
+}
+// Appends trades, writing the header only when the file is new so resumed downloads extend it.
+pub fn export_trades_to_csv(data: &[HistoricalTrade], file_path: &str) -> Result<(), Box<dyn Error>> {
+    let started = Instant::now();
+    let is_new = std::fs::metadata(file_path).map(|m| m.len() == 0).unwrap_or(true);
+    let file = OpenOptions::new().create(true).append(true).open(file_path)?;
+    let mut wtr = WriterBuilder::new().has_headers(is_new).from_writer(file);
+    for record in data {
+        wtr.serialize(record)?;
+    }
+    wtr.flush()?;
+    crate::metrics::sink_write("csv", data.len(), started.elapsed().as_secs_f64());
+    Ok(())
+}
+
+// Inserts trades into a `trades` table; a trade id seen before is skipped, so replaying a page is harmless.
+pub fn export_trades_to_sqlite(data: &[HistoricalTrade], file_path: &str) -> Result<(), Box<dyn Error>> {
+    let started = Instant::now();
+    let mut conn = Connection::open(file_path)?;
+    conn.execute_batch(record::TRADES_SCHEMA)?;
+    let tx = conn.transaction()?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT OR IGNORE INTO trades (exchange, symbol, trade_id, price, volume, side, timestamp_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        for t in data {
+            insert.execute(params![t.exchange, t.symbol, t.trade_id, t.price, t.volume, t.side, t.timestamp_ms as i64])?;
+        }
+    }
+    tx.commit()?;
+    crate::metrics::sink_write("sqlite", data.len(), started.elapsed().as_secs_f64());
     Ok(())
-}
\ No newline at end of file
+}
+
+// Appends events in the binary record format (see `codec`), which is a fraction of the size of CSV.
+pub fn export_events_to_binary(data: &[MarketEvent], file_path: &str) -> Result<(), Box<dyn Error>> {
+    let started = Instant::now();
+    let file = OpenOptions::new().create(true).append(true).open(file_path)?;
+    let mut writer = EventWriter::new(BufWriter::new(file));
+    for event in data {
+        writer.write(event)?;
+    }
+    writer.flush()?;
+    crate::metrics::sink_write("binary", data.len(), started.elapsed().as_secs_f64());
+    Ok(())
+}
+
+// The kind of sink `url` names: "nats", "kafka", "redis", "sqlite" or "parquet".
+pub fn sink_kind(url: &str) -> Option<&'static str> {
+    let path = std::path::Path::new(url);
+    match url.split_once("://") {
+        Some(("nats", _)) => Some("nats"),
+        Some(("kafka", _)) => Some("kafka"),
+        Some(("redis", _)) => Some("redis"),
+        Some(_) => None,
+        None if record::is_sqlite(path) => Some("sqlite"),
+        None if record::is_parquet(path) => Some("parquet"),
+        None => None,
+    }
+}
+
+// Connects to the bus or cache named by `url` (nats://, kafka://host[,host] or redis://), or
+// opens the SQLite (.db, .sqlite, .sqlite3) or Parquet (.parquet) file it names for recording
+// (see `record`), and forwards `events` to it in the background. The task flushes and ends once
+// `events` closes.
+pub async fn spawn_sink(
+    url: &str,
+    encoding: Encoding,
+    prefix: &str,
+    ttl: Option<Duration>,
+    max_trades: usize,
+    snapshot_every: Duration,
+    events: broadcast::Receiver<MarketEvent>,
+) -> Result<JoinHandle<()>, SoqaError> {
+    // The encoding is for nats and kafka, the prefix for all three, the TTL and trades for redis.
+    #[cfg(not(any(feature = "nats", feature = "kafka", feature = "redis")))]
+    let _ = prefix;
+    #[cfg(not(any(feature = "nats", feature = "kafka")))]
+    let _ = encoding;
+    #[cfg(not(feature = "redis"))]
+    let _ = (ttl, max_trades);
+    let idle = Duration::from_millis(100);
+    let path = std::path::Path::new(url);
+    if !url.contains("://") && record::is_sqlite(path) {
+        return Ok(tokio::spawn(forward(record::SqliteSink::open(path, snapshot_every)?, events, idle)));
+    }
+    if !url.contains("://") && record::is_parquet(path) {
+        return Ok(tokio::spawn(forward(record::ParquetSink::create(path, snapshot_every)?, events, idle)));
+    }
+    match url.split_once("://") {
+        #[cfg(feature = "kafka")]
+        Some(("kafka", brokers)) => {
+            let brokers = brokers.split(',').map(str::to_string).collect();
+            let transport = kafka::KafkaTransport::connect(brokers).await?;
+            let sink = BusSink::new(transport).with_prefix(prefix).with_encoding(encoding);
+            Ok(tokio::spawn(forward(sink, events, idle)))
+        }
+        #[cfg(feature = "nats")]
+        Some(("nats", _)) => {
+            let transport = nats::NatsTransport::connect(url).await?;
+            let sink = BusSink::new(transport).with_prefix(prefix).with_encoding(encoding);
+            Ok(tokio::spawn(forward(sink, events, idle)))
+        }
+        #[cfg(feature = "redis")]
+        Some(("redis", _)) => {
+            let mut sink = redis::RedisSink::connect(url).await?.with_prefix(prefix).with_trades(max_trades);
+            if let Some(ttl) = ttl {
+                sink = sink.with_ttl(ttl);
+            }
+            Ok(tokio::spawn(forward(sink, events, idle)))
+        }
+        Some((scheme, _)) if ["kafka", "nats", "redis"].contains(&scheme) => {
+            Err(SoqaError::ExportError(format!("{} sinks need a build with the `{}` feature", scheme, scheme)))
+        }
+        _ => Err(SoqaError::ExportError(format!("unsupported sink {}, expected nats://, kafka://, redis:// or a .db/.parquet file", url))),
+    }
+}
@@ -0,0 +1,168 @@
+// L2 history as rows: a full snapshot of each market every so often and, in between, only the
+// levels that changed. `BookRecorder` turns published books into rows, the sinks in `record`
+// store them in SQLite or Parquet, and `book_at` rebuilds the book at any time from such a file.
+
+use crate::error::SoqaError;
+use crate::exchanges::book::{BookUpdate, LocalBook};
+use crate::export::record;
+use crate::models::OrderBookL2;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowKind {
+    Snapshot,
+    Delta,
+}
+
+impl RowKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RowKind::Snapshot => "snapshot",
+            RowKind::Delta => "delta",
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Result<Self, SoqaError> {
+        match s {
+            "snapshot" => Ok(RowKind::Snapshot),
+            "delta" => Ok(RowKind::Delta),
+            other => Err(SoqaError::ExportError(format!("unknown book row kind {}", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+impl BookSide {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BookSide::Bid => "bid",
+            BookSide::Ask => "ask",
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Result<Self, SoqaError> {
+        match s {
+            "bid" => Ok(BookSide::Bid),
+            "ask" => Ok(BookSide::Ask),
+            other => Err(SoqaError::ExportError(format!("unknown book side {}", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookRow {
+    pub exchange: String,
+    pub symbol: String,
+    pub timestamp: SystemTime,
+    // Numbers the recorded books of one market; all rows of one book share it.
+    pub sequence: u64,
+    pub kind: RowKind,
+    pub side: BookSide,
+    pub price: f64,
+    // Zero in a delta removes the level.
+    pub size: f64,
+}
+
+#[derive(Debug, Default)]
+struct Market {
+    sequence: u64,
+    last_snapshot: Option<SystemTime>,
+    bids: Vec<(f64, f64)>,
+    asks: Vec<(f64, f64)>,
+}
+
+// Levels of `new` that are not in `old` as they are, then levels of `old` gone from `new` with
+// size zero.
+fn changes(old: &[(f64, f64)], new: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut changes: Vec<_> = new.iter().filter(|level| !old.contains(level)).copied().collect();
+    changes.extend(old.iter().filter(|(price, _)| !new.iter().any(|(p, _)| p == price)).map(|&(price, _)| (price, 0.0)));
+    changes
+}
+
+// Turns each market's stream of full books into snapshot and delta rows. The first book of a
+// market, and the first one `snapshot_every` after the last snapshot, is written whole.
+#[derive(Debug)]
+pub struct BookRecorder {
+    snapshot_every: Duration,
+    markets: BTreeMap<(String, String), Market>,
+}
+
+impl BookRecorder {
+    pub fn new(snapshot_every: Duration) -> Self {
+        BookRecorder { snapshot_every, markets: BTreeMap::new() }
+    }
+
+    // Continues the sequence after `sequence`, e.g. when appending to an existing file.
+    pub fn resume(&mut self, exchange: &str, symbol: &str, sequence: u64) {
+        self.markets.entry((exchange.to_string(), symbol.to_string())).or_default().sequence = sequence;
+    }
+
+    pub fn record(&mut self, book: &OrderBookL2) -> Vec<BookRow> {
+        let market = self.markets.entry((book.exchange.clone(), book.symbol.clone())).or_default();
+        market.sequence += 1;
+        let snapshot = match market.last_snapshot {
+            None => true,
+            Some(at) => book.timestamp.duration_since(at).map(|age| age >= self.snapshot_every).unwrap_or(false),
+        };
+        let (kind, bids, asks) = if snapshot {
+            market.last_snapshot = Some(book.timestamp);
+            (RowKind::Snapshot, book.bids.clone(), book.asks.clone())
+        } else {
+            (RowKind::Delta, changes(&market.bids, &book.bids), changes(&market.asks, &book.asks))
+        };
+        market.bids = book.bids.clone();
+        market.asks = book.asks.clone();
+        let row = |side, (price, size)| BookRow {
+            exchange: book.exchange.clone(),
+            symbol: book.symbol.clone(),
+            timestamp: book.timestamp,
+            sequence: market.sequence,
+            kind,
+            side,
+            price,
+            size,
+        };
+        let mut rows: Vec<_> = bids.into_iter().map(|level| row(BookSide::Bid, level)).collect();
+        rows.extend(asks.into_iter().map(|level| row(BookSide::Ask, level)));
+        rows
+    }
+}
+
+// Rebuilds one market's book as of `at`: the newest snapshot at or before `at`, then the deltas
+// recorded after it, in sequence order, until one is newer than `at`.
+pub fn reconstruct(rows: impl IntoIterator<Item = BookRow>, exchange: &str, symbol: &str, at: SystemTime) -> Option<OrderBookL2> {
+    let mut rows: Vec<_> = rows.into_iter().filter(|row| row.exchange == exchange && row.symbol == symbol).collect();
+    rows.sort_by_key(|row| row.sequence);
+    let start = rows.iter().filter(|row| row.kind == RowKind::Snapshot && row.timestamp <= at).map(|row| row.sequence).max()?;
+    let mut book = LocalBook::new(0);
+    let mut timestamp = at;
+    for group in rows.chunk_by(|a, b| a.sequence == b.sequence).filter(|group| group[0].sequence >= start) {
+        if group[0].timestamp > at {
+            break;
+        }
+        let mut update = BookUpdate { snapshot: group[0].sequence == start, timestamp: group[0].timestamp, ..BookUpdate::default() };
+        for row in group {
+            match row.side {
+                BookSide::Bid => update.bids.push((row.price, row.size)),
+                BookSide::Ask => update.asks.push((row.price, row.size)),
+            }
+        }
+        // Books without a checksum always apply.
+        let _ = book.apply(&update);
+        timestamp = update.timestamp;
+    }
+    Some(book.to_l2(exchange, symbol, timestamp))
+}
+
+// The book of `exchange`/`symbol` as of `at`, read from a recording made by either sink; `None`
+// when nothing was recorded for it by then.
+pub fn book_at(path: impl AsRef<Path>, exchange: &str, symbol: &str, at: SystemTime) -> Result<Option<OrderBookL2>, SoqaError> {
+    Ok(reconstruct(record::read_book_rows(path, exchange, symbol, at)?, exchange, symbol, at))
+}
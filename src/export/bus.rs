@@ -0,0 +1,253 @@
+use crate::codec::{Encoding, EventReader};
+use crate::error::SoqaError;
+use crate::merge::{self, Watermark};
+use crate::models::MarketEvent;
+use crate::subscriptions::Channel;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+// Destination for normalized events; implementations may buffer until `flush`.
+pub trait Sink: Send {
+    fn write(&mut self, event: &MarketEvent) -> impl Future<Output = Result<(), SoqaError>> + Send;
+    fn flush(&mut self) -> impl Future<Output = Result<(), SoqaError>> + Send;
+
+    // Writes whatever is still buffered once no more events will come.
+    fn close(&mut self) -> impl Future<Output = Result<(), SoqaError>> + Send {
+        self.flush()
+    }
+}
+
+// A keyed message bound for a bus topic (NATS subject or Kafka topic).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub topic: String,
+    pub key: String,
+    pub payload: Vec<u8>,
+    pub timestamp: SystemTime,
+}
+
+// Delivers batches of messages to a broker. The batch is borrowed so a failed send can be retried.
+pub trait Transport: Send {
+    fn name(&self) -> &'static str;
+    fn send(&mut self, batch: &[Message]) -> impl Future<Output = Result<(), SoqaError>> + Send;
+}
+
+// `<prefix>.<exchange>.<symbol>.<channel>`, e.g. `soqa.kraken.XBT-USD.l1`; characters that are not
+// valid in both NATS subjects and Kafka topic names become `-`.
+pub fn topic(prefix: &str, event: &MarketEvent) -> String {
+    let symbol: String = event
+        .symbol()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '-' })
+        .collect();
+    format!("{}.{}.{}.{}", prefix, event.exchange(), symbol, Channel::of(event).as_str())
+}
+
+// Messages of one exchange/symbol share a key, so they land on one partition in order.
+pub fn key(event: &MarketEvent) -> String {
+    format!("{}:{}", event.exchange(), event.symbol())
+}
+
+// Turns events into keyed messages and hands them to the transport in batches.
+pub struct BusSink<T: Transport> {
+    transport: T,
+    prefix: String,
+    encoding: Encoding,
+    max_batch: usize,
+    linger: Duration,
+    max_buffered: usize,
+    batch: Vec<Message>,
+    oldest: Option<Instant>,
+    retry_at: Option<Instant>,
+}
+
+// Wait after a failed send before `write` tries the broker again.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+impl<T: Transport> BusSink<T> {
+    pub fn new(transport: T) -> Self {
+        BusSink {
+            transport,
+            prefix: "soqa".to_string(),
+            encoding: Encoding::Json,
+            max_batch: 100,
+            linger: Duration::from_millis(50),
+            max_buffered: 100_000,
+            batch: Vec::new(),
+            oldest: None,
+            retry_at: None,
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    // A batch is sent once it holds `max_batch` messages or its oldest message is `linger` old.
+    pub fn with_batching(mut self, max_batch: usize, linger: Duration) -> Self {
+        self.max_batch = max_batch.max(1);
+        self.linger = linger;
+        self
+    }
+
+    // Messages kept for retry while the broker is unreachable; beyond this the oldest are dropped.
+    pub fn with_max_buffered(mut self, max_buffered: usize) -> Self {
+        self.max_buffered = max_buffered.max(1);
+        self
+    }
+}
+
+impl<T: Transport> Sink for BusSink<T> {
+    async fn write(&mut self, event: &MarketEvent) -> Result<(), SoqaError> {
+        self.batch.push(Message {
+            topic: topic(&self.prefix, event),
+            key: key(event),
+            payload: self.encoding.encode(event),
+            timestamp: event.timestamp(),
+        });
+        let oldest = *self.oldest.get_or_insert_with(Instant::now);
+        let due = self.batch.len() >= self.max_batch || oldest.elapsed() >= self.linger;
+        if due && self.retry_at.is_none_or(|at| Instant::now() >= at) {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    // A failed send keeps the batch for the next flush, up to `max_buffered` messages.
+    async fn flush(&mut self) -> Result<(), SoqaError> {
+        if self.batch.is_empty() {
+            self.oldest = None;
+            return Ok(());
+        }
+        let records = self.batch.len();
+        let started = Instant::now();
+        if let Err(e) = self.transport.send(&self.batch).await {
+            self.retry_at = Some(Instant::now() + RETRY_BACKOFF);
+            if self.batch.len() > self.max_buffered {
+                let dropped = self.batch.len() - self.max_buffered;
+                self.batch.drain(..dropped);
+                warn!(target: "soqa::export", dropped, "bus sink buffer full, oldest messages dropped");
+            }
+            return Err(e);
+        }
+        crate::metrics::sink_write(self.transport.name(), records, started.elapsed().as_secs_f64());
+        self.batch.clear();
+        self.oldest = None;
+        self.retry_at = None;
+        Ok(())
+    }
+}
+
+// In-process stand-in for a broker: keeps every message per topic, for tests and local wiring.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBroker {
+    topics: Arc<Mutex<BTreeMap<String, Vec<Message>>>>,
+    batches: Arc<Mutex<usize>>,
+}
+
+impl MemoryBroker {
+    pub fn new() -> Self {
+        MemoryBroker::default()
+    }
+
+    pub fn topics(&self) -> Vec<String> {
+        self.topics.lock().unwrap().keys().cloned().collect()
+    }
+
+    pub fn messages(&self, topic: &str) -> Vec<Message> {
+        self.topics.lock().unwrap().get(topic).cloned().unwrap_or_default()
+    }
+
+    // Number of `send` calls received, i.e. batches.
+    pub fn batches(&self) -> usize {
+        *self.batches.lock().unwrap()
+    }
+}
+
+impl Transport for MemoryBroker {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn send(&mut self, batch: &[Message]) -> Result<(), SoqaError> {
+        let mut topics = self.topics.lock().unwrap();
+        for message in batch {
+            topics.entry(message.topic.clone()).or_default().push(message.clone());
+        }
+        *self.batches.lock().unwrap() += 1;
+        Ok(())
+    }
+}
+
+// Publishes a recorded binary capture (see `export_events_to_binary`) to a sink, so recorded and
+// live data reach the same consumers. Returns the number of events sent.
+pub async fn replay<S: Sink>(file_path: &str, sink: &mut S) -> Result<u64, SoqaError> {
+    let file = std::fs::File::open(file_path).map_err(|e| SoqaError::ExportError(e.to_string()))?;
+    let mut sent = 0;
+    for event in EventReader::new(std::io::BufReader::new(file)) {
+        sink.write(&event?).await?;
+        sent += 1;
+    }
+    sink.flush().await?;
+    Ok(sent)
+}
+
+// Publishes several captures, e.g. one per venue, as a single stream in exchange-time order.
+// Events more than `watermark.lateness` out of order within a capture are handled as late.
+pub async fn replay_merged<S: Sink>(file_paths: &[&str], sink: &mut S, watermark: Watermark) -> Result<u64, SoqaError> {
+    let failed = RefCell::new(None);
+    let mut sources = Vec::new();
+    for file_path in file_paths {
+        let file = std::fs::File::open(file_path).map_err(|e| SoqaError::ExportError(format!("{}: {}", file_path, e)))?;
+        sources.push(EventReader::new(std::io::BufReader::new(file)).map_while(|event| match event {
+            Ok(event) => Some(event),
+            Err(e) => {
+                failed.borrow_mut().get_or_insert(e);
+                None
+            }
+        }));
+    }
+    let mut sent = 0;
+    for event in merge::merge_iter(sources, watermark) {
+        sink.write(&event).await?;
+        sent += 1;
+    }
+    if let Some(e) = failed.into_inner() {
+        return Err(e);
+    }
+    sink.flush().await?;
+    Ok(sent)
+}
+
+// Drains the gateway broadcast into `sink` until the channel closes. A partial batch is flushed
+// whenever no event arrives for `idle`, so quiet markets do not hold messages back.
+pub async fn forward<S: Sink>(mut sink: S, mut events: broadcast::Receiver<MarketEvent>, idle: Duration) {
+    loop {
+        let result = match tokio::time::timeout(idle, events.recv()).await {
+            Ok(Ok(event)) => sink.write(&event).await,
+            Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => {
+                warn!(target: "soqa::export", skipped, "bus sink lagging, events dropped");
+                continue;
+            }
+            Ok(Err(broadcast::error::RecvError::Closed)) => break,
+            Err(_) => sink.flush().await,
+        };
+        if let Err(e) = result {
+            warn!(target: "soqa::export", error = %e, "bus sink write failed");
+        }
+    }
+    if let Err(e) = sink.close().await {
+        warn!(target: "soqa::export", error = %e, "bus sink flush failed");
+    }
+}
@@ -0,0 +1,83 @@
+use super::bus::{Message, Transport};
+use crate::error::SoqaError;
+use rskafka::chrono::{DateTime, Utc};
+use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+use rskafka::client::{Client, ClientBuilder};
+use rskafka::record::Record;
+use std::collections::{BTreeMap, HashMap};
+
+fn kafka_error(e: impl std::fmt::Display) -> SoqaError {
+    SoqaError::ExportError(format!("kafka: {}", e))
+}
+
+// FNV-1a, so a key maps to the same partition across runs and hosts. This is not the murmur2
+// hash of Kafka's default partitioner, so Java producers put the same key on other partitions;
+// consumers that join our topics with theirs by partition must not assume they line up.
+fn partition_for(key: &str, partitions: usize) -> usize {
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    (hash % partitions as u64) as usize
+}
+
+// Produces to any broker speaking the Kafka wire protocol (Kafka, Redpanda, ...). Topics that do
+// not exist yet are created with one partition.
+pub struct KafkaTransport {
+    client: Client,
+    partitions: HashMap<String, Vec<PartitionClient>>,
+}
+
+impl KafkaTransport {
+    // `brokers` are bootstrap addresses such as `127.0.0.1:9092`.
+    pub async fn connect(brokers: Vec<String>) -> Result<Self, SoqaError> {
+        let client = ClientBuilder::new(brokers).build().await.map_err(kafka_error)?;
+        Ok(KafkaTransport { client, partitions: HashMap::new() })
+    }
+
+    async fn partitions(&mut self, topic: &str) -> Result<&[PartitionClient], SoqaError> {
+        if !self.partitions.contains_key(topic) {
+            let topics = self.client.list_topics().await.map_err(kafka_error)?;
+            let ids: Vec<i32> = match topics.into_iter().find(|t| t.name == topic) {
+                Some(existing) => existing.partitions.into_iter().collect(),
+                None => {
+                    let controller = self.client.controller_client().map_err(kafka_error)?;
+                    controller.create_topic(topic, 1, 1, 5_000).await.map_err(kafka_error)?;
+                    vec![0]
+                }
+            };
+            let mut clients = Vec::with_capacity(ids.len());
+            for id in ids {
+                clients.push(self.client.partition_client(topic, id, UnknownTopicHandling::Retry).await.map_err(kafka_error)?);
+            }
+            self.partitions.insert(topic.to_string(), clients);
+        }
+        Ok(&self.partitions[topic])
+    }
+}
+
+impl Transport for KafkaTransport {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    async fn send(&mut self, batch: &[Message]) -> Result<(), SoqaError> {
+        let mut by_topic: BTreeMap<&str, Vec<&Message>> = BTreeMap::new();
+        for message in batch {
+            by_topic.entry(&message.topic).or_default().push(message);
+        }
+        for (topic, messages) in by_topic {
+            let partitions = self.partitions(topic).await?;
+            let mut records: Vec<Vec<Record>> = vec![Vec::new(); partitions.len()];
+            for message in messages {
+                records[partition_for(&message.key, partitions.len())].push(Record {
+                    key: Some(message.key.clone().into_bytes()),
+                    value: Some(message.payload.clone()),
+                    headers: BTreeMap::new(),
+                    timestamp: DateTime::<Utc>::from(message.timestamp),
+                });
+            }
+            for (partition, records) in partitions.iter().zip(records) {
+                partition.produce(records, Compression::NoCompression).await.map_err(kafka_error)?;
+            }
+        }
+        Ok(())
+    }
+}
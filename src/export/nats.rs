@@ -0,0 +1,35 @@
+use super::bus::{Message, Transport};
+use crate::error::SoqaError;
+
+fn nats_error(e: impl std::fmt::Display) -> SoqaError {
+    SoqaError::ExportError(format!("nats: {}", e))
+}
+
+// Publishes each message on the subject named by its topic, with the key in a `Soqa-Key` header.
+pub struct NatsTransport {
+    client: async_nats::Client,
+}
+
+impl NatsTransport {
+    // `url` is a server address such as `nats://127.0.0.1:4222`.
+    pub async fn connect(url: &str) -> Result<Self, SoqaError> {
+        let client = async_nats::connect(url).await.map_err(nats_error)?;
+        Ok(NatsTransport { client })
+    }
+}
+
+impl Transport for NatsTransport {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    async fn send(&mut self, batch: &[Message]) -> Result<(), SoqaError> {
+        for message in batch {
+            let mut headers = async_nats::HeaderMap::new();
+            headers.insert("Soqa-Key", message.key.as_str());
+            let payload = message.payload.clone().into();
+            self.client.publish_with_headers(message.topic.clone(), headers, payload).await.map_err(nats_error)?;
+        }
+        self.client.flush().await.map_err(nats_error)
+    }
+}
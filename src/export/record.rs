@@ -0,0 +1,679 @@
+// Recording sinks for research: quotes, trades and L2 history (see `book`) in one SQLite
+// database or in one Parquet file per channel, and the readers behind `query` and `book_at`.
+
+use crate::error::SoqaError;
+use crate::export::book::{BookRecorder, BookRow, BookSide, RowKind};
+use crate::export::bus::Sink;
+use crate::models::{MarketEvent, OrderBookL1, QuoteSource, Trade};
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::record::{Row, RowAccessor};
+use parquet::schema::parser::parse_message_type;
+use rusqlite::{params, Connection};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+// Which records to read; unset fields match everything. `end` is exclusive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    pub exchange: Option<String>,
+    pub symbol: Option<String>,
+    pub start: Option<SystemTime>,
+    pub end: Option<SystemTime>,
+}
+
+impl Filter {
+    pub fn matches(&self, exchange: &str, symbol: &str, timestamp: SystemTime) -> bool {
+        self.exchange.as_deref().is_none_or(|e| e == exchange)
+            && self.symbol.as_deref().is_none_or(|s| s == symbol)
+            && self.start.is_none_or(|start| timestamp >= start)
+            && self.end.is_none_or(|end| timestamp < end)
+    }
+}
+
+pub(crate) fn nanos(timestamp: SystemTime) -> i64 {
+    timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_nanos() as i64
+}
+
+pub(crate) fn from_nanos(nanos: i64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos.max(0) as u64)
+}
+
+fn millis(timestamp: SystemTime) -> i64 {
+    timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+fn sqlite_error(e: rusqlite::Error) -> SoqaError {
+    SoqaError::ExportError(format!("sqlite: {}", e))
+}
+
+fn parquet_error(e: parquet::errors::ParquetError) -> SoqaError {
+    SoqaError::ExportError(format!("parquet: {}", e))
+}
+
+fn source(s: &str) -> QuoteSource {
+    match s {
+        "book" => QuoteSource::Book,
+        "bbo" => QuoteSource::Bbo,
+        _ => QuoteSource::Ticker,
+    }
+}
+
+// `.parquet` paths are Parquet recordings, `.db`, `.sqlite` and `.sqlite3` SQLite ones.
+pub fn is_parquet(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "parquet")
+}
+
+pub fn is_sqlite(path: &Path) -> bool {
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some("db" | "sqlite" | "sqlite3"))
+}
+
+fn unsupported(path: &Path) -> SoqaError {
+    SoqaError::ExportError(format!("{}: expected a .parquet, .db, .sqlite or .sqlite3 file", path.display()))
+}
+
+// Shared with `export_trades_to_sqlite`, so downloaded and live trades can be queried together.
+pub(crate) const TRADES_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS trades (
+        exchange TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        trade_id TEXT NOT NULL,
+        price REAL NOT NULL,
+        volume REAL NOT NULL,
+        side TEXT NOT NULL,
+        timestamp_ms INTEGER NOT NULL,
+        PRIMARY KEY (exchange, symbol, trade_id)
+    )";
+
+const SQLITE_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS quotes (
+        exchange TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        timestamp_ns INTEGER NOT NULL,
+        bid REAL NOT NULL,
+        bid_volume REAL NOT NULL,
+        ask REAL NOT NULL,
+        ask_volume REAL NOT NULL,
+        source TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS quotes_market ON quotes (exchange, symbol, timestamp_ns);
+    CREATE TABLE IF NOT EXISTS l2_book (
+        exchange TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        sequence INTEGER NOT NULL,
+        timestamp_ns INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        side TEXT NOT NULL,
+        price REAL NOT NULL,
+        size REAL NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS l2_book_market ON l2_book (exchange, symbol, sequence);";
+
+// Runs `f` on the blocking pool, so file and database writes do not stall the runtime's workers.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> Result<T, SoqaError> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| SoqaError::ExportError(format!("writer task: {}", e)))
+}
+
+// Records buffered for the next SQLite transaction.
+#[derive(Default)]
+struct Pending {
+    quotes: Vec<OrderBookL1>,
+    trades: Vec<Trade>,
+    rows: Vec<BookRow>,
+}
+
+impl Pending {
+    fn len(&self) -> usize {
+        self.quotes.len() + self.trades.len() + self.rows.len()
+    }
+}
+
+// Appends quotes to `quotes`, trades to `trades` and L2 history to `l2_book`. Opening an
+// existing database continues each market's book sequence where it left off. Inserts run on the
+// blocking pool; a batch that fails is kept for the next flush.
+pub struct SqliteSink {
+    conn: Arc<Mutex<Connection>>,
+    recorder: BookRecorder,
+    pending: Pending,
+    // Live trades carry no venue id, so they are keyed by time and arrival order instead.
+    trade_seq: u64,
+}
+
+impl SqliteSink {
+    pub fn open(path: impl AsRef<Path>, snapshot_every: Duration) -> Result<Self, SoqaError> {
+        let conn = Connection::open(path).map_err(sqlite_error)?;
+        conn.execute_batch(SQLITE_SCHEMA).map_err(sqlite_error)?;
+        conn.execute_batch(TRADES_SCHEMA).map_err(sqlite_error)?;
+        let mut recorder = BookRecorder::new(snapshot_every);
+        {
+            let mut last = conn
+                .prepare("SELECT exchange, symbol, MAX(sequence) FROM l2_book GROUP BY exchange, symbol")
+                .map_err(sqlite_error)?;
+            let markets = last
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?)))
+                .map_err(sqlite_error)?;
+            for market in markets {
+                let (exchange, symbol, sequence) = market.map_err(sqlite_error)?;
+                recorder.resume(&exchange, &symbol, sequence as u64);
+            }
+        }
+        Ok(SqliteSink { conn: Arc::new(Mutex::new(conn)), recorder, pending: Pending::default(), trade_seq: 0 })
+    }
+}
+
+fn insert(conn: &mut Connection, pending: &Pending, mut trade_seq: u64) -> rusqlite::Result<usize> {
+    let tx = conn.transaction()?;
+    {
+        let mut insert = tx.prepare_cached(
+            "INSERT INTO quotes (exchange, symbol, timestamp_ns, bid, bid_volume, ask, ask_volume, source)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?;
+        for q in &pending.quotes {
+            insert.execute(params![q.exchange, q.symbol, nanos(q.timestamp), q.bid, q.bid_volume, q.ask, q.ask_volume, q.source.as_str()])?;
+        }
+        let mut insert = tx.prepare_cached(
+            "INSERT OR IGNORE INTO trades (exchange, symbol, trade_id, price, volume, side, timestamp_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        for t in &pending.trades {
+            trade_seq += 1;
+            let trade_id = format!("live-{}-{}", nanos(t.timestamp), trade_seq);
+            insert.execute(params![t.exchange, t.symbol, trade_id, t.price, t.volume, t.side, millis(t.timestamp)])?;
+        }
+        let mut insert = tx.prepare_cached(
+            "INSERT INTO l2_book (exchange, symbol, sequence, timestamp_ns, kind, side, price, size)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?;
+        for r in &pending.rows {
+            let values = params![r.exchange, r.symbol, r.sequence as i64, nanos(r.timestamp), r.kind.as_str(), r.side.as_str(), r.price, r.size];
+            insert.execute(values)?;
+        }
+    }
+    tx.commit()?;
+    Ok(pending.len())
+}
+
+impl Sink for SqliteSink {
+    async fn write(&mut self, event: &MarketEvent) -> Result<(), SoqaError> {
+        match event {
+            MarketEvent::L1(quote) => self.pending.quotes.push(quote.clone()),
+            MarketEvent::Trade(trade) => self.pending.trades.push(trade.clone()),
+            MarketEvent::L2(book) => self.pending.rows.extend(self.recorder.record(book)),
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), SoqaError> {
+        if self.pending.len() == 0 {
+            return Ok(());
+        }
+        let started = Instant::now();
+        let (conn, pending, trade_seq) = (self.conn.clone(), std::mem::take(&mut self.pending), self.trade_seq);
+        let (pending, result) = blocking(move || {
+            let result = insert(&mut conn.lock().unwrap(), &pending, trade_seq);
+            (pending, result)
+        })
+        .await?;
+        match result {
+            Ok(records) => {
+                self.trade_seq += pending.trades.len() as u64;
+                crate::metrics::sink_write("sqlite", records, started.elapsed().as_secs_f64());
+                Ok(())
+            }
+            Err(e) => {
+                self.pending = pending;
+                Err(sqlite_error(e))
+            }
+        }
+    }
+}
+
+// One Parquet column of a row group.
+enum Column {
+    Text(Vec<ByteArray>),
+    Long(Vec<i64>),
+    Double(Vec<f64>),
+}
+
+fn text<T>(records: &[T], f: impl Fn(&T) -> &str) -> Column {
+    Column::Text(records.iter().map(|r| ByteArray::from(f(r))).collect())
+}
+
+// A record type with a fixed Parquet layout.
+trait ParquetRecord: Sized {
+    const CHANNEL: &'static str;
+    const SCHEMA: &'static str;
+    fn columns(records: &[Self]) -> Vec<Column>;
+    fn from_row(row: &Row) -> parquet::errors::Result<Result<Self, SoqaError>>;
+}
+
+impl ParquetRecord for OrderBookL1 {
+    const CHANNEL: &'static str = "l1";
+    const SCHEMA: &'static str = "message quotes {
+        REQUIRED BYTE_ARRAY exchange (UTF8);
+        REQUIRED BYTE_ARRAY symbol (UTF8);
+        REQUIRED INT64 timestamp_ns;
+        REQUIRED DOUBLE bid;
+        REQUIRED DOUBLE bid_volume;
+        REQUIRED DOUBLE ask;
+        REQUIRED DOUBLE ask_volume;
+        REQUIRED BYTE_ARRAY source (UTF8);
+    }";
+
+    fn columns(quotes: &[Self]) -> Vec<Column> {
+        vec![
+            text(quotes, |q| &q.exchange),
+            text(quotes, |q| &q.symbol),
+            Column::Long(quotes.iter().map(|q| nanos(q.timestamp)).collect()),
+            Column::Double(quotes.iter().map(|q| q.bid).collect()),
+            Column::Double(quotes.iter().map(|q| q.bid_volume).collect()),
+            Column::Double(quotes.iter().map(|q| q.ask).collect()),
+            Column::Double(quotes.iter().map(|q| q.ask_volume).collect()),
+            text(quotes, |q| q.source.as_str()),
+        ]
+    }
+
+    fn from_row(row: &Row) -> parquet::errors::Result<Result<Self, SoqaError>> {
+        Ok(Ok(OrderBookL1 {
+            exchange: row.get_string(0)?.clone(),
+            symbol: row.get_string(1)?.clone(),
+            timestamp: from_nanos(row.get_long(2)?),
+            bid: row.get_double(3)?,
+            bid_volume: row.get_double(4)?,
+            ask: row.get_double(5)?,
+            ask_volume: row.get_double(6)?,
+            source: source(row.get_string(7)?),
+        }))
+    }
+}
+
+impl ParquetRecord for Trade {
+    const CHANNEL: &'static str = "trades";
+    const SCHEMA: &'static str = "message trades {
+        REQUIRED BYTE_ARRAY exchange (UTF8);
+        REQUIRED BYTE_ARRAY symbol (UTF8);
+        REQUIRED INT64 timestamp_ns;
+        REQUIRED DOUBLE price;
+        REQUIRED DOUBLE volume;
+        REQUIRED BYTE_ARRAY side (UTF8);
+    }";
+
+    fn columns(trades: &[Self]) -> Vec<Column> {
+        vec![
+            text(trades, |t| &t.exchange),
+            text(trades, |t| &t.symbol),
+            Column::Long(trades.iter().map(|t| nanos(t.timestamp)).collect()),
+            Column::Double(trades.iter().map(|t| t.price).collect()),
+            Column::Double(trades.iter().map(|t| t.volume).collect()),
+            text(trades, |t| &t.side),
+        ]
+    }
+
+    fn from_row(row: &Row) -> parquet::errors::Result<Result<Self, SoqaError>> {
+        Ok(Ok(Trade {
+            exchange: row.get_string(0)?.clone(),
+            symbol: row.get_string(1)?.clone(),
+            timestamp: from_nanos(row.get_long(2)?),
+            price: row.get_double(3)?,
+            volume: row.get_double(4)?,
+            side: row.get_string(5)?.clone(),
+        }))
+    }
+}
+
+impl ParquetRecord for BookRow {
+    const CHANNEL: &'static str = "l2";
+    const SCHEMA: &'static str = "message l2_book {
+        REQUIRED BYTE_ARRAY exchange (UTF8);
+        REQUIRED BYTE_ARRAY symbol (UTF8);
+        REQUIRED INT64 sequence;
+        REQUIRED INT64 timestamp_ns;
+        REQUIRED BYTE_ARRAY kind (UTF8);
+        REQUIRED BYTE_ARRAY side (UTF8);
+        REQUIRED DOUBLE price;
+        REQUIRED DOUBLE size;
+    }";
+
+    fn columns(rows: &[Self]) -> Vec<Column> {
+        vec![
+            text(rows, |r| &r.exchange),
+            text(rows, |r| &r.symbol),
+            Column::Long(rows.iter().map(|r| r.sequence as i64).collect()),
+            Column::Long(rows.iter().map(|r| nanos(r.timestamp)).collect()),
+            text(rows, |r| r.kind.as_str()),
+            text(rows, |r| r.side.as_str()),
+            Column::Double(rows.iter().map(|r| r.price).collect()),
+            Column::Double(rows.iter().map(|r| r.size).collect()),
+        ]
+    }
+
+    fn from_row(row: &Row) -> parquet::errors::Result<Result<Self, SoqaError>> {
+        let (kind, side) = (RowKind::parse(row.get_string(4)?), BookSide::parse(row.get_string(5)?));
+        let (exchange, symbol) = (row.get_string(0)?.clone(), row.get_string(1)?.clone());
+        let (sequence, timestamp_ns, price, size) = (row.get_long(2)?, row.get_long(3)?, row.get_double(6)?, row.get_double(7)?);
+        Ok(kind.and_then(|kind| {
+            Ok(BookRow { exchange, symbol, sequence: sequence as u64, timestamp: from_nanos(timestamp_ns), kind, side: side?, price, size })
+        }))
+    }
+}
+
+// `capture.parquet` keeps its quotes in `capture.l1.parquet`, trades in `capture.trades.parquet`
+// and L2 rows in `capture.l2.parquet`.
+pub fn parquet_path(path: impl AsRef<Path>, channel: &str) -> PathBuf {
+    let path = path.as_ref();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}.parquet", stem, channel))
+}
+
+// One channel's file; created with the first row group, so channels never seen leave no file.
+struct ParquetTable<T> {
+    path: PathBuf,
+    writer: Option<SerializedFileWriter<File>>,
+    records: Vec<T>,
+    // When the oldest buffered record arrived.
+    since: Option<Instant>,
+}
+
+impl<T: ParquetRecord> ParquetTable<T> {
+    fn new(base: &Path) -> Self {
+        ParquetTable { path: parquet_path(base, T::CHANNEL), writer: None, records: Vec::new(), since: None }
+    }
+
+    fn extend(&mut self, records: impl IntoIterator<Item = T>) {
+        self.records.extend(records);
+        if !self.records.is_empty() {
+            self.since.get_or_insert_with(Instant::now);
+        }
+    }
+
+    fn due(&self, rows: usize, age: Duration) -> bool {
+        self.records.len() >= rows || self.since.is_some_and(|since| since.elapsed() >= age)
+    }
+
+    fn write_row_group(&mut self) -> Result<usize, SoqaError> {
+        if self.records.is_empty() {
+            return Ok(0);
+        }
+        if self.writer.is_none() {
+            // Never truncates: a file left by an earlier run is an error, not something to replace.
+            let file = File::create_new(&self.path).map_err(|e| SoqaError::ExportError(format!("{}: {}", self.path.display(), e)))?;
+            let schema = Arc::new(parse_message_type(T::SCHEMA).map_err(parquet_error)?);
+            let props = Arc::new(WriterProperties::builder().set_compression(Compression::SNAPPY).build());
+            self.writer = Some(SerializedFileWriter::new(file, schema, props).map_err(parquet_error)?);
+        }
+        let writer = self.writer.as_mut().unwrap();
+        let records = std::mem::take(&mut self.records);
+        self.since = None;
+        let mut columns = T::columns(&records).into_iter();
+        let mut group = writer.next_row_group().map_err(parquet_error)?;
+        while let Some(mut writer) = group.next_column().map_err(parquet_error)? {
+            let written = match columns.next() {
+                Some(Column::Text(values)) => writer.typed::<ByteArrayType>().write_batch(&values, None, None),
+                Some(Column::Long(values)) => writer.typed::<Int64Type>().write_batch(&values, None, None),
+                Some(Column::Double(values)) => writer.typed::<DoubleType>().write_batch(&values, None, None),
+                None => return Err(SoqaError::ExportError(format!("{}: schema has more columns than the records", self.path.display()))),
+            };
+            written.map_err(parquet_error)?;
+            writer.close().map_err(parquet_error)?;
+        }
+        group.close().map_err(parquet_error)?;
+        Ok(records.len())
+    }
+
+    fn finish(&mut self) -> Result<usize, SoqaError> {
+        let written = self.write_row_group()?;
+        if let Some(writer) = self.writer.take() {
+            writer.close().map_err(parquet_error)?;
+        }
+        Ok(written)
+    }
+}
+
+fn read_parquet<T: ParquetRecord>(base: &Path) -> Result<Vec<T>, SoqaError> {
+    let path = parquet_path(base, T::CHANNEL);
+    let file = File::open(&path).map_err(|e| SoqaError::ExportError(format!("{}: {}", path.display(), e)))?;
+    let reader = SerializedFileReader::new(file).map_err(parquet_error)?;
+    reader
+        .get_row_iter(None)
+        .map_err(parquet_error)?
+        .map(|row| T::from_row(&row.map_err(parquet_error)?).map_err(parquet_error)?)
+        .collect()
+}
+
+// The channels' files, shared with the blocking pool while row groups are written.
+struct ParquetTables {
+    quotes: ParquetTable<OrderBookL1>,
+    trades: ParquetTable<Trade>,
+    book: ParquetTable<BookRow>,
+}
+
+impl ParquetTables {
+    fn due(&self, rows: usize, age: Duration) -> bool {
+        self.quotes.due(rows, age) || self.trades.due(rows, age) || self.book.due(rows, age)
+    }
+
+    // Writes a row group for each channel holding `rows` records or one older than `age`.
+    fn write_row_groups(&mut self, rows: usize, age: Duration) -> Result<usize, SoqaError> {
+        let mut written = 0;
+        if self.quotes.due(rows, age) {
+            written += self.quotes.write_row_group()?;
+        }
+        if self.trades.due(rows, age) {
+            written += self.trades.write_row_group()?;
+        }
+        if self.book.due(rows, age) {
+            written += self.book.write_row_group()?;
+        }
+        Ok(written)
+    }
+
+    fn finish(&mut self) -> Result<(), SoqaError> {
+        let started = Instant::now();
+        let written = self.quotes.finish()? + self.trades.finish()? + self.book.finish()?;
+        if written > 0 {
+            crate::metrics::sink_write("parquet", written, started.elapsed().as_secs_f64());
+        }
+        Ok(())
+    }
+}
+
+// Writes each channel to its own new Parquet file (see `parquet_path`) on the blocking pool, in
+// row groups of `row_group_rows` or of whatever arrived within `row_group_interval`. The last
+// group and the footers are written by `close`, `finish` or on drop, and the files cannot be
+// read before that. Existing files are never overwritten.
+pub struct ParquetSink {
+    recorder: BookRecorder,
+    tables: Arc<Mutex<ParquetTables>>,
+    row_group_rows: usize,
+    row_group_interval: Duration,
+}
+
+impl ParquetSink {
+    pub fn create(path: impl AsRef<Path>, snapshot_every: Duration) -> Result<Self, SoqaError> {
+        let path = path.as_ref();
+        let tables = ParquetTables { quotes: ParquetTable::new(path), trades: ParquetTable::new(path), book: ParquetTable::new(path) };
+        if let Some(existing) = [&tables.quotes.path, &tables.trades.path, &tables.book.path].into_iter().find(|p| p.exists()) {
+            return Err(SoqaError::ExportError(format!("{} already exists; choose another name", existing.display())));
+        }
+        Ok(ParquetSink {
+            recorder: BookRecorder::new(snapshot_every),
+            tables: Arc::new(Mutex::new(tables)),
+            row_group_rows: 65536,
+            row_group_interval: Duration::from_secs(60),
+        })
+    }
+
+    pub fn with_row_group_rows(mut self, rows: usize) -> Self {
+        self.row_group_rows = rows.max(1);
+        self
+    }
+
+    pub fn with_row_group_interval(mut self, interval: Duration) -> Self {
+        self.row_group_interval = interval;
+        self
+    }
+
+    // Blocks on the file writes; `close` does the same from async code.
+    pub fn finish(&mut self) -> Result<(), SoqaError> {
+        self.tables.lock().unwrap().finish()
+    }
+}
+
+impl Sink for ParquetSink {
+    async fn write(&mut self, event: &MarketEvent) -> Result<(), SoqaError> {
+        let mut tables = self.tables.lock().unwrap();
+        match event {
+            MarketEvent::L1(quote) => tables.quotes.extend([quote.clone()]),
+            MarketEvent::Trade(trade) => tables.trades.extend([trade.clone()]),
+            MarketEvent::L2(book) => tables.book.extend(self.recorder.record(book)),
+        }
+        Ok(())
+    }
+
+    // Parquet pays per row group, so groups that are neither full nor old stay buffered.
+    async fn flush(&mut self) -> Result<(), SoqaError> {
+        let (rows, age) = (self.row_group_rows, self.row_group_interval);
+        if !self.tables.lock().unwrap().due(rows, age) {
+            return Ok(());
+        }
+        let (tables, started) = (self.tables.clone(), Instant::now());
+        let written = blocking(move || tables.lock().unwrap().write_row_groups(rows, age)).await??;
+        if written > 0 {
+            crate::metrics::sink_write("parquet", written, started.elapsed().as_secs_f64());
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), SoqaError> {
+        let tables = self.tables.clone();
+        blocking(move || tables.lock().unwrap().finish()).await?
+    }
+}
+
+// A sink dropped without `close` writes its footers here, on whichever thread drops it.
+impl Drop for ParquetSink {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            warn!(target: "soqa::export", error = %e, "parquet sink failed to finish");
+        }
+    }
+}
+
+// Recorded quotes matching `filter`, oldest first.
+pub fn read_quotes(path: impl AsRef<Path>, filter: &Filter) -> Result<Vec<OrderBookL1>, SoqaError> {
+    let path = path.as_ref();
+    let mut quotes = if is_parquet(path) {
+        let quotes: Vec<OrderBookL1> = read_parquet(path)?;
+        quotes.into_iter().filter(|q| filter.matches(&q.exchange, &q.symbol, q.timestamp)).collect()
+    } else if is_sqlite(path) {
+        let conn = Connection::open(path).map_err(sqlite_error)?;
+        let mut query = conn
+            .prepare(
+                "SELECT exchange, symbol, timestamp_ns, bid, bid_volume, ask, ask_volume, source FROM quotes
+                 WHERE (?1 IS NULL OR exchange = ?1) AND (?2 IS NULL OR symbol = ?2)
+                   AND (?3 IS NULL OR timestamp_ns >= ?3) AND (?4 IS NULL OR timestamp_ns < ?4)",
+            )
+            .map_err(sqlite_error)?;
+        let rows = query
+            .query_map(params![filter.exchange, filter.symbol, filter.start.map(nanos), filter.end.map(nanos)], |row| {
+                Ok(OrderBookL1 {
+                    exchange: row.get(0)?,
+                    symbol: row.get(1)?,
+                    timestamp: from_nanos(row.get(2)?),
+                    bid: row.get(3)?,
+                    bid_volume: row.get(4)?,
+                    ask: row.get(5)?,
+                    ask_volume: row.get(6)?,
+                    source: source(&row.get::<_, String>(7)?),
+                })
+            })
+            .map_err(sqlite_error)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(sqlite_error)?
+    } else {
+        return Err(unsupported(path));
+    };
+    quotes.sort_by_key(|q| q.timestamp);
+    Ok(quotes)
+}
+
+// Recorded trades matching `filter`, oldest first. For SQLite these include trades saved by
+// `download`; their timestamps have millisecond resolution.
+pub fn read_trades(path: impl AsRef<Path>, filter: &Filter) -> Result<Vec<Trade>, SoqaError> {
+    let path = path.as_ref();
+    let mut trades = if is_parquet(path) {
+        let trades: Vec<Trade> = read_parquet(path)?;
+        trades.into_iter().filter(|t| filter.matches(&t.exchange, &t.symbol, t.timestamp)).collect()
+    } else if is_sqlite(path) {
+        let conn = Connection::open(path).map_err(sqlite_error)?;
+        let mut query = conn
+            .prepare(
+                "SELECT exchange, symbol, price, volume, side, timestamp_ms FROM trades
+                 WHERE (?1 IS NULL OR exchange = ?1) AND (?2 IS NULL OR symbol = ?2)
+                   AND (?3 IS NULL OR timestamp_ms >= ?3) AND (?4 IS NULL OR timestamp_ms < ?4)",
+            )
+            .map_err(sqlite_error)?;
+        let rows = query
+            .query_map(params![filter.exchange, filter.symbol, filter.start.map(millis), filter.end.map(millis)], |row| {
+                Ok(Trade {
+                    exchange: row.get(0)?,
+                    symbol: row.get(1)?,
+                    price: row.get(2)?,
+                    volume: row.get(3)?,
+                    side: row.get(4)?,
+                    timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(row.get::<_, i64>(5)?.max(0) as u64),
+                })
+            })
+            .map_err(sqlite_error)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(sqlite_error)?
+    } else {
+        return Err(unsupported(path));
+    };
+    trades.sort_by_key(|t| t.timestamp);
+    Ok(trades)
+}
+
+// One market's L2 rows; from SQLite only those from its newest snapshot at or before `at` on.
+pub fn read_book_rows(path: impl AsRef<Path>, exchange: &str, symbol: &str, at: SystemTime) -> Result<Vec<BookRow>, SoqaError> {
+    let path = path.as_ref();
+    if is_parquet(path) {
+        let rows: Vec<BookRow> = read_parquet(path)?;
+        return Ok(rows.into_iter().filter(|r| r.exchange == exchange && r.symbol == symbol).collect());
+    }
+    if !is_sqlite(path) {
+        return Err(unsupported(path));
+    }
+    let conn = Connection::open(path).map_err(sqlite_error)?;
+    let mut query = conn
+        .prepare(
+            "SELECT sequence, timestamp_ns, kind, side, price, size FROM l2_book
+             WHERE exchange = ?1 AND symbol = ?2 AND sequence >= (
+                 SELECT COALESCE(MAX(sequence), 0) FROM l2_book
+                 WHERE exchange = ?1 AND symbol = ?2 AND kind = 'snapshot' AND timestamp_ns <= ?3
+             )
+             ORDER BY sequence",
+        )
+        .map_err(sqlite_error)?;
+    let rows = query
+        .query_map(params![exchange, symbol, nanos(at)], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?, row.get(4)?, row.get(5)?))
+        })
+        .map_err(sqlite_error)?;
+    rows.map(|row| {
+        let (sequence, timestamp_ns, kind, side, price, size) = row.map_err(sqlite_error)?;
+        Ok(BookRow {
+            exchange: exchange.to_string(),
+            symbol: symbol.to_string(),
+            timestamp: from_nanos(timestamp_ns),
+            sequence: sequence as u64,
+            kind: RowKind::parse(&kind)?,
+            side: BookSide::parse(&side)?,
+            price,
+            size,
+        })
+    })
+    .collect()
+}
@@ -0,0 +1,147 @@
+use super::bus::Sink;
+use crate::error::SoqaError;
+use crate::models::MarketEvent;
+use ::redis::aio::MultiplexedConnection;
+use ::redis::{Client, Pipeline};
+use std::time::{Duration, Instant, SystemTime};
+
+fn redis_error(e: impl std::fmt::Display) -> SoqaError {
+    SoqaError::ExportError(format!("redis: {}", e))
+}
+
+fn millis(timestamp: SystemTime) -> u64 {
+    timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+// Latest-value cache and pub/sub fan-out for dashboards. For prefix `soqa`:
+//   soqa:l1:<exchange>:<symbol>      hash with bid, bid_volume, ask, ask_volume, timestamp_ms, source
+//   soqa:trades:<exchange>:<symbol>  sorted set of recent trades (JSON) scored by timestamp_ms
+// and every event is published as JSON on the channel `soqa:<l1|l2|trades>:<exchange>:<symbol>`.
+// Commands are pipelined and sent in batches like `BusSink`.
+pub struct RedisSink {
+    connection: MultiplexedConnection,
+    prefix: String,
+    ttl: Option<Duration>,
+    max_trades: usize,
+    max_batch: usize,
+    linger: Duration,
+    pipeline: Pipeline,
+    pending: usize,
+    oldest: Option<Instant>,
+}
+
+impl RedisSink {
+    // `url` is e.g. `redis://127.0.0.1:6379/0`.
+    pub async fn connect(url: &str) -> Result<Self, SoqaError> {
+        let client = Client::open(url).map_err(redis_error)?;
+        let connection = client.get_multiplexed_async_connection().await.map_err(redis_error)?;
+        Ok(RedisSink {
+            connection,
+            prefix: "soqa".to_string(),
+            ttl: None,
+            max_trades: 0,
+            max_batch: 100,
+            linger: Duration::from_millis(50),
+            pipeline: Pipeline::new(),
+            pending: 0,
+            oldest: None,
+        })
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    // Cached keys expire `ttl` after their last update, so a dead feed shows up as a missing
+    // quote rather than a stale one.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    // Keeps the newest `max_trades` trades per symbol; 0 (the default) stores none.
+    pub fn with_trades(mut self, max_trades: usize) -> Self {
+        self.max_trades = max_trades;
+        self
+    }
+
+    pub fn with_batching(mut self, max_batch: usize, linger: Duration) -> Self {
+        self.max_batch = max_batch.max(1);
+        self.linger = linger;
+        self
+    }
+
+    fn key(&self, channel: &str, event: &MarketEvent) -> String {
+        format!("{}:{}:{}:{}", self.prefix, channel, event.exchange(), event.symbol())
+    }
+
+    fn expire(&mut self, key: &str) {
+        if let Some(ttl) = self.ttl {
+            self.pipeline.pexpire(key, ttl.as_millis().max(1) as i64).ignore();
+        }
+    }
+
+    fn queue(&mut self, event: &MarketEvent) -> Result<(), SoqaError> {
+        let json = serde_json::to_string(event)?;
+        match event {
+            MarketEvent::L1(book) => {
+                let key = self.key("l1", event);
+                self.pipeline
+                    .hset_multiple(
+                        &key,
+                        &[
+                            ("bid", book.bid.to_string()),
+                            ("bid_volume", book.bid_volume.to_string()),
+                            ("ask", book.ask.to_string()),
+                            ("ask_volume", book.ask_volume.to_string()),
+                            ("timestamp_ms", millis(book.timestamp).to_string()),
+                            ("source", book.source.as_str().to_string()),
+                        ],
+                    )
+                    .ignore();
+                self.expire(&key);
+                self.pipeline.publish(key, &json).ignore();
+            }
+            MarketEvent::L2(_) => {
+                self.pipeline.publish(self.key("l2", event), &json).ignore();
+            }
+            MarketEvent::Trade(trade) => {
+                let key = self.key("trades", event);
+                if self.max_trades > 0 {
+                    self.pipeline.zadd(&key, &json, millis(trade.timestamp)).ignore();
+                    self.pipeline.zremrangebyrank(&key, 0, -(self.max_trades as isize) - 1).ignore();
+                    self.expire(&key);
+                }
+                self.pipeline.publish(key, &json).ignore();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Sink for RedisSink {
+    async fn write(&mut self, event: &MarketEvent) -> Result<(), SoqaError> {
+        self.queue(event)?;
+        self.pending += 1;
+        let oldest = *self.oldest.get_or_insert_with(Instant::now);
+        if self.pending >= self.max_batch || oldest.elapsed() >= self.linger {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), SoqaError> {
+        self.oldest = None;
+        if self.pending == 0 {
+            return Ok(());
+        }
+        let started = Instant::now();
+        let result = self.pipeline.query_async::<()>(&mut self.connection).await;
+        self.pipeline.clear();
+        let records = std::mem::take(&mut self.pending);
+        result.map_err(redis_error)?;
+        crate::metrics::sink_write("redis", records, started.elapsed().as_secs_f64());
+        Ok(())
+    }
+}
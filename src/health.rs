@@ -0,0 +1,365 @@
+use crate::delivery::OverflowPolicy;
+use crate::exchanges::book::ChecksumMismatch;
+use crate::metrics;
+use crate::models::OrderBookL1;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+// Tokio's clock, so tests can pause and advance it; outside a runtime it is the system clock.
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedStatus {
+    Healthy,
+    Degraded,
+    Stale,
+    Disconnected,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Violation {
+    CrossedBook { bid: f64, ask: f64 },
+    ZeroPrice { bid: f64, ask: f64 },
+    OutOfOrder { previous: SystemTime, current: SystemTime },
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HealthEvent {
+    Connected { exchange: String, symbol: String, reconnects: u64 },
+    Disconnected { exchange: String, symbol: String },
+    Stale { exchange: String, symbol: String, last_message_age_ms: u64 },
+    Recovered { exchange: String, symbol: String },
+    ParseFailure { exchange: String, symbol: String },
+    Violation { exchange: String, symbol: String, violation: Violation },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedHealth {
+    pub exchange: String,
+    pub symbol: String,
+    pub status: FeedStatus,
+    pub last_message_age_ms: Option<u64>,
+    pub messages_per_sec: f64,
+    pub messages: u64,
+    pub reconnects: u64,
+    pub parse_failures: u64,
+    pub crossed_books: u64,
+    pub zero_prices: u64,
+    pub out_of_order: u64,
+    // Events the delivery queue dropped or conflated because the consumer fell behind.
+    pub dropped: u64,
+    // Local books that failed the venue checksum and were resynced from a snapshot.
+    pub checksum_mismatches: u64,
+}
+
+#[derive(Debug, Default)]
+struct FeedStats {
+    connects: u64,
+    connected: bool,
+    last_message: Option<Instant>,
+    last_timestamp: Option<SystemTime>,
+    last_issue: Option<Instant>,
+    stale_reported: bool,
+    messages: u64,
+    window_start: Option<Instant>,
+    window_messages: u64,
+    messages_per_sec: f64,
+    parse_failures: u64,
+    crossed_books: u64,
+    zero_prices: u64,
+    out_of_order: u64,
+    dropped: u64,
+    checksum_mismatches: u64,
+}
+
+impl FeedStats {
+    fn status(&self, now: Instant, stale_after: Duration) -> FeedStatus {
+        let recent = |at: Option<Instant>| at.map(|at| now.duration_since(at) < stale_after).unwrap_or(false);
+        if !self.connected {
+            FeedStatus::Disconnected
+        } else if self.last_message.is_some() && !recent(self.last_message) {
+            FeedStatus::Stale
+        } else if recent(self.last_issue) {
+            FeedStatus::Degraded
+        } else {
+            FeedStatus::Healthy
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthMonitor {
+    feeds: Arc<Mutex<HashMap<(String, String), FeedStats>>>,
+    events: broadcast::Sender<HealthEvent>,
+    stale_after: Duration,
+}
+
+impl Default for HealthMonitor {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(30))
+    }
+}
+
+impl HealthMonitor {
+    pub fn new(stale_after: Duration) -> Self {
+        let (events, _) = broadcast::channel(1024);
+        HealthMonitor {
+            feeds: Arc::new(Mutex::new(HashMap::new())),
+            events,
+            stale_after,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<HealthEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn tracker(&self, exchange: &str, symbol: &str) -> FeedTracker {
+        FeedTracker::new(exchange, symbol, Some(self.clone()))
+    }
+
+    pub fn snapshot(&self) -> Vec<FeedHealth> {
+        let now = Instant::now();
+        let feeds = self.feeds.lock().unwrap();
+        let mut snapshot: Vec<FeedHealth> = feeds
+            .iter()
+            .map(|((exchange, symbol), stats)| FeedHealth {
+                exchange: exchange.clone(),
+                symbol: symbol.clone(),
+                status: stats.status(now, self.stale_after),
+                last_message_age_ms: stats.last_message.map(|at| now.duration_since(at).as_millis() as u64),
+                messages_per_sec: stats.messages_per_sec,
+                messages: stats.messages,
+                reconnects: stats.connects.saturating_sub(1),
+                parse_failures: stats.parse_failures,
+                crossed_books: stats.crossed_books,
+                zero_prices: stats.zero_prices,
+                out_of_order: stats.out_of_order,
+                dropped: stats.dropped,
+                checksum_mismatches: stats.checksum_mismatches,
+            })
+            .collect();
+        snapshot.sort_by(|a, b| (&a.exchange, &a.symbol).cmp(&(&b.exchange, &b.symbol)));
+        snapshot
+    }
+
+    pub fn feed(&self, exchange: &str, symbol: &str) -> Option<FeedHealth> {
+        self.snapshot()
+            .into_iter()
+            .find(|feed| feed.exchange == exchange && feed.symbol == symbol)
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.snapshot().iter().all(|feed| feed.status == FeedStatus::Healthy)
+    }
+
+    // Emits `Stale` for feeds that have gone quiet; `Recovered` is sent on their next message.
+    pub fn spawn_watchdog(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                monitor.check_stale();
+            }
+        })
+    }
+
+    pub fn check_stale(&self) {
+        let now = Instant::now();
+        let mut feeds = self.feeds.lock().unwrap();
+        for ((exchange, symbol), stats) in feeds.iter_mut() {
+            if stats.stale_reported || stats.status(now, self.stale_after) != FeedStatus::Stale {
+                continue;
+            }
+            stats.stale_reported = true;
+            let age = stats.last_message.map(|at| now.duration_since(at).as_millis() as u64).unwrap_or(0);
+            let _ = self.events.send(HealthEvent::Stale {
+                exchange: exchange.clone(),
+                symbol: symbol.clone(),
+                last_message_age_ms: age,
+            });
+        }
+    }
+
+    fn update<R>(&self, exchange: &str, symbol: &str, f: impl FnOnce(&mut FeedStats) -> R) -> R {
+        let mut feeds = self.feeds.lock().unwrap();
+        let stats = feeds.entry((exchange.to_string(), symbol.to_string())).or_default();
+        f(stats)
+    }
+
+    fn emit(&self, event: HealthEvent) {
+        let _ = self.events.send(event);
+    }
+}
+
+// Per-feed handle the exchange clients report into. Calls are forwarded to the
+// metrics recorders and, when one is attached, to the health monitor.
+#[derive(Debug, Clone, Default)]
+pub struct FeedTracker {
+    monitor: Option<HealthMonitor>,
+    exchange: String,
+    symbol: String,
+}
+
+impl FeedTracker {
+    pub fn new(exchange: &str, symbol: &str, monitor: Option<HealthMonitor>) -> Self {
+        FeedTracker {
+            monitor,
+            exchange: exchange.to_string(),
+            symbol: symbol.to_string(),
+        }
+    }
+
+    pub fn connected(&self) {
+        metrics::connected(&self.exchange, &self.symbol);
+        let Some(monitor) = &self.monitor else { return };
+        let reconnects = monitor.update(&self.exchange, &self.symbol, |stats| {
+            stats.connects += 1;
+            stats.connected = true;
+            stats.connects - 1
+        });
+        monitor.emit(HealthEvent::Connected {
+            exchange: self.exchange.clone(),
+            symbol: self.symbol.clone(),
+            reconnects,
+        });
+    }
+
+    pub fn disconnected(&self) {
+        let Some(monitor) = &self.monitor else { return };
+        monitor.update(&self.exchange, &self.symbol, |stats| stats.connected = false);
+        monitor.emit(HealthEvent::Disconnected {
+            exchange: self.exchange.clone(),
+            symbol: self.symbol.clone(),
+        });
+    }
+
+    // A tracker for one symbol of a feed that carries several on one connection. Reconnects,
+    // bytes and parse failures stay on the connection's tracker; this one takes the symbol's
+    // events, so its quote checks, latency and staleness are the symbol's own.
+    pub fn symbol(&self, symbol: &str) -> FeedTracker {
+        FeedTracker::new(&self.exchange, symbol, self.monitor.clone())
+    }
+
+    pub fn message(&self, bytes: usize) {
+        metrics::message_received(&self.exchange, &self.symbol, bytes);
+        self.received();
+    }
+
+    // An event for a symbol tracker. Events only arrive over a live connection, so this also
+    // marks the symbol connected without counting a reconnect.
+    pub fn event(&self) {
+        let Some(monitor) = &self.monitor else { return };
+        monitor.update(&self.exchange, &self.symbol, |stats| stats.connected = true);
+        self.received();
+    }
+
+    // The connection under a symbol tracker went away; its tracker reports the disconnect.
+    pub fn detached(&self) {
+        let Some(monitor) = &self.monitor else { return };
+        monitor.update(&self.exchange, &self.symbol, |stats| stats.connected = false);
+    }
+
+    fn received(&self) {
+        let Some(monitor) = &self.monitor else { return };
+        let now = Instant::now();
+        let recovered = monitor.update(&self.exchange, &self.symbol, |stats| {
+            stats.messages += 1;
+            stats.last_message = Some(now);
+            let window_start = *stats.window_start.get_or_insert(now);
+            stats.window_messages += 1;
+            let elapsed = now.duration_since(window_start);
+            if elapsed >= Duration::from_secs(1) {
+                stats.messages_per_sec = stats.window_messages as f64 / elapsed.as_secs_f64();
+                stats.window_start = Some(now);
+                stats.window_messages = 0;
+            }
+            std::mem::take(&mut stats.stale_reported)
+        });
+        if recovered {
+            monitor.emit(HealthEvent::Recovered {
+                exchange: self.exchange.clone(),
+                symbol: self.symbol.clone(),
+            });
+        }
+    }
+
+    pub fn parse_failure(&self) {
+        metrics::parse_error(&self.exchange, &self.symbol);
+        let Some(monitor) = &self.monitor else { return };
+        monitor.update(&self.exchange, &self.symbol, |stats| {
+            stats.parse_failures += 1;
+            stats.last_issue = Some(Instant::now());
+        });
+        monitor.emit(HealthEvent::ParseFailure {
+            exchange: self.exchange.clone(),
+            symbol: self.symbol.clone(),
+        });
+    }
+
+    pub fn dropped(&self, policy: OverflowPolicy) {
+        metrics::dropped_event(&self.exchange, &self.symbol, policy.as_str());
+        let Some(monitor) = &self.monitor else { return };
+        monitor.update(&self.exchange, &self.symbol, |stats| stats.dropped += 1);
+    }
+
+    // A checksum mismatch always ends in a resync, so both are counted here.
+    pub fn checksum_mismatch(&self, mismatch: ChecksumMismatch) {
+        metrics::checksum_mismatch(&self.exchange, &self.symbol);
+        metrics::book_resync(&self.exchange, &self.symbol);
+        let Some(monitor) = &self.monitor else { return };
+        monitor.update(&self.exchange, &self.symbol, |stats| {
+            stats.checksum_mismatches += 1;
+            stats.last_issue = Some(Instant::now());
+        });
+        monitor.emit(HealthEvent::Violation {
+            exchange: self.exchange.clone(),
+            symbol: self.symbol.clone(),
+            violation: Violation::ChecksumMismatch { expected: mismatch.expected, actual: mismatch.actual },
+        });
+    }
+
+    // Only for venues whose `timestamp` is the exchange event time rather than the receive time.
+    pub fn event_latency(&self, event_time: SystemTime) {
+        if let Ok(latency) = SystemTime::now().duration_since(event_time) {
+            metrics::latency(&self.exchange, &self.symbol, latency.as_secs_f64());
+        }
+    }
+
+    pub fn quote(&self, order_book: &OrderBookL1) {
+        let Some(monitor) = &self.monitor else { return };
+        let violations = monitor.update(&self.exchange, &self.symbol, |stats| {
+            let mut violations = Vec::new();
+            if order_book.bid <= 0.0 || order_book.ask <= 0.0 {
+                stats.zero_prices += 1;
+                violations.push(Violation::ZeroPrice { bid: order_book.bid, ask: order_book.ask });
+            } else if order_book.bid > order_book.ask {
+                stats.crossed_books += 1;
+                violations.push(Violation::CrossedBook { bid: order_book.bid, ask: order_book.ask });
+            }
+            let latest = *stats.last_timestamp.get_or_insert(order_book.timestamp);
+            if order_book.timestamp < latest {
+                stats.out_of_order += 1;
+                violations.push(Violation::OutOfOrder { previous: latest, current: order_book.timestamp });
+            }
+            stats.last_timestamp = Some(latest.max(order_book.timestamp));
+            if !violations.is_empty() {
+                stats.last_issue = Some(Instant::now());
+            }
+            violations
+        });
+        for violation in violations {
+            monitor.emit(HealthEvent::Violation {
+                exchange: self.exchange.clone(),
+                symbol: self.symbol.clone(),
+                violation,
+            });
+        }
+    }
+}
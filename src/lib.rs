@@ -1,12 +1,35 @@
+
 pub mod models;
 pub mod error;
 pub mod config;
+pub mod delivery;
 pub mod cli;
 pub mod exchanges;
 pub mod visualization;
 pub mod export;
+pub mod health;
+pub mod metrics;
+pub mod logging;
+pub mod analytics;
+pub mod arbitrage;
+pub mod triangular;
+pub mod download;
+pub mod ratelimit;
+pub mod codec;
+pub mod subscriptions;
+pub mod shutdown;
+pub mod output;
+pub mod merge;
+pub mod conflate;
+pub mod query;
+#[cfg(feature = "shm")]
+pub mod shm;
 
 pub mod api {
     pub mod websocket;
     pub mod rest;
+    pub mod auth;
+    pub mod gateway;
+    #[cfg(feature = "grpc")]
+    pub mod grpc;
 }
\ No newline at end of file
@@ -1,54 +1,516 @@
 use clap::Parser;
 use soqa_sdk::cli::Cli;
 use soqa_sdk::config::Config;
-use soqa_sdk::exchanges::binance::BinanceClient;
-use soqa_sdk::exchanges::bybit::BybitClient;
-use soqa_sdk::exchanges::kraken::KrakenClient;
-use soqa_sdk::exchanges::okx::OkxClient;
-use soqa_sdk::exchanges::kucoin::KuCoinClient;
-use soqa_sdk::api::websocket::websocket_route;
-use soqa_sdk::api::rest::rest_routes;
-use warp::Filter;
+use soqa_sdk::conflate::{ConflateExt, Conflation};
+use soqa_sdk::delivery::{Delivery, EventQueue};
+use soqa_sdk::exchanges;
+use soqa_sdk::exchanges::adapter::{AdapterSpec, GenericClient};
+use soqa_sdk::analytics::{AnalyticsConfig, AnalyticsExt, SignalKind};
+use soqa_sdk::arbitrage::{ArbitrageExt, FeeModel};
+use soqa_sdk::triangular::{TriangularExt, TriangularScanner};
+use soqa_sdk::download::{download, download_book, BookDownloadRequest, DownloadRequest};
+use soqa_sdk::exchanges::history::HistoryClient;
+use soqa_sdk::exchanges::instruments::SymbolPattern;
+use soqa_sdk::merge::{LateEvents, MergeExt, Watermark};
+use soqa_sdk::models::MarketEvent;
+use soqa_sdk::error::SoqaError;
+use soqa_sdk::api::gateway::{self, GatewayConfig, TlsConfig};
+use soqa_sdk::health::{HealthEvent, HealthMonitor};
+use soqa_sdk::output::{OutputFormat, OutputWriter, Row};
+use soqa_sdk::export::record::{self, Filter};
+use soqa_sdk::query::{self, Report};
+use soqa_sdk::shutdown::{self, Shutdown};
+use soqa_sdk::subscriptions::{Channel, SubscriptionManager};
+use futures_util::{Stream, StreamExt};
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let cli = Cli::parse();
+    soqa_sdk::logging::init(&cli.log_level, cli.log_format);
+    let monitor = HealthMonitor::default();
+    monitor.spawn_watchdog(std::time::Duration::from_secs(1));
+    let mut health_events = monitor.subscribe();
+    tokio::spawn(async move {
+        while let Ok(event) = health_events.recv().await {
+            match &event {
+                HealthEvent::ParseFailure { .. } => tracing::debug!(target: "soqa::health", ?event),
+                HealthEvent::Connected { .. } | HealthEvent::Recovered { .. } => {
+                    tracing::info!(target: "soqa::health", ?event)
+                }
+                _ => tracing::warn!(target: "soqa::health", ?event),
+            }
+        }
+    });
 
     match cli.command {
-        soqa_sdk::cli::Commands::Start { exchange, symbol, level } => {
-            let config = Config::new(&exchange, &symbol);
-            match exchange.as_str() {
-                "binance" => {
-                    let client = BinanceClient::new(config);
-                    client.subscribe_l1(|order_book| println!("{:?}", order_book)).await.unwrap();
+        soqa_sdk::cli::Commands::Start { exchange, symbol, max_symbols, lateness_ms, late, queue_capacity, overflow, level, bbo, adapter, output_format, conflate_ms, conflate_bps, analytics, window_secs, levels, shm, sink, sink_encoding, sink_prefix, sink_ttl_secs, sink_max_trades, sink_snapshot_secs } => {
+            let sink_kind = sink.as_deref().and_then(soqa_sdk::export::sink_kind);
+            let sink_options: [(&str, bool, &[&str]); 5] = [
+                ("--sink-encoding", sink_encoding.is_some(), &["nats", "kafka"]),
+                ("--sink-prefix", sink_prefix.is_some(), &["nats", "kafka", "redis"]),
+                ("--sink-ttl-secs", sink_ttl_secs.is_some(), &["redis"]),
+                ("--sink-max-trades", sink_max_trades.is_some(), &["redis"]),
+                ("--sink-snapshot-secs", sink_snapshot_secs.is_some(), &["sqlite", "parquet"]),
+            ];
+            for (option, given, applies_to) in sink_options {
+                if given && !sink_kind.is_some_and(|kind| applies_to.contains(&kind)) {
+                    tracing::error!("{} only applies to {} sinks", option, applies_to.join(", "));
+                    return ExitCode::FAILURE;
+                }
+            }
+            if levels.is_some() && level != Channel::L2 {
+                tracing::error!("--levels only applies to --level L2; L1 imbalance uses the top of the book");
+                return ExitCode::FAILURE;
+            }
+            let patterns = match SymbolPattern::parse_all(&symbol) {
+                Ok(patterns) => patterns,
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            if adapter.is_some() && (exchange.len() > 1 || patterns.iter().any(|p| !p.is_exact())) {
+                tracing::error!("--adapter takes a single exchange and literal symbols");
+                return ExitCode::FAILURE;
+            }
+            // Adapter specs map symbols themselves, so theirs are used as written.
+            let subscriptions = match &adapter {
+                Some(_) => Ok(symbol.iter().map(|symbol| (exchange[0].clone(), symbol.clone())).collect()),
+                None => exchanges::instruments::expand(&exchange, &patterns).await,
+            };
+            let subscriptions = match subscriptions {
+                Ok(subscriptions) if subscriptions.is_empty() => {
+                    tracing::error!("no instruments match {}", symbol.join(","));
+                    return ExitCode::FAILURE;
+                }
+                Ok(subscriptions) if subscriptions.len() > max_symbols => {
+                    tracing::error!(
+                        "{} matches {} markets, more than --max-symbols {}; narrow it or raise the limit",
+                        symbol.join(","),
+                        subscriptions.len(),
+                        max_symbols
+                    );
+                    return ExitCode::FAILURE;
+                }
+                Ok(subscriptions) => subscriptions,
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            // The built-in clients carry many symbols per connection; adapters take one each.
+            let batch_size = if adapter.is_some() { 1 } else { exchanges::SYMBOLS_PER_CONNECTION };
+            let batches = exchanges::batches(&subscriptions, batch_size);
+            // Signals need their inputs as well as the chosen level: quotes for the quote-based
+            // ones (and for imbalance when only trades were asked for), trades for the trade-based.
+            let mut channels = vec![level];
+            let needs_quotes = analytics.iter().any(SignalKind::needs_quotes)
+                || (level == Channel::Trades && analytics.iter().any(SignalKind::needs_book));
+            if level != Channel::L1 && needs_quotes {
+                channels.push(Channel::L1);
+            }
+            if level != Channel::Trades && analytics.iter().any(SignalKind::needs_trades) {
+                channels.push(Channel::Trades);
+            }
+            let feeds = batches.len() * channels.len();
+            tracing::info!(markets = subscriptions.len(), feeds, "subscribing");
+
+            let manager = SubscriptionManager::default();
+            let shutdown = Shutdown::new();
+            // Every feed pushes into this one queue, so a slow consumer holds the feeds back (or
+            // loses events, per --overflow) instead of buffering without bound.
+            let queue = EventQueue::new(Delivery::new(queue_capacity, overflow));
+            let gateway = manager.clone();
+            #[cfg(feature = "shm")]
+            let ring = match shm {
+                Some(path) => match soqa_sdk::shm::ShmPublisher::create(path, 65536) {
+                    Ok(ring) => Some(std::sync::Mutex::new(ring)),
+                    Err(e) => {
+                        tracing::error!("{}", e);
+                        return ExitCode::FAILURE;
+                    }
+                },
+                None => None,
+            };
+            #[cfg(not(feature = "shm"))]
+            if shm.is_some() {
+                tracing::error!("--shm needs a build with the `shm` feature");
+                return ExitCode::FAILURE;
+            }
+            let sink = match sink {
+                Some(url) => {
+                    let ttl = sink_ttl_secs.map(std::time::Duration::from_secs);
+                    let snapshot_every = std::time::Duration::from_secs(sink_snapshot_secs.unwrap_or(60));
+                    let encoding = sink_encoding.unwrap_or_default();
+                    let prefix = sink_prefix.unwrap_or_else(|| "soqa".to_string());
+                    let max_trades = sink_max_trades.unwrap_or(0);
+                    let events = manager.subscribe();
+                    match soqa_sdk::export::spawn_sink(&url, encoding, &prefix, ttl, max_trades, snapshot_every, events).await {
+                        Ok(sink) => Some(sink),
+                        Err(e) => {
+                            tracing::error!("{}", e);
+                            return ExitCode::FAILURE;
+                        }
+                    }
                 }
-                "bybit" => {
-                    let client = BybitClient::new(config);
-                    client.subscribe_l1(|order_book| println!("{:?}", order_book)).await.unwrap();
+                None => None,
+            };
+            let mut subscribed_feeds = 0;
+            for (exchange, symbols) in &batches {
+                for &channel in &channels {
+                    let config = Config::new(exchange, &symbols[0])
+                        .with_symbols(symbols.clone())
+                        .with_health(monitor.clone())
+                        .with_shutdown(shutdown.clone())
+                        .with_queue(queue.clone())
+                        .with_bbo(bbo);
+                    let subscribed = match &adapter {
+                        Some(_) if channel != Channel::L1 => {
+                            Err(SoqaError::AdapterError(format!("adapters only provide L1, not {}", channel.as_str())))
+                        }
+                        Some(path) => match AdapterSpec::from_file(path) {
+                            Ok(spec) => GenericClient::new(spec, config).subscribe_l1(|_| {}).await,
+                            Err(e) => Err(e),
+                        },
+                        None => exchanges::subscribe(config, channel, |_| {}).await,
+                    };
+                    match subscribed {
+                        Ok(()) => subscribed_feeds += 1,
+                        Err(e) => tracing::error!("{} {}: {}", exchange, symbols.join(","), e),
+                    }
                 }
-                "kraken" => {
-                    let client = KrakenClient::new(config);
-                    client.subscribe_l1(|order_book| println!("{:?}", order_book)).await.unwrap();
+            }
+            if subscribed_feeds == 0 {
+                queue.close();
+            }
+            // Only the event stream below holds a sender now, so the sink ends once it is dropped.
+            drop(manager);
+
+            // A single feed is already in order, so there is nothing to wait for.
+            let lateness = match feeds {
+                1 => std::time::Duration::ZERO,
+                _ => std::time::Duration::from_millis(lateness_ms),
+            };
+            let watermark = Watermark::new(lateness).with_policy(late);
+            let late_events = watermark.late_events();
+            let events = queue
+                .into_stream()
+                .inspect(move |event| {
+                    #[cfg(feature = "shm")]
+                    if let Some(ring) = &ring {
+                        if let Err(e) = ring.lock().unwrap().publish(event) {
+                            tracing::warn!("{}", e);
+                        }
+                    }
+                    gateway.publish(event.clone());
+                })
+                .time_ordered(watermark);
+            let events = match Conflation::from_options(conflate_ms, conflate_bps) {
+                Some(conflation) => events.conflate(conflation).boxed(),
+                None => events.boxed(),
+            };
+            let output = async {
+                if analytics.is_empty() {
+                    print_rows(events, output_format).await
+                } else {
+                    let config = AnalyticsConfig {
+                        window: std::time::Duration::from_secs(window_secs),
+                        levels: levels.unwrap_or(5),
+                        signals: analytics,
+                    };
+                    print_rows(events.analytics(config), output_format).await
                 }
-                "okx" => {
-                    let client = OkxClient::new(config);
-                    client.subscribe_l1(|order_book| println!("{:?}", order_book)).await.unwrap();
+            };
+            let printed = tokio::select! {
+                printed = output => printed,
+                _ = shutdown::signal() => {
+                    tracing::info!("shutting down");
+                    Ok(())
                 }
-                "kucoin" => {
-                    let client = KuCoinClient::new(config);
-                    client.subscribe_l1(|order_book| println!("{:?}", order_book)).await.unwrap();
+            };
+            // Exiting successfully with nothing subscribed would hide the failure from scripts.
+            let mut status = if subscribed_feeds == 0 { ExitCode::FAILURE } else { ExitCode::SUCCESS };
+            if let Err(e) = ignore_broken_pipe(printed) {
+                tracing::error!("{}", e);
+                status = ExitCode::FAILURE;
+            }
+            shutdown.trigger();
+            report_late(&late_events);
+            if let Some(sink) = sink {
+                if tokio::time::timeout(std::time::Duration::from_secs(5), sink).await.is_err() {
+                    tracing::warn!("sink did not flush within 5s");
                 }
-                _ => println!("Unsupported exchange: {}", exchange),
             }
-            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            status
+        }
+        soqa_sdk::cli::Commands::Arb { exchange, symbol, venue_symbols, threshold_bps, fees, withdrawal_costs, bbo, lateness_ms, max_quote_age_ms, output_format } => {
+            let fees = FeeModel {
+                taker_fees_bps: fees.into_iter().collect(),
+                withdrawal_costs: withdrawal_costs.into_iter().collect(),
+                threshold_bps,
+                max_quote_age: Some(std::time::Duration::from_millis(max_quote_age_ms)),
+            };
+            let venue_symbols: std::collections::HashMap<_, _> = venue_symbols.into_iter().collect();
+            let shutdown = Shutdown::new();
+            let queue = EventQueue::new(Delivery::default());
+            let mut subscribed_feeds = 0;
+            for exchange in &exchange {
+                let symbol = venue_symbols.get(exchange).unwrap_or(&symbol);
+                let config = Config::new(exchange, symbol)
+                    .with_health(monitor.clone())
+                    .with_shutdown(shutdown.clone())
+                    .with_bbo(bbo)
+                    .with_queue(queue.clone());
+                match exchanges::subscribe_l1(config, |_| {}).await {
+                    Ok(()) => subscribed_feeds += 1,
+                    Err(e) => tracing::error!("{}: {}", exchange, e),
+                }
+            }
+            if subscribed_feeds == 0 {
+                queue.close();
+            }
+
+            let watermark = Watermark::new(std::time::Duration::from_millis(lateness_ms));
+            let late_events = watermark.late_events();
+            let opportunities = queue.into_stream().time_ordered(watermark).arbitrage(fees);
+            let printed = tokio::select! {
+                printed = print_rows(opportunities, output_format) => printed,
+                _ = shutdown::signal() => Ok(()),
+            };
+            shutdown.trigger();
+            report_late(&late_events);
+            exit_status(subscribed_feeds, printed)
+        }
+        soqa_sdk::cli::Commands::Tri { exchange, assets, threshold_bps, fee_bps, output_format } => {
+            let instruments = match exchanges::instruments::fetch_instruments(&exchange).await {
+                Ok(instruments) => instruments,
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let assets: Vec<String> = assets.iter().map(|a| a.to_uppercase()).collect();
+            let instruments: Vec<_> = instruments
+                .into_iter()
+                .filter(|i| assets.is_empty() || (assets.contains(&i.base) && assets.contains(&i.quote)))
+                .collect();
+            let fees = FeeModel {
+                taker_fees_bps: fee_bps.map(|fee| (exchange.clone(), fee)).into_iter().collect(),
+                threshold_bps,
+                ..FeeModel::default()
+            };
+            let scanner = TriangularScanner::new(&exchange, &instruments, fees);
+            let symbols = scanner.symbols();
+            tracing::info!(cycles = scanner.cycle_count(), pairs = symbols.len(), "scanning {}", exchange);
+
+            // Legs are sized against book depth, so every pair is subscribed at L2, many to a connection.
+            let shutdown = Shutdown::new();
+            let queue = EventQueue::new(Delivery::default());
+            let mut subscribed_feeds = 0;
+            for symbols in symbols.chunks(exchanges::SYMBOLS_PER_CONNECTION) {
+                let config = Config::new(&exchange, &symbols[0])
+                    .with_symbols(symbols.to_vec())
+                    .with_health(monitor.clone())
+                    .with_shutdown(shutdown.clone())
+                    .with_queue(queue.clone());
+                match exchanges::subscribe(config, Channel::L2, |_| {}).await {
+                    Ok(()) => subscribed_feeds += 1,
+                    Err(e) => tracing::error!("{}: {}", symbols.join(","), e),
+                }
+            }
+            if subscribed_feeds == 0 {
+                queue.close();
+            }
+
+            let opportunities = queue.into_stream().triangular(scanner);
+            let printed = tokio::select! {
+                printed = print_rows(opportunities, output_format) => printed,
+                _ = shutdown::signal() => Ok(()),
+            };
+            shutdown.trigger();
+            exit_status(subscribed_feeds, printed)
+        }
+        soqa_sdk::cli::Commands::Download { exchange, symbol, start, end, output, api_key, book, depth, snapshots, interval_ms } => {
+            let client = HistoryClient::new(&exchange).map(|client| match &api_key {
+                Some(key) => client.with_api_key(key),
+                None => client,
+            });
+            let mut client = match client {
+                Ok(client) => client,
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            if book {
+                let request = BookDownloadRequest {
+                    exchange,
+                    symbol,
+                    depth,
+                    snapshots,
+                    interval: std::time::Duration::from_millis(interval_ms),
+                    output,
+                };
+                return match download_book(&mut client, &request).await {
+                    Ok(taken) => {
+                        tracing::info!(snapshots = taken, "download complete: {}", request.output);
+                        ExitCode::SUCCESS
+                    }
+                    Err(e) => failure(e),
+                };
+            }
+            let end_ms = end.unwrap_or_else(|| {
+                std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64
+            });
+            let request = DownloadRequest {
+                exchange,
+                symbol,
+                start_ms: start.unwrap_or(end_ms.saturating_sub(3_600_000)),
+                end_ms,
+                output,
+            };
+            match download(&mut client, &request).await {
+                Ok(state) => {
+                    tracing::info!(pages = state.pages, trades = state.trades, "download complete: {}", request.output);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failure(e),
+            }
+        }
+        soqa_sdk::cli::Commands::Serve { config, bind, grpc_bind, tls_cert, tls_key, cors_origins, api_keys } => {
+            let mut gateway = match config {
+                Some(path) => match GatewayConfig::from_file(&path) {
+                    Ok(gateway) => gateway,
+                    Err(e) => {
+                        tracing::error!("{}", e);
+                        return ExitCode::FAILURE;
+                    }
+                },
+                None => GatewayConfig::default(),
+            };
+            if let Some(bind) = bind {
+                gateway.bind = bind;
+            }
+            if grpc_bind.is_some() {
+                gateway.grpc_bind = grpc_bind;
+            }
+            if let (Some(cert), Some(key)) = (tls_cert, tls_key) {
+                gateway.tls = Some(TlsConfig { cert, key });
+            }
+            gateway.cors_origins.extend(cors_origins);
+            gateway.api_keys.extend(api_keys);
+
+            let shutdown = Shutdown::new();
+            let stop = shutdown.clone();
+            tokio::spawn(async move {
+                shutdown::signal().await;
+                tracing::info!("shutting down");
+                stop.trigger();
+            });
+            match gateway::serve(gateway, monitor, shutdown).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => failure(e),
+            }
+        }
+        soqa_sdk::cli::Commands::Book { file, exchange, symbol, at, output_format } => {
+            let at = at.map_or_else(std::time::SystemTime::now, |ms| std::time::UNIX_EPOCH + std::time::Duration::from_millis(ms));
+            match soqa_sdk::export::book::book_at(&file, &exchange, &symbol, at) {
+                Ok(Some(book)) => {
+                    let mut out = OutputWriter::new(std::io::stdout(), output_format);
+                    match ignore_broken_pipe(out.write(&MarketEvent::L2(book)).and_then(|_| out.flush())) {
+                        Ok(()) => ExitCode::SUCCESS,
+                        Err(e) => failure(e),
+                    }
+                }
+                Ok(None) => {
+                    tracing::error!("no book recorded for {} {} by then", exchange, symbol);
+                    ExitCode::FAILURE
+                }
+                Err(e) => failure(e),
+            }
+        }
+        soqa_sdk::cli::Commands::Query { report, file, exchange, symbol, start, end, interval_secs, output_format } => {
+            let at = |ms: u64| std::time::UNIX_EPOCH + std::time::Duration::from_millis(ms);
+            let filter = Filter { exchange, symbol, start: start.map(at), end: end.map(at) };
+            let printed = match report {
+                Report::Quotes => record::read_quotes(&file, &filter)
+                    .map(|quotes| print_all(quotes.into_iter().map(MarketEvent::L1), output_format)),
+                Report::Trades => record::read_trades(&file, &filter)
+                    .map(|trades| print_all(trades.into_iter().map(MarketEvent::Trade), output_format)),
+                Report::Ohlcv => record::read_trades(&file, &filter).map(|trades| {
+                    print_all(query::ohlcv(&trades, std::time::Duration::from_secs(interval_secs)), output_format)
+                }),
+                Report::Spread => record::read_quotes(&file, &filter).map(|quotes| print_all(query::spread_stats(&quotes), output_format)),
+                Report::Counts => record::read_quotes(&file, &filter).map(|quotes| print_all(query::quote_counts(&quotes), output_format)),
+            };
+            match printed {
+                Ok(printed) => match ignore_broken_pipe(printed) {
+                    Ok(()) => ExitCode::SUCCESS,
+                    Err(e) => failure(e),
+                },
+                Err(e) => failure(e),
+            }
         }
         soqa_sdk::cli::Commands::Export { exchange, symbol, output } => {
-            println!("Exporting data for {} {} to {}", exchange, symbol, output);
+            tracing::info!("Exporting data for {} {} to {}", exchange, symbol, output);
+            ExitCode::SUCCESS
         }
     }
+}
+
+fn failure(e: impl std::fmt::Display) -> ExitCode {
+    tracing::error!("{}", e);
+    ExitCode::FAILURE
+}
+
+// A reader that goes away is how piped output normally ends, not an error.
+fn ignore_broken_pipe(result: std::io::Result<()>) -> std::io::Result<()> {
+    match result {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+// Fails when no feed could be subscribed or the output could not be written.
+fn exit_status(subscribed_feeds: usize, printed: std::io::Result<()>) -> ExitCode {
+    if let Err(e) = ignore_broken_pipe(printed) {
+        return failure(e);
+    }
+    if subscribed_feeds == 0 {
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn report_late(late_events: &LateEvents) {
+    if late_events.total() > 0 {
+        tracing::info!(
+            late = late_events.total(),
+            dropped = late_events.dropped(),
+            max_delay_ms = late_events.max_delay().as_millis() as u64,
+            by_exchange = ?late_events.by_exchange(),
+            "events arrived behind the merge watermark"
+        );
+    }
+}
 
-    let ws_route = websocket_route();
-    let rest_route = rest_routes();
-    let routes = ws_route.or(rest_route);
-    warp::serve(routes).run(([127, 0, 0, 1], 8081)).await;
-}
\ No newline at end of file
+fn print_all<R: Row>(rows: impl IntoIterator<Item = R>, format: OutputFormat) -> std::io::Result<()> {
+    let mut out = OutputWriter::new(std::io::stdout(), format);
+    for row in rows {
+        out.write(&row)?;
+    }
+    out.flush()
+}
+
+// Prints rows as they arrive until the stream ends or stdout goes away, e.g. when the reader
+// of a pipe exits.
+async fn print_rows<R: Row>(rows: impl Stream<Item = R>, format: OutputFormat) -> std::io::Result<()> {
+    let mut rows = std::pin::pin!(rows);
+    let mut out = OutputWriter::new(std::io::stdout(), format);
+    while let Some(row) = rows.next().await {
+        out.write(&row)?;
+        out.flush()?;
+    }
+    Ok(())
+}
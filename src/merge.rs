@@ -0,0 +1,262 @@
+// Interleaves events from several feeds by exchange timestamp. The watermark trails the newest
+// timestamp seen by `lateness`; events are held until the watermark passes them, so a venue
+// that delivers slightly behind the others still comes out in order. An event that arrives
+// behind the watermark is late: it is counted and, depending on the policy, emitted at once or
+// dropped. Live streams use `MergeExt`/`merge`, recorded data `reorder_iter`/`merge_iter`.
+
+use crate::models::MarketEvent;
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LatePolicy {
+    // Pass late events through out of order.
+    #[default]
+    Emit,
+    Drop,
+}
+
+// Late events seen by one merge, shared with whoever wants to report them.
+#[derive(Debug, Clone, Default)]
+pub struct LateEvents(Arc<Mutex<LateCounts>>);
+
+#[derive(Debug, Clone, Default)]
+struct LateCounts {
+    by_exchange: BTreeMap<String, u64>,
+    dropped: u64,
+    max_delay: Duration,
+}
+
+impl LateEvents {
+    pub fn total(&self) -> u64 {
+        self.0.lock().unwrap().by_exchange.values().sum()
+    }
+
+    pub fn by_exchange(&self) -> BTreeMap<String, u64> {
+        self.0.lock().unwrap().by_exchange.clone()
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.0.lock().unwrap().dropped
+    }
+
+    // The largest delay behind the watermark seen among them.
+    pub fn max_delay(&self) -> Duration {
+        self.0.lock().unwrap().max_delay
+    }
+
+    fn record(&self, event: &MarketEvent, delay: Duration, dropped: bool) {
+        let mut counts = self.0.lock().unwrap();
+        *counts.by_exchange.entry(event.exchange().to_string()).or_default() += 1;
+        counts.dropped += dropped as u64;
+        counts.max_delay = counts.max_delay.max(delay);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Watermark {
+    pub lateness: Duration,
+    pub late: LatePolicy,
+    late_events: LateEvents,
+}
+
+impl Watermark {
+    pub fn new(lateness: Duration) -> Self {
+        Watermark { lateness, ..Watermark::default() }
+    }
+
+    pub fn with_policy(mut self, late: LatePolicy) -> Self {
+        self.late = late;
+        self
+    }
+
+    // Handle to the late-event counts of every merge started with this watermark.
+    pub fn late_events(&self) -> LateEvents {
+        self.late_events.clone()
+    }
+}
+
+struct Held {
+    timestamp: SystemTime,
+    // Arrival order, so events with equal timestamps keep it.
+    seq: u64,
+    event: MarketEvent,
+}
+
+impl PartialEq for Held {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Held {}
+
+impl PartialOrd for Held {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Held {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.timestamp, self.seq).cmp(&(other.timestamp, other.seq))
+    }
+}
+
+// The reordering buffer behind every operator in this module.
+pub struct Reorder {
+    watermark: Watermark,
+    held: BinaryHeap<Reverse<Held>>,
+    newest: SystemTime,
+    // Timestamp of the last event handed out; nothing older can be put in order any more.
+    emitted: SystemTime,
+    seq: u64,
+}
+
+impl Reorder {
+    pub fn new(watermark: Watermark) -> Self {
+        Reorder {
+            watermark,
+            held: BinaryHeap::new(),
+            newest: SystemTime::UNIX_EPOCH,
+            emitted: SystemTime::UNIX_EPOCH,
+            seq: 0,
+        }
+    }
+
+    pub fn watermark(&self) -> SystemTime {
+        (self.newest.checked_sub(self.watermark.lateness).unwrap_or(SystemTime::UNIX_EPOCH)).max(self.emitted)
+    }
+
+    pub fn push(&mut self, event: MarketEvent) {
+        let timestamp = event.timestamp();
+        let watermark = self.watermark();
+        if timestamp < watermark {
+            let delay = watermark.duration_since(timestamp).unwrap_or_default();
+            let dropped = self.watermark.late == LatePolicy::Drop;
+            debug!(target: "soqa::merge", exchange = event.exchange(), symbol = event.symbol(), ?delay, dropped, "late event");
+            crate::metrics::late_event(event.exchange(), event.symbol());
+            self.watermark.late_events.record(&event, delay, dropped);
+            if dropped {
+                return;
+            }
+        }
+        self.newest = self.newest.max(timestamp);
+        self.held.push(Reverse(Held { timestamp, seq: self.seq, event }));
+        self.seq += 1;
+    }
+
+    // The oldest held event, once the watermark has passed it.
+    pub fn pop_ready(&mut self) -> Option<MarketEvent> {
+        let Reverse(oldest) = self.held.peek()?;
+        if oldest.timestamp + self.watermark.lateness > self.newest {
+            return None;
+        }
+        self.pop()
+    }
+
+    // The oldest held event regardless of the watermark, e.g. at end of input.
+    pub fn pop(&mut self) -> Option<MarketEvent> {
+        let Reverse(held) = self.held.pop()?;
+        self.emitted = self.emitted.max(held.timestamp);
+        Some(held.event)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.held.is_empty()
+    }
+}
+
+struct State<S> {
+    events: Pin<Box<S>>,
+    reorder: Reorder,
+    lateness: Duration,
+    ended: bool,
+    // Set when the input went quiet; releases everything held at that point.
+    flush: bool,
+}
+
+pub trait MergeExt: Stream<Item = MarketEvent> + Sized {
+    // Also releases everything held once the input has been quiet for `lateness`, so a stalled
+    // venue does not hold back the others.
+    fn time_ordered(self, watermark: Watermark) -> impl Stream<Item = MarketEvent> {
+        let state = State {
+            events: Box::pin(self),
+            lateness: watermark.lateness,
+            reorder: Reorder::new(watermark),
+            ended: false,
+            flush: false,
+        };
+        stream::unfold(state, |mut state| async move {
+            loop {
+                let ready = if state.ended || state.flush { state.reorder.pop() } else { state.reorder.pop_ready() };
+                if ready.is_some() {
+                    return ready.map(|event| (event, state));
+                }
+                state.flush = false;
+                if state.ended {
+                    return None;
+                }
+                let next = if state.reorder.is_empty() {
+                    Ok(state.events.next().await)
+                } else {
+                    tokio::time::timeout(state.lateness, state.events.next()).await
+                };
+                match next {
+                    Ok(Some(event)) => state.reorder.push(event),
+                    Ok(None) => state.ended = true,
+                    Err(_) => state.flush = true,
+                }
+            }
+        })
+    }
+}
+
+impl<S: Stream<Item = MarketEvent>> MergeExt for S {}
+
+// One time-ordered stream out of several subscription streams.
+pub fn merge<S: Stream<Item = MarketEvent>>(streams: impl IntoIterator<Item = S>, watermark: Watermark) -> impl Stream<Item = MarketEvent> {
+    stream::select_all(streams.into_iter().map(Box::pin)).time_ordered(watermark)
+}
+
+// Reorders recorded events, e.g. a capture written live from several feeds, without the idle
+// flush: the input is read as fast as it can be.
+pub fn reorder_iter(events: impl IntoIterator<Item = MarketEvent>, watermark: Watermark) -> impl Iterator<Item = MarketEvent> {
+    let mut events = events.into_iter();
+    let mut reorder = Reorder::new(watermark);
+    let mut ended = false;
+    std::iter::from_fn(move || loop {
+        if let Some(event) = if ended { reorder.pop() } else { reorder.pop_ready() } {
+            return Some(event);
+        }
+        if ended {
+            return None;
+        }
+        match events.next() {
+            Some(event) => reorder.push(event),
+            None => ended = true,
+        }
+    })
+}
+
+// Interleaves recorded sources, such as one capture per venue, by always taking the source whose
+// next event is oldest; `watermark` then fixes up disorder within each source.
+pub fn merge_iter<I: IntoIterator<Item = MarketEvent>>(sources: impl IntoIterator<Item = I>, watermark: Watermark) -> impl Iterator<Item = MarketEvent> {
+    let mut sources: Vec<_> = sources.into_iter().map(|source| source.into_iter().peekable()).collect();
+    let interleaved = std::iter::from_fn(move || {
+        let (_, oldest) = sources
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, source)| source.peek().map(|event| (event.timestamp(), i)))
+            .min()?;
+        sources[oldest].next()
+    });
+    reorder_iter(interleaved, watermark)
+}
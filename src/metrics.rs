@@ -0,0 +1,177 @@
+// Process-wide Prometheus metrics. Without the `metrics` feature every recorder is a no-op,
+// so call sites stay unconditional.
+
+#[cfg(feature = "metrics")]
+mod registry {
+    use prometheus::{
+        Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+    };
+    use std::sync::LazyLock;
+
+    pub struct Metrics {
+        pub registry: Registry,
+        pub messages: IntCounterVec,
+        pub bytes: IntCounterVec,
+        pub parse_errors: IntCounterVec,
+        pub connects: IntCounterVec,
+        pub reconnects: IntCounterVec,
+        pub latency: HistogramVec,
+        pub book_resyncs: IntCounterVec,
+        pub checksum_mismatches: IntCounterVec,
+        pub late_events: IntCounterVec,
+        pub dropped_events: IntCounterVec,
+        pub api_clients: IntGauge,
+        pub sink_records: IntCounterVec,
+        pub sink_write_seconds: Histogram,
+    }
+
+    fn counter(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+        let counter = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        counter
+    }
+
+    pub static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+        let registry = Registry::new();
+        let feed = &["exchange", "symbol"];
+        let latency = HistogramVec::new(
+            HistogramOpts::new("soqa_event_latency_seconds", "Exchange event time to local receive time")
+                .buckets(vec![0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+            feed,
+        )
+        .unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        let api_clients = IntGauge::new("soqa_api_clients", "Connected WebSocket API clients").unwrap();
+        registry.register(Box::new(api_clients.clone())).unwrap();
+        let sink_write_seconds = Histogram::with_opts(HistogramOpts::new(
+            "soqa_sink_write_seconds",
+            "Time spent writing a batch to a sink",
+        ))
+        .unwrap();
+        registry.register(Box::new(sink_write_seconds.clone())).unwrap();
+
+        Metrics {
+            messages: counter(&registry, "soqa_messages_total", "Messages received from the exchange", feed),
+            bytes: counter(&registry, "soqa_bytes_total", "Bytes received from the exchange", feed),
+            parse_errors: counter(&registry, "soqa_parse_errors_total", "Messages that failed to parse", feed),
+            connects: counter(&registry, "soqa_connects_total", "WebSocket connections opened", feed),
+            reconnects: counter(&registry, "soqa_reconnects_total", "WebSocket connections after the first", feed),
+            book_resyncs: counter(&registry, "soqa_book_resyncs_total", "Order book snapshot resyncs", feed),
+            checksum_mismatches: counter(
+                &registry,
+                "soqa_checksum_mismatches_total",
+                "Local order books that failed the venue checksum",
+                feed,
+            ),
+            late_events: counter(&registry, "soqa_late_events_total", "Events behind the merge watermark", feed),
+            dropped_events: counter(
+                &registry,
+                "soqa_dropped_events_total",
+                "Events dropped or conflated because a consumer fell behind",
+                &["exchange", "symbol", "policy"],
+            ),
+            sink_records: counter(&registry, "soqa_sink_records_total", "Records written to sinks", &["sink"]),
+            latency,
+            api_clients,
+            sink_write_seconds,
+            registry,
+        }
+    });
+
+    pub fn gather() -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&METRICS.registry.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub use registry::gather;
+
+#[cfg(feature = "metrics")]
+use registry::METRICS;
+
+pub fn message_received(exchange: &str, symbol: &str, bytes: usize) {
+    #[cfg(feature = "metrics")]
+    {
+        METRICS.messages.with_label_values(&[exchange, symbol]).inc();
+        METRICS.bytes.with_label_values(&[exchange, symbol]).inc_by(bytes as u64);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (exchange, symbol, bytes);
+}
+
+pub fn parse_error(exchange: &str, symbol: &str) {
+    #[cfg(feature = "metrics")]
+    METRICS.parse_errors.with_label_values(&[exchange, symbol]).inc();
+    #[cfg(not(feature = "metrics"))]
+    let _ = (exchange, symbol);
+}
+
+pub fn connected(exchange: &str, symbol: &str) {
+    #[cfg(feature = "metrics")]
+    {
+        let connects = METRICS.connects.with_label_values(&[exchange, symbol]);
+        if connects.get() > 0 {
+            METRICS.reconnects.with_label_values(&[exchange, symbol]).inc();
+        }
+        connects.inc();
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (exchange, symbol);
+}
+
+pub fn latency(exchange: &str, symbol: &str, seconds: f64) {
+    #[cfg(feature = "metrics")]
+    METRICS.latency.with_label_values(&[exchange, symbol]).observe(seconds);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (exchange, symbol, seconds);
+}
+
+pub fn book_resync(exchange: &str, symbol: &str) {
+    #[cfg(feature = "metrics")]
+    METRICS.book_resyncs.with_label_values(&[exchange, symbol]).inc();
+    #[cfg(not(feature = "metrics"))]
+    let _ = (exchange, symbol);
+}
+
+pub fn checksum_mismatch(exchange: &str, symbol: &str) {
+    #[cfg(feature = "metrics")]
+    METRICS.checksum_mismatches.with_label_values(&[exchange, symbol]).inc();
+    #[cfg(not(feature = "metrics"))]
+    let _ = (exchange, symbol);
+}
+
+pub fn late_event(exchange: &str, symbol: &str) {
+    #[cfg(feature = "metrics")]
+    METRICS.late_events.with_label_values(&[exchange, symbol]).inc();
+    #[cfg(not(feature = "metrics"))]
+    let _ = (exchange, symbol);
+}
+
+pub fn dropped_event(exchange: &str, symbol: &str, policy: &str) {
+    #[cfg(feature = "metrics")]
+    METRICS.dropped_events.with_label_values(&[exchange, symbol, policy]).inc();
+    #[cfg(not(feature = "metrics"))]
+    let _ = (exchange, symbol, policy);
+}
+
+pub fn api_client_connected() {
+    #[cfg(feature = "metrics")]
+    METRICS.api_clients.inc();
+}
+
+pub fn api_client_disconnected() {
+    #[cfg(feature = "metrics")]
+    METRICS.api_clients.dec();
+}
+
+pub fn sink_write(sink: &str, records: usize, seconds: f64) {
+    #[cfg(feature = "metrics")]
+    {
+        METRICS.sink_records.with_label_values(&[sink]).inc_by(records as u64);
+        METRICS.sink_write_seconds.observe(seconds);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (sink, records, seconds);
+}
@@ -1,7 +1,30 @@
 use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
 
-#[derive(Debug, Serialize, Deserialize)]
+// The kind of venue channel an L1 quote was taken from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteSource {
+    // A ticker channel, or a source that is not known, e.g. a declarative adapter.
+    #[default]
+    Ticker,
+    // The best level of a depth channel.
+    Book,
+    // A dedicated best bid/offer channel, usually the fastest.
+    Bbo,
+}
+
+impl QuoteSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuoteSource::Ticker => "ticker",
+            QuoteSource::Book => "book",
+            QuoteSource::Bbo => "bbo",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderBookL1 {
     pub exchange: String,
     pub symbol: String,
@@ -10,9 +33,11 @@ pub struct OrderBookL1 {
     pub ask: f64,
     pub ask_volume: f64,
     pub timestamp: SystemTime,
+    #[serde(default)]
+    pub source: QuoteSource,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderBookL2 {
     pub exchange: String,
     pub symbol: String,
@@ -21,7 +46,7 @@ pub struct OrderBookL2 {
     pub timestamp: SystemTime,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
     pub exchange: String,
     pub symbol: String,
@@ -29,4 +54,64 @@ pub struct Trade {
     pub volume: f64,
     pub side: String,
     pub timestamp: SystemTime,
-}
\ No newline at end of file
+}
+
+// Flat trade row as downloaded from a venue's REST history; keeps the venue trade id for
+// de-duplication and writes cleanly to CSV and SQL sinks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoricalTrade {
+    pub exchange: String,
+    pub symbol: String,
+    pub trade_id: String,
+    pub price: f64,
+    pub volume: f64,
+    pub side: String,
+    pub timestamp_ms: u64,
+}
+
+impl From<&HistoricalTrade> for Trade {
+    fn from(trade: &HistoricalTrade) -> Self {
+        Trade {
+            exchange: trade.exchange.clone(),
+            symbol: trade.symbol.clone(),
+            price: trade.price,
+            volume: trade.volume,
+            side: trade.side.clone(),
+            timestamp: SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(trade.timestamp_ms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MarketEvent {
+    L1(OrderBookL1),
+    L2(OrderBookL2),
+    Trade(Trade),
+}
+
+impl MarketEvent {
+    pub fn exchange(&self) -> &str {
+        match self {
+            MarketEvent::L1(book) => &book.exchange,
+            MarketEvent::L2(book) => &book.exchange,
+            MarketEvent::Trade(trade) => &trade.exchange,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self {
+            MarketEvent::L1(book) => &book.symbol,
+            MarketEvent::L2(book) => &book.symbol,
+            MarketEvent::Trade(trade) => &trade.symbol,
+        }
+    }
+
+    pub fn timestamp(&self) -> SystemTime {
+        match self {
+            MarketEvent::L1(book) => book.timestamp,
+            MarketEvent::L2(book) => book.timestamp,
+            MarketEvent::Trade(trade) => trade.timestamp,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// Console output of the commands: events, analytics signals or arbitrage opportunities as pretty
+// JSON, NDJSON, CSV or an aligned table, written as they arrive so the stream can be piped into
+// other tools.
+
+use crate::analytics::Signal;
+use crate::arbitrage::ArbOpportunity;
+use crate::models::MarketEvent;
+use crate::triangular::TriangularOpportunity;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    // One pretty-printed JSON object per record.
+    Json,
+    // One compact JSON object per line.
+    Ndjson,
+    Csv,
+    #[default]
+    Table,
+}
+
+// Book levels per side shown in table output; CSV gets the full depth.
+const TABLE_LEVELS: usize = 5;
+const TABLE_WIDTH: usize = 12;
+
+// A record with a flat, printable form. The header is reprinted when the columns change.
+pub trait Row: Serialize {
+    fn columns(&self) -> &'static [&'static str];
+    fn values(&self, max_levels: Option<usize>) -> Vec<String>;
+}
+
+fn millis(timestamp: SystemTime) -> String {
+    timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis().to_string()
+}
+
+// `price@size` pairs, best first, separated by spaces.
+fn levels(levels: &[(f64, f64)], max_levels: Option<usize>) -> String {
+    let shown = max_levels.unwrap_or(levels.len()).min(levels.len());
+    levels[..shown].iter().map(|(price, size)| format!("{}@{}", price, size)).collect::<Vec<_>>().join(" ")
+}
+
+impl Row for MarketEvent {
+    fn columns(&self) -> &'static [&'static str] {
+        match self {
+            MarketEvent::L1(_) => &["exchange", "symbol", "timestamp_ms", "bid", "bid_volume", "ask", "ask_volume", "source"],
+            MarketEvent::L2(_) => &["exchange", "symbol", "timestamp_ms", "bids", "asks"],
+            MarketEvent::Trade(_) => &["exchange", "symbol", "timestamp_ms", "price", "volume", "side"],
+        }
+    }
+
+    fn values(&self, max_levels: Option<usize>) -> Vec<String> {
+        let mut values = vec![self.exchange().to_string(), self.symbol().to_string(), millis(self.timestamp())];
+        match self {
+            MarketEvent::L1(book) => {
+                values.extend([book.bid, book.bid_volume, book.ask, book.ask_volume].map(|v| v.to_string()));
+                values.push(book.source.as_str().to_string());
+            }
+            MarketEvent::L2(book) => values.extend([levels(&book.bids, max_levels), levels(&book.asks, max_levels)]),
+            MarketEvent::Trade(trade) => {
+                values.extend([trade.price.to_string(), trade.volume.to_string(), trade.side.clone()])
+            }
+        }
+        values
+    }
+}
+
+impl Row for Signal {
+    fn columns(&self) -> &'static [&'static str] {
+        &["exchange", "symbol", "timestamp_ms", "kind", "value"]
+    }
+
+    fn values(&self, _max_levels: Option<usize>) -> Vec<String> {
+        let kind = serde_json::to_value(self.kind).ok().and_then(|k| k.as_str().map(str::to_string)).unwrap_or_default();
+        vec![self.exchange.clone(), self.symbol.clone(), millis(self.timestamp), kind, self.value.to_string()]
+    }
+}
+
+impl Row for ArbOpportunity {
+    fn columns(&self) -> &'static [&'static str] {
+        &[
+            "instrument", "buy_exchange", "sell_exchange", "opened_at_ms", "status", "buy_price", "sell_price", "size",
+            "edge_bps", "expected_profit", "duration_ms",
+        ]
+    }
+
+    fn values(&self, _max_levels: Option<usize>) -> Vec<String> {
+        let status = serde_json::to_value(self.status).ok().and_then(|s| s.as_str().map(str::to_string)).unwrap_or_default();
+        let mut values = vec![self.instrument.clone(), self.buy_exchange.clone(), self.sell_exchange.clone(), millis(self.opened_at), status];
+        values.extend([self.buy_price, self.sell_price, self.size, self.edge_bps, self.expected_profit].map(|v| v.to_string()));
+        values.push(self.duration.as_millis().to_string());
+        values
+    }
+}
+
+impl Row for TriangularOpportunity {
+    fn columns(&self) -> &'static [&'static str] {
+        &["exchange", "timestamp_ms", "path", "symbols", "size", "edge_bps", "expected_profit"]
+    }
+
+    fn values(&self, _max_levels: Option<usize>) -> Vec<String> {
+        let mut values = vec![self.exchange.clone(), millis(self.timestamp), self.path.join(">"), self.symbols.join(",")];
+        values.extend([self.size, self.edge_bps, self.expected_profit].map(|v| v.to_string()));
+        values
+    }
+}
+
+enum Target<W: Write> {
+    Plain(W),
+    Csv(Box<csv::Writer<W>>),
+}
+
+pub struct OutputWriter<W: Write> {
+    target: Target<W>,
+    format: OutputFormat,
+    columns: Option<&'static [&'static str]>,
+}
+
+fn table_line(cells: &[String]) -> String {
+    let last = cells.len().saturating_sub(1);
+    cells
+        .iter()
+        .enumerate()
+        .map(|(i, cell)| if i == last { cell.clone() } else { format!("{:<width$}", cell, width = TABLE_WIDTH.max(cell.len())) })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl<W: Write> OutputWriter<W> {
+    pub fn new(inner: W, format: OutputFormat) -> Self {
+        let target = match format {
+            OutputFormat::Csv => Target::Csv(Box::new(csv::WriterBuilder::new().has_headers(false).flexible(true).from_writer(inner))),
+            _ => Target::Plain(inner),
+        };
+        OutputWriter { target, format, columns: None }
+    }
+
+    pub fn write<R: Row>(&mut self, row: &R) -> io::Result<()> {
+        let columns = row.columns();
+        let header = self.columns != Some(columns);
+        self.columns = Some(columns);
+        match &mut self.target {
+            Target::Csv(writer) => {
+                if header {
+                    writer.write_record(columns)?;
+                }
+                writer.write_record(row.values(None))?;
+            }
+            Target::Plain(writer) => match self.format {
+                OutputFormat::Json => {
+                    serde_json::to_writer_pretty(&mut *writer, row)?;
+                    writeln!(writer)?;
+                }
+                OutputFormat::Ndjson => {
+                    serde_json::to_writer(&mut *writer, row)?;
+                    writeln!(writer)?;
+                }
+                _ => {
+                    if header {
+                        let columns: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+                        writeln!(writer, "{}", table_line(&columns))?;
+                    }
+                    writeln!(writer, "{}", table_line(&row.values(Some(TABLE_LEVELS))))?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.target {
+            Target::Csv(writer) => writer.flush(),
+            Target::Plain(writer) => writer.flush(),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// Answers for `query` over recorded quotes and trades (see `export::record`): OHLCV candles,
+// spread statistics and per-venue quote counts, printable through `output`.
+
+use crate::models::{OrderBookL1, Trade};
+use crate::output::Row;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Report {
+    // Candles built from trades, one per market and interval.
+    Ohlcv,
+    // Spread in basis points of the mid, per market.
+    Spread,
+    // Quotes per venue.
+    Counts,
+    // The matching quotes or trades themselves.
+    Quotes,
+    Trades,
+}
+
+fn millis(timestamp: SystemTime) -> u64 {
+    timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Candle {
+    pub exchange: String,
+    pub symbol: String,
+    pub start_ms: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub trades: u64,
+}
+
+// Buckets trades by market and by `interval` from the epoch; intervals without trades are left
+// out. Candles come ordered by market, then time.
+pub fn ohlcv(trades: &[Trade], interval: Duration) -> Vec<Candle> {
+    let interval = (interval.as_millis() as u64).max(1);
+    let mut ordered: Vec<_> = trades.iter().collect();
+    ordered.sort_by_key(|t| t.timestamp);
+    let mut candles: BTreeMap<(&str, &str, u64), Candle> = BTreeMap::new();
+    for t in ordered {
+        let start_ms = millis(t.timestamp) / interval * interval;
+        let candle = candles.entry((&t.exchange, &t.symbol, start_ms)).or_insert_with(|| Candle {
+            exchange: t.exchange.clone(),
+            symbol: t.symbol.clone(),
+            start_ms,
+            open: t.price,
+            high: t.price,
+            low: t.price,
+            close: t.price,
+            volume: 0.0,
+            trades: 0,
+        });
+        candle.high = candle.high.max(t.price);
+        candle.low = candle.low.min(t.price);
+        candle.close = t.price;
+        candle.volume += t.volume;
+        candle.trades += 1;
+    }
+    candles.into_values().collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpreadStats {
+    pub exchange: String,
+    pub symbol: String,
+    pub quotes: u64,
+    pub mean_bps: f64,
+    pub median_bps: f64,
+    pub p95_bps: f64,
+    pub min_bps: f64,
+    pub max_bps: f64,
+}
+
+// Spread statistics per market. Quotes missing a side (a price of zero) are skipped; crossed
+// books count with a negative spread.
+pub fn spread_stats(quotes: &[OrderBookL1]) -> Vec<SpreadStats> {
+    let mut spreads: BTreeMap<(&str, &str), Vec<f64>> = BTreeMap::new();
+    for q in quotes.iter().filter(|q| q.bid > 0.0 && q.ask > 0.0) {
+        let mid = (q.bid + q.ask) / 2.0;
+        spreads.entry((&q.exchange, &q.symbol)).or_default().push((q.ask - q.bid) / mid * 10_000.0);
+    }
+    spreads
+        .into_iter()
+        .map(|((exchange, symbol), mut bps)| {
+            bps.sort_by(f64::total_cmp);
+            let n = bps.len();
+            let median = if n % 2 == 0 { (bps[n / 2 - 1] + bps[n / 2]) / 2.0 } else { bps[n / 2] };
+            // Nearest rank.
+            let p95 = bps[((n as f64 * 0.95).ceil() as usize).clamp(1, n) - 1];
+            SpreadStats {
+                exchange: exchange.to_string(),
+                symbol: symbol.to_string(),
+                quotes: n as u64,
+                mean_bps: bps.iter().sum::<f64>() / n as f64,
+                median_bps: median,
+                p95_bps: p95,
+                min_bps: bps[0],
+                max_bps: bps[n - 1],
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VenueCount {
+    pub exchange: String,
+    pub symbols: u64,
+    pub quotes: u64,
+    pub first_ms: u64,
+    pub last_ms: u64,
+    // Over the span from the first to the last quote; zero when that is empty.
+    pub quotes_per_sec: f64,
+}
+
+pub fn quote_counts(quotes: &[OrderBookL1]) -> Vec<VenueCount> {
+    let mut venues: BTreeMap<&str, (Vec<&str>, u64, SystemTime, SystemTime)> = BTreeMap::new();
+    for q in quotes {
+        let venue = venues.entry(&q.exchange).or_insert_with(|| (Vec::new(), 0, q.timestamp, q.timestamp));
+        if !venue.0.contains(&q.symbol.as_str()) {
+            venue.0.push(&q.symbol);
+        }
+        venue.1 += 1;
+        venue.2 = venue.2.min(q.timestamp);
+        venue.3 = venue.3.max(q.timestamp);
+    }
+    venues
+        .into_iter()
+        .map(|(exchange, (symbols, quotes, first, last))| {
+            let span = last.duration_since(first).unwrap_or_default().as_secs_f64();
+            VenueCount {
+                exchange: exchange.to_string(),
+                symbols: symbols.len() as u64,
+                quotes,
+                first_ms: millis(first),
+                last_ms: millis(last),
+                quotes_per_sec: if span > 0.0 { quotes as f64 / span } else { 0.0 },
+            }
+        })
+        .collect()
+}
+
+impl Row for Candle {
+    fn columns(&self) -> &'static [&'static str] {
+        &["exchange", "symbol", "start_ms", "open", "high", "low", "close", "volume", "trades"]
+    }
+
+    fn values(&self, _max_levels: Option<usize>) -> Vec<String> {
+        let mut values = vec![self.exchange.clone(), self.symbol.clone(), self.start_ms.to_string()];
+        values.extend([self.open, self.high, self.low, self.close, self.volume].map(|v| v.to_string()));
+        values.push(self.trades.to_string());
+        values
+    }
+}
+
+impl Row for SpreadStats {
+    fn columns(&self) -> &'static [&'static str] {
+        &["exchange", "symbol", "quotes", "mean_bps", "median_bps", "p95_bps", "min_bps", "max_bps"]
+    }
+
+    fn values(&self, _max_levels: Option<usize>) -> Vec<String> {
+        let mut values = vec![self.exchange.clone(), self.symbol.clone(), self.quotes.to_string()];
+        values.extend([self.mean_bps, self.median_bps, self.p95_bps, self.min_bps, self.max_bps].map(|v| format!("{:.3}", v)));
+        values
+    }
+}
+
+impl Row for VenueCount {
+    fn columns(&self) -> &'static [&'static str] {
+        &["exchange", "symbols", "quotes", "first_ms", "last_ms", "quotes_per_sec"]
+    }
+
+    fn values(&self, _max_levels: Option<usize>) -> Vec<String> {
+        vec![
+            self.exchange.clone(),
+            self.symbols.to_string(),
+            self.quotes.to_string(),
+            self.first_ms.to_string(),
+            self.last_ms.to_string(),
+            format!("{:.3}", self.quotes_per_sec),
+        ]
+    }
+}
@@ -0,0 +1,278 @@
+use crate::error::SoqaError;
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+const MAX_RETRIES: u32 = 5;
+
+// Errors Kraken returns with HTTP 200 when its call counter is full.
+const KRAKEN_RATE_LIMITED: [&str; 2] = ["EAPI:Rate limit exceeded", "EGeneral:Too many requests"];
+
+// Token bucket: up to `capacity` weight at once, refilled at `per_second`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub capacity: f64,
+    pub per_second: f64,
+}
+
+impl RateLimit {
+    pub fn new(capacity: f64, window: Duration) -> Self {
+        RateLimit { capacity, per_second: capacity / window.as_secs_f64() }
+    }
+
+    // A call counter that each request raises by its weight, that decays by `decay` a second and
+    // that rejects requests past `max`: a bucket of `max` tokens refilled at `decay`.
+    pub fn counter(max: f64, decay: f64) -> Self {
+        RateLimit { capacity: max, per_second: decay }
+    }
+}
+
+pub fn default_limit(exchange: &str, endpoint: &str) -> RateLimit {
+    match (exchange, endpoint) {
+        // REQUEST_WEIGHT limit per IP.
+        ("binance", _) => RateLimit::new(6000.0, Duration::from_secs(60)),
+        ("bybit", _) => RateLimit::new(600.0, Duration::from_secs(5)),
+        // OKX limits each endpoint separately.
+        ("okx", "/api/v5/market/books") => RateLimit::new(40.0, Duration::from_secs(2)),
+        ("okx", _) => RateLimit::new(20.0, Duration::from_secs(2)),
+        // Kraken doesn't report its counter, so this assumes the starter tier's decay and a counter
+        // filled by other clients only shows up as `EAPI:Rate limit exceeded`.
+        ("kraken", _) => RateLimit::counter(15.0, 0.33),
+        ("kucoin", _) => RateLimit::new(2000.0, Duration::from_secs(30)),
+        _ => RateLimit { capacity: 10.0, per_second: 10.0 },
+    }
+}
+
+pub fn weight(exchange: &str, endpoint: &str) -> f64 {
+    match (exchange, endpoint) {
+        ("binance", "/api/v3/aggTrades") => 4.0,
+        ("binance", "/api/v3/historicalTrades") => 25.0,
+        ("binance", "/api/v3/exchangeInfo") => 20.0,
+        ("binance", "/api/v3/depth") => 50.0,
+        ("kraken", "/0/public/Trades") => 2.0,
+        ("kucoin", "/api/v1/bullet-public") => 10.0,
+        ("kucoin", "/api/v1/market/histories") => 3.0,
+        ("kucoin", "/api/v2/symbols") => 4.0,
+        _ => 1.0,
+    }
+}
+
+fn bucket_key(exchange: &str, endpoint: &str) -> String {
+    match exchange {
+        "okx" => format!("okx:{}", endpoint),
+        _ => exchange.to_string(),
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+    blocked_until: Option<Instant>,
+    backoffs: u32,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Self {
+        Bucket { limit, tokens: limit.capacity, updated: Instant::now(), blocked_until: None, backoffs: 0 }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.capacity);
+        self.updated = now;
+    }
+
+    // After the venue refused a request: empties the bucket and blocks it for `retry_after`, or
+    // for an exponential backoff when the venue didn't say.
+    fn back_off(&mut self, now: Instant, retry_after: Option<Duration>) -> Duration {
+        let retry_after = retry_after.unwrap_or_else(|| Duration::from_secs(1 << self.backoffs.min(6)));
+        self.backoffs += 1;
+        self.tokens = 0.0;
+        self.blocked_until = Some(now + retry_after);
+        retry_after
+    }
+
+    fn wait_for(&self, weight: f64, now: Instant) -> Duration {
+        let blocked = self.blocked_until.map(|until| until.saturating_duration_since(now)).unwrap_or_default();
+        let missing = (weight - self.tokens).max(0.0);
+        blocked.max(Duration::from_secs_f64(missing / self.limit.per_second))
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    limits: HashMap<String, RateLimit>,
+    buckets: HashMap<String, Bucket>,
+}
+
+// Shared by every REST caller of a venue; cloning shares the buckets.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    state: Arc<Mutex<State>>,
+    max_wait: Duration,
+}
+
+static GLOBAL: LazyLock<RateLimiter> = LazyLock::new(RateLimiter::default);
+
+pub fn global() -> RateLimiter {
+    GLOBAL.clone()
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter {
+            state: Arc::new(Mutex::new(State { limits: HashMap::new(), buckets: HashMap::new() })),
+            max_wait: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RateLimiter {
+    // Overrides the limit of a venue, or of one endpoint for venues limited per endpoint.
+    pub fn with_limit(self, exchange: &str, endpoint: Option<&str>, limit: RateLimit) -> Self {
+        let key = bucket_key(exchange, endpoint.unwrap_or_default());
+        {
+            let mut state = self.state.lock().unwrap();
+            state.buckets.remove(&key);
+            state.limits.insert(key, limit);
+        }
+        self
+    }
+
+    // Longest a caller queues before getting `RateLimited` instead.
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    fn with_bucket<T>(&self, exchange: &str, endpoint: &str, f: impl FnOnce(&mut Bucket, Instant) -> T) -> T {
+        let key = bucket_key(exchange, endpoint);
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let limit = state.limits.get(&key).copied().unwrap_or_else(|| default_limit(exchange, endpoint));
+        let bucket = state.buckets.entry(key).or_insert_with(|| Bucket::new(limit));
+        bucket.refill(now);
+        f(bucket, now)
+    }
+
+    // Waits until `weight` is available and takes it.
+    pub async fn acquire(&self, exchange: &str, endpoint: &str, weight: f64) -> Result<(), SoqaError> {
+        loop {
+            let wait = self.with_bucket(exchange, endpoint, |bucket, now| {
+                let weight = weight.min(bucket.limit.capacity);
+                let wait = bucket.wait_for(weight, now);
+                if wait.is_zero() {
+                    bucket.tokens -= weight;
+                }
+                wait
+            });
+            if wait.is_zero() {
+                return Ok(());
+            }
+            if wait > self.max_wait {
+                return Err(SoqaError::RateLimited { exchange: exchange.to_string(), retry_after: wait });
+            }
+            debug!(target: "soqa::ratelimit", exchange, endpoint, ?wait, "queued");
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    // Syncs the bucket with the venue's own accounting and handles 429/418 responses.
+    pub fn observe(&self, exchange: &str, endpoint: &str, status: u16, headers: &HeaderMap) -> Result<(), SoqaError> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).and_then(|v| v.parse::<f64>().ok());
+        self.with_bucket(exchange, endpoint, |bucket, now| {
+            match exchange {
+                "binance" => {
+                    if let Some(used) = header("x-mbx-used-weight-1m") {
+                        bucket.tokens = (bucket.limit.capacity - used).max(0.0);
+                    }
+                }
+                "bybit" => {
+                    if let (Some(0.0), Some(reset_ms)) = (header("x-bapi-limit-status"), header("x-bapi-limit-reset-timestamp")) {
+                        let now_ms = std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_millis() as f64;
+                        bucket.blocked_until = Some(now + Duration::from_millis((reset_ms - now_ms).max(0.0) as u64));
+                    }
+                }
+                _ => {}
+            }
+            if status == 429 || status == 418 {
+                let retry_after = bucket.back_off(now, header("retry-after").map(Duration::from_secs_f64));
+                return Err(SoqaError::RateLimited { exchange: exchange.to_string(), retry_after });
+            }
+            bucket.backoffs = 0;
+            Ok(())
+        })
+    }
+
+    // Handles rate limiting a venue reports in the body of a successful response: Kraken answers
+    // HTTP 200 with `{"error":["EAPI:Rate limit exceeded"]}` once its call counter is full.
+    pub fn observe_body(&self, exchange: &str, endpoint: &str, body: &str) -> Result<(), SoqaError> {
+        if exchange != "kraken" || !KRAKEN_RATE_LIMITED.iter().any(|error| body.contains(error)) {
+            return Ok(());
+        }
+        self.with_bucket(exchange, endpoint, |bucket, now| {
+            let retry_after = bucket.back_off(now, None);
+            Err(SoqaError::RateLimited { exchange: exchange.to_string(), retry_after })
+        })
+    }
+
+    // Sends `request` once its weight is available, retrying after 429/418 backoffs.
+    pub async fn send(&self, exchange: &str, request: RequestBuilder) -> Result<Response, SoqaError> {
+        let endpoint = endpoint(&request);
+        let weight = weight(exchange, &endpoint);
+        let mut attempt = 0;
+        loop {
+            self.acquire(exchange, &endpoint, weight).await?;
+            let retry = request
+                .try_clone()
+                .ok_or_else(|| SoqaError::ConnectionError("request cannot be retried".to_string()))?;
+            let response = retry.send().await.map_err(SoqaError::Http)?;
+            match self.observe(exchange, &endpoint, response.status().as_u16(), response.headers()) {
+                Ok(()) => return Ok(response),
+                Err(e) if attempt + 1 < MAX_RETRIES => {
+                    warn!(target: "soqa::ratelimit", exchange, endpoint = %endpoint, "{}, backing off", e);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    // Like `send`, but reads the body and retries when it reports rate limiting too.
+    pub async fn send_text(&self, exchange: &str, request: RequestBuilder) -> Result<(StatusCode, String), SoqaError> {
+        let endpoint = endpoint(&request);
+        let mut attempt = 0;
+        loop {
+            let retry = request
+                .try_clone()
+                .ok_or_else(|| SoqaError::ConnectionError("request cannot be retried".to_string()))?;
+            let response = self.send(exchange, retry).await?;
+            let status = response.status();
+            let body = response.text().await.map_err(SoqaError::Http)?;
+            match self.observe_body(exchange, &endpoint, &body) {
+                Ok(()) => return Ok((status, body)),
+                Err(e) if attempt + 1 < MAX_RETRIES => {
+                    warn!(target: "soqa::ratelimit", exchange, endpoint = %endpoint, "{}, backing off", e);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+fn endpoint(request: &RequestBuilder) -> String {
+    request
+        .try_clone()
+        .and_then(|r| r.build().ok())
+        .map(|r| r.url().path().to_string())
+        .unwrap_or_default()
+}
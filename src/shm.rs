@@ -0,0 +1,193 @@
+// Single-writer, multi-reader ring buffer of `codec` records in a memory-mapped file, for
+// consumers on the same host. Each slot is guarded by its own sequence number (a seqlock): the
+// writer never waits for readers, and a reader that falls more than a lap behind skips ahead
+// and counts the events it lost.
+//
+// Layout: a 128-byte header (magic, version, slot size, capacity, then the write sequence on its
+// own cache line) followed by `capacity` slots of `SLOT_SIZE` bytes: slot sequence (u64), record
+// length (u64), record bytes.
+
+use crate::codec;
+use crate::error::SoqaError;
+use crate::models::MarketEvent;
+use memmap2::{Mmap, MmapMut};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+const MAGIC: u64 = u64::from_le_bytes(*b"SOQARING");
+const LAYOUT_VERSION: u64 = 1;
+const HEADER_SIZE: usize = 128;
+const WRITE_SEQ_OFFSET: usize = 64;
+pub const SLOT_SIZE: usize = 256;
+const SLOT_HEADER: usize = 16;
+pub const MAX_RECORD: usize = SLOT_SIZE - SLOT_HEADER;
+
+fn shm_error(e: impl std::fmt::Display) -> SoqaError {
+    SoqaError::ShmError(e.to_string())
+}
+
+// Safety: `base` must point at a live mapping longer than `offset + 8`. Mappings are page aligned
+// and every offset used here is a multiple of 8.
+unsafe fn word<'a>(base: *const u8, offset: usize) -> &'a AtomicU64 {
+    &*(base.add(offset) as *const AtomicU64)
+}
+
+fn slot_offset(capacity: u64, seq: u64) -> usize {
+    HEADER_SIZE + (seq & (capacity - 1)) as usize * SLOT_SIZE
+}
+
+pub struct ShmPublisher {
+    map: MmapMut,
+    capacity: u64,
+    seq: u64,
+    buf: Vec<u8>,
+}
+
+impl ShmPublisher {
+    // Creates (or truncates) the ring at `path`, e.g. /dev/shm/soqa-btcusdt. `capacity` is rounded
+    // up to a power of two.
+    pub fn create(path: impl AsRef<Path>, capacity: usize) -> Result<Self, SoqaError> {
+        let capacity = capacity.max(2).next_power_of_two() as u64;
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path).map_err(shm_error)?;
+        file.set_len((HEADER_SIZE + capacity as usize * SLOT_SIZE) as u64).map_err(shm_error)?;
+        let mut map = unsafe { MmapMut::map_mut(&file) }.map_err(shm_error)?;
+        let base = map.as_mut_ptr() as *const u8;
+        unsafe { word(base, 8) }.store(LAYOUT_VERSION, Ordering::Relaxed);
+        unsafe { word(base, 16) }.store(SLOT_SIZE as u64, Ordering::Relaxed);
+        unsafe { word(base, 24) }.store(capacity, Ordering::Relaxed);
+        unsafe { word(base, WRITE_SEQ_OFFSET) }.store(0, Ordering::Relaxed);
+        // Readers check the magic last.
+        unsafe { word(base, 0) }.store(MAGIC, Ordering::Release);
+        Ok(ShmPublisher { map, capacity, seq: 0, buf: Vec::with_capacity(SLOT_SIZE) })
+    }
+
+    pub fn publish(&mut self, event: &MarketEvent) -> Result<(), SoqaError> {
+        self.buf.clear();
+        codec::encode(event, &mut self.buf);
+        if self.buf.len() > MAX_RECORD {
+            return Err(shm_error(format!("record of {} bytes does not fit a {} byte slot", self.buf.len(), SLOT_SIZE)));
+        }
+        self.buf.resize(self.buf.len().next_multiple_of(8), 0);
+
+        let base = self.map.as_mut_ptr() as *const u8;
+        let slot = slot_offset(self.capacity, self.seq);
+        let guard = unsafe { word(base, slot) };
+        guard.store(self.seq * 2 + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        unsafe { word(base, slot + 8) }.store(self.buf.len() as u64, Ordering::Relaxed);
+        for (i, chunk) in self.buf.chunks_exact(8).enumerate() {
+            unsafe { word(base, slot + SLOT_HEADER + i * 8) }.store(u64::from_le_bytes(chunk.try_into().unwrap()), Ordering::Relaxed);
+        }
+        guard.store(self.seq * 2 + 2, Ordering::Release);
+        self.seq += 1;
+        unsafe { word(base, WRITE_SEQ_OFFSET) }.store(self.seq, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn published(&self) -> u64 {
+        self.seq
+    }
+}
+
+pub struct ShmReader {
+    map: Mmap,
+    capacity: u64,
+    next: u64,
+    lost: u64,
+    buf: Vec<u8>,
+}
+
+impl ShmReader {
+    // Attaches to an existing ring and starts at the newest event.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SoqaError> {
+        let file = OpenOptions::new().read(true).open(path).map_err(shm_error)?;
+        let map = unsafe { Mmap::map(&file) }.map_err(shm_error)?;
+        if map.len() < HEADER_SIZE {
+            return Err(shm_error("file is too small to be a ring"));
+        }
+        let base = map.as_ptr();
+        if unsafe { word(base, 0) }.load(Ordering::Acquire) != MAGIC {
+            return Err(shm_error("not a soqa ring buffer"));
+        }
+        let version = unsafe { word(base, 8) }.load(Ordering::Relaxed);
+        let slot_size = unsafe { word(base, 16) }.load(Ordering::Relaxed);
+        if version != LAYOUT_VERSION || slot_size != SLOT_SIZE as u64 {
+            return Err(shm_error(format!("unsupported ring layout v{} with {} byte slots", version, slot_size)));
+        }
+        let capacity = unsafe { word(base, 24) }.load(Ordering::Relaxed);
+        // Slots are found by masking the sequence, which needs a power of two.
+        if !capacity.is_power_of_two() {
+            return Err(shm_error(format!("invalid ring capacity {}", capacity)));
+        }
+        let len = usize::try_from(capacity)
+            .ok()
+            .and_then(|capacity| capacity.checked_mul(SLOT_SIZE))
+            .and_then(|slots| slots.checked_add(HEADER_SIZE));
+        if len.is_none_or(|len| map.len() < len) {
+            return Err(shm_error("ring file is truncated"));
+        }
+        let next = unsafe { word(base, WRITE_SEQ_OFFSET) }.load(Ordering::Acquire);
+        Ok(ShmReader { map, capacity, next, lost: 0, buf: vec![0; MAX_RECORD] })
+    }
+
+    // Rewinds to the oldest event still in the ring.
+    pub fn from_oldest(mut self) -> Self {
+        let head = unsafe { word(self.map.as_ptr(), WRITE_SEQ_OFFSET) }.load(Ordering::Acquire);
+        self.next = head.saturating_sub(self.capacity);
+        self
+    }
+
+    // Events overwritten before this reader got to them.
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    fn skip_to(&mut self, seq: u64) {
+        self.lost += seq - self.next;
+        self.next = seq;
+    }
+
+    pub fn try_read(&mut self) -> Result<Option<MarketEvent>, SoqaError> {
+        let base = self.map.as_ptr();
+        loop {
+            let head = unsafe { word(base, WRITE_SEQ_OFFSET) }.load(Ordering::Acquire);
+            if self.next >= head {
+                return Ok(None);
+            }
+            if head - self.next > self.capacity {
+                self.skip_to(head - self.capacity);
+            }
+            let slot = slot_offset(self.capacity, self.next);
+            let guard = unsafe { word(base, slot) };
+            let before = guard.load(Ordering::Acquire);
+            if before != self.next * 2 + 2 {
+                // Being rewritten for a later lap.
+                self.skip_to((self.next + 1).max(head.saturating_sub(self.capacity)));
+                continue;
+            }
+            let len = (unsafe { word(base, slot + 8) }.load(Ordering::Relaxed) as usize).min(MAX_RECORD);
+            for i in 0..len / 8 {
+                let value = unsafe { word(base, slot + SLOT_HEADER + i * 8) }.load(Ordering::Relaxed);
+                self.buf[i * 8..i * 8 + 8].copy_from_slice(&value.to_le_bytes());
+            }
+            fence(Ordering::Acquire);
+            if guard.load(Ordering::Relaxed) != before {
+                self.skip_to(self.next + 1);
+                continue;
+            }
+            self.next += 1;
+            return codec::decode(&self.buf[..len]).map(|(event, _)| Some(event));
+        }
+    }
+
+    // Busy-polls for the next event; for dedicated cores where latency matters more than CPU.
+    pub fn read_spin(&mut self) -> Result<MarketEvent, SoqaError> {
+        loop {
+            if let Some(event) = self.try_read()? {
+                return Ok(event);
+            }
+            std::hint::spin_loop();
+        }
+    }
+}
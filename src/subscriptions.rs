@@ -0,0 +1,122 @@
+// Fan-out point between the exchange feeds and every consumer inside the process: the WebSocket
+// and gRPC gateways subscribe here, and it keeps the latest state of each market for snapshot
+// requests.
+
+use crate::models::{MarketEvent, OrderBookL1, OrderBookL2, Trade};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    #[default]
+    #[value(name = "L1")]
+    L1,
+    #[value(name = "L2")]
+    L2,
+    Trades,
+}
+
+impl Channel {
+    pub fn of(event: &MarketEvent) -> Channel {
+        match event {
+            MarketEvent::L1(_) => Channel::L1,
+            MarketEvent::L2(_) => Channel::L2,
+            MarketEvent::Trade(_) => Channel::Trades,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Channel::L1 => "l1",
+            Channel::L2 => "l2",
+            Channel::Trades => "trades",
+        }
+    }
+}
+
+// Server-side subscription filter; an empty list matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct EventFilter {
+    #[serde(default)]
+    pub exchanges: Vec<String>,
+    #[serde(default)]
+    pub symbols: Vec<String>,
+    #[serde(default)]
+    pub channels: Vec<Channel>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &MarketEvent) -> bool {
+        (self.exchanges.is_empty() || self.exchanges.iter().any(|e| e.eq_ignore_ascii_case(event.exchange())))
+            && (self.symbols.is_empty() || self.symbols.iter().any(|s| s == event.symbol()))
+            && (self.channels.is_empty() || self.channels.contains(&Channel::of(event)))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MarketSnapshot {
+    pub exchange: String,
+    pub symbol: String,
+    pub l1: Option<OrderBookL1>,
+    pub l2: Option<OrderBookL2>,
+    pub last_trade: Option<Trade>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionManager {
+    events: broadcast::Sender<MarketEvent>,
+    latest: Arc<RwLock<BTreeMap<(String, String), MarketSnapshot>>>,
+}
+
+impl Default for SubscriptionManager {
+    fn default() -> Self {
+        SubscriptionManager::new(1024)
+    }
+}
+
+impl SubscriptionManager {
+    // `capacity` events are buffered per subscriber before a slow one starts losing events.
+    pub fn new(capacity: usize) -> Self {
+        let (events, _) = broadcast::channel(capacity);
+        SubscriptionManager { events, latest: Arc::default() }
+    }
+
+    pub fn publish(&self, event: MarketEvent) {
+        {
+            let mut latest = self.latest.write().unwrap();
+            let snapshot = latest
+                .entry((event.exchange().to_string(), event.symbol().to_string()))
+                .or_insert_with(|| MarketSnapshot {
+                    exchange: event.exchange().to_string(),
+                    symbol: event.symbol().to_string(),
+                    ..MarketSnapshot::default()
+                });
+            match &event {
+                MarketEvent::L1(book) => snapshot.l1 = Some(book.clone()),
+                MarketEvent::L2(book) => snapshot.l2 = Some(book.clone()),
+                MarketEvent::Trade(trade) => snapshot.last_trade = Some(trade.clone()),
+            }
+        }
+        let _ = self.events.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<MarketEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn sender(&self) -> broadcast::Sender<MarketEvent> {
+        self.events.clone()
+    }
+
+    pub fn snapshot(&self, exchange: &str, symbol: &str) -> Option<MarketSnapshot> {
+        self.latest.read().unwrap().get(&(exchange.to_string(), symbol.to_string())).cloned()
+    }
+
+    // (exchange, symbol) of every market that has published at least one event.
+    pub fn markets(&self) -> Vec<(String, String)> {
+        self.latest.read().unwrap().keys().cloned().collect()
+    }
+}
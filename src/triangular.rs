@@ -0,0 +1,230 @@
+use crate::arbitrage::{canonical_symbol, FeeModel, VenueBook};
+use crate::exchanges::instruments::Instrument;
+use crate::models::MarketEvent;
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TriangularOpportunity {
+    pub exchange: String,
+    // Currencies visited, starting and ending with the same one, e.g. USDT -> BTC -> ETH -> USDT.
+    pub path: Vec<String>,
+    pub symbols: Vec<String>,
+    // Executable amount of the starting currency.
+    pub size: f64,
+    pub expected_profit: f64,
+    pub edge_bps: f64,
+    pub timestamp: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    // Spend quote currency on the asks.
+    Buy,
+    // Sell base currency into the bids.
+    Sell,
+}
+
+#[derive(Debug, Clone)]
+struct Leg {
+    key: String,
+    symbol: String,
+    side: Side,
+}
+
+#[derive(Debug, Clone)]
+struct Cycle {
+    path: Vec<String>,
+    legs: Vec<Leg>,
+}
+
+// Builds every three-currency cycle the listed pairs allow, in both directions.
+fn find_cycles(instruments: &[Instrument]) -> Vec<Cycle> {
+    let mut pairs: HashMap<(&str, &str), &Instrument> = HashMap::new();
+    let mut neighbours: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for instrument in instruments {
+        let (base, quote) = (instrument.base.as_str(), instrument.quote.as_str());
+        pairs.entry((base.min(quote), base.max(quote))).or_insert(instrument);
+        neighbours.entry(base).or_default().insert(quote);
+        neighbours.entry(quote).or_default().insert(base);
+    }
+    let pair = |a: &str, b: &str| pairs.get(&(a.min(b), a.max(b))).copied();
+
+    let mut triangles = BTreeSet::new();
+    for (a, b) in pairs.keys() {
+        for c in neighbours[a].intersection(&neighbours[b]) {
+            let mut triangle = [*a, *b, *c];
+            triangle.sort();
+            triangles.insert(triangle);
+        }
+    }
+
+    let mut cycles = Vec::new();
+    for triangle in triangles {
+        // Start from the currency the other two are quoted in, when there is one (USDT in BTC/ETH/USDT).
+        let start = triangle
+            .iter()
+            .copied()
+            .max_by_key(|currency| {
+                triangle.iter().filter(|other| pair(currency, other).map(|i| i.quote == *currency).unwrap_or(false)).count()
+            })
+            .unwrap_or(triangle[0]);
+        let others: Vec<&str> = triangle.iter().copied().filter(|c| *c != start).collect();
+        for (first, second) in [(others[0], others[1]), (others[1], others[0])] {
+            let path = [start, first, second, start];
+            let legs = path
+                .windows(2)
+                .filter_map(|step| {
+                    let instrument = pair(step[0], step[1])?;
+                    let side = if instrument.quote == step[0] { Side::Buy } else { Side::Sell };
+                    Some(Leg { key: canonical_symbol(&instrument.symbol), symbol: instrument.symbol.clone(), side })
+                })
+                .collect();
+            cycles.push(Cycle { path: path.iter().map(|c| c.to_string()).collect(), legs });
+        }
+    }
+    cycles
+}
+
+// Converts a book side into (rate, capacity) levels, with capacity in the leg's input currency.
+fn leg_levels(book: &VenueBook, side: Side, fee: f64) -> Vec<(f64, f64)> {
+    match side {
+        Side::Buy => book.asks.iter().map(|(price, size)| ((1.0 - fee) / price, price * size)).collect(),
+        Side::Sell => book.bids.iter().map(|(price, size)| (price * (1.0 - fee), *size)).collect(),
+    }
+}
+
+// Walks the depth of every leg at once while the marginal cycle rate stays above one.
+// Returns (amount in, amount out) in the starting currency.
+fn walk_cycle(legs: &[Vec<(f64, f64)>]) -> Option<(f64, f64)> {
+    let mut index = vec![0; legs.len()];
+    let mut capacity: Vec<f64> = legs.iter().map(|levels| levels.first().map(|l| l.1)).collect::<Option<_>>()?;
+    let (mut amount_in, mut amount_out) = (0.0, 0.0);
+    loop {
+        let rates: Vec<f64> = legs.iter().zip(&index).map(|(levels, i)| levels[*i].0).collect();
+        let rate: f64 = rates.iter().product();
+        if rate <= 1.0 {
+            break;
+        }
+        // Amount reaching each leg per unit of the starting currency.
+        let multipliers: Vec<f64> = rates
+            .iter()
+            .scan(1.0, |carry, r| {
+                let m = *carry;
+                *carry *= r;
+                Some(m)
+            })
+            .collect();
+        let (limiting, step) = capacity
+            .iter()
+            .zip(&multipliers)
+            .map(|(cap, m)| cap / m)
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+        amount_in += step;
+        amount_out += step * rate;
+        for (k, cap) in capacity.iter_mut().enumerate() {
+            *cap -= step * multipliers[k];
+            if k == limiting || *cap <= f64::EPSILON * step * multipliers[k] {
+                index[k] += 1;
+                match legs[k].get(index[k]) {
+                    Some(level) => *cap = level.1,
+                    None => return (amount_in > 0.0).then_some((amount_in, amount_out)),
+                }
+            }
+        }
+    }
+    (amount_in > 0.0).then_some((amount_in, amount_out))
+}
+
+// Evaluates triangular cycles on one venue as its books update.
+#[derive(Debug, Clone)]
+pub struct TriangularScanner {
+    exchange: String,
+    fees: FeeModel,
+    cycles: Vec<Cycle>,
+    by_symbol: HashMap<String, Vec<usize>>,
+    books: HashMap<String, VenueBook>,
+}
+
+impl TriangularScanner {
+    pub fn new(exchange: &str, instruments: &[Instrument], fees: FeeModel) -> Self {
+        let cycles = find_cycles(instruments);
+        let mut by_symbol: HashMap<String, Vec<usize>> = HashMap::new();
+        for (id, cycle) in cycles.iter().enumerate() {
+            for leg in &cycle.legs {
+                by_symbol.entry(leg.key.clone()).or_default().push(id);
+            }
+        }
+        TriangularScanner { exchange: exchange.to_string(), fees, cycles, by_symbol, books: HashMap::new() }
+    }
+
+    pub fn cycle_count(&self) -> usize {
+        self.cycles.len()
+    }
+
+    // Venue symbols that take part in at least one cycle, i.e. the feeds worth subscribing to.
+    pub fn symbols(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        self.cycles
+            .iter()
+            .flat_map(|cycle| &cycle.legs)
+            .filter(|leg| seen.insert(leg.key.clone()))
+            .map(|leg| leg.symbol.clone())
+            .collect()
+    }
+
+    pub fn on_event(&mut self, event: &MarketEvent) -> Vec<TriangularOpportunity> {
+        let book = match event {
+            MarketEvent::L1(book) => VenueBook::from(book),
+            MarketEvent::L2(book) => VenueBook::from(book),
+            MarketEvent::Trade(_) => return Vec::new(),
+        };
+        if event.exchange() != self.exchange {
+            return Vec::new();
+        }
+        let key = canonical_symbol(event.symbol());
+        let Some(ids) = self.by_symbol.get(&key) else {
+            return Vec::new();
+        };
+        self.books.insert(key, book);
+
+        let fee = self.fees.taker_fee(&self.exchange);
+        ids.iter()
+            .filter_map(|id| {
+                let cycle = &self.cycles[*id];
+                let legs: Vec<_> = cycle
+                    .legs
+                    .iter()
+                    .map(|leg| Some(leg_levels(self.books.get(&leg.key)?, leg.side, fee)))
+                    .collect::<Option<_>>()?;
+                let (amount_in, amount_out) = walk_cycle(&legs)?;
+                let edge_bps = (amount_out / amount_in - 1.0) * 10_000.0;
+                if edge_bps < self.fees.threshold_bps {
+                    return None;
+                }
+                Some(TriangularOpportunity {
+                    exchange: self.exchange.clone(),
+                    path: cycle.path.clone(),
+                    symbols: cycle.legs.iter().map(|leg| leg.symbol.clone()).collect(),
+                    size: amount_in,
+                    expected_profit: amount_out - amount_in,
+                    edge_bps,
+                    timestamp: event.timestamp(),
+                })
+            })
+            .collect()
+    }
+}
+
+pub trait TriangularExt: Stream<Item = MarketEvent> + Sized {
+    fn triangular(self, scanner: TriangularScanner) -> impl Stream<Item = TriangularOpportunity> {
+        self.scan(scanner, |scanner, event| future::ready(Some(scanner.on_event(&event))))
+            .flat_map(stream::iter)
+    }
+}
+
+impl<S: Stream<Item = MarketEvent>> TriangularExt for S {}
@@ -0,0 +1,100 @@
+mod common;
+
+use common::at;
+
+use clap::ValueEnum;
+use futures_util::stream::{self, StreamExt};
+use soqa_sdk::analytics::{
+    book_imbalance, microprice, spread_bps, AnalyticsConfig, AnalyticsExt, RealizedVolatility, SignalKind,
+    TradeFlowImbalance, Twap, Vwap,
+};
+use soqa_sdk::models::{MarketEvent, OrderBookL1, Trade};
+use std::time::Duration;
+
+fn trade(secs: u64, price: f64, volume: f64, side: &str) -> Trade {
+    Trade { side: side.to_string(), ..common::trade("binance", "BTCUSDT", price, volume, at(secs)) }
+}
+
+fn quote(secs: u64, bid: f64, bid_volume: f64, ask: f64, ask_volume: f64) -> OrderBookL1 {
+    OrderBookL1 { bid_volume, ask_volume, ..common::quote("binance", "BTCUSDT", bid, ask, at(secs)) }
+}
+
+#[test]
+fn book_signals() {
+    let book = quote(0, 100.0, 3.0, 101.0, 1.0);
+    assert_eq!(microprice(&book), Some(100.75));
+    assert!((spread_bps(&book).unwrap() - 99.502487).abs() < 1e-6);
+
+    let bids = [(100.0, 1.0), (99.0, 2.0), (98.0, 10.0)];
+    let asks = [(101.0, 1.0), (102.0, 1.0), (103.0, 10.0)];
+    let l2 = common::book("okx", "BTCUSDT", &bids, &asks, at(0));
+    assert_eq!(book_imbalance(&l2, 2), Some(0.2));
+}
+
+#[test]
+fn trade_signals_need_a_trade_feed() {
+    let needs: Vec<_> = SignalKind::value_variants().iter().filter(|kind| kind.needs_trades()).copied().collect();
+    assert_eq!(needs, vec![SignalKind::Vwap, SignalKind::TradeFlowImbalance]);
+}
+
+#[test]
+fn every_signal_names_its_feed() {
+    for kind in SignalKind::value_variants() {
+        let needs = [kind.needs_trades(), kind.needs_quotes(), kind.needs_book()];
+        assert_eq!(needs.iter().filter(|needs| **needs).count(), 1, "{:?}", kind);
+    }
+}
+
+#[tokio::test]
+async fn imbalance_prefers_the_l2_book() {
+    let config = AnalyticsConfig { levels: 2, signals: vec![SignalKind::Imbalance], ..AnalyticsConfig::default() };
+    let l2 = common::book("binance", "BTCUSDT", &[(100.0, 1.0), (99.0, 2.0)], &[(101.0, 1.0), (102.0, 1.0)], at(1));
+    let events = vec![
+        MarketEvent::L1(quote(0, 100.0, 3.0, 101.0, 1.0)),
+        MarketEvent::L2(l2),
+        MarketEvent::L1(quote(2, 100.0, 3.0, 101.0, 1.0)),
+    ];
+    let values: Vec<f64> = stream::iter(events).analytics(config).map(|signal| signal.value).collect().await;
+    assert_eq!(values, vec![0.5, 0.2]);
+}
+
+#[test]
+fn rolling_windows_evict_old_samples() {
+    let mut vwap = Vwap::new(Duration::from_secs(10));
+    assert_eq!(vwap.update(&trade(0, 100.0, 1.0, "buy")), Some(100.0));
+    assert_eq!(vwap.update(&trade(5, 110.0, 3.0, "sell")), Some(107.5));
+    assert_eq!(vwap.update(&trade(20, 120.0, 1.0, "buy")), Some(120.0));
+
+    let mut flow = TradeFlowImbalance::new(Duration::from_secs(10));
+    flow.update(&trade(0, 100.0, 3.0, "buy"));
+    assert_eq!(flow.update(&trade(1, 100.0, 1.0, "sell")), Some(0.5));
+
+    let mut twap = Twap::new(Duration::from_secs(60));
+    twap.update(at(0), 100.0);
+    twap.update(at(30), 110.0);
+    assert_eq!(twap.update(at(40), 90.0), Some((100.0 * 30.0 + 110.0 * 10.0) / 40.0));
+
+    let mut volatility = RealizedVolatility::new(Duration::from_secs(60));
+    assert_eq!(volatility.update(at(0), 100.0), None);
+    let vol = volatility.update(at(1), 101.0).unwrap();
+    assert!((vol - (101.0f64 / 100.0).ln()).abs() < 1e-12);
+}
+
+#[tokio::test]
+async fn analytics_combinator_emits_selected_signals() {
+    let events = stream::iter(vec![
+        MarketEvent::L1(quote(0, 100.0, 1.0, 101.0, 1.0)),
+        MarketEvent::Trade(trade(1, 100.5, 2.0, "buy")),
+    ]);
+    let config = AnalyticsConfig {
+        signals: vec![SignalKind::Microprice, SignalKind::Vwap],
+        ..AnalyticsConfig::default()
+    };
+    let signals: Vec<_> = events.analytics(config).collect().await;
+
+    assert_eq!(signals.len(), 2);
+    assert_eq!(signals[0].kind, SignalKind::Microprice);
+    assert_eq!(signals[0].value, 100.5);
+    assert_eq!(signals[1].kind, SignalKind::Vwap);
+    assert_eq!(signals[1].value, 100.5);
+}
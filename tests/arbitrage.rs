@@ -0,0 +1,100 @@
+mod common;
+
+use common::at;
+
+use futures_util::stream::{self, StreamExt};
+use soqa_sdk::arbitrage::{
+    canonical_symbol, executable_fill, ArbitrageDetector, ArbitrageExt, FeeModel, OpportunityStatus,
+};
+use soqa_sdk::models::{MarketEvent, OrderBookL1};
+use std::time::Duration;
+
+fn quote(exchange: &str, symbol: &str, secs: u64, bid: f64, ask: f64) -> MarketEvent {
+    MarketEvent::L1(OrderBookL1 { bid_volume: 2.0, ..common::quote(exchange, symbol, bid, ask, at(secs)) })
+}
+
+fn zero_fees() -> FeeModel {
+    FeeModel {
+        taker_fees_bps: [("binance".to_string(), 0.0), ("kraken".to_string(), 0.0)].into_iter().collect(),
+        ..FeeModel::default()
+    }
+}
+
+#[test]
+fn canonicalizes_venue_symbols() {
+    assert_eq!(canonical_symbol("BTC-USDT"), "BTCUSDT");
+    assert_eq!(canonical_symbol("XBT/USD"), "BTCUSD");
+    assert_eq!(canonical_symbol("btcusdt"), "BTCUSDT");
+}
+
+#[test]
+fn fills_across_depth_while_profitable() {
+    let asks = [(100.0, 1.0), (101.0, 1.0), (103.0, 5.0)];
+    let bids = [(102.0, 1.5), (101.5, 5.0)];
+    let fill = executable_fill(&asks, &bids, 0.0, 0.0).unwrap();
+    assert_eq!(fill.size, 2.0);
+    assert_eq!(fill.buy_price, 100.5);
+    assert_eq!(fill.sell_price, (102.0 * 1.5 + 101.5 * 0.5) / 2.0);
+
+    // A 1% fee on each leg eats the whole edge.
+    assert_eq!(executable_fill(&asks, &bids, 0.01, 0.01), None);
+}
+
+#[test]
+fn tracks_opportunity_lifecycle() {
+    let mut detector = ArbitrageDetector::new(zero_fees());
+    assert!(detector.on_event(&quote("binance", "BTCUSD", 0, 99.0, 100.0)).is_empty());
+
+    let opened = detector.on_event(&quote("kraken", "XBT/USD", 1, 101.0, 102.0));
+    assert_eq!(opened.len(), 1);
+    assert_eq!(opened[0].status, OpportunityStatus::Open);
+    assert_eq!((opened[0].buy_exchange.as_str(), opened[0].sell_exchange.as_str()), ("binance", "kraken"));
+    assert_eq!(opened[0].size, 1.0);
+    assert_eq!(opened[0].expected_profit, 1.0);
+    assert_eq!(opened[0].edge_bps, 100.0);
+
+    let still_open = detector.on_event(&quote("kraken", "XBT/USD", 3, 100.5, 102.0));
+    assert_eq!(still_open[0].duration, Duration::from_secs(2));
+
+    let closed = detector.on_event(&quote("kraken", "XBT/USD", 4, 99.5, 102.0));
+    assert_eq!(closed.len(), 1);
+    assert_eq!(closed[0].status, OpportunityStatus::Closed);
+    assert_eq!(closed[0].duration, Duration::from_secs(3));
+}
+
+#[tokio::test]
+async fn threshold_and_withdrawal_costs_filter_opportunities() {
+    let events = || {
+        stream::iter(vec![quote("binance", "BTCUSD", 0, 99.0, 100.0), quote("kraken", "XBTUSD", 1, 100.5, 101.0)])
+    };
+
+    let found: Vec<_> = events().arbitrage(zero_fees()).collect().await;
+    assert_eq!(found.len(), 1);
+
+    let fees = FeeModel { threshold_bps: 60.0, ..zero_fees() };
+    assert!(events().arbitrage(fees).collect::<Vec<_>>().await.is_empty());
+
+    let mut fees = zero_fees();
+    fees.withdrawal_costs.insert("binance".to_string(), 1.0);
+    assert!(events().arbitrage(fees).collect::<Vec<_>>().await.is_empty());
+}
+
+#[test]
+fn expires_quotes_from_quiet_venues() {
+    let fees = FeeModel { max_quote_age: Some(Duration::from_secs(5)), ..zero_fees() };
+    let mut detector = ArbitrageDetector::new(fees);
+    detector.on_event(&quote("binance", "BTCUSD", 0, 99.0, 100.0));
+    assert_eq!(detector.on_event(&quote("kraken", "XBT/USD", 1, 101.0, 102.0))[0].status, OpportunityStatus::Open);
+    assert_eq!(detector.on_event(&quote("kraken", "XBT/USD", 5, 101.0, 102.0))[0].status, OpportunityStatus::Open);
+
+    // Binance has been quiet for more than 5s, so its quote no longer counts.
+    let closed = detector.on_event(&quote("kraken", "XBT/USD", 6, 101.0, 102.0));
+    assert_eq!(closed.len(), 1);
+    assert_eq!(closed[0].status, OpportunityStatus::Closed);
+    assert_eq!((closed[0].buy_exchange.as_str(), closed[0].buy_price), ("binance", 100.0));
+    assert_eq!(closed[0].duration, Duration::from_secs(5));
+    assert!(detector.on_event(&quote("kraken", "XBT/USD", 7, 101.0, 102.0)).is_empty());
+
+    // A fresh quote brings the venue back.
+    assert_eq!(detector.on_event(&quote("binance", "BTCUSD", 8, 99.0, 100.0))[0].status, OpportunityStatus::Open);
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use common::{at, temp_path};
+use soqa_sdk::export::book::{book_at, reconstruct, BookRecorder, BookSide, RowKind};
+use soqa_sdk::export::record::{parquet_path, ParquetSink, SqliteSink};
+use soqa_sdk::export::Sink;
+use soqa_sdk::models::{MarketEvent, OrderBookL2};
+use std::time::Duration;
+
+fn book(secs: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> OrderBookL2 {
+    common::book("bybit", "BTCUSDT", bids, asks, at(secs))
+}
+
+// Snapshot at 0s, deltas at 1s and 2s, the next snapshot at 10s.
+fn books() -> Vec<OrderBookL2> {
+    vec![
+        book(0, &[(100.0, 1.0), (99.0, 2.0)], &[(101.0, 1.0)]),
+        book(1, &[(100.0, 1.5), (99.0, 2.0)], &[(101.0, 1.0)]),
+        book(2, &[(99.0, 2.0)], &[(100.5, 3.0), (101.0, 1.0)]),
+        book(10, &[(99.5, 4.0)], &[(100.5, 3.0)]),
+    ]
+}
+
+#[test]
+fn records_snapshots_and_deltas() {
+    let mut recorder = BookRecorder::new(Duration::from_secs(10));
+    let rows: Vec<Vec<_>> = books().iter().map(|book| recorder.record(book)).collect();
+
+    assert!(rows[0].iter().all(|row| row.kind == RowKind::Snapshot && row.sequence == 1));
+    assert_eq!(rows[0].len(), 3);
+    let delta: Vec<_> = rows[1].iter().map(|row| (row.kind, row.side, row.price, row.size, row.sequence)).collect();
+    assert_eq!(delta, vec![(RowKind::Delta, BookSide::Bid, 100.0, 1.5, 2)]);
+    let delta: Vec<_> = rows[2].iter().map(|row| (row.side, row.price, row.size)).collect();
+    assert_eq!(delta, vec![(BookSide::Bid, 100.0, 0.0), (BookSide::Ask, 100.5, 3.0)]);
+    assert!(rows[3].iter().all(|row| row.kind == RowKind::Snapshot));
+
+    let rows: Vec<_> = rows.into_iter().flatten().collect();
+    for (i, expected) in books().iter().enumerate() {
+        let rebuilt = reconstruct(rows.clone(), "bybit", "BTCUSDT", expected.timestamp + Duration::from_millis(500)).unwrap();
+        assert_eq!((rebuilt.bids, rebuilt.asks, rebuilt.timestamp), (expected.bids.clone(), expected.asks.clone(), expected.timestamp), "book {}", i);
+    }
+    assert!(reconstruct(rows.clone(), "bybit", "BTCUSDT", at(0) - Duration::from_secs(1)).is_none());
+    assert!(reconstruct(rows, "okx", "BTCUSDT", at(5)).is_none());
+}
+
+#[tokio::test]
+async fn sqlite_sink_round_trips_and_resumes() {
+    let path = temp_path("book.db");
+    let _ = std::fs::remove_file(&path);
+    let mut sink = SqliteSink::open(&path, Duration::from_secs(10)).unwrap();
+    for book in &books()[..3] {
+        sink.write(&MarketEvent::L2(book.clone())).await.unwrap();
+    }
+    sink.flush().await.unwrap();
+    drop(sink);
+
+    // A second run continues the sequence and starts with a fresh snapshot.
+    let mut sink = SqliteSink::open(&path, Duration::from_secs(10)).unwrap();
+    sink.write(&MarketEvent::L2(books()[3].clone())).await.unwrap();
+    sink.flush().await.unwrap();
+
+    let rebuilt = book_at(&path, "bybit", "BTCUSDT", at(1)).unwrap().unwrap();
+    assert_eq!(rebuilt.bids, books()[1].bids);
+    let rebuilt = book_at(&path, "bybit", "BTCUSDT", at(9)).unwrap().unwrap();
+    assert_eq!((rebuilt.bids, rebuilt.asks), (books()[2].bids.clone(), books()[2].asks.clone()));
+    let rebuilt = book_at(&path, "bybit", "BTCUSDT", at(60)).unwrap().unwrap();
+    assert_eq!(rebuilt.bids, books()[3].bids);
+    assert!(book_at(&path, "bybit", "ETHUSDT", at(60)).unwrap().is_none());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn parquet_sink_round_trips() {
+    let path = temp_path("capture.parquet");
+    let mut sink = ParquetSink::create(&path, Duration::from_secs(10)).unwrap().with_row_group_rows(2);
+    for book in books() {
+        sink.write(&MarketEvent::L2(book)).await.unwrap();
+        sink.flush().await.unwrap();
+    }
+    sink.write(&MarketEvent::L2(book(11, &[(99.5, 4.0)], &[]))).await.unwrap();
+    sink.finish().unwrap();
+
+    assert!(parquet_path(&path, "l2").exists());
+    let rebuilt = book_at(&path, "bybit", "BTCUSDT", at(2)).unwrap().unwrap();
+    assert_eq!((rebuilt.bids, rebuilt.asks), (books()[2].bids.clone(), books()[2].asks.clone()));
+    let rebuilt = book_at(&path, "bybit", "BTCUSDT", at(11)).unwrap().unwrap();
+    assert!(rebuilt.asks.is_empty());
+    std::fs::remove_file(parquet_path(&path, "l2")).unwrap();
+}
+
+#[tokio::test]
+async fn parquet_sink_writes_old_groups_and_keeps_earlier_files() {
+    let path = temp_path("aged.parquet");
+    let l2 = parquet_path(&path, "l2");
+    let sink = ParquetSink::create(&path, Duration::from_secs(10)).unwrap();
+    let mut sink = sink.with_row_group_rows(1000).with_row_group_interval(Duration::ZERO);
+    sink.write(&MarketEvent::L2(books()[0].clone())).await.unwrap();
+    assert!(!l2.exists());
+    // The group is far from full, but its records are older than the interval.
+    sink.flush().await.unwrap();
+    assert!(l2.exists());
+    sink.close().await.unwrap();
+    drop(sink);
+
+    // A second run with the same name must not truncate the first one's file.
+    assert!(ParquetSink::create(&path, Duration::from_secs(10)).is_err());
+    let rebuilt = book_at(&path, "bybit", "BTCUSDT", at(0)).unwrap().unwrap();
+    assert_eq!(rebuilt.bids, books()[0].bids);
+    std::fs::remove_file(l2).unwrap();
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use soqa_sdk::codec::{decode, Encoding};
+use soqa_sdk::export::bus::topic;
+use soqa_sdk::export::{
+    export_events_to_binary, forward, replay, replay_merged, BusSink, MemoryBroker, Message, Sink, Transport,
+};
+use soqa_sdk::merge::Watermark;
+use soqa_sdk::models::MarketEvent;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+
+fn quote(exchange: &str, symbol: &str, bid: f64) -> MarketEvent {
+    MarketEvent::L1(common::quote(exchange, symbol, bid, bid + 1.0, common::millis(1_718_000_000_000)))
+}
+
+fn trade(price: f64) -> MarketEvent {
+    MarketEvent::Trade(common::trade("kraken", "XBT/USD", price, 0.5, SystemTime::UNIX_EPOCH))
+}
+
+#[tokio::test]
+async fn routes_keyed_messages_per_topic_in_batches() {
+    assert_eq!(topic("md", &trade(1.0)), "md.kraken.XBT-USD.trades");
+
+    let broker = MemoryBroker::new();
+    let mut sink = BusSink::new(broker.clone()).with_batching(3, Duration::from_secs(60));
+    sink.write(&quote("binance", "BTCUSDT", 100.0)).await.unwrap();
+    sink.write(&trade(200.0)).await.unwrap();
+    assert_eq!(broker.batches(), 0);
+    sink.write(&quote("binance", "BTCUSDT", 101.0)).await.unwrap();
+    assert_eq!(broker.batches(), 1);
+    sink.write(&trade(201.0)).await.unwrap();
+    sink.flush().await.unwrap();
+    assert_eq!(broker.batches(), 2);
+
+    assert_eq!(broker.topics(), vec!["soqa.binance.BTCUSDT.l1", "soqa.kraken.XBT-USD.trades"]);
+    let quotes = broker.messages("soqa.binance.BTCUSDT.l1");
+    assert_eq!(quotes.len(), 2);
+    assert_eq!(quotes[0].key, "binance:BTCUSDT");
+    let first: serde_json::Value = serde_json::from_slice(&quotes[0].payload).unwrap();
+    assert_eq!(first["type"], "l1");
+    assert_eq!(first["bid"], 100.0);
+    let trades = broker.messages("soqa.kraken.XBT-USD.trades");
+    assert_eq!(trades.iter().map(|m| m.key.as_str()).collect::<Vec<_>>(), vec!["kraken:XBT/USD"; 2]);
+}
+
+// Fails while `down` is set, then hands batches to the broker.
+struct Flaky {
+    broker: MemoryBroker,
+    down: Arc<AtomicBool>,
+}
+
+impl Transport for Flaky {
+    fn name(&self) -> &'static str {
+        "flaky"
+    }
+
+    async fn send(&mut self, batch: &[Message]) -> Result<(), soqa_sdk::error::SoqaError> {
+        if self.down.load(Ordering::SeqCst) {
+            return Err(soqa_sdk::error::SoqaError::ExportError("broker down".to_string()));
+        }
+        self.broker.send(batch).await
+    }
+}
+
+#[tokio::test]
+async fn keeps_failed_batches_up_to_the_buffer_cap() {
+    let broker = MemoryBroker::new();
+    let down = Arc::new(AtomicBool::new(true));
+    let transport = Flaky { broker: broker.clone(), down: down.clone() };
+    let mut sink = BusSink::new(transport).with_batching(2, Duration::from_secs(60)).with_max_buffered(3);
+    sink.write(&trade(1.0)).await.unwrap();
+    assert!(sink.write(&trade(2.0)).await.is_err());
+    // Writes inside the retry backoff only buffer.
+    sink.write(&trade(3.0)).await.unwrap();
+    sink.write(&trade(4.0)).await.unwrap();
+    assert!(sink.flush().await.is_err());
+
+    down.store(false, Ordering::SeqCst);
+    sink.flush().await.unwrap();
+    let prices: Vec<f64> = broker
+        .messages("soqa.kraken.XBT-USD.trades")
+        .iter()
+        .map(|m| serde_json::from_slice::<serde_json::Value>(&m.payload).unwrap()["price"].as_f64().unwrap())
+        .collect();
+    assert_eq!(prices, vec![2.0, 3.0, 4.0]);
+}
+
+#[derive(Default)]
+struct Recorded(Vec<MarketEvent>);
+
+impl Sink for Recorded {
+    async fn write(&mut self, event: &MarketEvent) -> Result<(), soqa_sdk::error::SoqaError> {
+        self.0.push(event.clone());
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), soqa_sdk::error::SoqaError> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn replays_captures_merged_by_exchange_time() {
+    let capture = |exchange: &str, offsets: &[u64]| {
+        let path = std::env::temp_dir().join(format!("soqa-bus-merge-{}-{}.bin", exchange, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+        let events: Vec<_> = offsets
+            .iter()
+            .map(|&ms| match quote(exchange, "BTCUSDT", ms as f64) {
+                MarketEvent::L1(mut book) => {
+                    book.timestamp += Duration::from_millis(ms);
+                    MarketEvent::L1(book)
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        export_events_to_binary(&events, &path).unwrap();
+        path
+    };
+    let okx = capture("okx", &[10, 30, 20]);
+    let binance = capture("binance", &[15, 25]);
+
+    let mut sink = Recorded::default();
+    let watermark = Watermark::new(Duration::from_millis(50));
+    assert_eq!(replay_merged(&[&okx, &binance], &mut sink, watermark.clone()).await.unwrap(), 5);
+    let order: Vec<(&str, f64)> = sink
+        .0
+        .iter()
+        .map(|event| match event {
+            MarketEvent::L1(book) => (book.exchange.as_str(), book.bid),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(order, vec![("okx", 10.0), ("binance", 15.0), ("okx", 20.0), ("binance", 25.0), ("okx", 30.0)]);
+    assert_eq!(watermark.late_events().total(), 0);
+    assert!(replay_merged(&[&okx, "/nonexistent/capture.bin"], &mut sink, Watermark::default()).await.is_err());
+    std::fs::remove_file(okx).unwrap();
+    std::fs::remove_file(binance).unwrap();
+}
+
+#[tokio::test]
+async fn replays_recorded_capture_as_binary_messages() {
+    let path = std::env::temp_dir().join(format!("soqa-bus-replay-{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = std::fs::remove_file(path);
+    let recorded: Vec<_> = (0..5).map(|i| quote("okx", "BTC-USDT", 100.0 + i as f64)).collect();
+    export_events_to_binary(&recorded, path).unwrap();
+
+    let broker = MemoryBroker::new();
+    let mut sink = BusSink::new(broker.clone()).with_encoding(Encoding::Binary);
+    assert_eq!(replay(path, &mut sink).await.unwrap(), 5);
+    let received: Vec<_> = broker
+        .messages("soqa.okx.BTC-USDT.l1")
+        .iter()
+        .map(|m| decode(&m.payload).unwrap().0)
+        .collect();
+    assert_eq!(received.len(), recorded.len());
+    for (got, want) in received.iter().zip(&recorded) {
+        assert_eq!(format!("{:?}", got), format!("{:?}", want));
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn forwards_live_events_and_flushes_when_idle() {
+    let (events, _) = broadcast::channel(16);
+    let broker = MemoryBroker::new();
+    let sink = BusSink::new(broker.clone()).with_batching(100, Duration::from_secs(60));
+    let task = tokio::spawn(forward(sink, events.subscribe(), Duration::from_millis(20)));
+
+    events.send(quote("bybit", "BTCUSDT", 100.0)).unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(broker.messages("soqa.bybit.BTCUSDT.l1").len(), 1);
+
+    events.send(quote("bybit", "BTCUSDT", 101.0)).unwrap();
+    drop(events);
+    task.await.unwrap();
+    assert_eq!(broker.messages("soqa.bybit.BTCUSDT.l1").len(), 2);
+}
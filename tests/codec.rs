@@ -0,0 +1,119 @@
+mod common;
+
+use futures_util::StreamExt;
+use soqa_sdk::api::websocket::websocket_route;
+use soqa_sdk::codec::{decode, encode, encode_to_vec, EventReader, MAX_RECORD_LEN};
+use soqa_sdk::export::export_events_to_binary;
+use soqa_sdk::models::{MarketEvent, OrderBookL1, OrderBookL2, QuoteSource, Trade};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+
+fn events() -> Vec<MarketEvent> {
+    let timestamp = SystemTime::UNIX_EPOCH + Duration::from_nanos(1_718_000_000_123_456_789);
+    vec![
+        MarketEvent::L1(OrderBookL1 {
+            bid_volume: 1.5,
+            ask_volume: 0.25,
+            source: QuoteSource::Bbo,
+            ..common::quote("binance", "BTCUSDT", 67012.34, 67012.35, timestamp)
+        }),
+        MarketEvent::L2(OrderBookL2 {
+            exchange: "okx".to_string(),
+            symbol: "BTC-USDT".to_string(),
+            bids: vec![(100.0, 1.0), (99.5, 2.0)],
+            asks: vec![(100.5, 3.0)],
+            timestamp,
+        }),
+        MarketEvent::Trade(Trade { side: "sell".to_string(), ..common::trade("kraken", "XBT/USD", 67005.0, 0.01, timestamp) }),
+    ]
+}
+
+fn json(event: &MarketEvent) -> String {
+    serde_json::to_string(event).unwrap()
+}
+
+#[test]
+fn round_trips_every_kind() {
+    let mut buf = Vec::new();
+    for event in events() {
+        encode(&event, &mut buf);
+    }
+    let mut offset = 0;
+    for expected in events() {
+        let (event, used) = decode(&buf[offset..]).unwrap();
+        assert_eq!(json(&event), json(&expected));
+        offset += used;
+    }
+    assert_eq!(offset, buf.len());
+
+    let l1 = encode_to_vec(&events()[0]);
+    assert_eq!(l1.len(), 63);
+    assert!(decode(&l1[..l1.len() - 1]).is_err());
+
+    // Records written before the quote source was added end after `ask_volume`.
+    let mut older = l1[..l1.len() - 1].to_vec();
+    older[..4].copy_from_slice(&58u32.to_le_bytes());
+    match decode(&older).unwrap().0 {
+        MarketEvent::L1(book) => assert_eq!((book.bid, book.source), (67012.34, QuoteSource::Ticker)),
+        other => panic!("expected L1, got {:?}", other),
+    }
+
+    let mut future = l1.clone();
+    future[4] = 2;
+    assert!(decode(&future).is_err());
+}
+
+#[test]
+fn binary_file_sink_appends_records() {
+    let path = std::env::temp_dir().join(format!("soqa-codec-{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = std::fs::remove_file(path);
+    export_events_to_binary(&events()[..2], path).unwrap();
+    export_events_to_binary(&events()[2..], path).unwrap();
+
+    let read: Vec<_> = EventReader::new(std::fs::File::open(path).unwrap()).collect::<Result<_, _>>().unwrap();
+    assert_eq!(read.iter().map(json).collect::<Vec<_>>(), events().iter().map(json).collect::<Vec<_>>());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn long_strings_and_lengths_are_bounded() {
+    let mut event = events().remove(2);
+    if let MarketEvent::Trade(trade) = &mut event {
+        trade.symbol = "é".repeat(200);
+    }
+    match decode(&encode_to_vec(&event)).unwrap().0 {
+        MarketEvent::Trade(trade) => assert_eq!(trade.symbol, "é".repeat(127)),
+        other => panic!("expected a trade, got {:?}", other),
+    }
+
+    let mut huge = ((MAX_RECORD_LEN + 1) as u32).to_le_bytes().to_vec();
+    huge.extend_from_slice(&[0; 64]);
+    assert!(decode(&huge).is_err());
+    let mut reader = EventReader::new(&huge[..]);
+    assert!(reader.next().unwrap().is_err());
+}
+
+#[tokio::test]
+async fn gateway_sends_binary_frames_on_request() {
+    let (events_tx, _) = broadcast::channel(16);
+    let (addr, server) = warp::serve(websocket_route(events_tx.clone())).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let (mut binary, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws?encoding=binary", addr)).await.unwrap();
+    let (mut text, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+    while events_tx.receiver_count() < 2 {
+        tokio::task::yield_now().await;
+    }
+    events_tx.send(events()[2].clone()).unwrap();
+
+    match binary.next().await.unwrap().unwrap() {
+        Message::Binary(frame) => assert_eq!(json(&decode(&frame).unwrap().0), json(&events()[2])),
+        other => panic!("expected binary frame, got {:?}", other),
+    }
+    match text.next().await.unwrap().unwrap() {
+        Message::Text(frame) => assert_eq!(frame, json(&events()[2])),
+        other => panic!("expected text frame, got {:?}", other),
+    }
+}
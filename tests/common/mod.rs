@@ -0,0 +1,55 @@
+// Events shared by the integration tests. A test overrides the fields it checks with struct
+// update syntax, so a new model field only needs a value here.
+#![allow(dead_code)]
+
+use soqa_sdk::models::{OrderBookL1, OrderBookL2, QuoteSource, Trade};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+pub fn millis(ms: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(ms)
+}
+
+// `secs` seconds into a fixed, recent day.
+pub fn at(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + secs)
+}
+
+// A file in the temp dir unique to this test process.
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("soqa-test-{}-{}", std::process::id(), name))
+}
+
+pub fn quote(exchange: &str, symbol: &str, bid: f64, ask: f64, timestamp: SystemTime) -> OrderBookL1 {
+    OrderBookL1 {
+        exchange: exchange.to_string(),
+        symbol: symbol.to_string(),
+        bid,
+        bid_volume: 1.0,
+        ask,
+        ask_volume: 1.0,
+        timestamp,
+        source: QuoteSource::Ticker,
+    }
+}
+
+pub fn trade(exchange: &str, symbol: &str, price: f64, volume: f64, timestamp: SystemTime) -> Trade {
+    Trade {
+        exchange: exchange.to_string(),
+        symbol: symbol.to_string(),
+        price,
+        volume,
+        side: "buy".to_string(),
+        timestamp,
+    }
+}
+
+pub fn book(exchange: &str, symbol: &str, bids: &[(f64, f64)], asks: &[(f64, f64)], timestamp: SystemTime) -> OrderBookL2 {
+    OrderBookL2 {
+        exchange: exchange.to_string(),
+        symbol: symbol.to_string(),
+        bids: bids.to_vec(),
+        asks: asks.to_vec(),
+        timestamp,
+    }
+}
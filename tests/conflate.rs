@@ -0,0 +1,95 @@
+mod common;
+
+use futures_util::stream::{self, StreamExt};
+use soqa_sdk::api::websocket::websocket_route;
+use soqa_sdk::conflate::{ConflateExt, Conflation, Conflator};
+use soqa_sdk::exchanges::receiver_stream;
+use soqa_sdk::models::MarketEvent;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, mpsc};
+
+fn quote(symbol: &str, bid: f64) -> MarketEvent {
+    MarketEvent::L1(common::quote("binance", symbol, bid, bid, SystemTime::UNIX_EPOCH))
+}
+
+fn bid(event: &MarketEvent) -> f64 {
+    match event {
+        MarketEvent::L1(book) => book.bid,
+        _ => panic!("expected a quote, got {:?}", event),
+    }
+}
+
+#[test]
+fn coalesces_per_market_and_emits_big_moves_at_once() {
+    let mut conflator = Conflator::new(Conflation::every(Duration::from_millis(100)).with_min_change_bps(10.0));
+    // First quote of each market goes out immediately.
+    assert_eq!(conflator.push(quote("BTCUSDT", 100.0)).map(|e| bid(&e)), Some(100.0));
+    assert!(conflator.push(quote("ETHUSDT", 10.0)).is_some());
+    // 5 bps: held, and replaced by the next small move.
+    assert!(conflator.push(quote("BTCUSDT", 100.05)).is_none());
+    assert!(conflator.push(quote("BTCUSDT", 100.08)).is_none());
+    // 20 bps from the last emitted quote: emitted, and nothing is left pending.
+    assert_eq!(conflator.push(quote("BTCUSDT", 100.2)).map(|e| bid(&e)), Some(100.2));
+    assert!(conflator.push(quote("ETHUSDT", 10.001)).is_none());
+    let trade = MarketEvent::Trade(common::trade("binance", "BTCUSDT", 1.0, 1.0, SystemTime::UNIX_EPOCH));
+    assert!(conflator.push(trade).is_some());
+    assert_eq!(conflator.tick().iter().map(bid).collect::<Vec<_>>(), vec![10.001]);
+    assert!(conflator.tick().is_empty());
+
+    // Change-only mode drops small moves instead of holding them.
+    let mut on_change = Conflator::new(Conflation::on_change(10.0));
+    on_change.push(quote("BTCUSDT", 100.0));
+    assert!(on_change.push(quote("BTCUSDT", 100.05)).is_none());
+    assert!(on_change.tick().is_empty());
+    assert_eq!(Conflation::from_options(Some(0), None), None);
+}
+
+#[tokio::test]
+async fn emits_latest_quote_each_interval() {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let conflated = receiver_stream(rx).conflate(Conflation::every(Duration::from_millis(50)));
+    let mut conflated = std::pin::pin!(conflated);
+    for bid in [1.0, 2.0, 3.0, 4.0] {
+        tx.send(quote("BTCUSDT", bid)).unwrap();
+    }
+    let first = tokio::time::timeout(Duration::from_millis(20), conflated.next()).await.unwrap().unwrap();
+    assert_eq!(bid(&first), 1.0);
+    let latest = tokio::time::timeout(Duration::from_secs(1), conflated.next()).await.unwrap().unwrap();
+    assert_eq!(bid(&latest), 4.0);
+
+    // Held quotes are flushed when the input ends.
+    tx.send(quote("BTCUSDT", 5.0)).unwrap();
+    drop(tx);
+    assert_eq!(conflated.map(|e| bid(&e)).collect::<Vec<_>>().await, vec![5.0]);
+
+    let passthrough: Vec<_> = stream::iter(vec![quote("A", 1.0), quote("B", 2.0)]).conflate(Conflation::every(Duration::from_secs(60))).collect().await;
+    assert_eq!(passthrough.len(), 2);
+}
+
+#[tokio::test]
+async fn websocket_clients_choose_their_own_conflation() {
+    let (events, _) = broadcast::channel(64);
+    let routes = websocket_route(events.clone());
+    let mut raw = warp::test::ws().path("/ws").handshake(routes.clone()).await.unwrap();
+    let mut conflated = warp::test::ws().path("/ws?conflate_ms=60000&conflate_bps=50").handshake(routes).await.unwrap();
+
+    for bid in [100.0, 100.1, 101.0] {
+        events.send(quote("BTCUSDT", bid)).unwrap();
+    }
+    let mut received = Vec::new();
+    for _ in 0..3 {
+        let message = raw.recv().await.unwrap();
+        let event: serde_json::Value = serde_json::from_str(message.to_str().unwrap()).unwrap();
+        received.push(event["bid"].as_f64().unwrap());
+    }
+    assert_eq!(received, vec![100.0, 100.1, 101.0]);
+
+    // 100.1 is 10 bps off and waits for the (distant) tick; 101.0 is 100 bps off.
+    let mut received = Vec::new();
+    for _ in 0..2 {
+        let message = conflated.recv().await.unwrap();
+        let event: serde_json::Value = serde_json::from_str(message.to_str().unwrap()).unwrap();
+        received.push(event["bid"].as_f64().unwrap());
+    }
+    assert_eq!(received, vec![100.0, 101.0]);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use soqa_sdk::delivery::{Delivery, EventQueue, OverflowPolicy};
+use soqa_sdk::models::MarketEvent;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+fn quote(symbol: &str, bid: f64) -> MarketEvent {
+    MarketEvent::L1(common::quote("binance", symbol, bid, bid + 1.0, SystemTime::UNIX_EPOCH))
+}
+
+fn trade(price: f64) -> MarketEvent {
+    MarketEvent::Trade(common::trade("binance", "BTCUSDT", price, 1.0, SystemTime::UNIX_EPOCH))
+}
+
+fn price(event: &MarketEvent) -> f64 {
+    match event {
+        MarketEvent::L1(book) => book.bid,
+        MarketEvent::Trade(trade) => trade.price,
+        MarketEvent::L2(_) => unreachable!(),
+    }
+}
+
+async fn drain(queue: &EventQueue) -> Vec<f64> {
+    queue.close();
+    let mut prices = Vec::new();
+    while let Some(event) = queue.pop().await {
+        prices.push(price(&event));
+    }
+    prices
+}
+
+#[tokio::test]
+async fn drop_policies_keep_the_queue_bounded() {
+    let oldest = EventQueue::new(Delivery::new(2, OverflowPolicy::DropOldest));
+    let newest = EventQueue::new(Delivery::new(2, OverflowPolicy::DropNewest));
+    for bid in [1.0, 2.0, 3.0] {
+        oldest.push(quote("BTCUSDT", bid)).await;
+        newest.push(quote("BTCUSDT", bid)).await;
+    }
+    assert_eq!((oldest.len(), oldest.dropped()), (2, 1));
+    assert_eq!(drain(&oldest).await, vec![2.0, 3.0]);
+    assert_eq!((newest.len(), newest.dropped()), (2, 1));
+    assert_eq!(drain(&newest).await, vec![1.0, 2.0]);
+}
+
+#[tokio::test]
+async fn conflate_keeps_the_latest_book_once_full() {
+    let queue = EventQueue::new(Delivery::new(3, OverflowPolicy::Conflate));
+    assert!(!queue.push(quote("BTCUSDT", 1.0)).await);
+    queue.push(trade(5.0)).await;
+    // Below capacity nothing is conflated.
+    assert!(!queue.push(quote("BTCUSDT", 2.0)).await);
+    // Full: replaces the newest queued BTCUSDT quote in place.
+    assert!(queue.push(quote("BTCUSDT", 3.0)).await);
+    // No queued ETHUSDT quote, so the oldest queued quote makes room.
+    assert!(queue.push(quote("ETHUSDT", 10.0)).await);
+    assert_eq!(queue.dropped(), 2);
+    // Trades are never conflated away; they wait for room.
+    let blocked = tokio::time::timeout(Duration::from_millis(50), queue.push(trade(6.0))).await;
+    assert!(blocked.is_err());
+    assert_eq!(drain(&queue).await, vec![5.0, 3.0, 10.0]);
+}
+
+#[tokio::test]
+async fn block_waits_for_the_consumer() {
+    let queue = EventQueue::new(Delivery::new(1, OverflowPolicy::Block));
+    queue.push(quote("BTCUSDT", 1.0)).await;
+    let blocked = tokio::time::timeout(Duration::from_millis(50), queue.push(quote("BTCUSDT", 2.0))).await;
+    assert!(blocked.is_err());
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let consumer = {
+        let received = received.clone();
+        queue.spawn_consumer(move |event| received.lock().unwrap().push(price(&event)))
+    };
+    for bid in [3.0, 4.0, 5.0] {
+        tokio::time::timeout(Duration::from_secs(1), queue.push(quote("BTCUSDT", bid))).await.unwrap();
+    }
+    queue.close();
+    consumer.await.unwrap();
+    assert_eq!(*received.lock().unwrap(), vec![1.0, 3.0, 4.0, 5.0]);
+    assert_eq!(queue.dropped(), 0);
+    // Closed: nothing more is accepted.
+    queue.push(quote("BTCUSDT", 6.0)).await;
+    assert!(queue.is_empty());
+}
+
+#[tokio::test]
+async fn queue_streams_until_closed() {
+    use futures_util::StreamExt;
+    let queue = EventQueue::new(Delivery::new(4, OverflowPolicy::Block));
+    queue.push(quote("BTCUSDT", 1.0)).await;
+    queue.push(trade(2.0)).await;
+    queue.close();
+    let prices: Vec<f64> = queue.into_stream().map(|event| price(&event)).collect().await;
+    assert_eq!(prices, vec![1.0, 2.0]);
+}
@@ -0,0 +1,277 @@
+use soqa_sdk::exchanges::book::{BookUpdate, Checksum, LocalBook};
+use soqa_sdk::exchanges::{binance, bybit, kraken, kucoin, okx};
+use soqa_sdk::models::QuoteSource;
+use std::time::{Duration, SystemTime};
+
+fn at(ms: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(ms)
+}
+
+#[test]
+fn local_book_applies_snapshots_and_deltas() {
+    let mut book = LocalBook::new(2);
+    book.apply(&BookUpdate {
+        snapshot: true,
+        bids: vec![(100.0, 1.0), (99.0, 2.0), (98.0, 3.0)],
+        asks: vec![(101.0, 1.0), (102.0, 2.0)],
+        timestamp: at(1),
+        ..BookUpdate::default()
+    })
+    .unwrap();
+    assert_eq!(book.bids(), &[(100.0, 1.0), (99.0, 2.0)]);
+
+    book.apply(&BookUpdate {
+        snapshot: false,
+        bids: vec![(100.0, 0.0), (99.5, 4.0)],
+        asks: vec![(100.5, 1.5), (102.0, 0.0)],
+        timestamp: at(2),
+        ..BookUpdate::default()
+    })
+    .unwrap();
+    assert_eq!(book.bids(), &[(99.5, 4.0), (99.0, 2.0)]);
+    assert_eq!(book.asks(), &[(100.5, 1.5), (101.0, 1.0)]);
+
+    book.apply(&BookUpdate { snapshot: true, timestamp: at(3), ..BookUpdate::default() }).unwrap();
+    assert!(book.is_empty());
+}
+
+#[test]
+fn binance_depth_and_trades() {
+    let depth = binance::parse_l2(
+        r#"{"lastUpdateId":160,"bids":[["0.0024","10"],["0.0023","5"]],"asks":[["0.0026","100"]]}"#,
+        "BNBBTC",
+    )
+    .unwrap();
+    assert_eq!(depth.bids, vec![(0.0024, 10.0), (0.0023, 5.0)]);
+    assert_eq!(depth.asks, vec![(0.0026, 100.0)]);
+
+    // The combined stream wraps each payload and names the stream, which is all a depth message has to go by.
+    let combined: binance::StreamMsg<binance::DepthMsg> = serde_json::from_str(
+        r#"{"stream":"bnbbtc@depth20@100ms","data":{"lastUpdateId":160,"bids":[["0.0024","10"]],"asks":[["0.0026","100"]]}}"#,
+    )
+    .unwrap();
+    assert_eq!(combined.symbol(), "bnbbtc");
+    assert_eq!(combined.data.to_l2("BNBBTC").unwrap().bids, vec![(0.0024, 10.0)]);
+
+    let trade = binance::parse_trade(
+        r#"{"e":"trade","E":1718000000001,"s":"BTCUSDT","t":12345,"p":"67000.10","q":"0.002","T":1718000000000,"m":true,"M":true}"#,
+    )
+    .unwrap();
+    assert_eq!((trade.symbol.as_str(), trade.price, trade.volume, trade.side.as_str()), ("BTCUSDT", 67000.1, 0.002, "sell"));
+    assert_eq!(trade.timestamp, at(1718000000000));
+}
+
+#[test]
+fn bybit_depth_and_trades() {
+    let snapshot = bybit::parse_book_update(
+        r#"{"topic":"orderbook.50.BTCUSDT","ts":1718000000000,"type":"snapshot","data":{"s":"BTCUSDT","b":[["67000","1"],["66999","2"]],"a":[["67001","3"]],"u":1,"seq":1}}"#,
+    )
+    .unwrap();
+    assert!(snapshot.snapshot);
+    let delta = bybit::parse_book_update(
+        r#"{"topic":"orderbook.50.BTCUSDT","ts":1718000000100,"type":"delta","data":{"s":"BTCUSDT","b":[["67000","0"]],"a":[["67000.5","1"]],"u":2,"seq":2}}"#,
+    )
+    .unwrap();
+    assert!(!delta.snapshot);
+    let mut book = LocalBook::new(50);
+    book.apply(&snapshot).unwrap();
+    book.apply(&delta).unwrap();
+    assert_eq!(book.bids(), &[(66999.0, 2.0)]);
+    assert_eq!(book.asks(), &[(67000.5, 1.0), (67001.0, 3.0)]);
+
+    let trades = bybit::parse_trades(
+        r#"{"topic":"publicTrade.BTCUSDT","type":"snapshot","ts":1718000000200,"data":[{"T":1718000000199,"s":"BTCUSDT","S":"Buy","v":"0.01","p":"67001","L":"PlusTick","i":"1","BT":false}]}"#,
+        "BTCUSDT",
+    )
+    .unwrap();
+    assert_eq!((trades[0].price, trades[0].volume, trades[0].side.as_str()), (67001.0, 0.01, "buy"));
+    assert!(bybit::parse_book_update(r#"{"success":true,"ret_msg":"","op":"subscribe"}"#).is_none());
+}
+
+#[test]
+fn okx_trades() {
+    let trades = okx::parse_trades(
+        r#"{"arg":{"channel":"trades","instId":"BTC-USDT"},"data":[{"instId":"BTC-USDT","tradeId":"1","px":"67000.5","sz":"0.1","side":"sell","ts":"1718000000001","count":"1"}]}"#,
+        "BTCUSDT",
+    )
+    .unwrap();
+    assert_eq!((trades[0].price, trades[0].volume, trades[0].side.as_str()), (67000.5, 0.1, "sell"));
+}
+
+#[test]
+fn kucoin_depth_and_matches() {
+    let book = kucoin::parse_l2(
+        r#"{"type":"message","topic":"/spotMarket/level2Depth50:BTC-USDT","subject":"level2","data":{"asks":[["67001","1"]],"bids":[["67000","2"]],"timestamp":1718000000000}}"#,
+        "BTCUSDT",
+    )
+    .unwrap();
+    assert_eq!((book.bids[0], book.asks[0]), ((67000.0, 2.0), (67001.0, 1.0)));
+
+    let trade = kucoin::parse_trade(
+        r#"{"type":"message","topic":"/market/match:BTC-USDT","subject":"trade.l3match","data":{"price":"67000.1","size":"0.05","side":"buy","symbol":"BTC-USDT","time":"1718000000000123456","tradeId":"1"}}"#,
+        "BTCUSDT",
+    )
+    .unwrap();
+    assert_eq!((trade.price, trade.volume, trade.side.as_str()), (67000.1, 0.05, "buy"));
+    assert_eq!(trade.timestamp, SystemTime::UNIX_EPOCH + Duration::from_nanos(1718000000000123456));
+}
+
+#[test]
+fn kraken_book_and_trades() {
+    let snapshot = kraken::parse_book_update(
+        r#"[0,{"as":[["67001.0","1.0","1718000000.100000"]],"bs":[["67000.0","2.0","1718000000.200000"]]},"book-25","XBT/USD"]"#,
+    )
+    .unwrap();
+    assert!(snapshot.snapshot);
+    assert_eq!(snapshot.timestamp, at(1718000000200));
+    // Both sides changed in one update: each arrives in its own object.
+    let update = kraken::parse_book_update(
+        r#"[0,{"a":[["67001.0","0.00000000","1718000000.300000"]]},{"b":[["67000.5","1.5","1718000000.300000"]],"c":"974942666"},"book-25","XBT/USD"]"#,
+    )
+    .unwrap();
+    let mut book = LocalBook::new(25);
+    book.apply(&snapshot).unwrap();
+    book.apply(&update).unwrap();
+    assert_eq!(book.bids(), &[(67000.5, 1.5), (67000.0, 2.0)]);
+    assert!(book.asks().is_empty());
+
+    let trades = kraken::parse_trades(
+        r#"[0,[["67000.10000","0.01000000","1718000000.500000","s","m",""],["67000.20000","0.02000000","1718000000.600000","b","l",""]],"trade","XBT/USD"]"#,
+        "BTCUSD",
+    )
+    .unwrap();
+    assert_eq!(trades.len(), 2);
+    assert_eq!((trades[0].side.as_str(), trades[1].side.as_str()), ("sell", "buy"));
+    assert!(kraken::parse_trades(r#"{"event":"heartbeat"}"#, "BTCUSD").is_none());
+}
+
+#[test]
+fn best_bid_offer_channels() {
+    let binance = binance::parse_bbo(
+        r#"{"u":400900217,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}"#,
+    )
+    .unwrap();
+    assert_eq!((binance.symbol.as_str(), binance.bid, binance.ask_volume), ("BNBUSDT", 25.3519, 40.66));
+
+    let okx = okx::parse_bbo(
+        r#"{"arg":{"channel":"bbo-tbt","instId":"BTC-USDT"},"data":[{"asks":[["67001.5","0.8","0","3"]],"bids":[["67001.4","1.2","0","5"]],"ts":"1718000000123","seqId":363996337}]}"#,
+        "BTCUSDT",
+    )
+    .unwrap();
+    assert_eq!((okx.bid, okx.bid_volume, okx.ask, okx.timestamp), (67001.4, 1.2, 67001.5, at(1718000000123)));
+
+    let kucoin = kucoin::parse_bbo(
+        r#"{"type":"message","topic":"/spotMarket/level1:BTC-USDT","subject":"level1","data":{"asks":["9989","8"],"bids":["9984","10"],"timestamp":1586948108193}}"#,
+        "BTCUSDT",
+    )
+    .unwrap();
+    assert_eq!((kucoin.bid, kucoin.bid_volume, kucoin.ask, kucoin.ask_volume), (9984.0, 10.0, 9989.0, 8.0));
+    assert_eq!(kucoin.timestamp, at(1586948108193));
+    assert!(kucoin::parse_bbo(include_str!("data/kucoin_ticker.json"), "BTCUSDT").is_none());
+
+    let kraken = kraken::parse_bbo(
+        r#"[0,["5698.40000","5700.00000","1542057299.545897","1.01234567","0.98765432"],"spread","XBT/USD"]"#,
+        "BTCUSD",
+    )
+    .unwrap();
+    assert_eq!((kraken.bid, kraken.bid_volume, kraken.ask, kraken.ask_volume), (5698.4, 1.01234567, 5700.0, 0.98765432));
+    assert_eq!(kraken.timestamp, SystemTime::UNIX_EPOCH + Duration::from_micros(1542057299545897));
+
+    for quote in [&binance, &okx, &kucoin, &kraken] {
+        assert_eq!(quote.source, QuoteSource::Bbo);
+    }
+    // The default channels record where their quotes came from too.
+    assert_eq!(bybit::parse_l1(include_str!("data/bybit_orderbook.json"), "ETHUSDT").unwrap().source, QuoteSource::Bbo);
+    assert_eq!(okx::parse_l1(include_str!("data/okx_books.json"), "BTCUSDT").unwrap().source, QuoteSource::Book);
+    assert_eq!(binance::parse_l1(include_str!("data/binance_ticker.json")).unwrap().source, QuoteSource::Ticker);
+}
+
+// The sample book from OKX's checksum guide: `3366.1:7:3366.8:9:3366:6:3368:8` hashes to -1881014294.
+const OKX_SNAPSHOT: &str = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[["3366.8","9","10","3"],["3368","8","3","4"]],"bids":[["3366.1","7","0","3"],["3366","6","3","4"]],"ts":"1718000000000","checksum":-1881014294,"prevSeqId":-1,"seqId":1}]}"#;
+
+#[test]
+fn okx_book_checksum_and_resync() {
+    let raw = |levels: &[(&str, &str)]| levels.iter().map(|(p, s)| (p.to_string(), s.to_string())).collect::<Vec<_>>();
+    let checksum = Checksum::Okx.compute(&raw(&[("3366.1", "7"), ("3366", "6")]), &raw(&[("3366.8", "9"), ("3368", "8")]));
+    assert_eq!(checksum as i32, -1881014294);
+
+    let mut book = LocalBook::new(400).with_checksum(Checksum::Okx);
+    let snapshot = okx::parse_book_update(OKX_SNAPSHOT).unwrap();
+    assert!(snapshot.snapshot);
+    book.apply(&snapshot).unwrap();
+    assert_eq!(book.bids(), &[(3366.1, 7.0), (3366.0, 6.0)]);
+
+    // Removing 3366.1 leaves `3366:6:3366.8:9:3368:8`; the venue's checksum is taken as sent.
+    let remaining = Checksum::Okx.compute(&raw(&[("3366", "6")]), &raw(&[("3366.8", "9"), ("3368", "8")]));
+    let update = |checksum: i32| {
+        format!(
+            r#"{{"arg":{{"channel":"books","instId":"BTC-USDT"}},"action":"update","data":[{{"asks":[],"bids":[["3366.1","0","0","0"]],"ts":"1718000000100","checksum":{},"prevSeqId":1,"seqId":2}}]}}"#,
+            checksum
+        )
+    };
+    let mut diverged = book.clone();
+    book.apply(&okx::parse_book_update(&update(remaining as i32)).unwrap()).unwrap();
+    assert_eq!(book.bids(), &[(3366.0, 6.0)]);
+    // Default OKX L1 is the top of this book, since the delta alone has no ask.
+    let quote = book.quote("okx", "BTC-USDT", SystemTime::UNIX_EPOCH).unwrap();
+    assert_eq!((quote.bid, quote.bid_volume, quote.ask, quote.ask_volume), (3366.0, 6.0, 3366.8, 9.0));
+
+    let mismatch = diverged.apply(&okx::parse_book_update(&update(12345)).unwrap()).unwrap_err();
+    assert_eq!((mismatch.expected, mismatch.actual), (12345, remaining));
+    assert!(diverged.awaiting_snapshot() && diverged.is_empty());
+    // Deltas are ignored until the snapshot of the new subscription arrives.
+    diverged.apply(&okx::parse_book_update(&update(remaining as i32)).unwrap()).unwrap();
+    assert!(diverged.is_empty());
+    diverged.apply(&snapshot).unwrap();
+    assert!(!diverged.awaiting_snapshot());
+    assert_eq!(diverged.asks(), &[(3366.8, 9.0), (3368.0, 8.0)]);
+}
+
+#[test]
+fn kraken_book_checksum_and_resync() {
+    // The ten-level sample book from Kraken's checksum guide, whose published checksum is
+    // 974947235. Each price and volume loses its '.' and leading zeros, asks come first.
+    let side = |prices: &[&str]| -> Vec<(String, String)> {
+        prices.iter().map(|p| (p.to_string(), "0.00000500".to_string())).collect()
+    };
+    let asks = side(&[
+        "0.05005", "0.05010", "0.05015", "0.05020", "0.05025", "0.05030", "0.05035", "0.05040", "0.05045", "0.05050",
+    ]);
+    let bids = side(&[
+        "0.05000", "0.04995", "0.04990", "0.04980", "0.04975", "0.04970", "0.04965", "0.04960", "0.04955", "0.04950",
+    ]);
+    assert_eq!(Checksum::Kraken.compute(&bids, &asks), 974947235);
+
+    // The snapshot starts with a larger best bid; the update brings the book back to the guide's,
+    // so the guide's checksum is the one it must carry.
+    let mut resized = bids.clone();
+    resized[0].1 = "0.00001000".to_string();
+    let levels = |side: &[(String, String)]| {
+        side.iter().map(|(p, v)| format!(r#"["{}","{}","1718000000.100000"]"#, p, v)).collect::<Vec<_>>().join(",")
+    };
+    let snapshot = kraken::parse_book_update(&format!(
+        r#"[336,{{"as":[{}],"bs":[{}]}},"book-25","XBT/USD"]"#,
+        levels(&asks),
+        levels(&resized)
+    ))
+    .unwrap();
+    let mut book = LocalBook::new(25).with_checksum(Checksum::Kraken);
+    book.apply(&snapshot).unwrap();
+
+    let update = |checksum: u32| {
+        kraken::parse_book_update(&format!(
+            r#"[336,{{"b":[["0.05000","0.00000500","1718000000.200000"]],"c":"{}"}},"book-25","XBT/USD"]"#,
+            checksum
+        ))
+        .unwrap()
+    };
+    let expected = 974947235;
+    let mut diverged = book.clone();
+    book.apply(&update(expected)).unwrap();
+    assert_eq!(book.bids()[0], (0.05, 0.000005));
+
+    assert!(diverged.apply(&update(expected ^ 1)).is_err());
+    assert!(diverged.awaiting_snapshot() && diverged.is_empty());
+    diverged.apply(&snapshot).unwrap();
+    assert_eq!(diverged.bids().len(), 10);
+}
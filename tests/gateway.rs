@@ -0,0 +1,117 @@
+mod common;
+
+use soqa_sdk::api::auth::ApiKeys;
+use soqa_sdk::api::gateway::{routes, GatewayConfig};
+use soqa_sdk::codec::Encoding;
+use soqa_sdk::delivery::{Delivery, OverflowPolicy};
+use soqa_sdk::health::HealthMonitor;
+use soqa_sdk::models::{MarketEvent, OrderBookL1};
+use soqa_sdk::subscriptions::{Channel, SubscriptionManager};
+use std::time::SystemTime;
+
+const CONFIG: &str = r#"
+bind = "0.0.0.0:9000"
+api_keys = ["secret"]
+cors_origins = ["https://dashboard.example.com"]
+
+[[feeds]]
+exchange = "binance"
+symbol = "BTCUSDT"
+channels = ["l1", "trades"]
+
+[[feeds]]
+exchange = "okx"
+symbol = "BTCUSDT"
+queue_capacity = 64
+overflow = "drop-oldest"
+
+[[sinks]]
+url = "nats://127.0.0.1:4222"
+encoding = "binary"
+"#;
+
+fn quote() -> MarketEvent {
+    let quote = common::quote("binance", "BTCUSDT", 100.0, 100.5, SystemTime::now());
+    MarketEvent::L1(OrderBookL1 { ask_volume: 2.0, ..quote })
+}
+
+#[test]
+fn config_loads_feeds_and_sinks() {
+    let config = GatewayConfig::from_toml_str(CONFIG).unwrap();
+    assert_eq!(config.bind.port(), 9000);
+    assert_eq!(config.feeds[0].channels, vec![Channel::L1, Channel::Trades]);
+    assert_eq!(config.feeds[1].channels, vec![Channel::L1]);
+    assert_eq!(config.feeds[0].delivery, Delivery::default());
+    assert_eq!(config.feeds[1].delivery, Delivery::new(64, OverflowPolicy::DropOldest));
+    assert_eq!(config.sinks[0].encoding, Encoding::Binary);
+    assert_eq!(config.sinks[0].prefix, "soqa");
+    config.validate().unwrap();
+}
+
+#[test]
+fn validate_rejects_what_cannot_run() {
+    let adapter_l2 = r#"[[feeds]]
+exchange = "myvenue"
+symbol = "BTCUSDT"
+channels = ["l2"]
+adapter = "adapters/myvenue.toml""#;
+    assert!(GatewayConfig::from_toml_str(adapter_l2).unwrap().validate().is_err());
+    let unknown = "[[feeds]]\nexchange = \"nowhere\"\nsymbol = \"BTCUSDT\"";
+    assert!(GatewayConfig::from_toml_str(unknown).unwrap().validate().is_err());
+    let adapter_bbo = "[[feeds]]\nexchange = \"myvenue\"\nsymbol = \"BTCUSDT\"\nbbo = true\nadapter = \"adapters/myvenue.toml\"";
+    assert!(GatewayConfig::from_toml_str(adapter_bbo).unwrap().validate().is_err());
+    let origin = r#"cors_origins = ["dashboard.example.com/app"]"#;
+    assert!(GatewayConfig::from_toml_str(origin).unwrap().validate().is_err());
+    let ttl_on_file = "[[sinks]]\nurl = \"depth.db\"\nttl_secs = 5";
+    assert!(GatewayConfig::from_toml_str(ttl_on_file).unwrap().validate().is_err());
+    let trades_on_redis = "[[sinks]]\nurl = \"redis://127.0.0.1:6379\"\nmax_trades = 100";
+    GatewayConfig::from_toml_str(trades_on_redis).unwrap().validate().unwrap();
+}
+
+#[tokio::test]
+async fn websocket_requires_an_api_key() {
+    let manager = SubscriptionManager::default();
+    let routes = routes(&manager, HealthMonitor::default(), ApiKeys::new(["secret".to_string()]), &[]);
+
+    let denied = warp::test::request().path("/ws").reply(&routes).await;
+    assert_eq!(denied.status(), 401);
+    let wrong = warp::test::ws().path("/ws").header("x-api-key", "nope").handshake(routes.clone()).await;
+    assert!(wrong.is_err());
+    // Health and unknown paths are not affected by the key check.
+    assert_eq!(warp::test::request().path("/health").reply(&routes).await.status(), 200);
+    assert_eq!(warp::test::request().path("/nope").reply(&routes).await.status(), 404);
+
+    let mut client = warp::test::ws().path("/ws?api_key=secret").handshake(routes.clone()).await.unwrap();
+    manager.publish(quote());
+    let message = client.recv().await.unwrap();
+    let event: serde_json::Value = serde_json::from_str(message.to_str().unwrap()).unwrap();
+    assert_eq!(event["bid"], 100.0);
+
+    let bearer = warp::test::ws().path("/ws").header("authorization", "Bearer secret").handshake(routes).await;
+    assert!(bearer.is_ok());
+}
+
+#[tokio::test]
+async fn cors_allows_configured_origins() {
+    let manager = SubscriptionManager::default();
+    let origins = vec!["https://dashboard.example.com".to_string()];
+    let routes = routes(&manager, HealthMonitor::default(), ApiKeys::default(), &origins);
+
+    let allowed = warp::test::request()
+        .method("OPTIONS")
+        .path("/health")
+        .header("origin", "https://dashboard.example.com")
+        .header("access-control-request-method", "GET")
+        .header("access-control-request-headers", "x-api-key")
+        .reply(&routes)
+        .await;
+    assert_eq!(allowed.status(), 200);
+    assert_eq!(allowed.headers()["access-control-allow-origin"], "https://dashboard.example.com");
+
+    let other = warp::test::request()
+        .path("/health")
+        .header("origin", "https://elsewhere.example.com")
+        .reply(&routes)
+        .await;
+    assert_eq!(other.status(), 403);
+}
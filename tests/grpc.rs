@@ -0,0 +1,116 @@
+#![cfg(feature = "grpc")]
+
+mod common;
+
+use soqa_sdk::api::grpc::proto::market_data_client::MarketDataClient;
+use soqa_sdk::api::grpc::proto::{self, Channel, Payload};
+use soqa_sdk::api::auth::ApiKeys;
+use soqa_sdk::api::grpc::{serve, serve_with, venue_instruments};
+use soqa_sdk::exchanges::instruments::Instrument;
+use soqa_sdk::health::HealthMonitor;
+use soqa_sdk::shutdown::Shutdown;
+use soqa_sdk::models::{MarketEvent, OrderBookL1};
+use soqa_sdk::subscriptions::SubscriptionManager;
+use std::time::{Duration, SystemTime};
+use tonic::Code;
+
+fn quote(exchange: &str, bid: f64) -> MarketEvent {
+    let quote = common::quote(exchange, "BTCUSDT", bid, bid + 1.0, common::millis(1_718_000_000_000));
+    MarketEvent::L1(OrderBookL1 { ask_volume: 2.0, ..quote })
+}
+
+fn trade(exchange: &str, price: f64) -> MarketEvent {
+    MarketEvent::Trade(common::trade(exchange, "BTCUSDT", price, 0.5, SystemTime::UNIX_EPOCH))
+}
+
+async fn start(manager: SubscriptionManager, monitor: HealthMonitor) -> MarketDataClient<tonic::transport::Channel> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener, manager, monitor));
+    MarketDataClient::connect(format!("http://{}", addr)).await.unwrap()
+}
+
+#[tokio::test]
+async fn subscribe_filters_on_the_server() {
+    let manager = SubscriptionManager::default();
+    let mut client = start(manager.clone(), HealthMonitor::default()).await;
+    let request = proto::SubscribeRequest {
+        exchanges: vec!["okx".to_string()],
+        symbols: vec![],
+        channels: vec![Channel::Trades as i32],
+    };
+    let mut stream = client.subscribe(request).await.unwrap().into_inner();
+
+    manager.publish(quote("okx", 100.0));
+    manager.publish(trade("binance", 101.0));
+    manager.publish(trade("okx", 102.0));
+    let event = tokio::time::timeout(Duration::from_secs(5), stream.message()).await.unwrap().unwrap().unwrap();
+    assert_eq!(event.exchange, "okx");
+    assert_eq!(event.symbol, "BTCUSDT");
+    match event.payload {
+        Some(Payload::Trade(t)) => assert_eq!((t.price, t.side.as_str()), (102.0, "buy")),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn snapshots_instruments_and_health() {
+    let manager = SubscriptionManager::default();
+    let monitor = HealthMonitor::default();
+    let mut client = start(manager.clone(), monitor.clone()).await;
+
+    let missing = proto::SnapshotRequest { exchange: "bybit".to_string(), symbol: "BTCUSDT".to_string() };
+    assert_eq!(client.get_snapshot(missing.clone()).await.unwrap_err().code(), Code::NotFound);
+
+    manager.publish(quote("bybit", 100.0));
+    manager.publish(quote("bybit", 105.0));
+    manager.publish(trade("bybit", 104.0));
+    let snapshot = client.get_snapshot(missing).await.unwrap().into_inner();
+    match snapshot.l1.and_then(|e| e.payload) {
+        Some(Payload::L1(l1)) => assert_eq!((l1.bid, l1.ask), (105.0, 106.0)),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(snapshot.l2.is_none());
+    assert_eq!(snapshot.last_trade.unwrap().timestamp_ns, 0);
+
+    let instruments = client.list_instruments(proto::ListInstrumentsRequest::default()).await.unwrap().into_inner();
+    let listed: Vec<_> = instruments.instruments.iter().map(|i| (i.exchange.as_str(), i.symbol.as_str(), i.streaming)).collect();
+    assert_eq!(listed, vec![("bybit", "BTCUSDT", true)]);
+
+    let tracker = monitor.tracker("bybit", "BTCUSDT");
+    tracker.connected();
+    tracker.message(128);
+    let health = client.health(proto::HealthRequest {}).await.unwrap().into_inner();
+    assert!(health.healthy);
+    assert_eq!(health.feeds.len(), 1);
+    assert_eq!((health.feeds[0].status.as_str(), health.feeds[0].messages), ("healthy", 1));
+}
+
+#[tokio::test]
+async fn api_keys_are_checked_and_shutdown_stops_the_server() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = Shutdown::new();
+    let keys = ApiKeys::new(["secret".to_string()]);
+    let server = tokio::spawn(serve_with(listener, SubscriptionManager::default(), HealthMonitor::default(), keys, shutdown.clone()));
+    let mut client = MarketDataClient::connect(format!("http://{}", addr)).await.unwrap();
+
+    let denied = client.health(proto::HealthRequest {}).await.unwrap_err();
+    assert_eq!(denied.code(), Code::Unauthenticated);
+    let mut request = tonic::Request::new(proto::HealthRequest {});
+    request.metadata_mut().insert("x-api-key", "secret".parse().unwrap());
+    assert!(client.health(request).await.is_ok());
+
+    drop(client);
+    shutdown.trigger();
+    tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap().unwrap();
+}
+
+#[test]
+fn venue_instruments_match_configured_symbols() {
+    let instruments = vec![Instrument::new("BTC-USDT", "BTC", "USDT"), Instrument::new("ETH-USDT", "ETH", "USDT")];
+    let markets = vec![("okx".to_string(), "BTCUSDT".to_string())];
+    let listed: Vec<_> =
+        venue_instruments("okx", instruments, &markets).into_iter().map(|i| (i.symbol, i.streaming)).collect();
+    assert_eq!(listed, vec![("BTC-USDT".to_string(), true), ("ETH-USDT".to_string(), false)]);
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use soqa_sdk::delivery::OverflowPolicy;
+use soqa_sdk::exchanges::book::ChecksumMismatch;
+use soqa_sdk::health::{FeedStatus, HealthEvent, HealthMonitor, Violation};
+use soqa_sdk::models::OrderBookL1;
+use std::time::{Duration, SystemTime};
+
+fn quote(bid: f64, ask: f64, timestamp: SystemTime) -> OrderBookL1 {
+    common::quote("okx", "BTCUSDT", bid, ask, timestamp)
+}
+
+#[test]
+fn detects_sanity_violations() {
+    let monitor = HealthMonitor::default();
+    let mut events = monitor.subscribe();
+    let tracker = monitor.tracker("okx", "BTCUSDT");
+    let now = SystemTime::now();
+
+    tracker.connected();
+    tracker.message(0);
+    tracker.quote(&quote(100.0, 100.5, now));
+    assert_eq!(monitor.feed("okx", "BTCUSDT").unwrap().status, FeedStatus::Healthy);
+
+    tracker.quote(&quote(101.0, 100.5, now));
+    tracker.quote(&quote(0.0, 100.5, now));
+    tracker.quote(&quote(100.0, 100.5, now - Duration::from_secs(1)));
+    tracker.parse_failure();
+    tracker.dropped(OverflowPolicy::Conflate);
+    tracker.checksum_mismatch(ChecksumMismatch { expected: 1, actual: 2 });
+
+    let feed = monitor.feed("okx", "BTCUSDT").unwrap();
+    assert_eq!(feed.status, FeedStatus::Degraded);
+    assert_eq!(feed.crossed_books, 1);
+    assert_eq!(feed.zero_prices, 1);
+    assert_eq!(feed.out_of_order, 1);
+    assert_eq!(feed.parse_failures, 1);
+    assert_eq!(feed.dropped, 1);
+    assert_eq!(feed.checksum_mismatches, 1);
+    assert!(!monitor.is_healthy());
+
+    assert!(matches!(events.try_recv().unwrap(), HealthEvent::Connected { reconnects: 0, .. }));
+    assert!(matches!(
+        events.try_recv().unwrap(),
+        HealthEvent::Violation { violation: Violation::CrossedBook { .. }, .. }
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn reports_stale_feeds_and_reconnects() {
+    let monitor = HealthMonitor::new(Duration::from_millis(20));
+    let mut events = monitor.subscribe();
+    let tracker = monitor.tracker("kraken", "BTCUSD");
+
+    tracker.connected();
+    tracker.message(0);
+    tokio::time::advance(Duration::from_millis(19)).await;
+    monitor.check_stale();
+    assert_eq!(monitor.feed("kraken", "BTCUSD").unwrap().status, FeedStatus::Healthy);
+    tokio::time::advance(Duration::from_millis(1)).await;
+    monitor.check_stale();
+    assert_eq!(monitor.feed("kraken", "BTCUSD").unwrap().status, FeedStatus::Stale);
+
+    tracker.message(0);
+    tracker.disconnected();
+    tracker.connected();
+    let feed = monitor.feed("kraken", "BTCUSD").unwrap();
+    assert_eq!(feed.status, FeedStatus::Healthy);
+    assert_eq!(feed.reconnects, 1);
+    assert_eq!(feed.messages, 2);
+
+    let kinds: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+    assert!(matches!(kinds[1], HealthEvent::Stale { .. }));
+    assert!(matches!(kinds[2], HealthEvent::Recovered { .. }));
+    assert!(matches!(kinds[4], HealthEvent::Connected { reconnects: 1, .. }));
+}
+
+#[tokio::test(start_paused = true)]
+async fn tracks_each_symbol_of_a_shared_connection() {
+    let monitor = HealthMonitor::new(Duration::from_millis(20));
+    let connection = monitor.tracker("okx", "BTCUSDT,ETHUSDT");
+    let btc = connection.symbol("BTCUSDT");
+    let eth = connection.symbol("ETHUSDT");
+    let now = SystemTime::now();
+
+    connection.connected();
+    connection.message(0);
+    btc.event();
+    btc.quote(&quote(100.0, 100.5, now));
+    eth.event();
+    eth.quote(&common::quote("okx", "ETHUSDT", 3000.0, 3000.5, now - Duration::from_secs(1)));
+    tokio::time::advance(Duration::from_millis(20)).await;
+    connection.message(0);
+    btc.event();
+
+    // ETH's older quote is not out of order against BTC's, and ETH going quiet is visible even
+    // though the connection keeps receiving.
+    assert_eq!(monitor.feed("okx", "BTCUSDT").unwrap().status, FeedStatus::Healthy);
+    assert_eq!(monitor.feed("okx", "ETHUSDT").unwrap().status, FeedStatus::Stale);
+    assert_eq!(monitor.feed("okx", "ETHUSDT").unwrap().out_of_order, 0);
+    assert_eq!(monitor.feed("okx", "BTCUSDT,ETHUSDT").unwrap().status, FeedStatus::Healthy);
+
+    connection.disconnected();
+    btc.detached();
+    connection.connected();
+    btc.event();
+    assert_eq!(monitor.feed("okx", "BTCUSDT,ETHUSDT").unwrap().reconnects, 1);
+    assert_eq!(monitor.feed("okx", "BTCUSDT").unwrap().reconnects, 0);
+    assert_eq!(monitor.feed("okx", "BTCUSDT").unwrap().status, FeedStatus::Healthy);
+}
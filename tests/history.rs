@@ -0,0 +1,238 @@
+use soqa_sdk::download::{download, download_book, state_path, BookDownloadRequest, DownloadRequest};
+use soqa_sdk::exchanges::history::{
+    parse_binance_agg_trades, parse_binance_book, parse_bybit_book, parse_bybit_trades, parse_kraken_book,
+    parse_kraken_trades, parse_kucoin_book, parse_kucoin_trades, parse_okx_book, parse_okx_trades, HistoryClient,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use warp::Filter;
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/data/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+#[test]
+fn parses_recorded_history_payloads() {
+    let binance = parse_binance_agg_trades(&fixture("binance_agg_trades.json"), "BTCUSDT").unwrap();
+    assert_eq!(binance.len(), 2);
+    assert_eq!((binance[0].trade_id.as_str(), binance[0].side.as_str()), ("3216847201", "sell"));
+    assert_eq!((binance[0].price, binance[0].timestamp_ms), (67012.34, 1718000000123));
+
+    let bybit = parse_bybit_trades(&fixture("bybit_trades.json"), "BTCUSDT").unwrap();
+    assert_eq!((bybit[0].side.as_str(), bybit[0].volume, bybit[0].timestamp_ms), ("sell", 0.002, 1718000000250));
+
+    let okx = parse_okx_trades(&fixture("okx_trades.json"), "BTC-USDT").unwrap();
+    assert_eq!(okx[1].trade_id, "523748891");
+
+    let (kraken, last) = parse_kraken_trades(&fixture("kraken_trades.json"), "XBT/USD").unwrap();
+    assert_eq!(last, "1718000001103400000");
+    assert_eq!((kraken[1].trade_id.as_str(), kraken[1].side.as_str()), ("72011235", "sell"));
+    assert_eq!(kraken[1].timestamp_ms, 1718000001103);
+
+    let kucoin = parse_kucoin_trades(&fixture("kucoin_trades.json"), "BTC-USDT").unwrap();
+    assert_eq!((kucoin[0].price, kucoin[0].timestamp_ms), (67009.9, 1718000000600));
+
+    assert!(parse_okx_trades(r#"{"code":"51001","msg":"Instrument ID does not exist","data":[]}"#, "X").is_err());
+}
+
+#[test]
+fn parses_rest_book_snapshots() {
+    let binance = parse_binance_book(r#"{"lastUpdateId":1,"bids":[["100.5","2"]],"asks":[["101","1"],["102","3"]]}"#, "BTCUSDT").unwrap();
+    assert_eq!((binance.bids, binance.asks), (vec![(100.5, 2.0)], vec![(101.0, 1.0), (102.0, 3.0)]));
+
+    let bybit = parse_bybit_book(
+        r#"{"retCode":0,"retMsg":"OK","result":{"s":"BTCUSDT","b":[["100","1"]],"a":[["101","2"]],"ts":1718000000000,"u":5}}"#,
+        "BTCUSDT",
+    )
+    .unwrap();
+    assert_eq!((bybit.bids[0], bybit.asks[0]), ((100.0, 1.0), (101.0, 2.0)));
+    assert_eq!(bybit.timestamp, SystemTime::UNIX_EPOCH + Duration::from_millis(1718000000000));
+
+    let okx = parse_okx_book(
+        r#"{"code":"0","msg":"","data":[{"asks":[["101","2","0","1"]],"bids":[["100","1","0","3"]],"ts":"1718000000000"}]}"#,
+        "BTC-USDT",
+    )
+    .unwrap();
+    assert_eq!((okx.bids[0], okx.asks[0]), ((100.0, 1.0), (101.0, 2.0)));
+
+    let kraken = parse_kraken_book(
+        r#"{"error":[],"result":{"XXBTZUSD":{"asks":[["101.0","2.0",1718000000]],"bids":[["100.0","1.0",1718000000]]}}}"#,
+        "XBT/USD",
+    )
+    .unwrap();
+    assert_eq!((kraken.symbol.as_str(), kraken.bids[0], kraken.asks[0]), ("XBT/USD", (100.0, 1.0), (101.0, 2.0)));
+
+    let kucoin = parse_kucoin_book(
+        r#"{"code":"200000","data":{"time":1718000000000,"sequence":"7","bids":[["100","1"]],"asks":[["101","2"]]}}"#,
+        "BTC-USDT",
+    )
+    .unwrap();
+    assert_eq!((kucoin.bids[0], kucoin.asks[0]), ((100.0, 1.0), (101.0, 2.0)));
+
+    assert!(parse_kraken_book(r#"{"error":["EQuery:Unknown asset pair"]}"#, "X").is_err());
+}
+
+// Serves 250 OKX trades (ids 1..=250, ts 1000 + id) newest first, 100 per page.
+// While `fail` is set, id-paginated requests return HTTP 500.
+async fn mock_okx(fail: Arc<AtomicBool>) -> String {
+    let route = warp::path!("api" / "v5" / "market" / "history-trades")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(move |query: HashMap<String, String>| {
+            let after: u64 = query["after"].parse().unwrap();
+            let by_id = query["type"] == "1";
+            if by_id && fail.load(Ordering::SeqCst) {
+                return warp::reply::with_status("boom".to_string(), warp::http::StatusCode::INTERNAL_SERVER_ERROR);
+            }
+            let data: Vec<String> = (1..=250u64)
+                .rev()
+                .filter(|id| if by_id { *id < after } else { 1000 + id < after })
+                .take(100)
+                .map(|id| format!(r#"{{"tradeId":"{}","px":"100.5","sz":"1","side":"buy","ts":"{}"}}"#, id, 1000 + id))
+                .collect();
+            let body = format!(r#"{{"code":"0","msg":"","data":[{}]}}"#, data.join(","));
+            warp::reply::with_status(body, warp::http::StatusCode::OK)
+        });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    format!("http://{}", addr)
+}
+
+fn temp_output(name: &str) -> String {
+    let output = std::env::temp_dir().join(format!("soqa-{}-{}", std::process::id(), name));
+    let output = output.to_str().unwrap().to_string();
+    let _ = std::fs::remove_file(&output);
+    let _ = std::fs::remove_file(state_path(&output));
+    output
+}
+
+#[tokio::test]
+async fn download_paginates_and_resumes() {
+    let fail = Arc::new(AtomicBool::new(true));
+    let url = mock_okx(fail.clone()).await;
+    let output = std::env::temp_dir().join(format!("soqa-history-{}.db", std::process::id()));
+    let output = output.to_str().unwrap().to_string();
+    let _ = std::fs::remove_file(&output);
+    let _ = std::fs::remove_file(state_path(&output));
+    let request = DownloadRequest {
+        exchange: "okx".to_string(),
+        symbol: "BTC-USDT".to_string(),
+        start_ms: 1001,
+        end_ms: 1251,
+        output: output.clone(),
+    };
+
+    let mut client = HistoryClient::new("okx").unwrap().with_base_url(&url);
+    assert!(download(&mut client, &request).await.is_err());
+    let saved = std::fs::read_to_string(state_path(&output)).unwrap();
+    assert!(saved.contains(r#""cursor":"151""#));
+
+    fail.store(false, Ordering::SeqCst);
+    let state = download(&mut client, &request).await.unwrap();
+    assert!(state.done);
+    assert_eq!((state.pages, state.trades), (3, 250));
+
+    let conn = rusqlite::Connection::open(&output).unwrap();
+    let (count, min_ts): (i64, i64) =
+        conn.query_row("SELECT COUNT(*), MIN(timestamp_ms) FROM trades", [], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+    assert_eq!((count, min_ts), (250, 1001));
+
+    let other = DownloadRequest { end_ms: 2000, ..request };
+    assert!(download(&mut client, &other).await.is_err());
+
+    let _ = std::fs::remove_file(&output);
+    let _ = std::fs::remove_file(state_path(&output));
+}
+
+#[tokio::test]
+async fn csv_download_skips_rows_written_before_a_crash() {
+    let fail = Arc::new(AtomicBool::new(true));
+    let url = mock_okx(fail.clone()).await;
+    let output = temp_output("history.csv");
+    let request = DownloadRequest {
+        exchange: "okx".to_string(),
+        symbol: "BTC-USDT".to_string(),
+        start_ms: 1001,
+        end_ms: 1251,
+        output: output.clone(),
+    };
+
+    let mut client = HistoryClient::new("okx").unwrap().with_base_url(&url);
+    assert!(download(&mut client, &request).await.is_err());
+    // A crash after the append but before the cursor is saved replays the first page.
+    std::fs::remove_file(state_path(&output)).unwrap();
+
+    fail.store(false, Ordering::SeqCst);
+    download(&mut client, &request).await.unwrap();
+    let mut ids: Vec<String> = csv::Reader::from_path(&output)
+        .unwrap()
+        .records()
+        .map(|record| record.unwrap()[2].to_string())
+        .collect();
+    assert_eq!(ids.len(), 250);
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 250);
+
+    let _ = std::fs::remove_file(&output);
+    let _ = std::fs::remove_file(state_path(&output));
+}
+
+// Serves `count` Bybit trades stamped 5000 and up.
+async fn mock_bybit(count: usize) -> String {
+    let route = warp::path!("v5" / "market" / "recent-trade").map(move || {
+        let list: Vec<String> = (0..count)
+            .map(|i| format!(r#"{{"execId":"{}","price":"100","size":"1","side":"Buy","time":"{}"}}"#, i, 5000 + i))
+            .collect();
+        format!(r#"{{"retCode":0,"retMsg":"OK","result":{{"list":[{}]}}}}"#, list.join(","))
+    });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn single_page_venues_refuse_ranges_they_cannot_reach() {
+    let mut full = HistoryClient::new("bybit").unwrap().with_base_url(&mock_bybit(60).await);
+    assert!(full.fetch_page("BTCUSDT", 1000, 10_000, None).await.is_err());
+    assert_eq!(full.fetch_page("BTCUSDT", 5000, 10_000, None).await.unwrap().trades.len(), 60);
+
+    let mut partial = HistoryClient::new("bybit").unwrap().with_base_url(&mock_bybit(10).await);
+    assert_eq!(partial.fetch_page("BTCUSDT", 1000, 10_000, None).await.unwrap().trades.len(), 10);
+}
+
+#[tokio::test]
+async fn book_download_stores_every_snapshot_whole() {
+    let route = warp::path!("api" / "v5" / "market" / "books").and(warp::query::<HashMap<String, String>>()).map(
+        |query: HashMap<String, String>| {
+            assert_eq!((query["instId"].as_str(), query["sz"].as_str()), ("BTC-USDT", "2"));
+            r#"{"code":"0","msg":"","data":[{"asks":[["101","2","0","1"],["102","1","0","1"]],"bids":[["100","1","0","3"],["99","4","0","1"]],"ts":"1718000000000"}]}"#
+        },
+    );
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let output = temp_output("book.db");
+    let request = BookDownloadRequest {
+        exchange: "okx".to_string(),
+        symbol: "BTC-USDT".to_string(),
+        depth: 2,
+        snapshots: 2,
+        interval: Duration::ZERO,
+        output: output.clone(),
+    };
+
+    let mut client = HistoryClient::new("okx").unwrap().with_base_url(&format!("http://{}", addr));
+    assert_eq!(download_book(&mut client, &request).await.unwrap(), 2);
+    let conn = rusqlite::Connection::open(&output).unwrap();
+    let kinds: Vec<(String, i64)> = conn
+        .prepare("SELECT kind, COUNT(*) FROM l2_book GROUP BY kind")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(kinds, vec![("snapshot".to_string(), 8)]);
+
+    let _ = std::fs::remove_file(&output);
+}
@@ -0,0 +1,227 @@
+use soqa_sdk::exchanges::adapter::AdapterSpec;
+use soqa_sdk::exchanges::{binance, bybit, kraken, kucoin, okx};
+use soqa_sdk::models::{OrderBookL1, QuoteSource};
+
+fn assert_same_quote(generic: &OrderBookL1, handwritten: &OrderBookL1) {
+    assert_eq!(generic.exchange, handwritten.exchange);
+    assert_eq!(generic.symbol, handwritten.symbol);
+    assert_eq!(generic.bid, handwritten.bid);
+    assert_eq!(generic.bid_volume, handwritten.bid_volume);
+    assert_eq!(generic.ask, handwritten.ask);
+    assert_eq!(generic.ask_volume, handwritten.ask_volume);
+    assert_eq!(generic.source, handwritten.source);
+}
+
+#[test]
+fn builtin_specs_match_handwritten_parsers() {
+    let cases: Vec<(&str, &str, &str, Option<OrderBookL1>)> = vec![
+        ("binance", "BTCUSDT", include_str!("data/binance_ticker.json"), binance::parse_l1(include_str!("data/binance_ticker.json"))),
+        ("bybit", "ETHUSDT", include_str!("data/bybit_orderbook.json"), bybit::parse_l1(include_str!("data/bybit_orderbook.json"), "ETHUSDT")),
+        ("okx", "BTCUSDT", include_str!("data/okx_bbo.json"), okx::parse_bbo(include_str!("data/okx_bbo.json"), "BTCUSDT")),
+        ("kraken", "BTCUSD", include_str!("data/kraken_ticker.json"), kraken::parse_l1(include_str!("data/kraken_ticker.json"), "BTCUSD")),
+        ("kucoin", "ETHUSDT", include_str!("data/kucoin_ticker.json"), kucoin::parse_l1(include_str!("data/kucoin_ticker.json"), "ETHUSDT")),
+    ];
+
+    for (name, symbol, payload, handwritten) in cases {
+        let spec = AdapterSpec::builtin(name).unwrap();
+        let generic = spec.parse_l1(payload, symbol).unwrap();
+        assert_same_quote(&generic, &handwritten.unwrap());
+    }
+}
+
+#[test]
+fn spec_skips_control_messages() {
+    let okx = AdapterSpec::builtin("okx").unwrap();
+    assert!(okx
+        .parse_l1(r#"{"event":"subscribe","arg":{"channel":"books","instId":"BTC-USDT"}}"#, "BTCUSDT")
+        .is_none());
+
+    let kraken = AdapterSpec::builtin("kraken").unwrap();
+    assert!(kraken.parse_l1(r#"{"event":"heartbeat"}"#, "BTCUSD").is_none());
+}
+
+#[test]
+fn okx_spec_ignores_book_deltas() {
+    // A `books` update only carries the levels that changed, so its first level is not the top of the book.
+    let okx = AdapterSpec::builtin("okx").unwrap();
+    assert!(okx.parse_l1(include_str!("data/okx_books_update.json"), "BTCUSDT").is_none());
+    assert!(okx.parse_l1(include_str!("data/okx_books.json"), "BTCUSDT").is_none());
+}
+
+#[test]
+fn spec_loads_from_json() {
+    let spec = AdapterSpec::from_json_str(
+        r#"{
+            "name": "example",
+            "url": "wss://example.com/ws/{symbol}",
+            "symbol": {"case": "lower", "map": {"BTCUSDT": "BTC_USDT"}},
+            "fields": {"bid": "/bid/0", "bid_volume": "/bid/1", "ask": "/ask/0", "ask_volume": "/ask/1", "timestamp": "/t", "timestamp_unit": "s"}
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(spec.exchange_symbol("BTCUSDT"), "btc_usdt");
+    let quote = spec
+        .parse_l1(r#"{"bid":[100.5,2],"ask":["100.6","3"],"t":1700000000}"#, "BTCUSDT")
+        .unwrap();
+    assert_eq!(quote.exchange, "example");
+    assert_eq!(quote.bid, 100.5);
+    assert_eq!(quote.ask_volume, 3.0);
+    assert_eq!(quote.source, QuoteSource::Ticker);
+    assert_eq!(
+        quote.timestamp,
+        std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000)
+    );
+}
+
+#[test]
+fn typed_parsers_read_the_fixtures() {
+    let quotes = [
+        (binance::parse_l1(include_str!("data/binance_ticker.json")), "BTCUSDT", [105007.98, 3.47152, 105007.99, 2.89311]),
+        (bybit::parse_l1(include_str!("data/bybit_orderbook.json"), "ETHUSDT"), "ETHUSDT", [2498.57, 0.38006, 2498.58, 7.90089]),
+        (okx::parse_l1(include_str!("data/okx_books.json"), "BTCUSDT"), "BTCUSDT", [105008.0, 1.02930412, 105008.1, 0.51284271]),
+        (kraken::parse_l1(include_str!("data/kraken_ticker.json"), "BTCUSD"), "BTCUSD", [105008.0, 0.2443, 105008.1, 1.0312]),
+        (kucoin::parse_l1(include_str!("data/kucoin_ticker.json"), "ETHUSDT"), "ETHUSDT", [2498.57, 0.3800612, 2498.58, 7.9008921]),
+    ];
+    for (quote, symbol, [bid, bid_volume, ask, ask_volume]) in quotes {
+        let quote = quote.unwrap();
+        assert_eq!(quote.symbol, symbol);
+        assert_eq!((quote.bid, quote.bid_volume, quote.ask, quote.ask_volume), (bid, bid_volume, ask, ask_volume));
+    }
+}
+
+#[test]
+fn typed_parsers_reject_control_messages() {
+    assert!(okx::parse_l1(r#"{"event":"subscribe","arg":{"channel":"books","instId":"BTC-USDT"}}"#, "BTCUSDT").is_none());
+    assert!(kraken::parse_l1(r#"{"event":"heartbeat"}"#, "BTCUSD").is_none());
+    assert!(kucoin::parse_l1(r#"{"id":"1","type":"welcome"}"#, "ETHUSDT").is_none());
+    assert!(bybit::parse_l1(r#"{"success":true,"ret_msg":"","op":"subscribe"}"#, "ETHUSDT").is_none());
+}
+
+#[tokio::test]
+async fn feed_reconnects_and_resubscribes_after_a_drop() {
+    use futures_util::{SinkExt, StreamExt};
+    use soqa_sdk::config::Config;
+    use soqa_sdk::exchanges::adapter::GenericClient;
+    use soqa_sdk::health::HealthMonitor;
+    use soqa_sdk::shutdown::Shutdown;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (subscribed, mut subscriptions) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut open = Vec::new();
+        for bid in [100.0, 101.0] {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            if let Some(Ok(Message::Text(message))) = ws.next().await {
+                subscribed.send(message).unwrap();
+            }
+            let quote = format!(r#"{{"bid":{},"bv":1,"ask":{},"av":2}}"#, bid, bid + 1.0);
+            ws.send(Message::Text(quote)).await.unwrap();
+            // The first connection is dropped without a close frame.
+            if bid > 100.0 {
+                open.push(ws);
+            }
+        }
+        std::future::pending::<()>().await;
+    });
+
+    let spec = AdapterSpec::from_json_str(&format!(
+        r#"{{
+            "name": "local",
+            "url": "ws://{}",
+            "subscribe": ["{{\"op\":\"subscribe\",\"args\":[\"{{symbol}}\"]}}"],
+            "fields": {{"bid": "/bid", "bid_volume": "/bv", "ask": "/ask", "ask_volume": "/av"}}
+        }}"#,
+        addr
+    ))
+    .unwrap();
+    let monitor = HealthMonitor::default();
+    let shutdown = Shutdown::new();
+    let config = Config::new("local", "BTCUSDT").with_health(monitor.clone()).with_shutdown(shutdown.clone());
+    let (tx, mut quotes) = mpsc::unbounded_channel();
+    GenericClient::new(spec, config).subscribe_l1(move |quote| tx.send(quote.bid).unwrap()).await.unwrap();
+
+    let within = Duration::from_secs(5);
+    assert_eq!(tokio::time::timeout(within, quotes.recv()).await.unwrap(), Some(100.0));
+    assert_eq!(tokio::time::timeout(within, quotes.recv()).await.unwrap(), Some(101.0));
+    let first = subscriptions.recv().await.unwrap();
+    assert_eq!(first, r#"{"op":"subscribe","args":["BTCUSDT"]}"#);
+    assert_eq!(subscriptions.recv().await.unwrap(), first);
+    assert_eq!(monitor.feed("local", "BTCUSDT").unwrap().reconnects, 1);
+
+    shutdown.trigger();
+    assert_eq!(tokio::time::timeout(within, quotes.recv()).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn feed_bootstraps_again_on_reconnect() {
+    use futures_util::{SinkExt, StreamExt};
+    use soqa_sdk::config::Config;
+    use soqa_sdk::exchanges::adapter::GenericClient;
+    use soqa_sdk::shutdown::Shutdown;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite::Message;
+    use warp::Filter;
+
+    // Hands out a new token on every call, like KuCoin's bullet-public.
+    let issued = Arc::new(AtomicUsize::new(0));
+    let counter = issued.clone();
+    let bullet = warp::post().map(move || {
+        let token = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        warp::reply::json(&serde_json::json!({"data": {"token": format!("t{}", token), "pingInterval": 18000}}))
+    });
+    let (http, server) = warp::serve(bullet).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (subscribed, mut subscriptions) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut open = Vec::new();
+        for bid in [100.0, 101.0] {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            if let Some(Ok(Message::Text(message))) = ws.next().await {
+                subscribed.send(message).unwrap();
+            }
+            let quote = format!(r#"{{"bid":{},"bv":1,"ask":{},"av":2}}"#, bid, bid + 1.0);
+            ws.send(Message::Text(quote)).await.unwrap();
+            if bid > 100.0 {
+                open.push(ws);
+            }
+        }
+        std::future::pending::<()>().await;
+    });
+
+    let spec = AdapterSpec::from_json_str(&format!(
+        r#"{{
+            "name": "local",
+            "url": "ws://{}",
+            "subscribe": ["{{\"token\":\"{{token}}\",\"topic\":\"{{symbol}}\"}}"],
+            "bootstrap": {{"url": "http://{}/", "vars": {{"token": "/data/token"}}, "ping_interval_ms": "/data/pingInterval"}},
+            "fields": {{"bid": "/bid", "bid_volume": "/bv", "ask": "/ask", "ask_volume": "/av"}}
+        }}"#,
+        addr, http
+    ))
+    .unwrap();
+    let shutdown = Shutdown::new();
+    let config = Config::new("local", "BTCUSDT").with_shutdown(shutdown.clone());
+    let (tx, mut quotes) = mpsc::unbounded_channel();
+    GenericClient::new(spec, config).subscribe_l1(move |quote| tx.send(quote.bid).unwrap()).await.unwrap();
+
+    let within = Duration::from_secs(5);
+    assert_eq!(tokio::time::timeout(within, quotes.recv()).await.unwrap(), Some(100.0));
+    assert_eq!(tokio::time::timeout(within, quotes.recv()).await.unwrap(), Some(101.0));
+    assert_eq!(subscriptions.recv().await.unwrap(), r#"{"token":"t1","topic":"BTCUSDT"}"#);
+    assert_eq!(subscriptions.recv().await.unwrap(), r#"{"token":"t2","topic":"BTCUSDT"}"#);
+    assert_eq!(issued.load(Ordering::SeqCst), 2);
+    shutdown.trigger();
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use futures_util::stream::{self, StreamExt};
+use soqa_sdk::exchanges::instruments::{resolve_symbols, Instrument, SymbolPattern};
+use soqa_sdk::exchanges::{batches, receiver_stream};
+use soqa_sdk::merge::{merge, merge_iter, reorder_iter, LatePolicy, MergeExt, Watermark};
+use soqa_sdk::models::MarketEvent;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+
+fn trade(exchange: &str, ms: u64) -> MarketEvent {
+    MarketEvent::Trade(common::trade(exchange, "BTCUSDT", 100.0, 1.0, common::millis(ms)))
+}
+
+fn millis(event: &MarketEvent) -> u128 {
+    event.timestamp().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis()
+}
+
+#[tokio::test]
+async fn interleaves_by_exchange_time() {
+    let input = stream::iter(vec![trade("okx", 3), trade("binance", 1), trade("okx", 2), trade("binance", 2)]);
+    let merged: Vec<MarketEvent> = input.time_ordered(Watermark::new(Duration::from_millis(50))).collect().await;
+    let order: Vec<(&str, u128)> = merged.iter().map(|e| (e.exchange(), millis(e))).collect();
+    // Equal timestamps keep their arrival order.
+    assert_eq!(order, vec![("binance", 1), ("okx", 2), ("binance", 2), ("okx", 3)]);
+}
+
+#[tokio::test]
+async fn releases_on_watermark_and_when_idle() {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let watermark = Watermark::new(Duration::from_millis(20));
+    let late_events = watermark.late_events();
+    let merged = receiver_stream(rx).time_ordered(watermark);
+    let mut merged = std::pin::pin!(merged);
+
+    tx.send(trade("okx", 1000)).unwrap();
+    tx.send(trade("binance", 1030)).unwrap();
+    // 1030 is more than 20ms past 1000, so 1000 is released while 1030 waits.
+    let first = tokio::time::timeout(Duration::from_millis(15), merged.next()).await.unwrap().unwrap();
+    assert_eq!(millis(&first), 1000);
+    // Nothing else arrives, so 1030 goes out once the input has been quiet for 20ms.
+    let second = tokio::time::timeout(Duration::from_secs(1), merged.next()).await.unwrap().unwrap();
+    assert_eq!(millis(&second), 1030);
+    // Older than what was already emitted: passed through rather than dropped.
+    tx.send(trade("okx", 1010)).unwrap();
+    drop(tx);
+    let late = tokio::time::timeout(Duration::from_secs(1), merged.next()).await.unwrap().unwrap();
+    assert_eq!(millis(&late), 1010);
+    assert!(merged.next().await.is_none());
+    assert_eq!(late_events.total(), 1);
+    assert_eq!(late_events.max_delay(), Duration::from_millis(20));
+}
+
+#[tokio::test]
+async fn merges_streams_and_drops_late_events() {
+    let binance = stream::iter(vec![trade("binance", 100), trade("binance", 140), trade("binance", 200)]);
+    // 105 is only 35ms behind 140 and still fits; 50 is far behind the 80ms watermark.
+    let okx = stream::iter(vec![trade("okx", 105), trade("okx", 50), trade("okx", 210)]);
+    let watermark = Watermark::new(Duration::from_millis(60)).with_policy(LatePolicy::Drop);
+    let late_events = watermark.late_events();
+    let merged: Vec<MarketEvent> = merge([binance, okx], watermark).collect().await;
+    let times: Vec<u128> = merged.iter().map(millis).collect();
+    assert_eq!(times, vec![100, 105, 140, 200, 210]);
+    assert_eq!((late_events.total(), late_events.dropped()), (1, 1));
+    assert_eq!(late_events.by_exchange().get("okx"), Some(&1));
+}
+
+#[test]
+fn reorders_recorded_sources() {
+    let capture = vec![trade("okx", 20), trade("okx", 10), trade("okx", 30)];
+    let times: Vec<u128> = reorder_iter(capture.clone(), Watermark::new(Duration::from_millis(15))).map(|e| millis(&e)).collect();
+    assert_eq!(times, vec![10, 20, 30]);
+
+    // One capture per venue: interleaved by head, then fixed up within each.
+    let binance = vec![trade("binance", 15), trade("binance", 25)];
+    let watermark = Watermark::new(Duration::from_millis(15));
+    let merged: Vec<(String, u128)> = merge_iter([capture, binance], watermark.clone())
+        .map(|e| (e.exchange().to_string(), millis(&e)))
+        .collect();
+    let merged: Vec<(&str, u128)> = merged.iter().map(|(exchange, ms)| (exchange.as_str(), *ms)).collect();
+    assert_eq!(merged, vec![("okx", 10), ("binance", 15), ("okx", 20), ("binance", 25), ("okx", 30)]);
+    assert_eq!(watermark.late_events().total(), 0);
+}
+
+#[test]
+fn symbol_patterns_expand_against_instruments() {
+    let instruments = vec![
+        Instrument::new("BTC-USDT", "BTC", "USDT"),
+        Instrument::new("ETH-USDT", "ETH", "USDT"),
+        Instrument::new("ETH-BTC", "ETH", "BTC"),
+        Instrument::new("SOL-USDC", "SOL", "USDC"),
+    ];
+    let patterns = SymbolPattern::parse_all(&["*usdt".to_string(), "/^(ETH|SOL)-/".to_string(), "BTCUSD".to_string()]).unwrap();
+    assert!(!patterns[0].is_exact() && !patterns[1].is_exact() && patterns[2].is_exact());
+    assert_eq!(
+        resolve_symbols(&patterns, &instruments),
+        vec!["BTC-USDT", "ETH-USDT", "ETH-BTC", "SOL-USDC", "BTCUSD"]
+    );
+    // Globs also match `base + quote`, and `?` is a single character.
+    assert!(SymbolPattern::parse("ETHBT?").unwrap().matches(&instruments[2]));
+    assert!(SymbolPattern::parse("/([/").is_err());
+
+    // Literal symbols take the venue's spelling of the same market, or the canonical form when it is not listed.
+    let kraken = vec![Instrument::new("XBT/USDT", "XBT", "USDT"), Instrument::new("ETH/USDT", "ETH", "USDT")];
+    let literals = SymbolPattern::parse_all(&["BTC-USDT".to_string(), "ethusdt".to_string(), "SOL_USDT".to_string()]).unwrap();
+    assert_eq!(resolve_symbols(&literals, &kraken), vec!["XBT/USDT", "ETH/USDT", "SOLUSDT"]);
+    assert_eq!(resolve_symbols(&literals, &[]), vec!["BTCUSDT", "ETHUSDT", "SOLUSDT"]);
+}
+
+#[test]
+fn subscriptions_are_batched_per_venue() {
+    let pairs: Vec<(String, String)> = [("binance", "A"), ("okx", "B"), ("binance", "C"), ("binance", "D")]
+        .iter()
+        .map(|(exchange, symbol)| (exchange.to_string(), symbol.to_string()))
+        .collect();
+    let batched = batches(&pairs, 2);
+    let batched: Vec<(&str, Vec<&str>)> =
+        batched.iter().map(|(exchange, symbols)| (exchange.as_str(), symbols.iter().map(String::as_str).collect())).collect();
+    assert_eq!(batched, vec![("binance", vec!["A", "C"]), ("binance", vec!["D"]), ("okx", vec!["B"])]);
+}
@@ -0,0 +1,21 @@
+#![cfg(feature = "metrics")]
+
+use soqa_sdk::health::FeedTracker;
+use soqa_sdk::metrics;
+
+#[test]
+fn exports_feed_counters() {
+    let tracker = FeedTracker::new("binance", "BTCUSDT", None);
+    tracker.connected();
+    tracker.connected();
+    tracker.message(128);
+    tracker.parse_failure();
+    metrics::sink_write("csv", 10, 0.002);
+
+    let output = metrics::gather();
+    assert!(output.contains(r#"soqa_messages_total{exchange="binance",symbol="BTCUSDT"} 1"#));
+    assert!(output.contains(r#"soqa_bytes_total{exchange="binance",symbol="BTCUSDT"} 128"#));
+    assert!(output.contains(r#"soqa_parse_errors_total{exchange="binance",symbol="BTCUSDT"} 1"#));
+    assert!(output.contains(r#"soqa_reconnects_total{exchange="binance",symbol="BTCUSDT"} 1"#));
+    assert!(output.contains(r#"soqa_sink_records_total{sink="csv"} 10"#));
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use soqa_sdk::arbitrage::{ArbOpportunity, OpportunityStatus};
+use soqa_sdk::models::{MarketEvent, OrderBookL1, OrderBookL2};
+use soqa_sdk::output::{OutputFormat, OutputWriter};
+use soqa_sdk::triangular::TriangularOpportunity;
+use std::time::{Duration, SystemTime};
+
+fn quote() -> MarketEvent {
+    let quote = common::quote("binance", "BTCUSDT", 100.0, 100.5, common::millis(1718000000000));
+    MarketEvent::L1(OrderBookL1 { bid_volume: 1.5, ask_volume: 2.0, ..quote })
+}
+
+fn trade() -> MarketEvent {
+    MarketEvent::Trade(common::trade("binance", "BTCUSDT", 100.25, 0.1, common::millis(1718000000001)))
+}
+
+fn render(format: OutputFormat, events: &[MarketEvent]) -> String {
+    let mut buffer = Vec::new();
+    let mut writer = OutputWriter::new(&mut buffer, format);
+    for event in events {
+        writer.write(event).unwrap();
+    }
+    writer.flush().unwrap();
+    drop(writer);
+    String::from_utf8(buffer).unwrap()
+}
+
+#[test]
+fn ndjson_writes_one_event_per_line() {
+    let output = render(OutputFormat::Ndjson, &[quote(), trade()]);
+    let lines: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["type"], "l1");
+    assert_eq!(lines[1]["side"], "buy");
+}
+
+#[test]
+fn json_is_pretty_printed() {
+    let output = render(OutputFormat::Json, &[quote()]);
+    assert!(output.lines().count() > 1);
+    let event: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(event["bid"], 100.0);
+}
+
+#[test]
+fn csv_repeats_the_header_when_columns_change() {
+    let output = render(OutputFormat::Csv, &[quote(), quote(), trade()]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "exchange,symbol,timestamp_ms,bid,bid_volume,ask,ask_volume,source",
+            "binance,BTCUSDT,1718000000000,100,1.5,100.5,2,ticker",
+            "binance,BTCUSDT,1718000000000,100,1.5,100.5,2,ticker",
+            "exchange,symbol,timestamp_ms,price,volume,side",
+            "binance,BTCUSDT,1718000000001,100.25,0.1,buy",
+        ]
+    );
+}
+
+#[test]
+fn table_aligns_columns_and_trims_depth() {
+    let book = MarketEvent::L2(OrderBookL2 {
+        exchange: "okx".to_string(),
+        symbol: "BTCUSDT".to_string(),
+        bids: (0..8).map(|i| (100.0 - i as f64, 1.0)).collect(),
+        asks: vec![(101.0, 2.0)],
+        timestamp: SystemTime::UNIX_EPOCH,
+    });
+    let output = render(OutputFormat::Table, &[book]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("exchange     symbol       timestamp_ms bids"));
+    assert!(lines[1].contains("100@1 99@1 98@1 97@1 96@1 101@2"));
+}
+
+#[test]
+fn opportunities_print_as_rows() {
+    let mut buffer = Vec::new();
+    let mut writer = OutputWriter::new(&mut buffer, OutputFormat::Csv);
+    writer
+        .write(&ArbOpportunity {
+            instrument: "BTCUSDT".to_string(),
+            buy_exchange: "okx".to_string(),
+            sell_exchange: "binance".to_string(),
+            buy_price: 100.0,
+            sell_price: 100.5,
+            size: 2.0,
+            edge_bps: 30.0,
+            expected_profit: 0.6,
+            status: OpportunityStatus::Closed,
+            opened_at: SystemTime::UNIX_EPOCH + Duration::from_millis(1718000000000),
+            duration: Duration::from_millis(250),
+        })
+        .unwrap();
+    writer
+        .write(&TriangularOpportunity {
+            exchange: "binance".to_string(),
+            path: ["USDT", "BTC", "ETH", "USDT"].map(String::from).to_vec(),
+            symbols: ["BTCUSDT", "ETHBTC", "ETHUSDT"].map(String::from).to_vec(),
+            size: 100.0,
+            expected_profit: 0.05,
+            edge_bps: 5.0,
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(1718000000001),
+        })
+        .unwrap();
+    writer.flush().unwrap();
+    drop(writer);
+    let lines: Vec<_> = std::str::from_utf8(&buffer).unwrap().lines().map(str::to_string).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "instrument,buy_exchange,sell_exchange,opened_at_ms,status,buy_price,sell_price,size,edge_bps,expected_profit,duration_ms",
+            "BTCUSDT,okx,binance,1718000000000,closed,100,100.5,2,30,0.6,250",
+            "exchange,timestamp_ms,path,symbols,size,edge_bps,expected_profit",
+            "binance,1718000000001,USDT>BTC>ETH>USDT,\"BTCUSDT,ETHBTC,ETHUSDT\",100,5,0.05",
+        ]
+    );
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use common::temp_path;
+
+use soqa_sdk::export::record::{parquet_path, read_quotes, read_trades, Filter, ParquetSink, SqliteSink};
+use soqa_sdk::export::{export_trades_to_sqlite, Sink};
+use soqa_sdk::models::{HistoricalTrade, MarketEvent, OrderBookL1, QuoteSource, Trade};
+use soqa_sdk::query::{ohlcv, quote_counts, spread_stats};
+use std::time::{Duration, SystemTime};
+
+const BASE_MS: u64 = 1_718_000_000_000;
+
+fn at(ms: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(BASE_MS + ms)
+}
+
+fn quote(exchange: &str, symbol: &str, ms: u64, bid: f64, ask: f64) -> OrderBookL1 {
+    OrderBookL1 { ask_volume: 2.0, source: QuoteSource::Bbo, ..common::quote(exchange, symbol, bid, ask, at(ms)) }
+}
+
+fn trade(ms: u64, price: f64, volume: f64) -> Trade {
+    common::trade("binance", "BTCUSDT", price, volume, at(ms))
+}
+
+fn quotes() -> Vec<OrderBookL1> {
+    vec![
+        quote("binance", "BTCUSDT", 0, 99.99, 100.01),
+        quote("binance", "BTCUSDT", 1_000, 99.98, 100.02),
+        quote("binance", "ETHUSDT", 1_500, 9.99, 10.01),
+        quote("binance", "BTCUSDT", 2_000, 99.95, 100.05),
+        quote("okx", "BTCUSDT", 500, 99.9, 100.1),
+        quote("okx", "BTCUSDT", 600, 0.0, 100.1),
+    ]
+}
+
+fn trades() -> Vec<Trade> {
+    vec![trade(0, 100.0, 1.0), trade(20_000, 102.0, 0.5), trade(59_999, 99.0, 2.0), trade(60_000, 101.0, 1.0), trade(130_000, 103.0, 1.0)]
+}
+
+#[test]
+fn builds_candles_per_interval() {
+    let candles = ohlcv(&trades(), Duration::from_secs(60));
+    let summary: Vec<_> = candles.iter().map(|c| (c.start_ms - BASE_MS / 60_000 * 60_000, c.open, c.high, c.low, c.close, c.volume, c.trades)).collect();
+    // BASE_MS is 20s into a minute, so the epoch-aligned minutes split the trades at 40s and 100s.
+    assert_eq!(
+        summary,
+        vec![
+            (0, 100.0, 102.0, 100.0, 102.0, 1.5, 2),
+            (60_000, 99.0, 101.0, 99.0, 101.0, 3.0, 2),
+            (120_000, 103.0, 103.0, 103.0, 103.0, 1.0, 1),
+        ]
+    );
+}
+
+#[test]
+fn summarizes_spreads_and_counts() {
+    let stats = spread_stats(&quotes());
+    let summary: Vec<_> = stats.iter().map(|s| (s.exchange.as_str(), s.symbol.as_str(), s.quotes)).collect();
+    assert_eq!(summary, vec![("binance", "BTCUSDT", 3), ("binance", "ETHUSDT", 1), ("okx", "BTCUSDT", 1)]);
+    let btc = &stats[0];
+    assert!((btc.min_bps - 2.0).abs() < 1e-6);
+    assert!((btc.median_bps - 4.0).abs() < 1e-6);
+    assert!((btc.p95_bps - 10.0).abs() < 1e-6);
+    assert!((btc.max_bps - 10.0).abs() < 1e-6);
+    assert!((btc.mean_bps - 16.0 / 3.0).abs() < 1e-6);
+
+    let counts = quote_counts(&quotes());
+    let summary: Vec<_> = counts.iter().map(|c| (c.exchange.as_str(), c.symbols, c.quotes, c.last_ms - c.first_ms)).collect();
+    assert_eq!(summary, vec![("binance", 2, 4, 2_000), ("okx", 1, 2, 100)]);
+    assert!((counts[0].quotes_per_sec - 2.0).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn reads_filtered_records_from_sqlite() {
+    let path = temp_path("capture.db");
+    let _ = std::fs::remove_file(&path);
+    let mut sink = SqliteSink::open(&path, Duration::from_secs(60)).unwrap();
+    for q in quotes() {
+        sink.write(&MarketEvent::L1(q)).await.unwrap();
+    }
+    for t in trades() {
+        sink.write(&MarketEvent::Trade(t)).await.unwrap();
+    }
+    sink.flush().await.unwrap();
+    drop(sink);
+    // Downloaded trades share the table.
+    let downloaded = HistoricalTrade {
+        exchange: "binance".to_string(),
+        symbol: "BTCUSDT".to_string(),
+        trade_id: "42".to_string(),
+        price: 98.0,
+        volume: 4.0,
+        side: "sell".to_string(),
+        timestamp_ms: BASE_MS + 200_000,
+    };
+    export_trades_to_sqlite(&[downloaded], path.to_str().unwrap()).unwrap();
+
+    let all = read_quotes(&path, &Filter::default()).unwrap();
+    assert_eq!(all.len(), 6);
+    assert!(all.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+    assert_eq!(all[0].source, QuoteSource::Bbo);
+    let filter = Filter { exchange: Some("binance".to_string()), symbol: Some("BTCUSDT".to_string()), start: Some(at(1_000)), end: Some(at(2_000)) };
+    let window = read_quotes(&path, &filter).unwrap();
+    assert_eq!(window.iter().map(|q| q.bid).collect::<Vec<_>>(), vec![99.98]);
+
+    let trades = read_trades(&path, &Filter { start: Some(at(60_000)), ..Filter::default() }).unwrap();
+    assert_eq!(trades.iter().map(|t| t.price).collect::<Vec<_>>(), vec![101.0, 103.0, 98.0]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn reads_filtered_records_from_parquet() {
+    let path = temp_path("capture.parquet");
+    let mut sink = ParquetSink::create(&path, Duration::from_secs(60)).unwrap().with_row_group_rows(4);
+    for q in quotes() {
+        sink.write(&MarketEvent::L1(q)).await.unwrap();
+        sink.flush().await.unwrap();
+    }
+    for t in trades() {
+        sink.write(&MarketEvent::Trade(t)).await.unwrap();
+    }
+    sink.close().await.unwrap();
+    assert!(!parquet_path(&path, "l2").exists());
+
+    let okx = read_quotes(&path, &Filter { exchange: Some("okx".to_string()), ..Filter::default() }).unwrap();
+    assert_eq!(okx.iter().map(|q| (q.bid, q.timestamp)).collect::<Vec<_>>(), vec![(99.9, at(500)), (0.0, at(600))]);
+    let trades = read_trades(&path, &Filter { end: Some(at(60_000)), ..Filter::default() }).unwrap();
+    assert_eq!(trades.len(), 3);
+    assert_eq!(ohlcv(&trades, Duration::from_secs(3600)).len(), 1);
+    std::fs::remove_file(parquet_path(&path, "l1")).unwrap();
+    std::fs::remove_file(parquet_path(&path, "trades")).unwrap();
+}
@@ -0,0 +1,92 @@
+use reqwest::header::{HeaderMap, HeaderValue};
+use soqa_sdk::error::SoqaError;
+use soqa_sdk::ratelimit::{RateLimit, RateLimiter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use warp::Filter;
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    pairs.iter().map(|(k, v)| (k.parse().unwrap(), HeaderValue::from_str(v).unwrap())).collect()
+}
+
+#[tokio::test]
+async fn queues_until_tokens_refill() {
+    let limiter = RateLimiter::default().with_limit("test", None, RateLimit { capacity: 2.0, per_second: 20.0 });
+    let started = Instant::now();
+    for _ in 0..3 {
+        limiter.acquire("test", "/", 1.0).await.unwrap();
+    }
+    assert!(started.elapsed() >= Duration::from_millis(40));
+
+    let impatient = limiter.clone().with_max_wait(Duration::ZERO);
+    assert!(matches!(impatient.acquire("test", "/", 2.0).await, Err(SoqaError::RateLimited { .. })));
+}
+
+#[tokio::test]
+async fn follows_exchange_weight_headers() {
+    let limiter = RateLimiter::default().with_max_wait(Duration::from_millis(1));
+    limiter.observe("binance", "/api/v3/aggTrades", 200, &headers(&[("x-mbx-used-weight-1m", "6000")])).unwrap();
+    assert!(limiter.acquire("binance", "/api/v3/aggTrades", 4.0).await.is_err());
+
+    let err = limiter.observe("kraken", "/0/public/Trades", 429, &headers(&[("retry-after", "3")])).unwrap_err();
+    assert!(matches!(err, SoqaError::RateLimited { retry_after, .. } if retry_after == Duration::from_secs(3)));
+    assert!(limiter.acquire("kraken", "/0/public/Trades", 1.0).await.is_err());
+
+    // OKX buckets are per endpoint.
+    let okx = RateLimiter::default()
+        .with_limit("okx", Some("/api/v5/market/history-trades"), RateLimit { capacity: 1.0, per_second: 0.1 })
+        .with_max_wait(Duration::ZERO);
+    okx.acquire("okx", "/api/v5/market/history-trades", 1.0).await.unwrap();
+    assert!(okx.acquire("okx", "/api/v5/market/history-trades", 1.0).await.is_err());
+    okx.acquire("okx", "/api/v5/public/instruments", 1.0).await.unwrap();
+}
+
+#[tokio::test]
+async fn send_backs_off_after_429() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let route = warp::path("ping").map(move || {
+        let status = if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+            warp::http::StatusCode::TOO_MANY_REQUESTS
+        } else {
+            warp::http::StatusCode::OK
+        };
+        warp::reply::with_header(warp::reply::with_status("pong", status), "Retry-After", "0")
+    });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let limiter = RateLimiter::default();
+    let request = reqwest::Client::new().get(format!("http://{}/ping", addr));
+    let response = limiter.send("local", request).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn send_text_backs_off_when_kraken_reports_a_full_counter() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let route = warp::path("ping").map(move || {
+        if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+            r#"{"error":["EAPI:Rate limit exceeded"]}"#
+        } else {
+            r#"{"error":[],"result":{}}"#
+        }
+    });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let limiter = RateLimiter::default().with_limit("kraken", None, RateLimit::counter(15.0, 100.0));
+    let request = reqwest::Client::new().get(format!("http://{}/ping", addr));
+    let (status, body) = limiter.send_text("kraken", request).await.unwrap();
+    assert_eq!(status.as_u16(), 200);
+    assert_eq!(body, r#"{"error":[],"result":{}}"#);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    let impatient = RateLimiter::default().with_max_wait(Duration::ZERO);
+    let err = impatient.observe_body("kraken", "/0/public/Trades", r#"{"error":["EAPI:Rate limit exceeded"]}"#).unwrap_err();
+    assert!(matches!(err, SoqaError::RateLimited { .. }));
+    assert!(impatient.acquire("kraken", "/0/public/Trades", 2.0).await.is_err());
+}
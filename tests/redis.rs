@@ -0,0 +1,127 @@
+#![cfg(feature = "redis")]
+
+// These tests need a Redis server: set SOQA_TEST_REDIS_URL, or have `redis-server` on PATH and a
+// throwaway instance is started on a free port. Without either they are skipped.
+
+mod common;
+
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use soqa_sdk::export::redis::RedisSink;
+use soqa_sdk::export::Sink;
+use soqa_sdk::models::{MarketEvent, OrderBookL1, Trade};
+use std::collections::HashMap;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+struct Server {
+    url: String,
+    child: Option<Child>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+async fn server() -> Option<Server> {
+    if let Ok(url) = std::env::var("SOQA_TEST_REDIS_URL") {
+        return Some(Server { url, child: None });
+    }
+    let port = std::net::TcpListener::bind("127.0.0.1:0").ok()?.local_addr().ok()?.port();
+    let child = Command::new("redis-server")
+        .args(["--port", &port.to_string(), "--save", "", "--appendonly", "no"])
+        .stdout(Stdio::null())
+        .spawn();
+    let Ok(child) = child else {
+        eprintln!("skipping: no SOQA_TEST_REDIS_URL and no redis-server on PATH");
+        return None;
+    };
+    let server = Server { url: format!("redis://127.0.0.1:{}", port), child: Some(child) };
+    for _ in 0..50 {
+        if tokio::net::TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return Some(server);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("redis-server did not start on port {}", port);
+}
+
+fn unique_prefix(name: &str) -> String {
+    format!("soqa-test-{}-{}", name, std::process::id())
+}
+
+fn quote(bid: f64) -> MarketEvent {
+    let quote = common::quote("binance", "BTCUSDT", bid, bid + 0.5, common::millis(1_718_000_000_000));
+    MarketEvent::L1(OrderBookL1 { bid_volume: 1.5, ask_volume: 2.0, ..quote })
+}
+
+fn trade(ms: u64) -> MarketEvent {
+    let trade = common::trade("okx", "BTC-USDT", 100.0 + ms as f64, 0.1, common::millis(ms));
+    MarketEvent::Trade(Trade { side: "sell".to_string(), ..trade })
+}
+
+#[tokio::test]
+async fn caches_latest_quote_and_publishes_updates() {
+    let Some(server) = server().await else { return };
+    let prefix = unique_prefix("l1");
+    let client = redis::Client::open(server.url.as_str()).unwrap();
+    let mut pubsub = client.get_async_pubsub().await.unwrap();
+    let channel = format!("{}:l1:binance:BTCUSDT", prefix);
+    pubsub.subscribe(&channel).await.unwrap();
+
+    let mut sink = RedisSink::connect(&server.url).await.unwrap().with_prefix(&prefix).with_ttl(Duration::from_secs(30));
+    sink.write(&quote(100.0)).await.unwrap();
+    sink.write(&quote(101.0)).await.unwrap();
+    sink.flush().await.unwrap();
+
+    let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+    let cached: HashMap<String, String> = conn.hgetall(&channel).await.unwrap();
+    assert_eq!(cached["bid"], "101");
+    assert_eq!(cached["ask"], "101.5");
+    assert_eq!(cached["timestamp_ms"], "1718000000000");
+    assert_eq!(cached["source"], "ticker");
+    let ttl: i64 = conn.pttl(&channel).await.unwrap();
+    assert!(ttl > 0 && ttl <= 30_000, "ttl {}", ttl);
+
+    let mut messages = pubsub.on_message();
+    for bid in [100.0, 101.0] {
+        let message = tokio::time::timeout(Duration::from_secs(5), messages.next()).await.unwrap().unwrap();
+        let event: serde_json::Value = serde_json::from_str(&message.get_payload::<String>().unwrap()).unwrap();
+        assert_eq!(event["bid"], bid);
+    }
+    let _: () = conn.del(&channel).await.unwrap();
+}
+
+#[tokio::test]
+async fn keeps_only_the_newest_trades() {
+    let Some(server) = server().await else { return };
+    let prefix = unique_prefix("trades");
+    let mut sink = RedisSink::connect(&server.url)
+        .await
+        .unwrap()
+        .with_prefix(&prefix)
+        .with_trades(3)
+        .with_batching(2, Duration::from_secs(60));
+    for ms in 1..=5 {
+        sink.write(&trade(ms)).await.unwrap();
+    }
+    sink.flush().await.unwrap();
+
+    let client = redis::Client::open(server.url.as_str()).unwrap();
+    let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+    let key = format!("{}:trades:okx:BTC-USDT", prefix);
+    let trades: Vec<String> = conn.zrange(&key, 0, -1).await.unwrap();
+    let prices: Vec<f64> = trades
+        .iter()
+        .map(|t| serde_json::from_str::<serde_json::Value>(t).unwrap()["price"].as_f64().unwrap())
+        .collect();
+    assert_eq!(prices, vec![103.0, 104.0, 105.0]);
+    let ttl: i64 = conn.pttl(&key).await.unwrap();
+    assert_eq!(ttl, -1);
+    let _: () = conn.del(&key).await.unwrap();
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "shm")]
+
+mod common;
+
+use soqa_sdk::models::MarketEvent;
+use soqa_sdk::shm::{ShmPublisher, ShmReader};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+fn ring_path(name: &str) -> PathBuf {
+    let dir = if std::path::Path::new("/dev/shm").is_dir() { PathBuf::from("/dev/shm") } else { std::env::temp_dir() };
+    dir.join(format!("soqa-test-{}-{}", name, std::process::id()))
+}
+
+fn quote(i: u64) -> MarketEvent {
+    MarketEvent::L1(common::quote("binance", "BTCUSDT", i as f64, i as f64 + 1.0, common::millis(i)))
+}
+
+fn bid(event: &MarketEvent) -> f64 {
+    match event {
+        MarketEvent::L1(book) => book.bid,
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn readers_see_events_in_order_and_count_overruns() {
+    let path = ring_path("order");
+    let mut publisher = ShmPublisher::create(&path, 8).unwrap();
+    let mut live = ShmReader::open(&path).unwrap();
+    assert!(live.try_read().unwrap().is_none());
+
+    publisher.publish(&quote(1)).unwrap();
+    publisher
+        .publish(&MarketEvent::Trade(common::trade("okx", "BTC-USDT", 100.0, 0.5, SystemTime::UNIX_EPOCH)))
+        .unwrap();
+    assert_eq!(bid(&live.try_read().unwrap().unwrap()), 1.0);
+    assert!(matches!(live.try_read().unwrap(), Some(MarketEvent::Trade(t)) if t.price == 100.0));
+    assert!(live.try_read().unwrap().is_none());
+
+    // Twenty more events overrun an eight-slot ring; the reader resumes at the oldest survivor.
+    for i in 2..22 {
+        publisher.publish(&quote(i)).unwrap();
+    }
+    assert_eq!(bid(&live.try_read().unwrap().unwrap()), 14.0);
+    assert_eq!(live.lost(), 12);
+
+    let late = ShmReader::open(&path).unwrap().from_oldest();
+    assert_eq!(late.lost(), 0);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn concurrent_reader_never_sees_torn_records() {
+    let path = ring_path("stress");
+    let mut publisher = ShmPublisher::create(&path, 1024).unwrap();
+    let mut reader = ShmReader::open(&path).unwrap();
+    const EVENTS: u64 = 200_000;
+
+    let consumer = std::thread::spawn(move || {
+        let mut last = 0.0;
+        let mut seen = 0;
+        while last < (EVENTS - 1) as f64 {
+            if let Some(MarketEvent::L1(book)) = reader.try_read().unwrap() {
+                assert_eq!(book.ask, book.bid + 1.0);
+                assert!(book.bid > last || seen == 0);
+                last = book.bid;
+                seen += 1;
+            }
+        }
+        (seen, reader.lost())
+    });
+    for i in 1..EVENTS {
+        publisher.publish(&quote(i)).unwrap();
+    }
+    let (seen, lost) = consumer.join().unwrap();
+    assert_eq!(seen + lost, EVENTS - 1);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn readers_reject_invalid_capacities() {
+    let path = ring_path("capacity");
+    ShmPublisher::create(&path, 8).unwrap();
+    let mut ring = std::fs::read(&path).unwrap();
+    for capacity in [0u64, 6, 1 << 62] {
+        ring[24..32].copy_from_slice(&capacity.to_le_bytes());
+        std::fs::write(&path, &ring).unwrap();
+        assert!(ShmReader::open(&path).is_err(), "capacity {}", capacity);
+    }
+    std::fs::remove_file(path).unwrap();
+}
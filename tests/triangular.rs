@@ -0,0 +1,81 @@
+mod common;
+
+use futures_util::stream::{self, StreamExt};
+use soqa_sdk::arbitrage::FeeModel;
+use soqa_sdk::exchanges::instruments::{parse_instruments, Instrument};
+use soqa_sdk::models::MarketEvent;
+use soqa_sdk::triangular::{TriangularExt, TriangularScanner};
+use std::time::SystemTime;
+
+fn book(symbol: &str, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>) -> MarketEvent {
+    MarketEvent::L2(common::book("binance", symbol, &bids, &asks, SystemTime::UNIX_EPOCH))
+}
+
+fn instruments() -> Vec<Instrument> {
+    vec![
+        Instrument::new("BTCUSDT", "BTC", "USDT"),
+        Instrument::new("ETHBTC", "ETH", "BTC"),
+        Instrument::new("ETHUSDT", "ETH", "USDT"),
+        Instrument::new("SOLUSDT", "SOL", "USDT"),
+    ]
+}
+
+fn fees(bps: f64, threshold_bps: f64) -> FeeModel {
+    FeeModel { taker_fees_bps: [("binance".to_string(), bps)].into_iter().collect(), threshold_bps, ..FeeModel::default() }
+}
+
+fn events() -> Vec<MarketEvent> {
+    vec![
+        book("BTCUSDT", vec![(99.0, 1.0)], vec![(100.0, 1.0)]),
+        book("ETHBTC", vec![(0.049, 10.0)], vec![(0.05, 10.0)]),
+        book("ETHUSDT", vec![(5.1, 5.0), (5.0, 100.0)], vec![(5.2, 100.0)]),
+    ]
+}
+
+#[test]
+fn builds_cycles_from_instrument_metadata() {
+    let scanner = TriangularScanner::new("binance", &instruments(), fees(0.0, 0.0));
+    assert_eq!(scanner.cycle_count(), 2);
+    let mut symbols = scanner.symbols();
+    symbols.sort();
+    assert_eq!(symbols, ["BTCUSDT", "ETHBTC", "ETHUSDT"]);
+}
+
+#[test]
+fn sizes_cycle_from_book_depth() {
+    let mut scanner = TriangularScanner::new("binance", &instruments(), fees(0.0, 0.0));
+    let found: Vec<_> = events().iter().flat_map(|event| scanner.on_event(event)).collect();
+
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].path, ["USDT", "BTC", "ETH", "USDT"]);
+    assert_eq!(found[0].symbols, ["BTCUSDT", "ETHBTC", "ETHUSDT"]);
+    // The 5 ETH bid at 5.1 is the bottleneck; the next level at 5.0 is no longer profitable.
+    assert!((found[0].size - 25.0).abs() < 1e-9);
+    assert!((found[0].expected_profit - 0.5).abs() < 1e-9);
+    assert!((found[0].edge_bps - 200.0).abs() < 1e-6);
+}
+
+#[tokio::test]
+async fn fees_and_threshold_apply() {
+    let scanner = TriangularScanner::new("binance", &instruments(), fees(10.0, 0.0));
+    let found: Vec<_> = stream::iter(events()).triangular(scanner).collect().await;
+    assert_eq!(found.len(), 1);
+    assert!(found[0].edge_bps < 200.0 && found[0].edge_bps > 160.0);
+
+    let scanner = TriangularScanner::new("binance", &instruments(), fees(10.0, 300.0));
+    assert!(stream::iter(events()).triangular(scanner).collect::<Vec<_>>().await.is_empty());
+}
+
+#[test]
+fn parses_venue_instrument_listings() {
+    let binance = r#"{"symbols":[
+        {"symbol":"ETHBTC","status":"TRADING","baseAsset":"ETH","quoteAsset":"BTC"},
+        {"symbol":"LUNABTC","status":"BREAK","baseAsset":"LUNA","quoteAsset":"BTC"}]}"#;
+    assert_eq!(parse_instruments("binance", binance).unwrap(), [Instrument::new("ETHBTC", "ETH", "BTC")]);
+
+    let kraken = r#"{"error":[],"result":{"XXBTZUSD":{"wsname":"XBT/USD","base":"XXBT","quote":"ZUSD","status":"online"}}}"#;
+    let parsed = parse_instruments("kraken", kraken).unwrap();
+    assert_eq!(parsed, [Instrument { symbol: "XBT/USD".to_string(), base: "BTC".to_string(), quote: "USD".to_string() }]);
+
+    assert!(parse_instruments("nasdaq", "{}").is_err());
+}